@@ -6,14 +6,19 @@ use std::{cell::RefCell, sync::Mutex};
 use image::{ImageBuffer, Rgba};
 use slog::Logger;
 #[cfg(feature = "image")]
-use smithay::backend::renderer::gles2::{Gles2Error, Gles2Renderer, Gles2Texture};
+use smithay::backend::renderer::{
+    gles2::{Gles2Error, Gles2Renderer, Gles2Texture},
+    ImportMem,
+};
 use smithay::{
     backend::{
-        renderer::{buffer_type, BufferType, Frame, ImportAll, Renderer, Texture, Transform},
+        renderer::{
+            buffer_dimensions, buffer_type, BufferType, Frame, ImportAll, Renderer, Texture, Transform,
+        },
         SwapBuffersError,
     },
     reexports::wayland_server::protocol::{wl_buffer, wl_surface},
-    utils::{Logical, Point, Rectangle},
+    utils::{Buffer, Logical, Point, Rectangle, Size},
     wayland::{
         compositor::{
             get_role, with_states, with_surface_tree_upward, Damage, SubsurfaceCachedState,
@@ -102,16 +107,33 @@ where
             // Pull a new buffer if available
             if let Some(data) = states.data_map.get::<RefCell<SurfaceData>>() {
                 let mut data = data.borrow_mut();
-                let attributes = states.cached_state.current::<SurfaceAttributes>();
+                let mut attributes = states.cached_state.current::<SurfaceAttributes>();
                 if data.texture.is_none() {
                     if let Some(buffer) = data.buffer.take() {
+                        let buffer_scale = attributes.buffer_scale;
+                        let buffer_transform = Transform::from(attributes.buffer_transform);
+                        // the size of the surface in buffer coordinates, before applying the buffer transformation
+                        let surface_size = buffer_dimensions(&buffer).map(|dim| {
+                            buffer_transform.transform_size(Size::<i32, Buffer>::from((dim.w, dim.h)))
+                        });
                         let damage = attributes
                             .damage
-                            .iter()
-                            .map(|dmg| match dmg {
-                                Damage::Buffer(rect) => *rect,
-                                // TODO also apply transformations
-                                Damage::Surface(rect) => rect.to_buffer(attributes.buffer_scale),
+                            .drain(..)
+                            .filter_map(|dmg| match dmg {
+                                Damage::Buffer(rect) => Some(rect),
+                                Damage::Surface(rect) => match surface_size {
+                                    Some(size) => {
+                                        let bounds = Rectangle::from_loc_and_size(
+                                            (0, 0),
+                                            size.to_logical(buffer_scale),
+                                        );
+                                        rect.intersection(bounds).map(|rect| {
+                                            buffer_transform
+                                                .transform_rect_in(rect.to_buffer(buffer_scale), &size)
+                                        })
+                                    }
+                                    None => Some(rect.to_buffer(buffer_scale)),
+                                },
                             })
                             .collect::<Vec<_>>();
 
@@ -172,12 +194,27 @@ where
                         let current = states.cached_state.current::<SubsurfaceCachedState>();
                         location += current.location;
                     }
+                    let position = location
+                        .to_f64()
+                        .to_physical(output_scale as f64)
+                        .to_i32_round::<i32>();
+                    let damage = Rectangle::from_loc_and_size(
+                        position,
+                        texture
+                            .texture
+                            .size()
+                            .to_logical(buffer_scale)
+                            .to_f64()
+                            .to_physical(output_scale as f64)
+                            .to_i32_ceil(),
+                    );
                     if let Err(err) = frame.render_texture_at(
                         &texture.texture,
-                        location.to_f64().to_physical(output_scale as f64).to_i32_round(),
+                        position.to_f64(),
                         buffer_scale,
                         output_scale as f64,
                         Transform::Normal, /* TODO */
+                        &[damage],
                         1.0,
                     ) {
                         result = Err(err.into());
@@ -338,6 +375,8 @@ where
     let value_str = value.to_string();
     let mut offset_x = 0f64;
     for digit in value_str.chars().map(|d| d.to_digit(10).unwrap()) {
+        let dst = Rectangle::from_loc_and_size((offset_x, 0.0), (22.0 * output_scale, 35.0 * output_scale));
+        let damage = Rectangle::from_extemities(dst.loc.to_i32_floor(), (dst.loc + dst.size).to_i32_ceil());
         frame
             .render_texture_from_to(
                 texture,
@@ -354,7 +393,8 @@ where
                     5 => Rectangle::from_loc_and_size((44, 70), (22, 35)),
                     _ => unreachable!(),
                 },
-                Rectangle::from_loc_and_size((offset_x, 0.0), (22.0 * output_scale, 35.0 * output_scale)),
+                dst,
+                &[damage],
                 Transform::Normal,
                 1.0,
             )
@@ -370,31 +410,9 @@ pub fn import_bitmap<C: std::ops::Deref<Target = [u8]>>(
     renderer: &mut Gles2Renderer,
     image: &ImageBuffer<Rgba<u8>, C>,
 ) -> Result<Gles2Texture, Gles2Error> {
-    use smithay::backend::renderer::gles2::ffi;
-
-    renderer.with_context(|renderer, gl| unsafe {
-        let mut tex = 0;
-        gl.GenTextures(1, &mut tex);
-        gl.BindTexture(ffi::TEXTURE_2D, tex);
-        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
-        gl.TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_T, ffi::CLAMP_TO_EDGE as i32);
-        gl.TexImage2D(
-            ffi::TEXTURE_2D,
-            0,
-            ffi::RGBA as i32,
-            image.width() as i32,
-            image.height() as i32,
-            0,
-            ffi::RGBA,
-            ffi::UNSIGNED_BYTE as u32,
-            image.as_ptr() as *const _,
-        );
-        gl.BindTexture(ffi::TEXTURE_2D, 0);
-
-        Gles2Texture::from_raw(
-            renderer,
-            tex,
-            (image.width() as i32, image.height() as i32).into(),
-        )
-    })
+    renderer.import_memory(
+        image.as_raw(),
+        (image.width() as i32, image.height() as i32).into(),
+        false,
+    )
 }
@@ -24,7 +24,15 @@ pub fn render_layers_and_windows(
     output_scale: f32,
     logger: &Logger,
 ) -> Result<(), SwapBuffersError> {
-    frame.clear([0.8, 0.8, 0.9, 1.0])?;
+    let output_damage = Rectangle::from_loc_and_size(
+        (0, 0),
+        output_geometry
+            .size
+            .to_f64()
+            .to_physical(output_scale as f64)
+            .to_i32_ceil(),
+    );
+    frame.clear([0.8, 0.8, 0.9, 1.0], &[output_damage])?;
 
     for layer in [Layer::Background, Layer::Bottom] {
         draw_layers(
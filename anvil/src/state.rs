@@ -108,8 +108,8 @@ impl<BackendData: Backend + 'static> AnvilState<BackendData> {
                         token_data,
                         surface,
                     } => {
-                        if token_data.timestamp.elapsed().as_secs() < 10 {
-                            // Just grant the wish
+                        if token_data.timestamp.elapsed().as_secs() < 10 && token_data.is_serial_current() {
+                            // The request was triggered by recent input on the focused client
                             anvil_state.window_map.borrow_mut().bring_surface_to_top(&surface);
                         } else {
                             // Discard the request
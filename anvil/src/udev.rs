@@ -20,7 +20,7 @@ use smithay::{
         libinput::{LibinputInputBackend, LibinputSessionInterface},
         renderer::{
             gles2::{Gles2Renderer, Gles2Texture},
-            Bind, Frame, Renderer, Transform,
+            Bind, Frame, Renderer, Texture, Transform,
         },
         session::{auto::AutoSession, Session, Signal as SessionSignal},
         udev::{UdevBackend, UdevEvent},
@@ -50,7 +50,7 @@ use smithay::{
     },
     utils::{
         signaling::{Linkable, SignalToken, Signaler},
-        Logical, Point,
+        Logical, Point, Rectangle,
     },
     wayland::{
         output::{Mode, PhysicalProperties},
@@ -191,7 +191,7 @@ pub fn run_udev(log: Logger) {
         .unwrap();
     let session_event_source = event_loop
         .handle()
-        .insert_source(notifier, |(), &mut (), _anvil_state| {})
+        .insert_source(notifier, |_, _, _anvil_state| {})
         .unwrap();
     for (dev, path) in udev_backend.device_list() {
         state.device_added(dev, path.into())
@@ -468,7 +468,9 @@ impl AnvilState<UdevData> {
             };
 
             let renderer = Rc::new(RefCell::new(unsafe {
-                Gles2Renderer::new(context, self.log.clone()).unwrap()
+                let mut renderer = Gles2Renderer::new(context, self.log.clone()).unwrap();
+                renderer.set_explicit_sync(true);
+                renderer
             }));
 
             #[cfg(feature = "egl")]
@@ -798,15 +800,26 @@ fn render_surface(
                                 logger,
                             )?;
                         } else {
-                            frame.render_texture_at(
-                                pointer_image,
-                                relative_ptr_location
+                            let position = relative_ptr_location
+                                .to_f64()
+                                .to_physical(output_scale as f64)
+                                .to_i32_round::<i32>();
+                            let damage = Rectangle::from_loc_and_size(
+                                position,
+                                pointer_image
+                                    .size()
+                                    .to_logical(1)
                                     .to_f64()
                                     .to_physical(output_scale as f64)
-                                    .to_i32_round(),
+                                    .to_i32_ceil(),
+                            );
+                            frame.render_texture_at(
+                                pointer_image,
+                                position.to_f64(),
                                 1,
                                 output_scale as f64,
                                 Transform::Normal,
+                                &[damage],
                                 1.0,
                             )?;
                         }
@@ -833,10 +846,11 @@ fn render_surface(
         .and_then(|x| x)
         .map_err(Into::<SwapBuffersError>::into)
     {
-        Ok(()) => surface
-            .surface
-            .queue_buffer()
-            .map_err(Into::<SwapBuffersError>::into),
+        Ok(()) => match renderer.take_render_fence() {
+            Ok(Some(fence)) => surface.surface.queue_buffer_with_fence(fence),
+            _ => surface.surface.queue_buffer(),
+        }
+        .map_err(Into::<SwapBuffersError>::into),
         Err(err) => Err(err),
     }
 }
@@ -873,7 +887,10 @@ fn initial_render(surface: &mut RenderSurface, renderer: &mut Gles2Renderer) ->
     renderer
         .render((1, 1).into(), Transform::Normal, |_, frame| {
             frame
-                .clear([0.8, 0.8, 0.9, 1.0])
+                .clear(
+                    [0.8, 0.8, 0.9, 1.0],
+                    &[Rectangle::from_loc_and_size((0, 0), (1, 1))],
+                )
                 .map_err(Into::<SwapBuffersError>::into)
         })
         .map_err(Into::<SwapBuffersError>::into)
@@ -35,6 +35,13 @@ fn gl_generate() {
                 "EGL_KHR_image_base",
                 "EGL_EXT_image_dma_buf_import",
                 "EGL_EXT_image_dma_buf_import_modifiers",
+                "EGL_EXT_output_base",
+                "EGL_EXT_output_drm",
+                "EGL_EXT_stream_consumer_egloutput",
+                "EGL_KHR_fence_sync",
+                "EGL_KHR_wait_sync",
+                "EGL_ANDROID_native_fence_sync",
+                "EGL_EXT_buffer_age",
             ],
         )
         .write_bindings(gl_generator::GlobalGenerator, &mut file)
@@ -53,6 +60,7 @@ fn gl_generate() {
                 "GL_OES_EGL_image_external",
                 "GL_EXT_texture_format_BGRA8888",
                 "GL_EXT_unpack_subimage",
+                "GL_EXT_disjoint_timer_query",
             ],
         )
         .write_bindings(gl_generator::StructGenerator, &mut file)
@@ -92,10 +100,28 @@ fn find_logind() {
     }
 }
 
+#[cfg(feature = "xwayland")]
+fn xwayland_shell_generate() {
+    use std::{env, path::PathBuf};
+    use wayland_scanner::{generate_code, Side};
+
+    let dest = PathBuf::from(&env::var("OUT_DIR").unwrap());
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed=protocols/xwayland-shell-v1.xml");
+    generate_code(
+        "protocols/xwayland-shell-v1.xml",
+        dest.join("xwayland_shell_v1_server_api.rs"),
+        Side::Server,
+    );
+}
+
 fn main() {
     #[cfg(any(feature = "backend_egl", feature = "renderer_gl"))]
     gl_generate();
 
+    #[cfg(feature = "xwayland")]
+    xwayland_shell_generate();
+
     #[cfg(feature = "backend_session_logind")]
     find_logind();
 }
@@ -0,0 +1,184 @@
+use smithay::{
+    backend::input::{
+        self, Axis, AxisSource, Event, InputBackend, InputEvent, KeyState, KeyboardKeyEvent,
+        PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent, PointerMotionEvent,
+    },
+    reexports::wayland_server::protocol::{wl_pointer, wl_surface::WlSurface},
+    utils::{Logical, Point},
+    wayland::{
+        seat::{keysyms as xkb, AxisFrame, FilterResult},
+        shell::wlr_layer::Layer,
+        SERIAL_COUNTER,
+    },
+};
+
+use crate::state::{Backend, CompositorState};
+
+enum KeyAction {
+    Quit,
+    VtSwitch(i32),
+}
+
+impl<BackendData: Backend + 'static> CompositorState<BackendData> {
+    pub fn process_input_event<B: InputBackend>(&mut self, event: InputEvent<B>) {
+        match event {
+            InputEvent::Keyboard { event } => self.on_keyboard_key::<B>(event),
+            InputEvent::PointerMotion { event } => {
+                let location = self.pointer_location + event.delta();
+                self.on_pointer_move(location, event.time());
+            }
+            InputEvent::PointerMotionAbsolute { event } => {
+                // absolute motion is relative to the first output, e.g. the winit window
+                let geometry = self
+                    .space
+                    .outputs()
+                    .next()
+                    .and_then(|output| self.space.output_geometry(output));
+                if let Some(geometry) = geometry {
+                    let location = event.position_transformed(geometry.size) + geometry.loc.to_f64();
+                    self.on_pointer_move(location, event.time());
+                }
+            }
+            InputEvent::PointerButton { event } => self.on_pointer_button::<B>(event),
+            InputEvent::PointerAxis { event } => self.on_pointer_axis::<B>(event),
+            _ => {}
+        }
+    }
+
+    fn on_keyboard_key<B: InputBackend>(&mut self, event: B::KeyboardKeyEvent) {
+        let serial = SERIAL_COUNTER.next_serial();
+        let action = self.keyboard.input(
+            event.key_code(),
+            event.state(),
+            serial,
+            event.time(),
+            |modifiers, handle| {
+                let keysym = handle.modified_sym();
+                if event.state() == KeyState::Released {
+                    FilterResult::Forward
+                } else if modifiers.ctrl && modifiers.alt && keysym == xkb::KEY_BackSpace {
+                    FilterResult::Intercept(KeyAction::Quit)
+                } else if (xkb::KEY_XF86Switch_VT_1..=xkb::KEY_XF86Switch_VT_12).contains(&keysym) {
+                    FilterResult::Intercept(KeyAction::VtSwitch(
+                        (keysym - xkb::KEY_XF86Switch_VT_1 + 1) as i32,
+                    ))
+                } else {
+                    FilterResult::Forward
+                }
+            },
+        );
+        match action {
+            Some(KeyAction::Quit) => {
+                info!(self.log, "Quitting.");
+                self.running = false;
+            }
+            Some(KeyAction::VtSwitch(vt)) => {
+                info!(self.log, "Switching to vt {}", vt);
+                self.backend_data.change_vt(vt);
+            }
+            None => {}
+        }
+    }
+
+    fn on_pointer_move(&mut self, location: Point<f64, Logical>, time: u32) {
+        self.pointer_location = self.clamp_coords(location);
+        let under = self.surface_under(self.pointer_location);
+        self.pointer
+            .motion(self.pointer_location, under, SERIAL_COUNTER.next_serial(), time);
+    }
+
+    fn on_pointer_button<B: InputBackend>(&mut self, event: B::PointerButtonEvent) {
+        let serial = SERIAL_COUNTER.next_serial();
+        let state = match event.state() {
+            input::ButtonState::Pressed => {
+                // click to focus, unless the pointer is grabbed
+                if !self.pointer.is_grabbed() {
+                    let window = self
+                        .space
+                        .element_under(self.pointer_location)
+                        .map(|(window, _)| window.clone());
+                    if let Some(window) = window {
+                        self.focus_window(&window);
+                    }
+                }
+                wl_pointer::ButtonState::Pressed
+            }
+            input::ButtonState::Released => wl_pointer::ButtonState::Released,
+        };
+        self.pointer
+            .button(event.button_code(), state, serial, event.time());
+    }
+
+    fn on_pointer_axis<B: InputBackend>(&mut self, event: B::PointerAxisEvent) {
+        let source = match event.source() {
+            AxisSource::Continuous => wl_pointer::AxisSource::Continuous,
+            AxisSource::Finger => wl_pointer::AxisSource::Finger,
+            AxisSource::Wheel | AxisSource::WheelTilt => wl_pointer::AxisSource::Wheel,
+        };
+
+        let mut frame = AxisFrame::new(event.time()).source(source);
+        for (axis, wl_axis) in [
+            (Axis::Horizontal, wl_pointer::Axis::HorizontalScroll),
+            (Axis::Vertical, wl_pointer::Axis::VerticalScroll),
+        ] {
+            let discrete = event.amount_discrete(axis);
+            let amount = event
+                .amount(axis)
+                .unwrap_or_else(|| discrete.unwrap_or(0.0) * 3.0);
+            if amount != 0.0 {
+                frame = frame.value(wl_axis, amount);
+                if let Some(discrete) = discrete {
+                    frame = frame.discrete(wl_axis, discrete as i32);
+                }
+            } else if source == wl_pointer::AxisSource::Finger {
+                frame = frame.stop(wl_axis);
+            }
+        }
+        self.pointer.axis(frame);
+    }
+
+    /// Find the surface accepting input at the given point, and its location
+    fn surface_under(&self, point: Point<f64, Logical>) -> Option<(WlSurface, Point<i32, Logical>)> {
+        let output = self.space.output_under(point).next()?;
+        let output_location = self.space.output_geometry(output)?.loc;
+        let layers = self.layer_map(output)?;
+        let layer_surface_under = |layer| {
+            let point = point - output_location.to_f64();
+            layers.layer_under(layer, point).and_then(|(surface, location)| {
+                surface
+                    .surface_under(point - location.to_f64())
+                    .map(|(surface, offset)| (surface, output_location + location + offset))
+            })
+        };
+
+        layer_surface_under(Layer::Overlay)
+            .or_else(|| layer_surface_under(Layer::Top))
+            .or_else(|| {
+                self.space.element_under(point).and_then(|(window, location)| {
+                    window
+                        .surface_under(point - location.to_f64())
+                        .map(|(surface, offset)| (surface, location + offset))
+                })
+            })
+            .or_else(|| layer_surface_under(Layer::Bottom))
+            .or_else(|| layer_surface_under(Layer::Background))
+    }
+
+    fn clamp_coords(&self, point: Point<f64, Logical>) -> Point<f64, Logical> {
+        let (max_x, max_y) = self
+            .space
+            .outputs()
+            .filter_map(|output| self.space.output_geometry(output))
+            .fold((0, 0), |(x, y), geometry| {
+                (
+                    x.max(geometry.loc.x + geometry.size.w),
+                    y.max(geometry.loc.y + geometry.size.h),
+                )
+            });
+        (
+            point.x.max(0.0).min(max_x as f64 - 1.0),
+            point.y.max(0.0).min(max_y as f64 - 1.0),
+        )
+            .into()
+    }
+}
@@ -0,0 +1,49 @@
+//! A small reference compositor
+//!
+//! This example wires together the building blocks of smithay into a working (if minimal)
+//! stacking compositor: the `wl_compositor`, `xdg_shell` and `wlr_layer_shell` globals delegating
+//! to the compositor state, a seat with pointer and keyboard, a [`Space`](smithay::desktop::Space)
+//! organizing the windows, a [`LayerMap`](smithay::desktop::LayerMap) per output and the
+//! damage-tracked rendering of both.
+//!
+//! It can either run nested as a winit window (`--winit`) or directly on a tty (`--tty-udev`),
+//! using udev, a session, DRM/GBM and libinput. Press `Ctrl+Alt+Backspace` to quit it.
+
+#![warn(rust_2018_idioms)]
+
+#[macro_use]
+extern crate slog;
+
+mod input;
+mod render;
+mod state;
+mod udev;
+mod winit;
+
+use slog::Drain;
+use std::sync::Mutex;
+
+fn main() {
+    let log = slog::Logger::root(Mutex::new(slog_term::term_full().fuse()).fuse(), o!());
+
+    match std::env::args().nth(1).as_deref() {
+        Some("--winit") => {
+            info!(log, "Starting with the winit backend");
+            winit::run_winit(log);
+        }
+        Some("--tty-udev") => {
+            info!(log, "Starting on a tty using udev");
+            udev::run_udev(log);
+        }
+        Some(other) => {
+            crit!(log, "Unknown backend: {}", other);
+        }
+        None => {
+            println!("USAGE: compositor --backend");
+            println!();
+            println!("Possible backends are:");
+            println!("\t--winit : Run as a X11 or Wayland client using winit.");
+            println!("\t--tty-udev : Run on a tty using udev (requires root if without logind).");
+        }
+    }
+}
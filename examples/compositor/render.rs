@@ -0,0 +1,112 @@
+use std::sync::Mutex;
+
+use smithay::{
+    backend::renderer::{
+        damage::OutputDamageTracker,
+        element::RenderElement,
+        utils::{
+            dnd_icon_render_elements, import_surface_tree, surface_tree_render_elements,
+            WaylandSurfaceRenderElement,
+        },
+        ImportAll, Renderer,
+    },
+    desktop::{LayerMap, Space, Window},
+    reexports::wayland_server::protocol::wl_surface::WlSurface,
+    utils::{Logical, Physical, Point, Rectangle},
+    wayland::{
+        compositor::with_states, output::Output, seat::CursorImageAttributes, shell::wlr_layer::Layer,
+    },
+};
+
+pub const CLEAR_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+
+/// Render the windows and layer surfaces of an output, together with the given
+/// elements (e.g. the cursor) on top
+#[allow(clippy::too_many_arguments)]
+pub fn render_output<R>(
+    renderer: &mut R,
+    output: &Output,
+    space: &Space<Window>,
+    layers: &LayerMap,
+    custom_elements: &[&dyn RenderElement<R>],
+    damage_tracker: &mut OutputDamageTracker,
+    age: usize,
+    log: &slog::Logger,
+) -> Result<Option<Vec<Rectangle<i32, Physical>>>, <R as Renderer>::Error>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: Send + 'static,
+{
+    let scale = output.current_scale() as f64;
+    let layer_elements = |renderer: &mut R, layer_list: &[Layer]| {
+        layer_list
+            .iter()
+            .flat_map(|layer| layers.render_elements(renderer, *layer, scale, log))
+            .collect::<Vec<_>>()
+    };
+    let upper = layer_elements(renderer, &[Layer::Overlay, Layer::Top]);
+    let lower = layer_elements(renderer, &[Layer::Bottom, Layer::Background]);
+    let windows = space
+        .render_elements_for_output(renderer, output)
+        .unwrap_or_default();
+
+    // front to back
+    let elements = custom_elements
+        .iter()
+        .copied()
+        .chain(upper.iter().map(as_render_element))
+        .chain(windows.iter().map(as_render_element))
+        .chain(lower.iter().map(as_render_element))
+        .collect::<Vec<_>>();
+    damage_tracker.render_output(renderer, age, &elements, CLEAR_COLOR, log)
+}
+
+fn as_render_element<R, E>(element: &E) -> &dyn RenderElement<R>
+where
+    R: Renderer,
+    E: RenderElement<R>,
+{
+    element
+}
+
+/// Creates the render elements of a cursor surface at the given location on the output
+pub fn cursor_elements<R>(
+    renderer: &mut R,
+    surface: &WlSurface,
+    location: Point<f64, Logical>,
+    scale: f64,
+    log: &slog::Logger,
+) -> Vec<WaylandSurfaceRenderElement>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: Send + 'static,
+{
+    let hotspot = with_states(surface, |states| {
+        states
+            .data_map
+            .get::<Mutex<CursorImageAttributes>>()
+            .map(|attributes| attributes.lock().unwrap().hotspot)
+    })
+    .ok()
+    .flatten()
+    .unwrap_or_default();
+    import_surface_tree(renderer, surface, log);
+    let location = (location - hotspot.to_f64()).to_physical(scale).to_i32_round();
+    surface_tree_render_elements(surface, location)
+}
+
+/// Creates the render elements of the drag'n'drop icon of a client, following the pointer
+pub fn dnd_icon_elements<R>(
+    renderer: &mut R,
+    surface: &WlSurface,
+    location: Point<f64, Logical>,
+    scale: f64,
+    log: &slog::Logger,
+) -> Vec<WaylandSurfaceRenderElement>
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: Send + 'static,
+{
+    import_surface_tree(renderer, surface, log);
+    dnd_icon_render_elements(surface, location, scale)
+}
@@ -0,0 +1,330 @@
+use std::{cell::RefCell, rc::Rc, sync::Mutex, time::Instant};
+
+use smithay::{
+    backend::renderer::utils::on_commit_buffer_handler,
+    delegate_compositor, delegate_layer_shell, delegate_xdg_shell,
+    desktop::{
+        utils::send_frames_surface_tree, Kind, LayerMap, LayerSurface, PopupKind, PopupManager, Space, Window,
+    },
+    reexports::{
+        calloop::{generic::Generic, Interest, LoopHandle, Mode, PostAction},
+        wayland_server::{protocol::wl_surface::WlSurface, Display},
+    },
+    utils::{Logical, Point},
+    wayland::{
+        compositor::{with_states, CompositorHandler},
+        data_device::{current_dnd_icon, default_action_chooser, init_data_device, set_data_device_focus},
+        output::Output,
+        seat::{CursorImageStatus, KeyboardHandle, PointerHandle, Seat, XkbConfig},
+        shell::{
+            wlr_layer::{LayerShellRequest, WlrLayerShellHandler},
+            xdg::{
+                XdgPopupSurfaceRoleAttributes, XdgRequest, XdgShellHandler, XdgToplevelSurfaceRoleAttributes,
+            },
+        },
+        shm::init_shm_global,
+        SERIAL_COUNTER,
+    },
+};
+
+/// Offset between the initial locations of consecutively mapped windows
+const CASCADE_OFFSET: i32 = 32;
+
+pub trait Backend {
+    fn seat_name(&self) -> String;
+    fn change_vt(&mut self, _vt: i32) {}
+}
+
+pub struct CompositorState<BackendData: 'static> {
+    pub backend_data: BackendData,
+    pub display: Rc<RefCell<Display>>,
+    pub handle: LoopHandle<'static, CompositorState<BackendData>>,
+    pub running: bool,
+    pub log: slog::Logger,
+    pub start_time: Instant,
+    // desktop
+    pub space: Space<Window>,
+    pub layers: Vec<LayerMap>,
+    pub popups: PopupManager,
+    // input
+    pub seat: Seat,
+    pub pointer: PointerHandle,
+    pub keyboard: KeyboardHandle,
+    pub pointer_location: Point<f64, Logical>,
+    pub cursor_status: Rc<RefCell<CursorImageStatus>>,
+}
+
+impl<BackendData: Backend + 'static> CompositorState<BackendData> {
+    pub fn init(
+        display: Rc<RefCell<Display>>,
+        handle: LoopHandle<'static, CompositorState<BackendData>>,
+        backend_data: BackendData,
+        log: slog::Logger,
+    ) -> CompositorState<BackendData> {
+        // dispatch the clients, with the state as dispatch data for the delegated globals
+        handle
+            .insert_source(
+                Generic::from_fd(display.borrow().get_poll_fd(), Interest::READ, Mode::Level),
+                |_, _, state: &mut CompositorState<BackendData>| {
+                    let display = state.display.clone();
+                    let mut display = display.borrow_mut();
+                    match display.dispatch(std::time::Duration::from_millis(0), state) {
+                        Ok(_) => Ok(PostAction::Continue),
+                        Err(err) => {
+                            error!(state.log, "I/O error on the Wayland display: {}", err);
+                            state.running = false;
+                            Err(err)
+                        }
+                    }
+                },
+            )
+            .expect("Failed to init the wayland event source.");
+
+        {
+            let mut display = display.borrow_mut();
+            delegate_compositor!(CompositorState<BackendData>, &mut display, log.clone());
+            delegate_xdg_shell!(CompositorState<BackendData>, &mut display, log.clone());
+            delegate_layer_shell!(CompositorState<BackendData>, &mut display, log.clone());
+            init_shm_global(&mut display, vec![], log.clone());
+            init_data_device(&mut display, |_| {}, default_action_chooser, log.clone());
+        }
+
+        let (mut seat, _) = Seat::new(&mut display.borrow_mut(), backend_data.seat_name(), log.clone());
+        let cursor_status = Rc::new(RefCell::new(CursorImageStatus::Default));
+        let pointer = seat.add_pointer({
+            let cursor_status = cursor_status.clone();
+            move |status| *cursor_status.borrow_mut() = status
+        });
+        let keyboard = seat
+            .add_keyboard(XkbConfig::default(), 200, 25, |seat, focus| {
+                set_data_device_focus(seat, focus.and_then(|surface| surface.as_ref().client()))
+            })
+            .expect("Failed to initialize the keyboard");
+
+        CompositorState {
+            backend_data,
+            display,
+            handle,
+            running: true,
+            start_time: Instant::now(),
+            space: Space::new(log.clone()),
+            layers: Vec::new(),
+            popups: PopupManager::new(log.clone()),
+            seat,
+            pointer,
+            keyboard,
+            pointer_location: (0.0, 0.0).into(),
+            cursor_status,
+            log,
+        }
+    }
+}
+
+impl<BackendData> CompositorState<BackendData> {
+    /// Map a new output right of the existing ones
+    pub fn map_output(&mut self, output: &Output) {
+        let x = self
+            .space
+            .outputs()
+            .filter_map(|output| self.space.output_geometry(output))
+            .map(|geometry| geometry.loc.x + geometry.size.w)
+            .max()
+            .unwrap_or(0);
+        self.space.map_output(output, (x, 0));
+        self.layers.push(LayerMap::new(output.clone(), self.log.clone()));
+    }
+
+    pub fn unmap_output(&mut self, output: &Output) {
+        self.space.unmap_output(output);
+        self.layers.retain(|layers| layers.output() != output);
+    }
+
+    pub fn layer_map(&self, output: &Output) -> Option<&LayerMap> {
+        self.layers.iter().find(|layers| layers.output() == output)
+    }
+
+    /// Clean up destroyed surfaces and update the outputs the windows are visible on
+    pub fn refresh(&mut self) {
+        self.space.refresh();
+        self.popups.cleanup();
+        for layers in &mut self.layers {
+            layers.refresh();
+        }
+    }
+
+    /// Sends the frame callbacks of all surfaces presented on the given output
+    pub fn send_frames(&self, output: &Output) {
+        let time = self.start_time.elapsed();
+        for window in self.space.visible_elements_for_output(output) {
+            window.send_frame(output, time, None);
+        }
+        if let Some(layers) = self.layer_map(output) {
+            layers.send_frames(time, None);
+        }
+        if let Some(icon) = current_dnd_icon(&self.seat) {
+            send_frames_surface_tree(&icon, output, time, None);
+        }
+    }
+
+    /// Find the mapped window of a toplevel surface
+    pub fn window_for_surface(&self, surface: &WlSurface) -> Option<Window> {
+        self.space
+            .elements()
+            .find(|window| window.toplevel().get_surface() == Some(surface))
+            .cloned()
+    }
+
+    /// Focus the given window, raising it to the top
+    pub fn focus_window(&mut self, window: &Window) {
+        self.space.raise_element(window, true);
+        self.keyboard
+            .set_focus(window.toplevel().get_surface(), SERIAL_COUNTER.next_serial());
+        for window in self
+            .space
+            .elements()
+            .filter(|window| initial_configure_sent(window))
+        {
+            window.configure();
+        }
+    }
+}
+
+/// Whether the toplevel of the window already received its initial configure
+fn initial_configure_sent(window: &Window) -> bool {
+    window
+        .toplevel()
+        .get_surface()
+        .and_then(|surface| {
+            with_states(surface, |states| {
+                states
+                    .data_map
+                    .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                    .map(|attributes| attributes.lock().unwrap().initial_configure_sent)
+            })
+            .ok()
+            .flatten()
+        })
+        .unwrap_or(true)
+}
+
+impl<BackendData> CompositorHandler for CompositorState<BackendData> {
+    fn commit(&mut self, surface: &WlSurface) {
+        on_commit_buffer_handler(surface);
+        self.popups.commit(surface);
+
+        // the initial configure of a xdg_surface has to be sent in response to its first commit
+        if let Some(window) = self.window_for_surface(surface) {
+            if !initial_configure_sent(&window) {
+                window.configure();
+            }
+        }
+        if let Some(PopupKind::Xdg(popup)) = self.popups.find_popup(surface) {
+            let initial_configure_sent = with_states(surface, |states| {
+                states
+                    .data_map
+                    .get::<Mutex<XdgPopupSurfaceRoleAttributes>>()
+                    .map(|attributes| attributes.lock().unwrap().initial_configure_sent)
+            });
+            if let Ok(Some(false)) = initial_configure_sent {
+                // the initial configure is always allowed
+                popup.send_configure().expect("initial configure failed");
+            }
+        }
+        if let Some(layers) = self
+            .layers
+            .iter_mut()
+            .find(|layers| layers.layer_for_surface(surface).is_some())
+        {
+            layers.arrange();
+        }
+    }
+}
+
+impl<BackendData> XdgShellHandler for CompositorState<BackendData> {
+    fn xdg_shell_request(&mut self, request: XdgRequest) {
+        match request {
+            XdgRequest::NewToplevel { surface } => {
+                let window = Window::new(Kind::Xdg(surface));
+                let zone = self
+                    .space
+                    .outputs()
+                    .next()
+                    .and_then(|output| self.layer_map(output))
+                    .map(|layers| layers.non_exclusive_zone())
+                    .unwrap_or_default();
+                let offset = (self.space.elements().count() as i32 % 8) * CASCADE_OFFSET;
+                self.space
+                    .map_element(window.clone(), zone.loc + Point::from((offset, offset)), false);
+                self.focus_window(&window);
+            }
+            XdgRequest::NewPopup { surface, positioner } => {
+                let _ = surface.with_pending_state(|state| state.geometry = positioner.get_geometry());
+                if let Err(err) = self.popups.track_popup(PopupKind::Xdg(surface)) {
+                    warn!(self.log, "Failed to track popup: {}", err);
+                }
+            }
+            XdgRequest::Grab { surface, serial, .. } => {
+                let popup = PopupKind::Xdg(surface.clone());
+                if self.popups.grab_popup(popup, &self.seat, serial).is_err() {
+                    surface.send_popup_done();
+                }
+            }
+            XdgRequest::Move {
+                surface,
+                seat,
+                serial,
+            } => {
+                let window = surface
+                    .get_surface()
+                    .and_then(|surface| self.window_for_surface(surface));
+                if let (Some(window), Some(seat)) = (window, Seat::from_resource(&seat)) {
+                    if let Err(err) = self.space.start_move_grab(&window, &seat, serial) {
+                        debug!(self.log, "Denied interactive move: {}", err);
+                    }
+                }
+            }
+            XdgRequest::Resize {
+                surface,
+                seat,
+                serial,
+                edges,
+            } => {
+                let window = surface
+                    .get_surface()
+                    .and_then(|surface| self.window_for_surface(surface));
+                if let (Some(window), Some(seat)) = (window, Seat::from_resource(&seat)) {
+                    if let Err(err) = self.space.start_resize_grab(&window, &seat, serial, edges.into()) {
+                        debug!(self.log, "Denied interactive resize: {}", err);
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+}
+
+impl<BackendData> WlrLayerShellHandler for CompositorState<BackendData> {
+    fn layer_shell_request(&mut self, request: LayerShellRequest) {
+        if let LayerShellRequest::NewLayerSurface {
+            surface,
+            output,
+            namespace,
+            ..
+        } = request
+        {
+            let output = output
+                .as_ref()
+                .and_then(Output::from_resource)
+                .or_else(|| self.space.outputs().next().cloned());
+            let layers = output.and_then(|output| self.layers.iter_mut().find(|l| l.output() == &output));
+            match layers {
+                Some(layers) => {
+                    if let Err(err) = layers.map_layer(&LayerSurface::new(surface, namespace)) {
+                        warn!(self.log, "Failed to map layer surface: {}", err);
+                    }
+                }
+                None => surface.send_close(),
+            }
+        }
+    }
+}
@@ -0,0 +1,568 @@
+use std::{
+    cell::RefCell,
+    collections::HashMap,
+    os::unix::io::{AsRawFd, RawFd},
+    path::PathBuf,
+    rc::Rc,
+    time::Duration,
+};
+
+use slog::Logger;
+use smithay::{
+    backend::{
+        allocator::dmabuf::Dmabuf,
+        drm::{DrmDevice, DrmError, DrmEvent, GbmBufferedSurface},
+        egl::{EGLContext, EGLDisplay},
+        libinput::{LibinputInputBackend, LibinputSessionInterface},
+        renderer::{
+            damage::OutputDamageTracker,
+            element::{texture::TextureBuffer, RenderElement},
+            gles2::{Gles2Renderer, Gles2Texture},
+            Bind, Transform,
+        },
+        session::{auto::AutoSession, Session, Signal as SessionSignal},
+        udev::{UdevBackend, UdevEvent},
+        SwapBuffersError,
+    },
+    desktop::{LayerMap, Space, Window},
+    reexports::{
+        calloop::{
+            timer::{Timer, TimerHandle},
+            EventLoop, RegistrationToken,
+        },
+        drm::{
+            self,
+            control::{connector, crtc, Device as ControlDevice},
+        },
+        gbm::Device as GbmDevice,
+        input::Libinput,
+        nix::{fcntl::OFlag, sys::stat::dev_t},
+        wayland_server::{
+            protocol::{
+                wl_output::{self, WlOutput},
+                wl_surface::WlSurface,
+            },
+            Display, Global,
+        },
+    },
+    utils::{
+        signaling::{Linkable, Signaler},
+        Logical, Point,
+    },
+    wayland::{
+        data_device::current_dnd_icon,
+        output::{Mode, Output, PhysicalProperties},
+        seat::CursorImageStatus,
+    },
+};
+
+use crate::{
+    render::{cursor_elements, dnd_icon_elements, render_output},
+    state::{Backend, CompositorState},
+};
+
+/// Size of the square drawn as the default cursor
+const POINTER_SIZE: i32 = 16;
+
+#[derive(Clone)]
+pub struct SessionFd(RawFd);
+impl AsRawFd for SessionFd {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+struct Surface {
+    surface: GbmBufferedSurface<SessionFd>,
+    output: Output,
+    global: Global<WlOutput>,
+    damage_tracker: OutputDamageTracker,
+}
+
+struct Device {
+    path: PathBuf,
+    surfaces: HashMap<crtc::Handle, Surface>,
+    renderer: Gles2Renderer,
+    pointer_texture: TextureBuffer<Gles2Texture>,
+    registration_token: RegistrationToken,
+}
+
+pub struct UdevData {
+    session: AutoSession,
+    signaler: Signaler<SessionSignal>,
+    devices: HashMap<dev_t, Device>,
+    render_timer: TimerHandle<(dev_t, crtc::Handle)>,
+    log: Logger,
+}
+
+impl Backend for UdevData {
+    fn seat_name(&self) -> String {
+        self.session.seat()
+    }
+
+    fn change_vt(&mut self, vt: i32) {
+        if let Err(err) = self.session.change_vt(vt) {
+            error!(self.log, "Error switching to vt {}: {}", vt, err);
+        }
+    }
+}
+
+pub fn run_udev(log: Logger) {
+    let mut event_loop = EventLoop::try_new().unwrap();
+    let display = Rc::new(RefCell::new(Display::new()));
+
+    let socket_name = display
+        .borrow_mut()
+        .add_socket_auto()
+        .unwrap()
+        .into_string()
+        .unwrap();
+    info!(log, "Listening on wayland socket"; "name" => socket_name.clone());
+    std::env::set_var("WAYLAND_DISPLAY", socket_name);
+
+    /*
+     * Initialize session
+     */
+    let (session, notifier) = match AutoSession::new(log.clone()) {
+        Some(ret) => ret,
+        None => {
+            crit!(log, "Could not initialize a session");
+            return;
+        }
+    };
+    let signaler = notifier.signaler();
+
+    let timer = Timer::new().unwrap();
+    let data = UdevData {
+        session,
+        signaler: signaler.clone(),
+        devices: HashMap::new(),
+        render_timer: timer.handle(),
+        log: log.clone(),
+    };
+    let mut state = CompositorState::init(display.clone(), event_loop.handle(), data, log.clone());
+
+    // re-render timer
+    event_loop
+        .handle()
+        .insert_source(timer, |(device_id, crtc), _, state| state.render(device_id, crtc))
+        .unwrap();
+
+    /*
+     * Initialize the udev and libinput backends
+     */
+    let udev_backend = match UdevBackend::new(state.backend_data.seat_name(), log.clone()) {
+        Ok(ret) => ret,
+        Err(err) => {
+            crit!(log, "Failed to initialize udev backend"; "error" => err);
+            return;
+        }
+    };
+
+    let mut libinput_context = Libinput::new_with_udev::<LibinputSessionInterface<AutoSession>>(
+        state.backend_data.session.clone().into(),
+    );
+    libinput_context
+        .udev_assign_seat(&state.backend_data.seat_name())
+        .unwrap();
+    let mut libinput_backend = LibinputInputBackend::new(libinput_context, log.clone());
+    libinput_backend.link(signaler);
+
+    /*
+     * Bind all our objects that get driven by the event loop
+     */
+    event_loop
+        .handle()
+        .insert_source(libinput_backend, |event, _, state| {
+            state.process_input_event(event)
+        })
+        .unwrap();
+    event_loop
+        .handle()
+        .insert_source(notifier, |signal, _, state| match signal {
+            // the devices are resumed at this point, so rendering can be restarted
+            SessionSignal::ActivateSession | SessionSignal::ActivateDevice { .. } => {
+                let device_ids = state.backend_data.devices.keys().copied().collect::<Vec<_>>();
+                for device_id in device_ids {
+                    state.render_device(device_id);
+                }
+            }
+            _ => {}
+        })
+        .unwrap();
+    for (device_id, path) in udev_backend.device_list() {
+        state.device_added(device_id, path.into());
+    }
+    event_loop
+        .handle()
+        .insert_source(udev_backend, |event, _, state| match event {
+            UdevEvent::Added { device_id, path } => state.device_added(device_id, path),
+            UdevEvent::Changed { device_id } => state.device_changed(device_id),
+            UdevEvent::Removed { device_id } => state.device_removed(device_id),
+        })
+        .map_err(|err| err.error)
+        .unwrap();
+
+    /*
+     * And run our loop
+     */
+    while state.running {
+        if event_loop
+            .dispatch(Some(Duration::from_millis(16)), &mut state)
+            .is_err()
+        {
+            state.running = false;
+        } else {
+            display.borrow_mut().flush_clients(&mut state);
+            state.refresh();
+        }
+    }
+}
+
+impl CompositorState<UdevData> {
+    fn device_added(&mut self, device_id: dev_t, path: PathBuf) {
+        let fd = match self.backend_data.session.open(
+            &path,
+            OFlag::O_RDWR | OFlag::O_CLOEXEC | OFlag::O_NOCTTY | OFlag::O_NONBLOCK,
+        ) {
+            Ok(fd) => SessionFd(fd),
+            Err(err) => {
+                warn!(
+                    self.log,
+                    "Skipping device {:?}, failed to open it: {}", device_id, err
+                );
+                return;
+            }
+        };
+        let mut drm = match DrmDevice::new(fd.clone(), true, self.log.clone()) {
+            Ok(drm) => drm,
+            Err(err) => {
+                warn!(
+                    self.log,
+                    "Skipping device {:?}, because of drm error: {}", device_id, err
+                );
+                return;
+            }
+        };
+        let gbm = match GbmDevice::new(fd) {
+            Ok(gbm) => gbm,
+            Err(err) => {
+                warn!(
+                    self.log,
+                    "Skipping device {:?}, because of gbm error: {}", device_id, err
+                );
+                return;
+            }
+        };
+        let renderer = EGLDisplay::new(&gbm, self.log.clone())
+            .and_then(|egl| EGLContext::new(&egl, self.log.clone()))
+            .map_err(|err| err.to_string())
+            .and_then(|context| {
+                unsafe { Gles2Renderer::new(context, self.log.clone()) }.map_err(|err| err.to_string())
+            });
+        let mut renderer = match renderer {
+            Ok(renderer) => renderer,
+            Err(err) => {
+                warn!(
+                    self.log,
+                    "Skipping device {:?}, failed to create a renderer: {}", device_id, err
+                );
+                return;
+            }
+        };
+        let pointer_texture = TextureBuffer::from_memory(
+            &mut renderer,
+            &[255; (POINTER_SIZE * POINTER_SIZE * 4) as usize],
+            (POINTER_SIZE, POINTER_SIZE),
+            1,
+            Transform::Normal,
+        )
+        .expect("Failed to upload the pointer texture");
+
+        let surfaces = self.scan_connectors(&mut drm, &gbm, &mut renderer);
+
+        drm.link(self.backend_data.signaler.clone());
+        let registration_token = match self
+            .handle
+            .insert_source(drm, move |event, _, state| match event {
+                DrmEvent::VBlank(crtc) => state.frame_finish(device_id, crtc),
+                DrmEvent::Error(err) => error!(state.log, "{:?}", err),
+            }) {
+            Ok(token) => token,
+            Err(err) => {
+                warn!(self.log, "Skipping device {:?}: {}", device_id, err.error);
+                return;
+            }
+        };
+
+        self.backend_data.devices.insert(
+            device_id,
+            Device {
+                path,
+                surfaces,
+                renderer,
+                pointer_texture,
+                registration_token,
+            },
+        );
+        // render the first frames, the following ones are triggered by the vblanks
+        self.render_device(device_id);
+    }
+
+    fn device_changed(&mut self, device_id: dev_t) {
+        // quick and dirty, just re-init the whole device
+        if let Some(path) = self.backend_data.devices.get(&device_id).map(|d| d.path.clone()) {
+            self.device_removed(device_id);
+            self.device_added(device_id, path);
+        }
+    }
+
+    fn device_removed(&mut self, device_id: dev_t) {
+        if let Some(device) = self.backend_data.devices.remove(&device_id) {
+            for (_, surface) in device.surfaces {
+                self.unmap_output(&surface.output);
+                surface.global.destroy();
+            }
+            self.handle.remove(device.registration_token);
+            debug!(self.log, "Dropping device {:?}", device_id);
+        }
+    }
+
+    /// Create a surface and an output for every connected connector
+    fn scan_connectors(
+        &mut self,
+        drm: &mut DrmDevice<SessionFd>,
+        gbm: &GbmDevice<SessionFd>,
+        renderer: &mut Gles2Renderer,
+    ) -> HashMap<crtc::Handle, Surface> {
+        let res_handles = drm.resource_handles().unwrap();
+        let connectors = res_handles
+            .connectors()
+            .iter()
+            .filter_map(|conn| drm.get_connector(*conn).ok())
+            .filter(|conn| conn.state() == connector::State::Connected)
+            .collect::<Vec<_>>();
+
+        let mut surfaces = HashMap::new();
+        for connector in connectors {
+            // very naive way of finding a free crtc for the connector
+            let crtc = connector
+                .encoders()
+                .iter()
+                .filter_map(|encoder| encoder.and_then(|encoder| drm.get_encoder(encoder).ok()))
+                .flat_map(|encoder| res_handles.filter_crtcs(encoder.possible_crtcs()))
+                .find(|crtc| !surfaces.contains_key(crtc));
+            let crtc = match crtc {
+                Some(crtc) => crtc,
+                None => continue,
+            };
+
+            let drm_mode = connector.modes()[0];
+            let mut surface = match drm.create_surface(crtc, drm_mode, &[connector.handle()]) {
+                Ok(surface) => surface,
+                Err(err) => {
+                    warn!(self.log, "Failed to create drm surface: {}", err);
+                    continue;
+                }
+            };
+            surface.link(self.backend_data.signaler.clone());
+            let formats =
+                Bind::<Dmabuf>::supported_formats(renderer).expect("Dmabuf renderer without formats");
+            let surface = match GbmBufferedSurface::new(surface, gbm.clone(), formats, self.log.clone()) {
+                Ok(surface) => surface,
+                Err(err) => {
+                    warn!(self.log, "Failed to create rendering surface: {}", err);
+                    continue;
+                }
+            };
+
+            let (w, h) = drm_mode.size();
+            let mode = Mode {
+                size: (w as i32, h as i32).into(),
+                refresh: (drm_mode.vrefresh() * 1000) as i32,
+            };
+            let (phys_w, phys_h) = connector.size().unwrap_or((0, 0));
+            let (output, global) = Output::new(
+                &mut self.display.borrow_mut(),
+                format!("{:?}-{}", connector.interface(), connector.interface_id()),
+                PhysicalProperties {
+                    size: (phys_w as i32, phys_h as i32).into(),
+                    subpixel: wl_output::Subpixel::Unknown,
+                    make: "Smithay".into(),
+                    model: "Generic DRM".into(),
+                },
+                self.log.clone(),
+            );
+            output.change_current_state(Some(mode), None, None, None);
+            output.set_preferred(mode);
+            self.map_output(&output);
+
+            surfaces.insert(
+                crtc,
+                Surface {
+                    surface,
+                    output,
+                    global,
+                    // scanout is rotated
+                    damage_tracker: OutputDamageTracker::new(mode.size, 1.0, Transform::Flipped180),
+                },
+            );
+        }
+        surfaces
+    }
+
+    fn frame_finish(&mut self, device_id: dev_t, crtc: crtc::Handle) {
+        let surface = self
+            .backend_data
+            .devices
+            .get_mut(&device_id)
+            .and_then(|device| device.surfaces.get_mut(&crtc));
+        if let Some(surface) = surface {
+            if let Err(err) = surface.surface.frame_submitted() {
+                warn!(self.log, "Error during frame submission: {}", err);
+            }
+            let output = surface.output.clone();
+            self.send_frames(&output);
+        }
+        self.render(device_id, crtc);
+    }
+
+    fn render_device(&mut self, device_id: dev_t) {
+        let crtcs = match self.backend_data.devices.get(&device_id) {
+            Some(device) => device.surfaces.keys().copied().collect::<Vec<_>>(),
+            None => return,
+        };
+        for crtc in crtcs {
+            self.render(device_id, crtc);
+        }
+    }
+
+    fn render(&mut self, device_id: dev_t, crtc: crtc::Handle) {
+        let device = match self.backend_data.devices.get_mut(&device_id) {
+            Some(device) => device,
+            None => return,
+        };
+        let surface = match device.surfaces.get_mut(&crtc) {
+            Some(surface) => surface,
+            None => return,
+        };
+        let layers = self
+            .layers
+            .iter()
+            .find(|layers| layers.output() == &surface.output);
+        let geometry = self.space.output_geometry(&surface.output);
+        let (layers, geometry) = match (layers, geometry) {
+            (Some(layers), Some(geometry)) => (layers, geometry),
+            _ => return,
+        };
+
+        let mut cursor_status = self.cursor_status.borrow_mut();
+        if let CursorImageStatus::Image(ref surface) = *cursor_status {
+            if !surface.as_ref().is_alive() {
+                *cursor_status = CursorImageStatus::Default;
+            }
+        }
+
+        let dnd_icon = current_dnd_icon(&self.seat);
+        let result = render_surface(
+            surface,
+            &mut device.renderer,
+            &device.pointer_texture,
+            &self.space,
+            layers,
+            self.pointer_location - geometry.loc.to_f64(),
+            &cursor_status,
+            dnd_icon.as_ref(),
+            &self.log,
+        );
+        let reschedule = match result {
+            Ok(true) => false,
+            Ok(false) => {
+                // nothing changed, no vblank will follow
+                drop(cursor_status);
+                let output = surface.output.clone();
+                self.send_frames(&output);
+                true
+            }
+            Err(err) => {
+                warn!(self.log, "Error during rendering: {:?}", err);
+                match err {
+                    SwapBuffersError::AlreadySwapped => false,
+                    SwapBuffersError::TemporaryFailure(err) => !matches!(
+                        err.downcast_ref::<DrmError>(),
+                        Some(&DrmError::DeviceInactive)
+                            | Some(&DrmError::Access {
+                                source: drm::SystemError::PermissionDenied,
+                                ..
+                            })
+                    ),
+                    SwapBuffersError::ContextLost(err) => panic!("Rendering loop lost: {}", err),
+                }
+            }
+        };
+
+        if reschedule {
+            self.backend_data
+                .render_timer
+                .add_timeout(Duration::from_millis(1000 / 60), (device_id, crtc));
+        }
+    }
+}
+
+/// Render a frame onto the surface, returns whether a frame was queued
+#[allow(clippy::too_many_arguments)]
+fn render_surface(
+    surface: &mut Surface,
+    renderer: &mut Gles2Renderer,
+    pointer_texture: &TextureBuffer<Gles2Texture>,
+    space: &Space<Window>,
+    layers: &LayerMap,
+    pointer_location: Point<f64, Logical>,
+    cursor_status: &CursorImageStatus,
+    dnd_icon: Option<&WlSurface>,
+    log: &Logger,
+) -> Result<bool, SwapBuffersError> {
+    let (dmabuf, age) = surface.surface.next_buffer()?;
+    renderer.bind(dmabuf)?;
+
+    let scale = surface.output.current_scale() as f64;
+    let default_pointer = match cursor_status {
+        CursorImageStatus::Default => {
+            Some(pointer_texture.render_element(pointer_location.to_physical(scale), 1.0))
+        }
+        _ => None,
+    };
+    let cursor_surface = match cursor_status {
+        CursorImageStatus::Image(surface) => cursor_elements(renderer, surface, pointer_location, scale, log),
+        _ => Vec::new(),
+    };
+    let dnd_icon = dnd_icon
+        .map(|surface| dnd_icon_elements(renderer, surface, pointer_location, scale, log))
+        .unwrap_or_default();
+    let custom_elements = default_pointer
+        .iter()
+        .map(|element| element as &dyn RenderElement<Gles2Renderer>)
+        .chain(
+            cursor_surface
+                .iter()
+                .chain(dnd_icon.iter())
+                .map(|element| element as &dyn RenderElement<Gles2Renderer>),
+        )
+        .collect::<Vec<_>>();
+
+    let damage = render_output(
+        renderer,
+        &surface.output,
+        space,
+        layers,
+        &custom_elements,
+        &mut surface.damage_tracker,
+        age as usize,
+        log,
+    )?;
+    if damage.is_none() {
+        return Ok(false);
+    }
+    surface.surface.queue_buffer()?;
+    Ok(true)
+}
@@ -0,0 +1,170 @@
+use std::{cell::RefCell, rc::Rc, time::Duration};
+
+use slog::Logger;
+use smithay::{
+    backend::{
+        renderer::{
+            damage::OutputDamageTracker, element::RenderElement, gles2::Gles2Renderer, Transform, Unbind,
+        },
+        winit::{self, WinitEvent},
+        SwapBuffersError,
+    },
+    reexports::{
+        calloop::EventLoop,
+        wayland_server::{protocol::wl_output, Display},
+    },
+    wayland::{
+        data_device::current_dnd_icon,
+        output::{Mode, Output, PhysicalProperties},
+        seat::CursorImageStatus,
+    },
+};
+
+use crate::{
+    render::{cursor_elements, dnd_icon_elements, render_output},
+    state::{Backend, CompositorState},
+};
+
+pub const OUTPUT_NAME: &str = "winit";
+
+pub struct WinitData;
+
+impl Backend for WinitData {
+    fn seat_name(&self) -> String {
+        String::from("winit")
+    }
+}
+
+pub fn run_winit(log: Logger) {
+    let mut event_loop = EventLoop::try_new().unwrap();
+    let display = Rc::new(RefCell::new(Display::new()));
+
+    let (mut backend, mut winit) = match winit::init(log.clone()) {
+        Ok(ret) => ret,
+        Err(err) => {
+            crit!(log, "Failed to initialize Winit backend: {}", err);
+            return;
+        }
+    };
+
+    let socket_name = display
+        .borrow_mut()
+        .add_socket_auto()
+        .unwrap()
+        .into_string()
+        .unwrap();
+    info!(log, "Listening on wayland socket"; "name" => socket_name.clone());
+    std::env::set_var("WAYLAND_DISPLAY", socket_name);
+
+    let mut state = CompositorState::init(display.clone(), event_loop.handle(), WinitData, log.clone());
+
+    let size = backend.window_size().physical_size;
+    let mode = Mode {
+        size,
+        refresh: 60_000,
+    };
+    let (output, _global) = Output::new(
+        &mut display.borrow_mut(),
+        OUTPUT_NAME.into(),
+        PhysicalProperties {
+            size: (0, 0).into(),
+            subpixel: wl_output::Subpixel::Unknown,
+            make: "Smithay".into(),
+            model: "Winit".into(),
+        },
+        log.clone(),
+    );
+    output.change_current_state(Some(mode), None, None, None);
+    output.set_preferred(mode);
+    state.map_output(&output);
+    let mut damage_tracker = OutputDamageTracker::new(size, 1.0, Transform::Normal);
+
+    info!(log, "Initialization completed, starting the main loop.");
+
+    while state.running {
+        let result = winit.dispatch_new_events(|event| match event {
+            WinitEvent::Resized { size, .. } => {
+                let mode = Mode {
+                    size,
+                    refresh: 60_000,
+                };
+                output.change_current_state(Some(mode), None, None, None);
+                output.set_preferred(mode);
+                damage_tracker.update_mode(size, 1.0, Transform::Normal);
+            }
+            WinitEvent::Input(event) => state.process_input_event(event),
+            _ => (),
+        });
+        if result.is_err() {
+            state.running = false;
+            break;
+        }
+
+        // drawing logic
+        let mut cursor_status = state.cursor_status.borrow_mut();
+        if let CursorImageStatus::Image(ref surface) = *cursor_status {
+            if !surface.as_ref().is_alive() {
+                *cursor_status = CursorImageStatus::Default;
+            }
+        }
+        backend
+            .window()
+            .set_cursor_visible(*cursor_status == CursorImageStatus::Default);
+
+        let result = backend.bind().and_then(|_| {
+            let age = backend.buffer_age();
+            let renderer = backend.renderer();
+            let cursor = match *cursor_status {
+                CursorImageStatus::Image(ref surface) => {
+                    cursor_elements(renderer, surface, state.pointer_location, 1.0, &log)
+                }
+                _ => Vec::new(),
+            };
+            let dnd_icon = current_dnd_icon(&state.seat)
+                .map(|surface| dnd_icon_elements(renderer, &surface, state.pointer_location, 1.0, &log))
+                .unwrap_or_default();
+            let custom_elements = cursor
+                .iter()
+                .chain(dnd_icon.iter())
+                .map(|element| element as &dyn RenderElement<Gles2Renderer>)
+                .collect::<Vec<_>>();
+            let layers = state.layer_map(&output).unwrap();
+            let damage = render_output(
+                renderer,
+                &output,
+                &state.space,
+                layers,
+                &custom_elements,
+                &mut damage_tracker,
+                age,
+                &log,
+            )
+            .map_err(Into::<SwapBuffersError>::into)?;
+            if damage.is_some() {
+                backend.submit()
+            } else {
+                renderer.unbind().map_err(Into::into)
+            }
+        });
+        drop(cursor_status);
+
+        if let Err(SwapBuffersError::ContextLost(err)) = result {
+            error!(log, "Critical Rendering Error: {}", err);
+            state.running = false;
+        }
+
+        // Send frame events so that client start drawing their next frame
+        state.send_frames(&output);
+        display.borrow_mut().flush_clients(&mut state);
+
+        if event_loop
+            .dispatch(Some(Duration::from_millis(16)), &mut state)
+            .is_err()
+        {
+            state.running = false;
+        } else {
+            display.borrow_mut().flush_clients(&mut state);
+            state.refresh();
+        }
+    }
+}
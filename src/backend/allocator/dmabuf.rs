@@ -10,10 +10,11 @@
 //! This can be especially useful in resources where other parts of the stack should decide upon
 //! the lifetime of the buffer. E.g. when you are only caching associated resources for a dmabuf.
 
-use super::{Buffer, Format, Fourcc, Modifier};
+use super::{fence::SyncFile, Buffer, Format, Fourcc, Modifier};
 use crate::utils::{Buffer as BufferCoords, Size};
 use std::hash::{Hash, Hasher};
-use std::os::unix::io::{IntoRawFd, RawFd};
+use std::io;
+use std::os::unix::io::{FromRawFd, IntoRawFd, RawFd};
 use std::sync::{Arc, Weak};
 
 /// Maximum amount of planes this implementation supports
@@ -224,6 +225,35 @@ impl Dmabuf {
     pub fn weak(&self) -> WeakDmabuf {
         WeakDmabuf(Arc::downgrade(&self.0))
     }
+
+    /// Export the implicit fence of the pending writes to this buffer as a [`SyncFile`].
+    ///
+    /// The fence is exported from the first plane, as the planes of a buffer usually share the
+    /// same memory. Requires linux 6.0 or newer, fails with `ENOTTY` on older kernels.
+    pub fn export_sync_file(&self) -> io::Result<SyncFile> {
+        // a dmabuf always has at least one plane
+        let fd = self.handles().next().unwrap();
+        let mut data = ioctl::DmaBufExportSyncFile {
+            flags: ioctl::DMA_BUF_SYNC_READ,
+            fd: -1,
+        };
+        unsafe { ioctl::dma_buf_export_sync_file(fd, &mut data) }
+            .map_err(|err| io::Error::from_raw_os_error(err as i32))?;
+        Ok(unsafe { SyncFile::from_raw_fd(data.fd) })
+    }
+}
+
+mod ioctl {
+    /// Wait for the writers of the buffer, as we only ever read from it
+    pub const DMA_BUF_SYNC_READ: u32 = 1 << 0;
+
+    #[repr(C)]
+    pub struct DmaBufExportSyncFile {
+        pub flags: u32,
+        pub fd: i32,
+    }
+
+    nix::ioctl_readwrite!(dma_buf_export_sync_file, b'b', 2, DmaBufExportSyncFile);
 }
 
 impl WeakDmabuf {
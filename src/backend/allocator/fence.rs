@@ -0,0 +1,75 @@
+//! Explicit synchronization primitives
+//!
+//! A [`SyncFile`] wraps a linux `sync_file` file descriptor, which signals the completion
+//! of some gpu or display work. They can be exported from a renderer after submitting
+//! a frame and passed on to the display hardware (see `DrmSurface::set_in_fence`) or imported
+//! into a renderer to wait for a client's rendering to finish before sampling its buffers.
+
+use std::io;
+use std::os::unix::io::{AsRawFd, FromRawFd, IntoRawFd, RawFd};
+use std::time::Duration;
+
+use nix::poll::{poll, PollFd, PollFlags};
+
+/// Owned `sync_file` file descriptor, closed on drop
+#[derive(Debug)]
+pub struct SyncFile(RawFd);
+
+impl SyncFile {
+    /// Duplicate the underlying file descriptor
+    pub fn try_clone(&self) -> io::Result<SyncFile> {
+        nix::unistd::dup(self.0)
+            .map(SyncFile)
+            .map_err(|err| io::Error::from_raw_os_error(err as i32))
+    }
+
+    /// Returns if the fence is already signaled, without blocking
+    pub fn is_signaled(&self) -> io::Result<bool> {
+        self.wait(Some(Duration::ZERO))
+    }
+
+    /// Block until the fence is signaled or the timeout expires.
+    ///
+    /// Returns `true` if the fence was signaled and `false` if the timeout expired.
+    /// A `timeout` of `None` waits indefinitely.
+    pub fn wait(&self, timeout: Option<Duration>) -> io::Result<bool> {
+        let timeout = timeout
+            .map(|timeout| timeout.as_millis().min(i32::MAX as u128) as i32)
+            .unwrap_or(-1);
+        let mut fds = [PollFd::new(self.0, PollFlags::POLLIN)];
+        loop {
+            match poll(&mut fds, timeout) {
+                Ok(0) => return Ok(false),
+                Ok(_) => return Ok(true),
+                Err(nix::errno::Errno::EINTR) | Err(nix::errno::Errno::EAGAIN) => continue,
+                Err(err) => return Err(io::Error::from_raw_os_error(err as i32)),
+            }
+        }
+    }
+}
+
+impl AsRawFd for SyncFile {
+    fn as_raw_fd(&self) -> RawFd {
+        self.0
+    }
+}
+
+impl IntoRawFd for SyncFile {
+    fn into_raw_fd(self) -> RawFd {
+        let fd = self.0;
+        std::mem::forget(self);
+        fd
+    }
+}
+
+impl FromRawFd for SyncFile {
+    unsafe fn from_raw_fd(fd: RawFd) -> SyncFile {
+        SyncFile(fd)
+    }
+}
+
+impl Drop for SyncFile {
+    fn drop(&mut self) {
+        let _ = nix::unistd::close(self.0);
+    }
+}
@@ -70,7 +70,10 @@ impl<B: Buffer> Slot<B> {
         &self.0.userdata
     }
 
-    /// Retrieve the age of the buffer
+    /// Retrieve the age of the buffer.
+    ///
+    /// The age is the number of submitted frames since this buffer was submitted,
+    /// `0` denotes unknown contents (e.g. a newly allocated buffer).
     pub fn age(&self) -> u8 {
         self.0.age.load(Ordering::SeqCst)
     }
@@ -167,7 +170,16 @@ where
         slot.0.age.store(1, Ordering::SeqCst);
         for other_slot in &self.slots {
             if !Arc::ptr_eq(other_slot, &slot.0) {
-                other_slot.age.fetch_add(1, Ordering::SeqCst);
+                // buffers, that were never submitted, keep an unknown age
+                let _ = other_slot
+                    .age
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |age| {
+                        if age > 0 {
+                            Some(age.saturating_add(1))
+                        } else {
+                            None
+                        }
+                    });
             }
         }
     }
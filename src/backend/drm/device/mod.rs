@@ -330,6 +330,8 @@ where
         if token != self.token {
             return Ok(PostAction::Continue);
         }
+        profile_scope!("drm::process_events");
+        trace_span!(TRACE, "drm::process_events", dev_id = self.dev_id);
         match self.receive_events() {
             Ok(events) => {
                 for event in events {
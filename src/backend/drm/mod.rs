@@ -79,9 +79,11 @@ pub(self) mod surface;
 pub use device::{DevPath, DrmDevice, DrmEvent};
 pub use error::Error as DrmError;
 pub use node::{CreateDrmNodeError, DrmNode, NodeType};
+#[cfg(feature = "backend_drm_eglstream")]
+pub use surface::eglstream::{EglStreamDevice, EglStreamSurface, Error as EglStreamSurfaceError};
 #[cfg(feature = "backend_gbm")]
 pub use surface::gbm::{Error as GbmBufferedSurfaceError, GbmBufferedSurface};
-pub use surface::DrmSurface;
+pub use surface::{DrmSurface, RenderSurface};
 
 use drm::control::{crtc, plane, Device as ControlDevice, PlaneType};
 
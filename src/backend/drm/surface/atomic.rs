@@ -11,6 +11,7 @@ use std::sync::{
     Arc, Mutex, RwLock,
 };
 
+use crate::backend::allocator::fence::SyncFile;
 use crate::backend::drm::{
     device::atomic::Mapping,
     device::{DevPath, DrmDeviceInternal},
@@ -118,6 +119,7 @@ pub struct AtomicDrmSurface<A: AsRawFd + 'static> {
     state: RwLock<State>,
     pending: RwLock<State>,
     test_buffer: Mutex<Option<(DumbBuffer, framebuffer::Handle)>>,
+    in_fence: Mutex<Option<SyncFile>>,
     pub(crate) logger: ::slog::Logger,
 }
 
@@ -165,6 +167,7 @@ impl<A: AsRawFd + 'static> AtomicDrmSurface<A> {
             state: RwLock::new(state),
             pending: RwLock::new(pending),
             test_buffer: Mutex::new(None),
+            in_fence: Mutex::new(None),
             logger,
         };
 
@@ -482,13 +485,28 @@ impl<A: AsRawFd + 'static> AtomicDrmSurface<A> {
         }
 
         if current.mode != pending.mode {
-            info!(self.logger, "Setting new mode: {:?}", pending.mode.name());
+            info!(
+                self.logger,
+                "Setting new mode";
+                "crtc" => ?self.crtc,
+                "mode" => ?pending.mode.name(),
+                "size" => ?pending.mode.size(),
+                "refresh" => pending.mode.vrefresh(),
+            );
+            trace_event!(
+                INFO,
+                crtc = ?self.crtc,
+                mode = ?pending.mode.name(),
+                size = ?pending.mode.size(),
+                refresh = pending.mode.vrefresh(),
+                "Setting new mode"
+            );
         }
 
         trace!(self.logger, "Testing screen config");
 
         // test the new config and return the request if it would be accepted by the driver.
-        let req = {
+        let mut req = {
             let req = self.build_request(
                 &mut added,
                 &mut removed,
@@ -525,6 +543,9 @@ impl<A: AsRawFd + 'static> AtomicDrmSurface<A> {
             }
         };
 
+        // the fence is not part of the test, it might not be signaled yet
+        let _fence = self.add_in_fence(&mut req);
+
         debug!(self.logger, "Setting screen: {:?}", req);
         let result = self
             .fd
@@ -571,7 +592,7 @@ impl<A: AsRawFd + 'static> AtomicDrmSurface<A> {
         }
 
         // page flips work just like commits with fewer parameters..
-        let req = self.build_request(
+        let mut req = self.build_request(
             &mut [].iter(),
             &mut [].iter(),
             self.plane,
@@ -580,6 +601,7 @@ impl<A: AsRawFd + 'static> AtomicDrmSurface<A> {
             None,
             None,
         )?;
+        let _fence = self.add_in_fence(&mut req);
 
         // .. and without `AtomicCommitFlags::AllowModeset`.
         // If we would set anything here, that would require a modeset, this would fail,
@@ -603,6 +625,34 @@ impl<A: AsRawFd + 'static> AtomicDrmSurface<A> {
         Ok(())
     }
 
+    pub fn set_in_fence(&self, fence: SyncFile) {
+        *self.in_fence.lock().unwrap() = Some(fence);
+    }
+
+    // Adds the pending in-fence to the primary plane of the request.
+    // The returned fence needs to be kept alive until the request is committed.
+    fn add_in_fence(&self, req: &mut AtomicModeReq) -> Option<SyncFile> {
+        let fence = self.in_fence.lock().unwrap().take()?;
+        match self.plane_prop_handle(self.plane, "IN_FENCE_FD") {
+            Ok(prop) => {
+                req.add_property(
+                    self.plane,
+                    prop,
+                    property::Value::SignedRange(fence.as_raw_fd() as i64),
+                );
+                Some(fence)
+            }
+            Err(_) => {
+                // the driver cannot wait for us, so block until rendering is done
+                trace!(self.logger, "IN_FENCE_FD unsupported, waiting for fence");
+                if let Err(err) = fence.wait(None) {
+                    warn!(self.logger, "Failed to wait for fence: {}", err);
+                }
+                None
+            }
+        }
+    }
+
     pub fn test_buffer(&self, fb: framebuffer::Handle, mode: &Mode) -> Result<bool, Error> {
         if !self.active.load(Ordering::SeqCst) {
             return Err(Error::DeviceInactive);
@@ -0,0 +1,545 @@
+//! EGLStream based rendering onto a [`DrmSurface`].
+//!
+//! Some driver stacks (most notably the proprietary nvidia driver before version 495)
+//! do not support gbm and can therefor not be driven by a [`GbmBufferedSurface`](super::gbm::GbmBufferedSurface).
+//! Instead they require an [EGLStream](https://www.khronos.org/registry/EGL/extensions/KHR/EGL_KHR_stream.txt)
+//! to be created, which connects an [`EGLSurface`] (the producer) to an output layer of a drm plane (the consumer).
+//!
+//! Buffer management is fully handled by the driver in that case, which is why the render target of
+//! an [`EglStreamSurface`] is an [`EGLSurface`] instead of a [`Dmabuf`](crate::backend::allocator::dmabuf::Dmabuf).
+
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::ptr;
+use std::rc::Rc;
+use std::sync::Arc;
+
+use drm::buffer::DrmFourcc;
+use drm::control::{crtc, dumbbuffer::DumbBuffer, framebuffer, plane, Device, Mode};
+
+use crate::backend::allocator::fence::SyncFile;
+use crate::backend::drm::{device::DevPath, DrmDevice, DrmError, DrmSurface, RenderSurface};
+use crate::backend::egl::{
+    display::{EGLDisplayHandle, PixelFormat},
+    ffi,
+    native::{EGLNativeDisplay, EGLNativeSurface, EGLPlatform},
+    wrap_egl_call, EGLContext, EGLDevice, EGLDisplay, EGLError, EGLSurface,
+    SwapBuffersError as EGLSwapBuffersError,
+};
+use crate::backend::SwapBuffersError;
+use crate::egl_platform;
+
+use slog::{debug, error, info, o, trace};
+
+/// Extensions required by the [`EGLDisplay`] to drive an [`EglStreamSurface`]
+const REQUIRED_EXTENSIONS: [&str; 6] = [
+    "EGL_EXT_output_base",
+    "EGL_EXT_output_drm",
+    "EGL_KHR_stream",
+    "EGL_KHR_stream_producer_eglsurface",
+    "EGL_EXT_stream_consumer_egloutput",
+    "EGL_NV_stream_attrib",
+];
+
+/// [`EGLNativeDisplay`] of an [`EGLDevice`] matching an open [`DrmDevice`].
+///
+/// The resulting [`EGLDisplay`] shares drm master with the [`DrmDevice`] and
+/// is able to create [`EglStreamSurface`]s.
+#[derive(Debug)]
+pub struct EglStreamDevice {
+    device: EGLDevice,
+    fd: RawFd,
+}
+
+impl EglStreamDevice {
+    /// Find the [`EGLDevice`] corresponding to the given [`DrmDevice`].
+    ///
+    /// Fails if EGL does not expose the device, e.g. because the driver does not support EGLStreams.
+    pub fn new<A: AsRawFd + 'static>(drm: &DrmDevice<A>) -> Result<EglStreamDevice, Error> {
+        let path = drm.dev_path().ok_or(Error::DeviceNotFound)?;
+        let device = EGLDevice::enumerate()?
+            .find(|device| {
+                device
+                    .drm_device_path()
+                    .map(|device_path| device_path == path)
+                    .unwrap_or(false)
+            })
+            .ok_or(Error::DeviceNotFound)?;
+
+        Ok(EglStreamDevice {
+            device,
+            fd: drm.as_raw_fd(),
+        })
+    }
+
+    /// Returns the underlying [`EGLDevice`]
+    pub fn device(&self) -> &EGLDevice {
+        &self.device
+    }
+}
+
+impl EGLNativeDisplay for EglStreamDevice {
+    fn supported_platforms(&self) -> Vec<EGLPlatform<'_>> {
+        // see: https://www.khronos.org/registry/EGL/extensions/EXT/EGL_EXT_platform_device.txt
+        // and: https://www.khronos.org/registry/EGL/extensions/EXT/EGL_EXT_device_drm.txt
+        vec![egl_platform!(
+            PLATFORM_DEVICE_EXT,
+            self.device.inner(),
+            &["EGL_EXT_platform_device"],
+            vec![
+                ffi::egl::DRM_MASTER_FD_EXT as ffi::EGLint,
+                self.fd,
+                ffi::egl::NONE as ffi::EGLint
+            ]
+        )]
+    }
+
+    fn surface_type(&self) -> ffi::EGLint {
+        ffi::egl::STREAM_BIT_KHR as ffi::EGLint
+    }
+}
+
+/// Producer side of an EGLStream, used as the native type of the [`EGLSurface`]
+/// returned by an [`EglStreamSurface`].
+#[derive(Debug)]
+struct StreamProducer {
+    display: Arc<EGLDisplayHandle>,
+    stream: ffi::egl::types::EGLStreamKHR,
+    size: (i32, i32),
+}
+
+// EGLStreams are not bound to a specific thread
+unsafe impl Send for StreamProducer {}
+unsafe impl Sync for StreamProducer {}
+
+unsafe impl EGLNativeSurface for StreamProducer {
+    fn create(
+        &self,
+        display: &Arc<EGLDisplayHandle>,
+        config_id: ffi::egl::types::EGLConfig,
+    ) -> Result<*const nix::libc::c_void, EGLError> {
+        let surface_attributes = [
+            ffi::egl::WIDTH as ffi::EGLint,
+            self.size.0,
+            ffi::egl::HEIGHT as ffi::EGLint,
+            self.size.1,
+            ffi::egl::NONE as ffi::EGLint,
+        ];
+        wrap_egl_call(|| unsafe {
+            ffi::egl::CreateStreamProducerSurfaceKHR(
+                display.handle,
+                config_id,
+                self.stream,
+                surface_attributes.as_ptr(),
+            )
+        })
+    }
+
+    fn swap_buffers(
+        &self,
+        display: &Arc<EGLDisplayHandle>,
+        surface: ffi::egl::types::EGLSurface,
+    ) -> Result<(), EGLSwapBuffersError> {
+        // Inserts a new frame into the stream, scan-out happens once it is acquired.
+        wrap_egl_call(|| unsafe {
+            ffi::egl::SwapBuffers(***display, surface as *const _);
+        })
+        .map_err(EGLSwapBuffersError::EGLSwapBuffers)
+    }
+}
+
+impl Drop for StreamProducer {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::egl::DestroyStreamKHR(self.display.handle, self.stream);
+        }
+    }
+}
+
+/// Simplified abstraction of an EGLStream displayed on a [`DrmSurface`].
+///
+/// Implements [`RenderSurface`] with an [`EGLSurface`] as its render target.
+#[derive(Debug)]
+pub struct EglStreamSurface<D: AsRawFd + 'static> {
+    drm: Arc<DrmSurface<D>>,
+    display: EGLDisplay,
+    config_id: ffi::egl::types::EGLConfig,
+    pixel_format: PixelFormat,
+    surface: Option<Rc<EGLSurface>>,
+    stream: ffi::egl::types::EGLStreamKHR,
+    dumb: Option<(DumbBuffer, framebuffer::Handle)>,
+    pending: bool,
+    queued: bool,
+    logger: ::slog::Logger,
+}
+
+impl<D> EglStreamSurface<D>
+where
+    D: AsRawFd + 'static,
+{
+    /// Create a new `EglStreamSurface` for a given [`DrmSurface`].
+    ///
+    /// The `context` needs to be created from an [`EGLDisplay`] of the
+    /// matching [`EglStreamDevice`] and be initialized with a config.
+    ///
+    /// This will commit the pending state of the surface (using a temporary dumb buffer),
+    /// as the driver requires the crtc to be active before a stream can be attached to it.
+    pub fn new<L>(drm: DrmSurface<D>, context: &EGLContext, log: L) -> Result<EglStreamSurface<D>, Error>
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let logger = crate::slog_or_fallback(log).new(o!("backend" => "drm_eglstream"));
+        let display = context.display.clone();
+
+        if let Some(missing) = REQUIRED_EXTENSIONS
+            .iter()
+            .find(|ext| !display.extensions.iter().any(|x| x == *ext))
+        {
+            error!(logger, "EGLDisplay is missing required extension: {}", missing);
+            return Err(Error::EglExtensionNotSupported {
+                missing,
+                required: &REQUIRED_EXTENSIONS,
+            });
+        }
+        let pixel_format = context.pixel_format().ok_or(Error::NoConfig)?;
+
+        let mut surface = EglStreamSurface {
+            drm: Arc::new(drm),
+            display,
+            config_id: context.config_id(),
+            pixel_format,
+            surface: None,
+            stream: ffi::egl::NO_STREAM_KHR,
+            dumb: None,
+            pending: false,
+            queued: false,
+            logger,
+        };
+        surface.modeset()?;
+        Ok(surface)
+    }
+
+    /// Applies any pending state of the underlying [`DrmSurface`] and (re-)creates the stream.
+    fn modeset(&mut self) -> Result<(), Error> {
+        // the stream is occupying the plane, tear it down first
+        self.surface.take();
+        self.stream = ffi::egl::NO_STREAM_KHR;
+        self.pending = false;
+        self.queued = false;
+
+        let mode = self.drm.pending_mode();
+        let (w, h) = mode.size();
+        debug!(self.logger, "Committing dumb buffer for mode {:?}", mode);
+
+        let buffer = self
+            .drm
+            .create_dumb_buffer((w as u32, h as u32), DrmFourcc::Xrgb8888, 32)
+            .map_err(|source| DrmError::Access {
+                errmsg: "Failed to create dumb buffer",
+                dev: self.drm.dev_path(),
+                source,
+            })?;
+        let fb = match self.drm.add_framebuffer(&buffer, 24, 32) {
+            Ok(fb) => fb,
+            Err(source) => {
+                let _ = self.drm.destroy_dumb_buffer(buffer);
+                return Err(DrmError::Access {
+                    errmsg: "Failed to add framebuffer",
+                    dev: self.drm.dev_path(),
+                    source,
+                }
+                .into());
+            }
+        };
+        if let Err(err) = self.drm.commit([(fb, self.drm.plane())].iter(), false) {
+            // the previous buffer (if any) is still scanned out, keep it around
+            let _ = self.drm.destroy_framebuffer(fb);
+            let _ = self.drm.destroy_dumb_buffer(buffer);
+            return Err(err.into());
+        }
+        // only release the previous buffer once the new one is on screen
+        if let Some((buffer, fb)) = self.dumb.replace((buffer, fb)) {
+            let _ = self.drm.destroy_framebuffer(fb);
+            let _ = self.drm.destroy_dumb_buffer(buffer);
+        }
+
+        let producer = self.create_stream((w as i32, h as i32))?;
+        self.stream = producer.stream;
+        let surface = EGLSurface::new(
+            &self.display,
+            self.pixel_format,
+            self.config_id,
+            producer,
+            self.logger.clone(),
+        )
+        .map_err(Error::SurfaceCreationFailed)?;
+        self.surface = Some(Rc::new(surface));
+
+        info!(self.logger, "EGLStream connected to plane {:?}", self.drm.plane());
+        Ok(())
+    }
+
+    fn create_stream(&self, size: (i32, i32)) -> Result<StreamProducer, Error> {
+        let handle = self.display.get_display_handle();
+
+        let layer_attributes = [
+            ffi::egl::DRM_PLANE_EXT as ffi::egl::types::EGLAttrib,
+            u32::from(self.drm.plane()) as ffi::egl::types::EGLAttrib,
+            ffi::egl::NONE as ffi::egl::types::EGLAttrib,
+        ];
+        let mut layer: ffi::egl::types::EGLOutputLayerEXT = ptr::null();
+        let mut num_layers = 0;
+        wrap_egl_call(|| unsafe {
+            ffi::egl::GetOutputLayersEXT(
+                handle.handle,
+                layer_attributes.as_ptr(),
+                &mut layer,
+                1,
+                &mut num_layers,
+            )
+        })
+        .map_err(|_| Error::NoOutputLayer(self.drm.plane()))?;
+        if num_layers < 1 {
+            return Err(Error::NoOutputLayer(self.drm.plane()));
+        }
+        trace!(self.logger, "Using output layer {:?}", layer);
+
+        // frames are acquired manually to be able to request a page-flip event
+        let stream_attributes = [
+            ffi::egl::CONSUMER_AUTO_ACQUIRE_EXT as ffi::EGLint,
+            ffi::egl::FALSE as ffi::EGLint,
+            ffi::egl::NONE as ffi::EGLint,
+        ];
+        let stream =
+            wrap_egl_call(|| unsafe { ffi::egl::CreateStreamKHR(handle.handle, stream_attributes.as_ptr()) })
+                .map_err(Error::StreamCreationFailed)?;
+        if stream == ffi::egl::NO_STREAM_KHR {
+            return Err(Error::StreamCreationFailed(EGLError::BadParameter));
+        }
+        let producer = StreamProducer {
+            display: handle.clone(),
+            stream,
+            size,
+        };
+
+        wrap_egl_call(|| unsafe { ffi::egl::StreamConsumerOutputEXT(handle.handle, stream, layer) })
+            .map_err(Error::StreamConnectionFailed)?;
+
+        Ok(producer)
+    }
+
+    /// Retrieves the surface to be rendered into.
+    ///
+    /// Buffer ages are not exposed by EGLStreams, the returned age is therefor always `0`.
+    /// Any pending state of the underlying [`DrmSurface`] (e.g. a new mode) is applied
+    /// before returning, which might recreate the surface.
+    pub fn next_buffer(&mut self) -> Result<(Rc<EGLSurface>, u8), Error> {
+        if self.surface.is_none() || self.drm.commit_pending() {
+            self.modeset()?;
+        }
+        Ok((self.surface.as_ref().unwrap().clone(), 0))
+    }
+
+    /// Queues the current contents of the surface for scan-out.
+    ///
+    /// *Note*: This function needs to be followed up with [`EglStreamSurface::frame_submitted`]
+    /// when a vblank event is received, that denotes successful scanout of the frame.
+    pub fn queue_buffer(&mut self) -> Result<(), Error> {
+        if let Some(surface) = self.surface.as_ref() {
+            surface.swap_buffers()?;
+            self.queued = true;
+            if !self.pending {
+                self.submit()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Queues the current contents of the surface for scan-out, once the given fence is signaled.
+    ///
+    /// The frames of the stream are managed by the driver, so this blocks until the fence is signaled
+    /// and calls [`EglStreamSurface::queue_buffer`] afterwards.
+    pub fn queue_buffer_with_fence(&mut self, fence: SyncFile) -> Result<(), Error> {
+        fence.wait(None).map_err(Error::FenceWaitFailed)?;
+        self.queue_buffer()
+    }
+
+    /// Marks the current frame as submitted.
+    ///
+    /// *Note*: Needs to be called, after the vblank event of the matching [`DrmDevice`]
+    /// was received after calling [`EglStreamSurface::queue_buffer`] on this surface.
+    pub fn frame_submitted(&mut self) -> Result<(), Error> {
+        if self.pending {
+            self.pending = false;
+            if self.queued {
+                self.submit()?;
+            }
+        }
+        Ok(())
+    }
+
+    fn submit(&mut self) -> Result<(), Error> {
+        // acquiring a frame triggers a page-flip, that will generate a vblank event for our crtc
+        let acquire_attributes = [
+            ffi::egl::DRM_FLIP_EVENT_DATA_NV as ffi::egl::types::EGLAttrib,
+            u32::from(self.drm.crtc()) as ffi::egl::types::EGLAttrib,
+            ffi::egl::NONE as ffi::egl::types::EGLAttrib,
+        ];
+        wrap_egl_call(|| unsafe {
+            ffi::egl::StreamConsumerAcquireAttribNV(
+                **self.display.display,
+                self.stream,
+                acquire_attributes.as_ptr(),
+            )
+        })
+        .map_err(Error::AcquireFailed)?;
+        self.queued = false;
+        self.pending = true;
+        Ok(())
+    }
+
+    /// Returns the underlying [`crtc`](drm::control::crtc) of this surface
+    pub fn crtc(&self) -> crtc::Handle {
+        self.drm.crtc()
+    }
+
+    /// Returns the underlying [`plane`](drm::control::plane) of this surface
+    pub fn plane(&self) -> plane::Handle {
+        self.drm.plane()
+    }
+
+    /// Returns the currently active [`Mode`](drm::control::Mode)
+    /// of the underlying [`crtc`](drm::control::crtc)
+    pub fn current_mode(&self) -> Mode {
+        self.drm.current_mode()
+    }
+
+    /// Returns the currently pending [`Mode`](drm::control::Mode)
+    /// to be used after the next call to [`next_buffer`](EglStreamSurface::next_buffer).
+    pub fn pending_mode(&self) -> Mode {
+        self.drm.pending_mode()
+    }
+
+    /// Tries to set a new [`Mode`](drm::control::Mode)
+    /// to be used after the next call to [`next_buffer`](EglStreamSurface::next_buffer).
+    ///
+    /// As the stream needs to be recreated for a new mode, any previously returned
+    /// [`EGLSurface`] will become invalid.
+    pub fn use_mode(&self, mode: Mode) -> Result<(), Error> {
+        self.drm.use_mode(mode).map_err(Error::DrmError)
+    }
+}
+
+impl<D: AsRawFd + 'static> Drop for EglStreamSurface<D> {
+    fn drop(&mut self) {
+        // destroy the surface before the stream and the stream before the framebuffer
+        self.surface.take();
+        if let Some((buffer, fb)) = self.dumb.take() {
+            let _ = self.drm.destroy_framebuffer(fb);
+            let _ = self.drm.destroy_dumb_buffer(buffer);
+        }
+    }
+}
+
+impl<D> RenderSurface for EglStreamSurface<D>
+where
+    D: AsRawFd + 'static,
+{
+    type Target = Rc<EGLSurface>;
+    type Error = Error;
+
+    fn next_buffer(&mut self) -> Result<(Rc<EGLSurface>, u8), Error> {
+        EglStreamSurface::next_buffer(self)
+    }
+
+    fn queue_buffer(&mut self) -> Result<(), Error> {
+        EglStreamSurface::queue_buffer(self)
+    }
+
+    fn queue_buffer_with_fence(&mut self, fence: SyncFile) -> Result<(), Error> {
+        EglStreamSurface::queue_buffer_with_fence(self, fence)
+    }
+
+    fn frame_submitted(&mut self) -> Result<(), Error> {
+        EglStreamSurface::frame_submitted(self)
+    }
+
+    fn crtc(&self) -> crtc::Handle {
+        EglStreamSurface::crtc(self)
+    }
+
+    fn plane(&self) -> plane::Handle {
+        EglStreamSurface::plane(self)
+    }
+
+    fn current_mode(&self) -> Mode {
+        EglStreamSurface::current_mode(self)
+    }
+
+    fn pending_mode(&self) -> Mode {
+        EglStreamSurface::pending_mode(self)
+    }
+
+    fn use_mode(&self, mode: Mode) -> Result<(), Error> {
+        EglStreamSurface::use_mode(self, mode)
+    }
+}
+
+/// Errors thrown by an [`EglStreamSurface`]
+#[derive(Debug, thiserror::Error)]
+pub enum Error {
+    /// No [`EGLDevice`] matching the drm node could be found
+    #[error("No EGLDevice matching the drm node could be found")]
+    DeviceNotFound,
+    /// A required EGL extension is not supported by the display
+    #[error("The EGL extension `{missing}` is not supported by the underlying EGL implementation, all of the following are required: {required:?}")]
+    EglExtensionNotSupported {
+        /// The first unsupported extension
+        missing: &'static str,
+        /// All extensions required by an [`EglStreamSurface`]
+        required: &'static [&'static str],
+    },
+    /// The given context was created without a config
+    #[error("The given EGLContext has no config")]
+    NoConfig,
+    /// No output layer could be found for the given plane
+    #[error("No EGLOutputLayer found for plane `{0:?}`")]
+    NoOutputLayer(plane::Handle),
+    /// Creating the stream failed
+    #[error("Failed to create EGLStream: {0}")]
+    StreamCreationFailed(#[source] EGLError),
+    /// Connecting the stream to the output layer failed
+    #[error("Failed to connect EGLStream to output layer: {0}")]
+    StreamConnectionFailed(#[source] EGLError),
+    /// Creating the producer surface failed
+    #[error("Failed to create EGLSurface for stream: {0}")]
+    SurfaceCreationFailed(#[source] EGLError),
+    /// Acquiring a new frame failed
+    #[error("Failed to acquire frame from EGLStream: {0}")]
+    AcquireFailed(#[source] EGLError),
+    /// Error enumerating egl devices
+    #[error("The underlying egl implementation encountered an error: {0}")]
+    EglError(#[from] crate::backend::egl::Error),
+    /// Error swapping the surface
+    #[error("Failed to swap buffers: {0}")]
+    SwapBuffersError(#[from] EGLSwapBuffersError),
+    /// Error accessing the drm device
+    #[error("The underlying drm surface encounted an error: {0}")]
+    DrmError(#[from] DrmError),
+    /// Waiting for the rendering of a frame to finish failed
+    #[error("Failed to wait for the render fence: {0}")]
+    FenceWaitFailed(#[source] std::io::Error),
+}
+
+impl From<Error> for SwapBuffersError {
+    fn from(err: Error) -> SwapBuffersError {
+        match err {
+            x @ Error::AcquireFailed(EGLError::ResourceBusy) => {
+                SwapBuffersError::TemporaryFailure(Box::new(x))
+            }
+            Error::DrmError(err) => err.into(),
+            Error::SwapBuffersError(err) => err.into(),
+            x @ Error::FenceWaitFailed(_) => SwapBuffersError::TemporaryFailure(Box::new(x)),
+            x => SwapBuffersError::ContextLost(Box::new(x)),
+        }
+    }
+}
@@ -8,10 +8,13 @@ use gbm::{BufferObject, Device as GbmDevice};
 
 use crate::backend::allocator::{
     dmabuf::{AsDmabuf, Dmabuf},
+    fence::SyncFile,
     gbm::GbmConvertError,
     Format, Fourcc, Modifier, Slot, Swapchain,
 };
-use crate::backend::drm::{device::DevPath, surface::DrmSurfaceInternal, DrmError, DrmSurface};
+use crate::backend::drm::{
+    device::DevPath, surface::DrmSurfaceInternal, DrmError, DrmSurface, RenderSurface,
+};
 use crate::backend::SwapBuffersError;
 
 use slog::{debug, error, o, trace, warn};
@@ -22,6 +25,7 @@ pub struct GbmBufferedSurface<D: AsRawFd + 'static> {
     current_fb: Slot<BufferObject<()>>,
     pending_fb: Option<Slot<BufferObject<()>>>,
     queued_fb: Option<Slot<BufferObject<()>>>,
+    queued_fence: Option<SyncFile>,
     next_fb: Option<Slot<BufferObject<()>>>,
     swapchain: Swapchain<GbmDevice<D>, BufferObject<()>>,
     drm: Arc<DrmSurface<D>>,
@@ -152,6 +156,7 @@ where
                     current_fb: buffer,
                     pending_fb: None,
                     queued_fb: None,
+                    queued_fence: None,
                     next_fb: None,
                     swapchain,
                     drm,
@@ -198,7 +203,26 @@ where
     /// when a vblank event is received, that denotes successful scanout of the buffer.
     /// Otherwise the underlying swapchain will eventually run out of buffers.
     pub fn queue_buffer(&mut self) -> Result<(), Error> {
+        profile_scope!("GbmBufferedSurface::queue_buffer");
+        self.queued_fb = self.next_fb.take();
+        self.queued_fence = None;
+        if self.pending_fb.is_none() && self.queued_fb.is_some() {
+            self.submit()?;
+        }
+        Ok(())
+    }
+
+    /// Queues the current buffer for rendering, once the given fence is signaled.
+    ///
+    /// Depending on the driver the kernel waits for the fence before scanning out the buffer,
+    /// otherwise this blocks until the fence is signaled (see [`DrmSurface::set_in_fence`]).
+    ///
+    /// *Note*: This function needs to be followed up with [`GbmBufferedSurface::frame_submitted`]
+    /// when a vblank event is received, that denotes successful scanout of the buffer.
+    pub fn queue_buffer_with_fence(&mut self, fence: SyncFile) -> Result<(), Error> {
+        profile_scope!("GbmBufferedSurface::queue_buffer");
         self.queued_fb = self.next_fb.take();
+        self.queued_fence = Some(fence);
         if self.pending_fb.is_none() && self.queued_fb.is_some() {
             self.submit()?;
         }
@@ -211,6 +235,7 @@ where
     /// was received after calling [`GbmBufferedSurface::queue_buffer`] on this surface.
     /// Otherwise the underlying swapchain will run out of buffers eventually.
     pub fn frame_submitted(&mut self) -> Result<(), Error> {
+        profile_scope!("GbmBufferedSurface::frame_submitted");
         if let Some(mut pending) = self.pending_fb.take() {
             std::mem::swap(&mut pending, &mut self.current_fb);
             self.swapchain.submitted(pending);
@@ -226,6 +251,9 @@ where
         // yes it does not look like it, but both of these lines should be safe in all cases.
         let slot = self.queued_fb.take().unwrap();
         let fb = slot.userdata().get::<FbHandle<D>>().unwrap().fb;
+        if let Some(fence) = self.queued_fence.take() {
+            self.drm.set_in_fence(fence);
+        }
 
         let flip = if self.drm.commit_pending() {
             self.drm.commit([(fb, self.drm.plane())].iter(), true)
@@ -314,6 +342,50 @@ where
     }
 }
 
+impl<D> RenderSurface for GbmBufferedSurface<D>
+where
+    D: AsRawFd + 'static,
+{
+    type Target = Dmabuf;
+    type Error = Error;
+
+    fn next_buffer(&mut self) -> Result<(Dmabuf, u8), Error> {
+        GbmBufferedSurface::next_buffer(self)
+    }
+
+    fn queue_buffer(&mut self) -> Result<(), Error> {
+        GbmBufferedSurface::queue_buffer(self)
+    }
+
+    fn queue_buffer_with_fence(&mut self, fence: SyncFile) -> Result<(), Error> {
+        GbmBufferedSurface::queue_buffer_with_fence(self, fence)
+    }
+
+    fn frame_submitted(&mut self) -> Result<(), Error> {
+        GbmBufferedSurface::frame_submitted(self)
+    }
+
+    fn crtc(&self) -> crtc::Handle {
+        GbmBufferedSurface::crtc(self)
+    }
+
+    fn plane(&self) -> plane::Handle {
+        GbmBufferedSurface::plane(self)
+    }
+
+    fn current_mode(&self) -> Mode {
+        GbmBufferedSurface::current_mode(self)
+    }
+
+    fn pending_mode(&self) -> Mode {
+        GbmBufferedSurface::pending_mode(self)
+    }
+
+    fn use_mode(&self, mode: Mode) -> Result<(), Error> {
+        GbmBufferedSurface::use_mode(self, mode)
+    }
+}
+
 #[derive(Debug)]
 struct FbHandle<D: AsRawFd + 'static> {
     drm: Arc<DrmSurface<D>>,
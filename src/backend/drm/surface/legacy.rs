@@ -7,13 +7,14 @@ use std::sync::{
     Arc, RwLock,
 };
 
+use crate::backend::allocator::fence::SyncFile;
 use crate::backend::drm::{
     device::legacy::set_connector_state,
     device::{DevPath, DrmDeviceInternal},
     error::Error,
 };
 
-use slog::{debug, info, o, trace};
+use slog::{debug, info, o, trace, warn};
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub struct State {
@@ -257,7 +258,22 @@ impl<A: AsRawFd + 'static> LegacyDrmSurface<A> {
             set_connector_state(&*self.fd, added.copied(), true)?;
 
             if current.mode != pending.mode {
-                info!(self.logger, "Setting new mode: {:?}", pending.mode.name());
+                info!(
+                    self.logger,
+                    "Setting new mode";
+                    "crtc" => ?self.crtc,
+                    "mode" => ?pending.mode.name(),
+                    "size" => ?pending.mode.size(),
+                    "refresh" => pending.mode.vrefresh(),
+                );
+                trace_event!(
+                    INFO,
+                    crtc = ?self.crtc,
+                    mode = ?pending.mode.name(),
+                    size = ?pending.mode.size(),
+                    refresh = pending.mode.vrefresh(),
+                    "Setting new mode"
+                );
             }
         }
 
@@ -306,6 +322,13 @@ impl<A: AsRawFd + 'static> LegacyDrmSurface<A> {
         Ok(())
     }
 
+    pub fn set_in_fence(&self, fence: SyncFile) {
+        // the legacy api has no way to pass fences to the kernel, so we block until rendering is done
+        if let Err(err) = fence.wait(None) {
+            warn!(self.logger, "Failed to wait for fence: {}", err);
+        }
+    }
+
     pub fn page_flip(&self, framebuffer: framebuffer::Handle, event: bool) -> Result<(), Error> {
         trace!(self.logger, "Queueing Page flip");
 
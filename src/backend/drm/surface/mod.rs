@@ -11,11 +11,14 @@ use drm::{Device as BasicDevice, DriverCapability};
 use nix::libc::dev_t;
 
 pub(super) mod atomic;
+#[cfg(feature = "backend_drm_eglstream")]
+pub(super) mod eglstream;
 #[cfg(feature = "backend_gbm")]
 pub(super) mod gbm;
 pub(super) mod legacy;
 use super::{device::DevPath, error::Error, plane_type, planes, PlaneType, Planes};
-use crate::backend::allocator::{Format, Fourcc, Modifier};
+use crate::backend::allocator::{fence::SyncFile, Format, Fourcc, Modifier};
+use crate::backend::SwapBuffersError;
 use atomic::AtomicDrmSurface;
 use legacy::LegacyDrmSurface;
 
@@ -41,6 +44,62 @@ pub enum DrmSurfaceInternal<A: AsRawFd + 'static> {
     Legacy(LegacyDrmSurface<A>),
 }
 
+/// Common interface of the buffer-management abstractions built on top of a [`DrmSurface`].
+///
+/// Different driver stacks require different ways of getting rendered contents onto a plane,
+/// e.g. allocating buffers via gbm ([`GbmBufferedSurface`](super::GbmBufferedSurface)) or
+/// letting the driver manage them through EGLStreams (`EglStreamSurface`, gated by the
+/// `backend_drm_eglstream` feature). This trait allows compositors to drive any of these
+/// the same way, given their renderer is able to [`Bind`](crate::backend::renderer::Bind)
+/// the respective [`Target`](RenderSurface::Target).
+pub trait RenderSurface {
+    /// Render target returned by [`next_buffer`](RenderSurface::next_buffer)
+    type Target;
+    /// Error type returned by the operations of this surface
+    type Error: std::error::Error + Into<SwapBuffersError>;
+
+    /// Retrieves the next target to be rendered into and its age.
+    ///
+    /// An age of `0` denotes unknown contents, requiring a full redraw.
+    ///
+    /// *Note*: This function can be called multiple times and
+    /// will return the same target until it is queued (see [`RenderSurface::queue_buffer`]).
+    fn next_buffer(&mut self) -> Result<(Self::Target, u8), Self::Error>;
+    /// Queues the current target for scan-out.
+    ///
+    /// *Note*: This function needs to be followed up with [`RenderSurface::frame_submitted`]
+    /// when a vblank event is received, that denotes successful scanout of the buffer.
+    fn queue_buffer(&mut self) -> Result<(), Self::Error>;
+    /// Queues the current target for scan-out, once the given fence is signaled.
+    ///
+    /// The fence usually denotes the completion of rendering into the target
+    /// (see [`Gles2Renderer::take_render_fence`](crate::backend::renderer::gles2::Gles2Renderer::take_render_fence)).
+    ///
+    /// Implementations unable to pass the fence on to the display hardware (see
+    /// [`DrmSurface::set_in_fence`]) need to block until the fence is signaled
+    /// and fail, if waiting for it fails.
+    fn queue_buffer_with_fence(&mut self, fence: SyncFile) -> Result<(), Self::Error>;
+    /// Marks the current frame as submitted.
+    ///
+    /// *Note*: Needs to be called, after the vblank event of the matching [`DrmDevice`](super::DrmDevice)
+    /// was received after calling [`RenderSurface::queue_buffer`] on this surface.
+    fn frame_submitted(&mut self) -> Result<(), Self::Error>;
+
+    /// Returns the underlying [`crtc`](drm::control::crtc) of this surface
+    fn crtc(&self) -> crtc::Handle;
+    /// Returns the underlying [`plane`](drm::control::plane) of this surface
+    fn plane(&self) -> plane::Handle;
+    /// Returns the currently active [`Mode`](drm::control::Mode)
+    /// of the underlying [`crtc`](drm::control::crtc)
+    fn current_mode(&self) -> Mode;
+    /// Returns the currently pending [`Mode`](drm::control::Mode)
+    /// to be used after the next commit.
+    fn pending_mode(&self) -> Mode;
+    /// Tries to set a new [`Mode`](drm::control::Mode)
+    /// to be used after the next commit.
+    fn use_mode(&self, mode: Mode) -> Result<(), Self::Error>;
+}
+
 impl<A: AsRawFd + 'static> AsRawFd for DrmSurface<A> {
     fn as_raw_fd(&self) -> RawFd {
         match &*self.internal {
@@ -214,6 +273,8 @@ impl<A: AsRawFd + 'static> DrmSurface<A> {
         mut framebuffers: impl Iterator<Item = &'a (framebuffer::Handle, plane::Handle)>,
         event: bool,
     ) -> Result<(), Error> {
+        profile_scope!("DrmSurface::commit");
+        trace_span!(DEBUG, "DrmSurface::commit", crtc = ?self.crtc(), event);
         match &*self.internal {
             DrmSurfaceInternal::Atomic(surf) => surf.commit(framebuffers, event),
             DrmSurfaceInternal::Legacy(surf) => {
@@ -241,6 +302,8 @@ impl<A: AsRawFd + 'static> DrmSurface<A> {
         mut framebuffers: impl Iterator<Item = &'a (framebuffer::Handle, plane::Handle)>,
         event: bool,
     ) -> Result<(), Error> {
+        profile_scope!("DrmSurface::page_flip");
+        trace_span!(TRACE, "DrmSurface::page_flip", crtc = ?self.crtc(), event);
         match &*self.internal {
             DrmSurfaceInternal::Atomic(surf) => surf.page_flip(framebuffers, event),
             DrmSurfaceInternal::Legacy(surf) => {
@@ -256,6 +319,18 @@ impl<A: AsRawFd + 'static> DrmSurface<A> {
         }
     }
 
+    /// Set a fence, that needs to be signaled before the framebuffer of the primary plane
+    /// is scanned out by the next [`commit`](DrmSurface::commit) or [`page_flip`](DrmSurface::page_flip).
+    ///
+    /// On atomic devices supporting the `IN_FENCE_FD` plane property, the kernel waits for the fence.
+    /// Otherwise the fence is waited upon on the cpu, blocking until it is signaled.
+    pub fn set_in_fence(&self, fence: SyncFile) {
+        match &*self.internal {
+            DrmSurfaceInternal::Atomic(surf) => surf.set_in_fence(fence),
+            DrmSurfaceInternal::Legacy(surf) => surf.set_in_fence(fence),
+        }
+    }
+
     /// Returns a set of supported pixel formats for attached buffers
     pub fn supported_formats(&self, plane: plane::Handle) -> Result<HashSet<Format>, Error> {
         // get plane formats
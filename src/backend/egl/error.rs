@@ -48,6 +48,15 @@ pub enum Error {
     /// Failed to query the available `EGLDevice`s
     #[error("Failed to query the available `EGLDevice`s")]
     QueryDevices(#[source] EGLError),
+    /// Failed to create a fence
+    #[error("Failed to create a fence")]
+    FenceCreationFailed(#[source] EGLError),
+    /// Failed to export a fence as native fence file descriptor
+    #[error("Failed to export a native fence")]
+    FenceExportFailed(#[source] EGLError),
+    /// Failed to wait for a fence
+    #[error("Failed to wait for a fence")]
+    FenceWaitFailed(#[source] EGLError),
 }
 
 /// Raw EGL error
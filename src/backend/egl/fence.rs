@@ -0,0 +1,140 @@
+//! EGL native fence synchronization
+//!
+//! Provides [`EGLFence`], a wrapper around `EGL_ANDROID_native_fence_sync` fences,
+//! which can be exported as and imported from [`SyncFile`]s.
+
+use std::os::unix::io::{FromRawFd, IntoRawFd};
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::backend::allocator::fence::SyncFile;
+
+use super::{display::EGLDisplayHandle, ffi, wrap_egl_call, EGLDisplay, EGLError, Error};
+
+/// A native fence sync object
+#[derive(Debug)]
+pub struct EGLFence {
+    display: Arc<EGLDisplayHandle>,
+    sync: ffi::egl::types::EGLSyncKHR,
+}
+
+impl EGLFence {
+    /// Returns if native fences are supported by the given display
+    pub fn is_supported(display: &EGLDisplay) -> bool {
+        display
+            .extensions
+            .iter()
+            .any(|ext| ext == "EGL_ANDROID_native_fence_sync")
+            && display.extensions.iter().any(|ext| ext == "EGL_KHR_fence_sync")
+    }
+
+    /// Create a new fence, signaled once all commands previously submitted
+    /// to the currently bound context are completed.
+    ///
+    /// A context of the given display needs to be current. The fence is only
+    /// inserted into the command stream on the next flush of the context.
+    pub fn create(display: &EGLDisplay) -> Result<EGLFence, Error> {
+        Self::create_native(display, ffi::egl::NO_NATIVE_FENCE_FD_ANDROID)
+    }
+
+    /// Import a [`SyncFile`] as fence.
+    ///
+    /// Use [`EGLFence::wait`] to let the gpu wait for it before executing further commands.
+    pub fn import(display: &EGLDisplay, sync_file: SyncFile) -> Result<EGLFence, Error> {
+        let fd = sync_file.into_raw_fd();
+        // on success the fd is owned by the implementation
+        let result = Self::create_native(display, fd);
+        if result.is_err() {
+            let _ = unsafe { SyncFile::from_raw_fd(fd) };
+        }
+        result
+    }
+
+    fn create_native(display: &EGLDisplay, fd: i32) -> Result<EGLFence, Error> {
+        if !Self::is_supported(display) {
+            return Err(Error::EglExtensionNotSupported(&[
+                "EGL_KHR_fence_sync",
+                "EGL_ANDROID_native_fence_sync",
+            ]));
+        }
+
+        let attribs = [
+            ffi::egl::SYNC_NATIVE_FENCE_FD_ANDROID as i32,
+            fd,
+            ffi::egl::NONE as i32,
+        ];
+        let sync = wrap_egl_call(|| unsafe {
+            ffi::egl::CreateSyncKHR(
+                **display.display,
+                ffi::egl::SYNC_NATIVE_FENCE_ANDROID,
+                attribs.as_ptr(),
+            )
+        })
+        .map_err(Error::FenceCreationFailed)?;
+        if sync == ffi::egl::NO_SYNC {
+            return Err(Error::FenceCreationFailed(EGLError::BadParameter));
+        }
+
+        Ok(EGLFence {
+            display: display.display.clone(),
+            sync,
+        })
+    }
+
+    /// Export this fence as [`SyncFile`].
+    ///
+    /// Fails for fences created with [`EGLFence::create`], if the context was not flushed yet.
+    pub fn export(&self) -> Result<SyncFile, Error> {
+        let fd = wrap_egl_call(|| unsafe { ffi::egl::DupNativeFenceFDANDROID(**self.display, self.sync) })
+            .map_err(Error::FenceExportFailed)?;
+        if fd == ffi::egl::NO_NATIVE_FENCE_FD_ANDROID {
+            return Err(Error::FenceExportFailed(EGLError::BadParameter));
+        }
+        Ok(unsafe { SyncFile::from_raw_fd(fd) })
+    }
+
+    /// Let the gpu wait for this fence, before executing any further commands
+    /// of the currently bound context. Does not block.
+    ///
+    /// Requires the `EGL_KHR_wait_sync` extension.
+    pub fn wait(&self) -> Result<(), Error> {
+        let result = wrap_egl_call(|| unsafe { ffi::egl::WaitSyncKHR(**self.display, self.sync, 0) })
+            .map_err(Error::FenceWaitFailed)?;
+        if result != ffi::egl::TRUE as i32 {
+            return Err(Error::FenceWaitFailed(EGLError::BadParameter));
+        }
+        Ok(())
+    }
+
+    /// Block until the fence is signaled or the timeout expires.
+    ///
+    /// Returns `true` if the fence was signaled and `false` if the timeout expired.
+    /// A `timeout` of `None` waits indefinitely.
+    pub fn client_wait(&self, timeout: Option<Duration>) -> Result<bool, Error> {
+        let timeout = timeout
+            .map(|timeout| timeout.as_nanos().min(u64::MAX as u128 - 1) as u64)
+            .unwrap_or(ffi::egl::FOREVER);
+        let result = wrap_egl_call(|| unsafe {
+            ffi::egl::ClientWaitSyncKHR(
+                **self.display,
+                self.sync,
+                ffi::egl::SYNC_FLUSH_COMMANDS_BIT as i32,
+                timeout,
+            )
+        })
+        .map_err(Error::FenceWaitFailed)?;
+        match result as u32 {
+            ffi::egl::CONDITION_SATISFIED => Ok(true),
+            ffi::egl::TIMEOUT_EXPIRED => Ok(false),
+            _ => Err(Error::FenceWaitFailed(EGLError::BadParameter)),
+        }
+    }
+}
+
+impl Drop for EGLFence {
+    fn drop(&mut self) {
+        unsafe {
+            ffi::egl::DestroySyncKHR(**self.display, self.sync);
+        }
+    }
+}
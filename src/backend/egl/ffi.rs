@@ -75,6 +75,7 @@ pub fn make_sure_egl_is_loaded() -> Result<Vec<String>, Error> {
         egl::UnbindWaylandDisplayWL::load_with(&proc_address);
         egl::QueryWaylandBufferWL::load_with(&proc_address);
         egl::DebugMessageControlKHR::load_with(&proc_address);
+        egl::StreamConsumerAcquireAttribNV::load_with(&proc_address);
     });
 
     let extensions = unsafe {
@@ -161,6 +162,33 @@ pub mod egl {
             extern "system" fn(EGLDEBUGPROCKHR, *const types::EGLAttrib) -> types::EGLint,
         >(wayland_storage::DebugMessageControlKHR.f)(callback, attrib_list)
     }
+    /*
+     * `gl_generator` does not know about the `EGL_EXT_stream_acquire_mode`, `EGL_NV_stream_attrib`
+     *  and `EGL_NV_output_drm_flip_event` extensions, used to drive EGLStreams on a drm plane.
+     */
+
+    // Accepted as attribute in eglCreateStreamKHR
+    pub const CONSUMER_AUTO_ACQUIRE_EXT: c_uint = 0x332B;
+    // Accepted as attribute in eglStreamConsumerAcquireAttribNV
+    pub const DRM_FLIP_EVENT_DATA_NV: c_uint = 0x333E;
+
+    #[allow(non_snake_case, unused_variables, dead_code)]
+    #[inline]
+    pub unsafe fn StreamConsumerAcquireAttribNV(
+        dpy: types::EGLDisplay,
+        stream: types::EGLStreamKHR,
+        attrib_list: *const types::EGLAttrib,
+    ) -> types::EGLBoolean {
+        __gl_imports::mem::transmute::<
+            _,
+            extern "system" fn(
+                types::EGLDisplay,
+                types::EGLStreamKHR,
+                *const types::EGLAttrib,
+            ) -> types::EGLBoolean,
+        >(wayland_storage::StreamConsumerAcquireAttribNV.f)(dpy, stream, attrib_list)
+    }
+
     /*
      * `gl_generator` cannot generate bindings for the `EGL_WL_bind_wayland_display` extension.
      *  Lets do it ourselves...
@@ -227,6 +255,32 @@ pub mod egl {
             f: super::missing_fn_panic as *const raw::c_void,
             is_loaded: false,
         };
+        pub static mut StreamConsumerAcquireAttribNV: FnPtr = FnPtr {
+            f: super::missing_fn_panic as *const raw::c_void,
+            is_loaded: false,
+        };
+    }
+
+    #[allow(non_snake_case)]
+    pub mod StreamConsumerAcquireAttribNV {
+        use super::{FnPtr, __gl_imports::raw, metaloadfn, wayland_storage};
+
+        #[inline]
+        #[allow(dead_code)]
+        pub fn is_loaded() -> bool {
+            unsafe { wayland_storage::StreamConsumerAcquireAttribNV.is_loaded }
+        }
+
+        #[allow(dead_code)]
+        pub fn load_with<F>(mut loadfn: F)
+        where
+            F: FnMut(&'static str) -> *const raw::c_void,
+        {
+            unsafe {
+                wayland_storage::StreamConsumerAcquireAttribNV =
+                    FnPtr::new(metaloadfn(&mut loadfn, "eglStreamConsumerAcquireAttribNV", &[]))
+            }
+        }
     }
 
     #[allow(non_snake_case)]
@@ -12,6 +12,7 @@ use crate::backend::egl::{
     native::EGLNativeSurface,
     EGLError, SwapBuffersError,
 };
+use crate::utils::{Physical, Size};
 
 use slog::{debug, o};
 
@@ -22,6 +23,7 @@ pub struct EGLSurface {
     pub(crate) surface: AtomicPtr<nix::libc::c_void>,
     config_id: ffi::egl::types::EGLConfig,
     pixel_format: PixelFormat,
+    buffer_age_supported: bool,
     logger: ::slog::Logger,
 }
 
@@ -75,6 +77,7 @@ impl EGLSurface {
             surface: AtomicPtr::new(surface as *mut _),
             config_id: config,
             pixel_format,
+            buffer_age_supported: display.extensions.iter().any(|ext| ext == "EGL_EXT_buffer_age"),
             logger: log,
         })
     }
@@ -122,6 +125,35 @@ impl EGLSurface {
         }
     }
 
+    /// Returns the age of the current back buffer of the surface.
+    ///
+    /// The age is the number of frames, since the contents of the buffer were last presented.
+    /// An age of `0` denotes unknown contents, requiring a full redraw.
+    ///
+    /// Returns `None` if `EGL_EXT_buffer_age` is not supported or the surface is invalid.
+    /// The surface needs to be current for the query to succeed.
+    pub fn buffer_age(&self) -> Option<i32> {
+        let surface = self.surface.load(Ordering::SeqCst);
+        if surface.is_null() || !self.buffer_age_supported {
+            return None;
+        }
+
+        let mut age = 0;
+        let queried = unsafe {
+            ffi::egl::QuerySurface(
+                **self.display,
+                surface as *const _,
+                ffi::egl::BUFFER_AGE_EXT as i32,
+                &mut age,
+            ) == ffi::egl::TRUE
+        };
+        if queried {
+            Some(age)
+        } else {
+            None
+        }
+    }
+
     /// Returns true if the OpenGL surface is the current one in the thread.
     pub fn is_current(&self) -> bool {
         let surface = self.surface.load(Ordering::SeqCst);
@@ -141,6 +173,38 @@ impl EGLSurface {
         self.pixel_format
     }
 
+    /// Returns the current size of the surface as reported by EGL.
+    ///
+    /// Returns `None` if the surface is currently invalid or the size could not be queried.
+    pub fn get_size(&self) -> Option<Size<i32, Physical>> {
+        let surface = self.surface.load(Ordering::SeqCst);
+        if surface.is_null() {
+            return None;
+        }
+
+        let mut width = 0;
+        let mut height = 0;
+        let queried = unsafe {
+            ffi::egl::QuerySurface(
+                **self.display,
+                surface as *const _,
+                ffi::egl::WIDTH as i32,
+                &mut width,
+            ) == ffi::egl::TRUE
+                && ffi::egl::QuerySurface(
+                    **self.display,
+                    surface as *const _,
+                    ffi::egl::HEIGHT as i32,
+                    &mut height,
+                ) == ffi::egl::TRUE
+        };
+        if queried {
+            Some(Size::from((width, height)))
+        } else {
+            None
+        }
+    }
+
     /// Tries to resize the underlying native surface.
     ///
     /// The two first arguments (width, height) are the new size of the surface,
@@ -0,0 +1,355 @@
+//! Input backend implementation for the headless backend.
+
+use std::{
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    },
+    time::Instant,
+};
+
+use calloop::channel::Sender;
+
+use crate::backend::input::{
+    self, Axis, AxisSource, ButtonState, Device, DeviceCapability, InputBackend, InputEvent, KeyState,
+    KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent, PointerMotionEvent,
+    UnusedEvent,
+};
+
+/// Marker used to define the `InputBackend` types for the headless backend.
+#[derive(Debug)]
+pub struct HeadlessInput;
+
+/// Virtual input device used by the backend to associate injected input events.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct HeadlessVirtualDevice;
+
+impl Device for HeadlessVirtualDevice {
+    fn id(&self) -> String {
+        "headless".to_owned()
+    }
+
+    fn name(&self) -> String {
+        "headless virtual input".to_owned()
+    }
+
+    fn has_capability(&self, capability: DeviceCapability) -> bool {
+        matches!(capability, DeviceCapability::Keyboard | DeviceCapability::Pointer)
+    }
+
+    fn usb_id(&self) -> Option<(u32, u32)> {
+        None
+    }
+
+    fn syspath(&self) -> Option<std::path::PathBuf> {
+        None
+    }
+}
+
+/// Headless-Backend internal event wrapping injected key presses into a [`KeyboardKeyEvent`].
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HeadlessKeyboardInputEvent {
+    pub(crate) time: u32,
+    pub(crate) key: u32,
+    pub(crate) count: u32,
+    pub(crate) state: KeyState,
+}
+
+impl input::Event<HeadlessInput> for HeadlessKeyboardInputEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> HeadlessVirtualDevice {
+        HeadlessVirtualDevice
+    }
+}
+
+impl KeyboardKeyEvent<HeadlessInput> for HeadlessKeyboardInputEvent {
+    fn key_code(&self) -> u32 {
+        self.key
+    }
+
+    fn state(&self) -> KeyState {
+        self.state
+    }
+
+    fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+/// Headless-Backend internal event wrapping injected scrolling into a [`PointerAxisEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadlessMouseWheelEvent {
+    pub(crate) time: u32,
+    pub(crate) axis: Axis,
+    pub(crate) amount: f64,
+    pub(crate) discrete: Option<f64>,
+    pub(crate) source: AxisSource,
+}
+
+impl input::Event<HeadlessInput> for HeadlessMouseWheelEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> HeadlessVirtualDevice {
+        HeadlessVirtualDevice
+    }
+}
+
+impl PointerAxisEvent<HeadlessInput> for HeadlessMouseWheelEvent {
+    fn amount(&self, axis: Axis) -> Option<f64> {
+        if self.axis == axis {
+            Some(self.amount)
+        } else {
+            Some(0.0)
+        }
+    }
+
+    fn amount_discrete(&self, axis: Axis) -> Option<f64> {
+        self.discrete
+            .map(|discrete| if self.axis == axis { discrete } else { 0.0 })
+    }
+
+    fn source(&self) -> AxisSource {
+        self.source
+    }
+}
+
+/// Headless-Backend internal event wrapping injected button presses into a [`PointerButtonEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct HeadlessMouseInputEvent {
+    pub(crate) time: u32,
+    pub(crate) button: u32,
+    pub(crate) state: ButtonState,
+}
+
+impl input::Event<HeadlessInput> for HeadlessMouseInputEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> HeadlessVirtualDevice {
+        HeadlessVirtualDevice
+    }
+}
+
+impl PointerButtonEvent<HeadlessInput> for HeadlessMouseInputEvent {
+    fn button_code(&self) -> u32 {
+        self.button
+    }
+
+    fn state(&self) -> ButtonState {
+        self.state
+    }
+}
+
+/// Headless-Backend internal event wrapping injected relative motion into a [`PointerMotionEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadlessMouseMotionEvent {
+    pub(crate) time: u32,
+    pub(crate) dx: f64,
+    pub(crate) dy: f64,
+}
+
+impl input::Event<HeadlessInput> for HeadlessMouseMotionEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> HeadlessVirtualDevice {
+        HeadlessVirtualDevice
+    }
+}
+
+impl PointerMotionEvent<HeadlessInput> for HeadlessMouseMotionEvent {
+    fn delta_x(&self) -> f64 {
+        self.dx
+    }
+
+    fn delta_y(&self) -> f64 {
+        self.dy
+    }
+}
+
+/// Headless-Backend internal event wrapping injected absolute motion into a [`PointerMotionAbsoluteEvent`]
+///
+/// The position is normalized to the range `0.0..=1.0`.
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct HeadlessMouseMovedEvent {
+    pub(crate) time: u32,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+}
+
+impl input::Event<HeadlessInput> for HeadlessMouseMovedEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> HeadlessVirtualDevice {
+        HeadlessVirtualDevice
+    }
+}
+
+impl PointerMotionAbsoluteEvent<HeadlessInput> for HeadlessMouseMovedEvent {
+    fn x(&self) -> f64 {
+        self.x
+    }
+
+    fn y(&self) -> f64 {
+        self.y
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        self.x * width as f64
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        self.y * height as f64
+    }
+}
+
+impl InputBackend for HeadlessInput {
+    type Device = HeadlessVirtualDevice;
+    type KeyboardKeyEvent = HeadlessKeyboardInputEvent;
+    type PointerAxisEvent = HeadlessMouseWheelEvent;
+    type PointerButtonEvent = HeadlessMouseInputEvent;
+    type PointerMotionEvent = HeadlessMouseMotionEvent;
+    type PointerMotionAbsoluteEvent = HeadlessMouseMovedEvent;
+
+    type TouchDownEvent = UnusedEvent;
+    type TouchUpEvent = UnusedEvent;
+    type TouchMotionEvent = UnusedEvent;
+    type TouchCancelEvent = UnusedEvent;
+    type TouchFrameEvent = UnusedEvent;
+    type TabletToolAxisEvent = UnusedEvent;
+    type TabletToolProximityEvent = UnusedEvent;
+    type TabletToolTipEvent = UnusedEvent;
+    type TabletToolButtonEvent = UnusedEvent;
+
+    type SpecialEvent = UnusedEvent;
+}
+
+/// The headless backend the input was injected into was destroyed.
+#[derive(Debug, thiserror::Error)]
+#[error("The headless backend was destroyed")]
+pub struct BackendDestroyed;
+
+/// Handle to inject input events into a [`HeadlessBackend`](super::HeadlessBackend).
+///
+/// The handle can be cloned and sent to other threads, e.g. the thread of a remote-display
+/// server receiving the input of its clients. The injected events are emitted by the backend
+/// as [`HeadlessEvent::Input`](super::HeadlessEvent::Input).
+#[derive(Debug, Clone)]
+pub struct VirtualInputHandle {
+    pub(super) sender: Sender<InputEvent<HeadlessInput>>,
+    pub(super) start: Instant,
+    pub(super) key_counter: Arc<AtomicU32>,
+}
+
+impl VirtualInputHandle {
+    fn time(&self) -> u32 {
+        self.start.elapsed().as_millis() as u32
+    }
+
+    fn send(&self, event: InputEvent<HeadlessInput>) -> Result<(), BackendDestroyed> {
+        self.sender.send(event).map_err(|_| BackendDestroyed)
+    }
+
+    /// Inject a key press or release.
+    ///
+    /// `key` is a linux input event code (e.g. `KEY_A`). Remote-display protocols transmitting
+    /// keysyms need to translate them into key codes first.
+    pub fn key(&self, key: u32, state: KeyState) -> Result<(), BackendDestroyed> {
+        let count = match state {
+            KeyState::Pressed => self.key_counter.fetch_add(1, Ordering::SeqCst) + 1,
+            KeyState::Released => {
+                // never fails, the closure always returns a value
+                let previous = self
+                    .key_counter
+                    .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |count| {
+                        Some(count.saturating_sub(1))
+                    })
+                    .unwrap();
+                previous.saturating_sub(1)
+            }
+        };
+        self.send(InputEvent::Keyboard {
+            event: HeadlessKeyboardInputEvent {
+                time: self.time(),
+                key,
+                count,
+                state,
+            },
+        })
+    }
+
+    /// Inject a relative pointer motion.
+    pub fn pointer_motion(&self, dx: f64, dy: f64) -> Result<(), BackendDestroyed> {
+        self.send(InputEvent::PointerMotion {
+            event: HeadlessMouseMotionEvent {
+                time: self.time(),
+                dx,
+                dy,
+            },
+        })
+    }
+
+    /// Inject an absolute pointer motion.
+    ///
+    /// The position is normalized to the range `0.0..=1.0` relative to the area viewed
+    /// by the remote client, e.g. the output it is displaying. Use
+    /// [`PointerMotionAbsoluteEvent::x_transformed`] and [`PointerMotionAbsoluteEvent::y_transformed`]
+    /// to map it into the output.
+    pub fn pointer_motion_absolute(&self, x: f64, y: f64) -> Result<(), BackendDestroyed> {
+        self.send(InputEvent::PointerMotionAbsolute {
+            event: HeadlessMouseMovedEvent {
+                time: self.time(),
+                x: x.clamp(0.0, 1.0),
+                y: y.clamp(0.0, 1.0),
+            },
+        })
+    }
+
+    /// Inject a pointer button press or release.
+    ///
+    /// `button` is a linux input event code (e.g. `BTN_LEFT`).
+    pub fn pointer_button(&self, button: u32, state: ButtonState) -> Result<(), BackendDestroyed> {
+        self.send(InputEvent::PointerButton {
+            event: HeadlessMouseInputEvent {
+                time: self.time(),
+                button,
+                state,
+            },
+        })
+    }
+
+    /// Inject scrolling along an axis.
+    ///
+    /// `discrete` denotes the amount of scroll wheel steps, if the scrolling originates from a wheel.
+    pub fn pointer_axis(
+        &self,
+        axis: Axis,
+        amount: f64,
+        discrete: Option<f64>,
+        source: AxisSource,
+    ) -> Result<(), BackendDestroyed> {
+        self.send(InputEvent::PointerAxis {
+            event: HeadlessMouseWheelEvent {
+                time: self.time(),
+                axis,
+                amount,
+                discrete,
+                source,
+            },
+        })
+    }
+}
@@ -0,0 +1,449 @@
+//! Implementation of a headless backend, e.g. for remote-display servers.
+//!
+//! This backend does not display anything by itself. Instead it pairs virtual outputs with a
+//! frame-ready callback, which receives every rendered frame, and provides a handle to inject
+//! input events. This gives remote-display servers (e.g. VNC or RDP) a clean integration point,
+//! without requiring any display hardware.
+//!
+//! The backend is initialized using [`HeadlessBackend::new`]:
+//!
+//! - The [`HeadlessBackend`] is inserted into an [`EventLoop`](calloop::EventLoop) to receive
+//!   injected input and the (emulated) vblanks of the virtual outputs as [`HeadlessEvent`]s.
+//! - [`HeadlessBackend::create_output`] creates a [`VirtualOutput`], which is rendered to using
+//!   [`VirtualOutput::render_to_dmabuf`] or [`VirtualOutput::render_to_memory`]. Rendered frames
+//!   are passed to the callback set with [`VirtualOutput::set_frame_callback`] as [`HeadlessFrame`].
+//! - [`HeadlessBackend::input_handle`] returns a [`VirtualInputHandle`] to inject input, which may
+//!   be sent to other threads.
+//!
+//! ## Example usage
+//!
+//! ```rust,no_run
+//! # use std::error::Error;
+//! use smithay::backend::headless::{HeadlessBackend, HeadlessEvent, HeadlessFrame};
+//!
+//! # struct CompositorState;
+//! fn init_headless_backend(
+//!    handle: calloop::LoopHandle<CompositorState>,
+//!    logger: slog::Logger
+//! ) -> Result<(), Box<dyn Error>> {
+//!     let mut backend = HeadlessBackend::new(logger)?;
+//!
+//!     // Create a virtual output with 60 Hz and pass its frames to the remote-display server.
+//!     let mut output = backend.create_output("remote-1", (1920, 1080), 60_000);
+//!     output.set_frame_callback(|frame| match frame {
+//!         HeadlessFrame::Memory { data, size, damage, .. } => { /* encode and send the damaged parts */ }
+//!         HeadlessFrame::Dmabuf { dmabuf, damage } => { /* e.g. pass the dmabuf to a hardware encoder */ }
+//!     });
+//!
+//!     // The remote-display server injects the input of its clients through this handle.
+//!     let input = backend.input_handle();
+//!
+//!     handle.insert_source(backend, |event, _, state| match event {
+//!         HeadlessEvent::Input(event) => { /* process input */ }
+//!         HeadlessEvent::VBlank(output) => { /* render the next frame of the output */ }
+//!     })?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+mod input;
+
+use crate::{
+    backend::{
+        allocator::{dmabuf::Dmabuf, Fourcc},
+        input::InputEvent,
+        renderer::{
+            damage::OutputDamageTracker, element::RenderElement, Bind, ExportMem, Offscreen, Renderer,
+            Transform,
+        },
+    },
+    utils::{Buffer, Physical, Rectangle, Size},
+};
+use calloop::{
+    channel::{self, Channel},
+    timer::{Timer, TimerHandle},
+    EventSource, Poll, PostAction, Readiness, Token, TokenFactory,
+};
+use slog::{info, o, Logger};
+use std::{
+    any::Any,
+    fmt, io,
+    sync::{atomic::AtomicU32, Arc},
+    time::{Duration, Instant},
+};
+
+pub use self::input::*;
+
+/// An event emitted by the headless backend.
+#[derive(Debug)]
+pub enum HeadlessEvent {
+    /// An input event was injected through a [`VirtualInputHandle`].
+    Input(InputEvent<HeadlessInput>),
+
+    /// The emulated vblank of a virtual output occurred.
+    ///
+    /// When this event is emitted, the next frame of the output may be rendered.
+    VBlank(VirtualOutputId),
+}
+
+/// Unique identifier of a [`VirtualOutput`] of a [`HeadlessBackend`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct VirtualOutputId(usize);
+
+/// Headless backend, emitting injected input and the vblanks of its virtual outputs.
+#[derive(Debug)]
+pub struct HeadlessBackend {
+    log: Logger,
+    input: Channel<InputEvent<HeadlessInput>>,
+    input_handle: VirtualInputHandle,
+    timer: Timer<VirtualOutputId>,
+    next_output_id: usize,
+}
+
+impl HeadlessBackend {
+    /// Initializes the headless backend.
+    pub fn new<L>(logger: L) -> io::Result<HeadlessBackend>
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "backend_headless"));
+        let (sender, input) = channel::channel();
+        let timer = Timer::new()?;
+
+        info!(log, "Initialized headless backend");
+
+        Ok(HeadlessBackend {
+            log,
+            input,
+            input_handle: VirtualInputHandle {
+                sender,
+                start: Instant::now(),
+                key_counter: Arc::new(AtomicU32::new(0)),
+            },
+            timer,
+            next_output_id: 0,
+        })
+    }
+
+    /// Returns a handle to inject input events into this backend.
+    pub fn input_handle(&self) -> VirtualInputHandle {
+        self.input_handle.clone()
+    }
+
+    /// Creates a new virtual output with the given size and refresh rate in millihertz.
+    pub fn create_output(
+        &mut self,
+        name: impl Into<String>,
+        size: impl Into<Size<i32, Physical>>,
+        refresh: i32,
+    ) -> VirtualOutput {
+        let id = VirtualOutputId(self.next_output_id);
+        self.next_output_id += 1;
+
+        let name = name.into();
+        let size = size.into();
+        info!(
+            self.log,
+            "Created virtual output {} ({}x{}@{}mHz)", name, size.w, size.h, refresh
+        );
+
+        VirtualOutput {
+            id,
+            name,
+            size,
+            refresh,
+            damage_tracker: OutputDamageTracker::new(size, 1.0, Transform::Normal),
+            offscreen: None,
+            frame_callback: None,
+            timer: self.timer.handle(),
+            next_vblank: None,
+        }
+    }
+}
+
+impl EventSource for HeadlessBackend {
+    type Event = HeadlessEvent;
+    type Metadata = ();
+    type Ret = ();
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> io::Result<PostAction>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        self.input.process_events(readiness, token, |event, _| {
+            if let channel::Event::Msg(event) = event {
+                callback(HeadlessEvent::Input(event), &mut ());
+            }
+        })?;
+        self.timer.process_events(readiness, token, |id, _| {
+            callback(HeadlessEvent::VBlank(id), &mut ());
+        })?;
+        Ok(PostAction::Continue)
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+        self.input.register(poll, token_factory)?;
+        self.timer.register(poll, token_factory)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+        self.input.reregister(poll, token_factory)?;
+        self.timer.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> io::Result<()> {
+        self.input.unregister(poll)?;
+        self.timer.unregister(poll)
+    }
+}
+
+/// A frame rendered to a [`VirtualOutput`]
+#[derive(Debug)]
+pub enum HeadlessFrame<'a> {
+    /// The frame was rendered into a dmabuf
+    Dmabuf {
+        /// The dmabuf containing the frame
+        dmabuf: &'a Dmabuf,
+        /// The damaged regions compared to the previous frame rendered into this dmabuf
+        damage: &'a [Rectangle<i32, Physical>],
+    },
+    /// The frame was rendered and read back into memory
+    Memory {
+        /// Pixel data ordered row by row from the top-left corner
+        data: &'a [u8],
+        /// Size of the frame in pixels
+        size: Size<i32, Buffer>,
+        /// Format of the pixel data
+        format: Fourcc,
+        /// The damaged regions compared to the previous frame
+        damage: &'a [Rectangle<i32, Physical>],
+    },
+}
+
+type FrameCallback = Box<dyn FnMut(HeadlessFrame<'_>)>;
+
+/// A virtual output of a [`HeadlessBackend`]
+///
+/// Rendering is damage tracked, only the changed parts of an output are redrawn and
+/// reported to the frame callback.
+pub struct VirtualOutput {
+    id: VirtualOutputId,
+    name: String,
+    size: Size<i32, Physical>,
+    refresh: i32,
+    damage_tracker: OutputDamageTracker,
+    offscreen: Option<Box<dyn Any>>,
+    frame_callback: Option<FrameCallback>,
+    timer: TimerHandle<VirtualOutputId>,
+    next_vblank: Option<Instant>,
+}
+
+impl fmt::Debug for VirtualOutput {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VirtualOutput")
+            .field("id", &self.id)
+            .field("name", &self.name)
+            .field("size", &self.size)
+            .field("refresh", &self.refresh)
+            .field("damage_tracker", &self.damage_tracker)
+            .field("next_vblank", &self.next_vblank)
+            .finish_non_exhaustive()
+    }
+}
+
+impl VirtualOutput {
+    /// Returns the identifier of this output, used by [`HeadlessEvent::VBlank`].
+    pub fn id(&self) -> VirtualOutputId {
+        self.id
+    }
+
+    /// Returns the name of this output.
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the size of this output.
+    pub fn size(&self) -> Size<i32, Physical> {
+        self.size
+    }
+
+    /// Returns the refresh rate of this output in millihertz.
+    pub fn refresh(&self) -> i32 {
+        self.refresh
+    }
+
+    /// Changes the size and refresh rate of this output, e.g. when a remote client resized its viewport.
+    ///
+    /// The next frame is fully redrawn.
+    pub fn set_mode(&mut self, size: impl Into<Size<i32, Physical>>, refresh: i32) {
+        let size = size.into();
+        if self.size != size {
+            self.offscreen = None;
+        }
+        self.size = size;
+        self.refresh = refresh;
+        self.damage_tracker.update_mode(size, 1.0, Transform::Normal);
+    }
+
+    /// Sets the callback receiving the frames rendered to this output.
+    pub fn set_frame_callback<F>(&mut self, callback: F)
+    where
+        F: FnMut(HeadlessFrame<'_>) + 'static,
+    {
+        self.frame_callback = Some(Box::new(callback));
+    }
+
+    /// Removes the frame callback of this output.
+    pub fn clear_frame_callback(&mut self) {
+        self.frame_callback = None;
+    }
+
+    /// Renders the given elements into a dmabuf.
+    ///
+    /// `age` is the age of the contents of the dmabuf (see [`Slot::age`](crate::backend::allocator::Slot::age)),
+    /// `0` meaning unknown contents. The dmabuf should have the size of the output.
+    ///
+    /// If anything changed, the dmabuf is passed to the frame callback as [`HeadlessFrame::Dmabuf`]
+    /// and the damage of the frame is returned. The next [`HeadlessEvent::VBlank`] of this output is
+    /// scheduled in any case.
+    pub fn render_to_dmabuf<R, E>(
+        &mut self,
+        renderer: &mut R,
+        dmabuf: &Dmabuf,
+        age: usize,
+        elements: &[E],
+        clear_color: [f32; 4],
+        log: &slog::Logger,
+    ) -> Result<Option<Vec<Rectangle<i32, Physical>>>, <R as Renderer>::Error>
+    where
+        R: Bind<Dmabuf>,
+        E: RenderElement<R>,
+    {
+        self.schedule_vblank();
+
+        renderer.bind(dmabuf.clone())?;
+        let damage = self
+            .damage_tracker
+            .render_output(renderer, age, elements, clear_color, log)?;
+
+        if let (Some(damage), Some(callback)) = (damage.as_ref(), self.frame_callback.as_mut()) {
+            callback(HeadlessFrame::Dmabuf { dmabuf, damage });
+        }
+        Ok(damage)
+    }
+
+    /// Renders the given elements into an offscreen buffer and reads back the result.
+    ///
+    /// The offscreen buffer is created by the renderer and reused for subsequent frames.
+    ///
+    /// If anything changed, the contents of the output are passed to the frame callback as
+    /// [`HeadlessFrame::Memory`] in [`Fourcc::Abgr8888`] format and the damage of the frame
+    /// is returned. The next [`HeadlessEvent::VBlank`] of this output is scheduled in any case.
+    pub fn render_to_memory<R, T, E>(
+        &mut self,
+        renderer: &mut R,
+        elements: &[E],
+        clear_color: [f32; 4],
+        log: &slog::Logger,
+    ) -> Result<Option<Vec<Rectangle<i32, Physical>>>, <R as Renderer>::Error>
+    where
+        R: Offscreen<T> + ExportMem,
+        T: Clone + 'static,
+        E: RenderElement<R>,
+    {
+        self.schedule_vblank();
+
+        let size = Size::<i32, Buffer>::from((self.size.w, self.size.h));
+        let (target, age) = match self
+            .offscreen
+            .as_ref()
+            .and_then(|target| target.downcast_ref::<T>())
+        {
+            Some(target) => (target.clone(), 1),
+            None => {
+                let target = renderer.create_buffer(size)?;
+                self.offscreen = Some(Box::new(target.clone()));
+                (target, 0)
+            }
+        };
+
+        renderer.bind(target)?;
+        let result = self
+            .damage_tracker
+            .render_output(renderer, age, elements, clear_color, log)
+            .and_then(|damage| match damage {
+                Some(damage) => renderer
+                    .copy_framebuffer(Rectangle::from_loc_and_size((0, 0), size), Fourcc::Abgr8888)
+                    .and_then(|mapping| renderer.map_texture(&mapping).map(|data| data.to_vec()))
+                    .map(|data| Some((damage, data))),
+                None => Ok(None),
+            });
+        let unbind = renderer.unbind();
+        // the rendering error is more relevant than a failed unbind
+        let result = result?;
+        unbind?;
+
+        Ok(result.map(|(damage, data)| {
+            if let Some(callback) = self.frame_callback.as_mut() {
+                callback(HeadlessFrame::Memory {
+                    data: &data,
+                    size,
+                    format: Fourcc::Abgr8888,
+                    damage: &damage,
+                });
+            }
+            damage
+        }))
+    }
+
+    /// Schedules the next emulated vblank, unless one is already pending
+    fn schedule_vblank(&mut self) {
+        let now = Instant::now();
+        if matches!(self.next_vblank, Some(next_vblank) if next_vblank > now) {
+            return;
+        }
+        let frame_duration = Duration::from_secs_f64(1_000.0 / self.refresh.max(1) as f64);
+        self.timer.add_timeout(frame_duration, self.id);
+        self.next_vblank = Some(now + frame_duration);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{HeadlessBackend, HeadlessEvent};
+    use crate::backend::input::{ButtonState, InputEvent, PointerButtonEvent};
+    use std::time::Duration;
+
+    #[test]
+    fn injected_input_is_emitted() {
+        let mut event_loop = calloop::EventLoop::<Vec<HeadlessEvent>>::try_new().unwrap();
+        let backend = HeadlessBackend::new(None).unwrap();
+        let input = backend.input_handle();
+        event_loop
+            .handle()
+            .insert_source(backend, |event, _, events| events.push(event))
+            .unwrap();
+
+        std::thread::spawn(move || input.pointer_button(0x110, ButtonState::Pressed).unwrap())
+            .join()
+            .unwrap();
+
+        let mut events = Vec::new();
+        event_loop
+            .dispatch(Some(Duration::from_millis(100)), &mut events)
+            .unwrap();
+        match events.as_slice() {
+            [HeadlessEvent::Input(InputEvent::PointerButton { event })] => {
+                assert_eq!(event.button_code(), 0x110);
+                assert_eq!(event.state(), ButtonState::Pressed);
+            }
+            events => panic!("unexpected events: {:?}", events),
+        }
+    }
+}
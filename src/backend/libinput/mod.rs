@@ -467,6 +467,8 @@ impl EventSource for LibinputInputBackend {
     where
         F: FnMut(Self::Event, &mut ()) -> Self::Ret,
     {
+        profile_scope!("libinput::process_events");
+        trace_span!(TRACE, "libinput::process_events");
         if token == self.token {
             self.context.dispatch()?;
 
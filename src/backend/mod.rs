@@ -75,6 +75,20 @@
 //! development and debugging. That backend is both a renderer and an input provider, and is
 //! accessible in the [`winit`] module, gated by the `backend_winit` cargo feature.
 //!
+//! ## Wayland backend
+//!
+//! Smithay also provides a backend running your compositor as a client of another Wayland
+//! compositor, without requiring an X server. It presents [`Dmabuf`](crate::backend::allocator::dmabuf::Dmabuf)s
+//! or shared memory buffers to an `xdg_toplevel` window and forwards the input of that window. It is
+//! accessible in the [`wayland`] module, gated by the `backend_wayland` cargo feature.
+//!
+//! ## Headless backend
+//!
+//! For remote-display servers (e.g. VNC or RDP) Smithay provides a backend without any display
+//! hardware. It delivers the frames rendered to virtual outputs to a callback and lets the server
+//! inject the input of its clients. It is accessible in the [`headless`] module, gated by the
+//! `backend_headless` cargo feature.
+//!
 
 pub mod allocator;
 pub mod input;
@@ -84,6 +98,8 @@ pub mod renderer;
 pub mod drm;
 #[cfg(feature = "backend_egl")]
 pub mod egl;
+#[cfg(feature = "backend_headless")]
+pub mod headless;
 #[cfg(feature = "backend_libinput")]
 pub mod libinput;
 #[cfg(feature = "backend_session")]
@@ -94,6 +110,8 @@ pub mod udev;
 #[cfg(feature = "backend_winit")]
 pub mod winit;
 
+#[cfg(feature = "backend_wayland")]
+pub mod wayland;
 #[cfg(feature = "backend_x11")]
 pub mod x11;
 
@@ -0,0 +1,296 @@
+//! Helper for effective output damage tracking
+//!
+//! The [`OutputDamageTracker`] keeps track of the elements rendered onto an output
+//! in previous frames. Given the age of the current buffer, it computes the minimal
+//! region that needs to be redrawn and only re-renders the elements touching that region.
+//! If nothing changed, rendering is skipped entirely.
+//!
+//! The buffer age is provided by the backends, e.g. through `RenderSurface::next_buffer`,
+//! `EGLSurface::buffer_age` or [`Slot::age`](crate::backend::allocator::Slot::age).
+//! If the age is unknown, `0` causes the whole output to be redrawn.
+//!
+//! ```no_run
+//! # use smithay::backend::renderer::{Renderer, damage::OutputDamageTracker, element::RenderElement};
+//! # fn render<R: Renderer, E: RenderElement<R>>(renderer: &mut R, elements: &[E], age: usize, log: &slog::Logger) {
+//! use smithay::backend::renderer::Transform;
+//!
+//! let mut damage_tracker = OutputDamageTracker::new((1920, 1080), 1.0, Transform::Normal);
+//!
+//! // for every frame, bind the next buffer and get its age
+//! match damage_tracker.render_output(renderer, age, elements, [0.0, 0.0, 0.0, 1.0], log) {
+//!     Ok(Some(damage)) => { /* submit the buffer */ }
+//!     Ok(None) => { /* nothing changed, skip this frame */ }
+//!     Err(err) => { /* handle the error */ }
+//! }
+//! # }
+//! ```
+
+use std::collections::{HashMap, VecDeque};
+
+use crate::utils::{Physical, Rectangle, Region, Size};
+
+use super::{
+    element::{CommitCounter, Id, RenderElement},
+    Frame, Renderer, Transform,
+};
+
+use slog::trace;
+
+/// Maximum buffer age the damage is tracked for
+const MAX_AGE: usize = 4;
+
+/// Maximum number of rectangles the damage of a frame is simplified to
+const MAX_DAMAGE_RECTS: usize = 16;
+
+#[derive(Debug, Clone, Copy)]
+struct ElementState {
+    last_commit: CommitCounter,
+    last_geometry: Rectangle<i32, Physical>,
+    last_z_index: usize,
+}
+
+/// Damage tracker for a single output
+#[derive(Debug)]
+pub struct OutputDamageTracker {
+    size: Size<i32, Physical>,
+    scale: f64,
+    transform: Transform,
+    damage_history: VecDeque<Vec<Rectangle<i32, Physical>>>,
+    element_states: HashMap<Id, ElementState>,
+}
+
+impl OutputDamageTracker {
+    /// Create a new damage tracker for an output with the given mode size, scale and transform.
+    pub fn new(
+        size: impl Into<Size<i32, Physical>>,
+        scale: f64,
+        transform: Transform,
+    ) -> OutputDamageTracker {
+        OutputDamageTracker {
+            size: size.into(),
+            scale,
+            transform,
+            damage_history: VecDeque::new(),
+            element_states: HashMap::new(),
+        }
+    }
+
+    /// Update the mode size, scale or transform of the output.
+    ///
+    /// Any change causes the next frame to be fully redrawn.
+    pub fn update_mode(&mut self, size: impl Into<Size<i32, Physical>>, scale: f64, transform: Transform) {
+        let size = size.into();
+        if self.size != size || self.scale != scale || self.transform != transform {
+            self.size = size;
+            self.scale = scale;
+            self.transform = transform;
+            self.reset();
+        }
+    }
+
+    /// Forget any previous state, causing the next frame to be fully redrawn.
+    pub fn reset(&mut self) {
+        self.damage_history.clear();
+        self.element_states.clear();
+    }
+
+    /// Geometry of the output in its own coordinate space
+    pub fn output_geometry(&self) -> Rectangle<i32, Physical> {
+        Rectangle::from_loc_and_size((0, 0), self.transform.transform_size(self.size))
+    }
+
+    /// Computes the damage of the current frame without rendering.
+    ///
+    /// `elements` are expected to be ordered from front to back.
+    /// The returned damage includes the damage of the last `age - 1` frames and
+    /// is empty, if the frame does not need to be redrawn.
+    ///
+    /// *Note*: This does not update the state of the tracker.
+    pub fn damage_output<E: crate::backend::renderer::element::Element>(
+        &self,
+        age: usize,
+        elements: &[E],
+    ) -> Vec<Rectangle<i32, Physical>> {
+        self.compute_damage(age, elements).0.into_rects()
+    }
+
+    fn compute_damage<E: crate::backend::renderer::element::Element>(
+        &self,
+        age: usize,
+        elements: &[E],
+    ) -> (Region<i32, Physical>, Vec<Rectangle<i32, Physical>>) {
+        let output_geo = self.output_geometry();
+        let mut damage = Vec::new();
+
+        for (z_index, element) in elements.iter().enumerate() {
+            let geometry = element.geometry(self.scale);
+            match self.element_states.get(element.id()) {
+                Some(state) if state.last_geometry == geometry && state.last_z_index == z_index => {
+                    damage.extend(
+                        element
+                            .damage_since(self.scale, Some(state.last_commit))
+                            .into_iter()
+                            .map(|mut rect| {
+                                rect.loc += geometry.loc;
+                                rect
+                            }),
+                    );
+                }
+                Some(state) => {
+                    damage.push(state.last_geometry);
+                    damage.push(geometry);
+                }
+                None => damage.push(geometry),
+            }
+        }
+
+        // elements that are gone need to be redrawn as well
+        damage.extend(
+            self.element_states
+                .iter()
+                .filter(|(id, _)| !elements.iter().any(|element| element.id() == *id))
+                .map(|(_, state)| state.last_geometry),
+        );
+
+        let frame_damage = if self.damage_history.is_empty() {
+            // nothing was rendered since the last reset, the whole output changed
+            vec![output_geo]
+        } else {
+            damage
+                .into_iter()
+                .filter_map(|rect| rect.intersection(output_geo))
+                .collect::<Vec<_>>()
+        };
+
+        let mut damage = frame_damage.clone();
+        // the history only covers the frames rendered since the last reset,
+        // older buffers need the damage of every frame since they were presented.
+        if age == 0 || age - 1 > self.damage_history.len() {
+            // the buffer contents are unknown
+            damage = vec![output_geo];
+        } else {
+            damage.extend(self.damage_history.iter().take(age - 1).flatten().copied());
+        }
+
+        let mut damage = Region::from_rects(damage);
+        damage.simplify(MAX_DAMAGE_RECTS);
+        (damage, frame_damage)
+    }
+
+    /// Render the given elements onto the output, only redrawing what changed.
+    ///
+    /// `elements` are expected to be ordered from front to back, `age` is the age of the
+    /// currently bound buffer (`0` meaning unknown contents). Buffers older than the
+    /// tracked history are fully redrawn.
+    ///
+    /// Returns the damage of the rendered frame or `None`, if nothing needed to be
+    /// redrawn and rendering was skipped entirely.
+    pub fn render_output<R, E>(
+        &mut self,
+        renderer: &mut R,
+        age: usize,
+        elements: &[E],
+        clear_color: [f32; 4],
+        log: &slog::Logger,
+    ) -> Result<Option<Vec<Rectangle<i32, Physical>>>, <R as Renderer>::Error>
+    where
+        R: Renderer,
+        E: RenderElement<R>,
+    {
+        profile_scope!("OutputDamageTracker::render_output");
+        let (damage, frame_damage) = self.compute_damage(age, elements);
+        let damage = damage.into_rects();
+        if damage.is_empty() {
+            trace!(log, "Skipping frame, no damage");
+            return Ok(None);
+        }
+        trace!(log, "Rendering damage: {:?}", damage);
+
+        let scale = self.scale;
+        renderer.render(self.size, self.transform, |renderer, frame| {
+            profile_scope!("OutputDamageTracker::draw");
+            frame.clear(clear_color, &damage)?;
+
+            for element in elements.iter().rev() {
+                let geometry = element.geometry(scale);
+                let element_damage = damage
+                    .iter()
+                    .filter_map(|rect| rect.intersection(geometry))
+                    .collect::<Vec<_>>();
+                if element_damage.is_empty() {
+                    continue;
+                }
+                element.draw(renderer, frame, scale, &element_damage, log)?;
+            }
+
+            Ok(())
+        })??;
+
+        self.damage_history.push_front(frame_damage);
+        self.damage_history.truncate(MAX_AGE);
+        self.element_states = elements
+            .iter()
+            .enumerate()
+            .map(|(z_index, element)| {
+                (
+                    element.id().clone(),
+                    ElementState {
+                        last_commit: element.current_commit(),
+                        last_geometry: element.geometry(scale),
+                        last_z_index: z_index,
+                    },
+                )
+            })
+            .collect();
+
+        Ok(Some(damage))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::OutputDamageTracker;
+    use crate::backend::renderer::{
+        element::{debug::DebugElement, Element},
+        Transform,
+    };
+    use crate::utils::Rectangle;
+
+    #[test]
+    fn untracked_age_redraws_output() {
+        let mut tracker = OutputDamageTracker::new((100, 50), 1.0, Transform::_90);
+        let full = vec![Rectangle::from_loc_and_size((0, 0), (50, 100))];
+        for age in 0..3 {
+            assert_eq!(tracker.damage_output::<&dyn Element>(age, &[]), full);
+        }
+
+        let rect = Rectangle::from_loc_and_size((10, 10), (5, 5));
+        tracker.damage_history.push_front(vec![rect]);
+        tracker.damage_history.push_front(Vec::new());
+        assert_eq!(tracker.damage_output::<&dyn Element>(2, &[]), vec![]);
+        assert_eq!(tracker.damage_output::<&dyn Element>(3, &[]), vec![rect]);
+        assert_eq!(tracker.damage_output::<&dyn Element>(4, &[]), full);
+    }
+
+    #[test]
+    fn redraw_damage_does_not_overlap() {
+        let mut tracker = OutputDamageTracker::new((100, 100), 1.0, Transform::Normal);
+        tracker.damage_history.push_front(Vec::new());
+        tracker.damage_history.push_front(vec![
+            Rectangle::from_loc_and_size((0, 0), (10, 10)),
+            Rectangle::from_loc_and_size((5, 5), (10, 10)),
+            Rectangle::from_loc_and_size((0, 0), (0, 5)),
+        ]);
+        tracker
+            .damage_history
+            .push_front(vec![Rectangle::from_loc_and_size((20, 20), (5, 5))]);
+        let damage = tracker.damage_output::<DebugElement>(3, &[]);
+        let area: i32 = damage.iter().map(|rect| rect.size.w * rect.size.h).sum();
+        assert_eq!(area, 175 + 25);
+        for (i, a) in damage.iter().enumerate() {
+            for b in &damage[i + 1..] {
+                assert_eq!(a.intersection(*b), None);
+            }
+        }
+    }
+}
@@ -0,0 +1,141 @@
+//! Render element limiting another element to an area of the output
+
+use crate::{
+    backend::renderer::Renderer,
+    utils::{Physical, Rectangle},
+};
+
+use super::{CommitCounter, Element, Id, RenderElement};
+
+/// A render element drawing only the part of another element inside a given area
+///
+/// Used e.g. to keep the elements shown on a mirrored output inside the area of the mirrored
+/// output, instead of drawing into the letterbox around it.
+#[derive(Debug)]
+pub struct CropRenderElement<E> {
+    element: E,
+    crop: Rectangle<i32, Physical>,
+}
+
+impl<E> CropRenderElement<E> {
+    /// Limit an element to the area `crop` of the output
+    ///
+    /// The area has to be given for the scale the element is rendered with.
+    pub fn new(element: E, crop: Rectangle<i32, Physical>) -> Self {
+        CropRenderElement { element, crop }
+    }
+
+    /// The cropped element
+    pub fn element(&self) -> &E {
+        &self.element
+    }
+}
+
+impl<E: Element> Element for CropRenderElement<E> {
+    fn id(&self) -> &Id {
+        self.element.id()
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.element.current_commit()
+    }
+
+    fn geometry(&self, scale: f64) -> Rectangle<i32, Physical> {
+        let geometry = self.element.geometry(scale);
+        geometry
+            .intersection(self.crop)
+            .unwrap_or_else(|| Rectangle::from_loc_and_size(geometry.loc, (0, 0)))
+    }
+
+    fn damage_since(&self, scale: f64, commit: Option<CommitCounter>) -> Vec<Rectangle<i32, Physical>> {
+        let geometry = self.element.geometry(scale);
+        let cropped = match geometry.intersection(self.crop) {
+            Some(cropped) => cropped,
+            None => return Vec::new(),
+        };
+        self.element
+            .damage_since(scale, commit)
+            .into_iter()
+            .filter_map(|mut rect| {
+                rect.loc += geometry.loc;
+                let mut rect = rect.intersection(cropped)?;
+                rect.loc -= cropped.loc;
+                Some(rect)
+            })
+            .collect()
+    }
+}
+
+impl<R: Renderer, E: RenderElement<R>> RenderElement<R> for CropRenderElement<E> {
+    fn draw(
+        &self,
+        renderer: &mut R,
+        frame: &mut <R as Renderer>::Frame,
+        scale: f64,
+        damage: &[Rectangle<i32, Physical>],
+        log: &slog::Logger,
+    ) -> Result<(), <R as Renderer>::Error> {
+        let damage = damage
+            .iter()
+            .filter_map(|rect| rect.intersection(self.crop))
+            .collect::<Vec<_>>();
+        if damage.is_empty() {
+            return Ok(());
+        }
+        self.element.draw(renderer, frame, scale, &damage, log)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CropRenderElement;
+    use crate::{
+        backend::renderer::element::{CommitCounter, Element, Id},
+        utils::{Physical, Rectangle},
+    };
+
+    struct TestElement {
+        id: Id,
+        geometry: Rectangle<i32, Physical>,
+    }
+
+    impl Element for TestElement {
+        fn id(&self) -> &Id {
+            &self.id
+        }
+
+        fn current_commit(&self) -> CommitCounter {
+            CommitCounter::default()
+        }
+
+        fn geometry(&self, _scale: f64) -> Rectangle<i32, Physical> {
+            self.geometry
+        }
+    }
+
+    #[test]
+    fn crops_geometry_and_damage() {
+        let element = TestElement {
+            id: Id::new(),
+            geometry: Rectangle::from_loc_and_size((-10, 20), (50, 50)),
+        };
+        let element = CropRenderElement::new(element, Rectangle::from_loc_and_size((0, 0), (100, 60)));
+        assert_eq!(
+            element.geometry(1.0),
+            Rectangle::from_loc_and_size((0, 20), (40, 40))
+        );
+        // fully damaged, relative to the cropped geometry
+        assert_eq!(
+            element.damage_since(1.0, None),
+            vec![Rectangle::from_loc_and_size((0, 0), (40, 40))]
+        );
+
+        let outside = TestElement {
+            id: Id::new(),
+            geometry: Rectangle::from_loc_and_size((200, 0), (10, 10)),
+        };
+        let outside = CropRenderElement::new(outside, Rectangle::from_loc_and_size((0, 0), (100, 60)));
+        assert_eq!(outside.geometry(1.0).size, (0, 0).into());
+        assert!(outside.damage_since(1.0, None).is_empty());
+    }
+}
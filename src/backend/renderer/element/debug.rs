@@ -0,0 +1,466 @@
+//! Debug overlay visualizing frame times, damage and plane assignments
+//!
+//! The [`DebugOverlay`] records the time between frames, the damage of the last frame and
+//! optionally the hardware planes used for the last frame. It provides [`DebugElement`]s drawing
+//! a frame time graph with an FPS readout, outlining the last damage and outlining the regions
+//! scanned out by planes, all of which can be toggled at runtime.
+//!
+//! The elements only use [`Frame::clear`] and thus work with any [`Renderer`].
+//!
+//! ```no_run
+//! # use smithay::backend::renderer::{Renderer, damage::OutputDamageTracker, element::debug::DebugOverlay};
+//! # fn render<R: Renderer>(renderer: &mut R, damage_tracker: &mut OutputDamageTracker, log: &slog::Logger) {
+//! let mut overlay = DebugOverlay::new((10, 10));
+//! overlay.set_damage_visible(true);
+//!
+//! // for every frame
+//! let elements = overlay.render_elements(1.0);
+//! if let Ok(Some(damage)) = damage_tracker.render_output(renderer, 0, &elements, [0.0, 0.0, 0.0, 1.0], log) {
+//!     overlay.tick();
+//!     overlay.add_damage(&damage);
+//!     // if elements were scanned out directly, record where
+//!     overlay.set_plane_assignments(Vec::new());
+//! }
+//! # }
+//! ```
+//!
+//! *Note*: Outlining the damage causes additional damage in the next frame,
+//! so the output is never idle while the damage is visible.
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use crate::utils::{Physical, Point, Rectangle, Size};
+
+use super::{CommitCounter, Element, Id, RenderElement};
+use crate::backend::renderer::{Frame, Renderer};
+
+/// Number of frames shown in the frame time graph
+const GRAPH_FRAMES: usize = 60;
+/// Width of a single bar of the frame time graph in physical pixels at scale 1
+const BAR_WIDTH: i32 = 2;
+/// Height of the frame time graph in physical pixels at scale 1
+const GRAPH_HEIGHT: i32 = 50;
+/// Frame time filling the whole height of the graph
+const GRAPH_MAX_FRAME_TIME: Duration = Duration::from_millis(50);
+
+const BACKGROUND_COLOR: [f32; 4] = [0.1, 0.1, 0.1, 1.0];
+const TARGET_LINE_COLOR: [f32; 4] = [0.5, 0.5, 0.5, 1.0];
+const DAMAGE_COLOR: [f32; 4] = [1.0, 0.0, 1.0, 1.0];
+const READOUT_COLOR: [f32; 4] = [1.0, 1.0, 1.0, 1.0];
+
+/// Width of a digit of the FPS readout in physical pixels at scale 1
+const DIGIT_WIDTH: i32 = 7;
+/// Height of a digit of the FPS readout in physical pixels at scale 1
+const DIGIT_HEIGHT: i32 = 12;
+/// Thickness of the segments of a digit in physical pixels at scale 1
+const DIGIT_SEGMENT: i32 = 2;
+/// Space around and between the digits in physical pixels at scale 1
+const DIGIT_SPACING: i32 = 3;
+/// Seven-segment encoding of the digits 0-9, segments a-g mapped to bits 0-6
+const DIGIT_SEGMENTS: [u8; 10] = [
+    0b011_1111, 0b000_0110, 0b101_1011, 0b100_1111, 0b110_0110, 0b110_1101, 0b111_1101, 0b000_0111,
+    0b111_1111, 0b110_1111,
+];
+
+/// Kind of hardware plane an output region was assigned to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum PlaneKind {
+    /// The primary plane, usually containing the composited frame
+    Primary,
+    /// A cursor plane
+    Cursor,
+    /// An overlay plane
+    Overlay,
+}
+
+impl PlaneKind {
+    fn color(&self) -> [f32; 4] {
+        match self {
+            PlaneKind::Primary => [0.0, 0.0, 1.0, 1.0],
+            PlaneKind::Cursor => [0.0, 1.0, 1.0, 1.0],
+            PlaneKind::Overlay => [1.0, 0.5, 0.0, 1.0],
+        }
+    }
+}
+
+/// Records frame statistics and provides elements to visualize them
+#[derive(Debug)]
+pub struct DebugOverlay {
+    graph_id: Id,
+    damage_id: Id,
+    planes_id: Id,
+    commit: CommitCounter,
+    location: Point<i32, Physical>,
+    frame_times: VecDeque<Duration>,
+    last_frame: Option<Instant>,
+    damage: Vec<Rectangle<i32, Physical>>,
+    planes: Vec<(Rectangle<i32, Physical>, PlaneKind)>,
+    fps_visible: bool,
+    damage_visible: bool,
+    planes_visible: bool,
+}
+
+impl DebugOverlay {
+    /// Create a new overlay, placing the frame time graph at the given location.
+    ///
+    /// The frame time graph is visible by default, the damage and plane outlines are not.
+    pub fn new(location: impl Into<Point<i32, Physical>>) -> DebugOverlay {
+        DebugOverlay {
+            graph_id: Id::new(),
+            damage_id: Id::new(),
+            planes_id: Id::new(),
+            commit: CommitCounter::default(),
+            location: location.into(),
+            frame_times: VecDeque::with_capacity(GRAPH_FRAMES),
+            last_frame: None,
+            damage: Vec::new(),
+            planes: Vec::new(),
+            fps_visible: true,
+            damage_visible: false,
+            planes_visible: false,
+        }
+    }
+
+    /// Record a new frame, should be called once for every rendered frame
+    pub fn tick(&mut self) {
+        let now = Instant::now();
+        if let Some(last_frame) = self.last_frame.replace(now) {
+            self.add_frame_time(now - last_frame);
+        }
+    }
+
+    /// Record the time of a frame directly, instead of measuring it with [`DebugOverlay::tick`]
+    pub fn add_frame_time(&mut self, frame_time: Duration) {
+        if self.frame_times.len() == GRAPH_FRAMES {
+            self.frame_times.pop_front();
+        }
+        self.frame_times.push_back(frame_time);
+        self.commit.increment();
+    }
+
+    /// Record the damage of the last rendered frame
+    pub fn add_damage(&mut self, damage: &[Rectangle<i32, Physical>]) {
+        self.damage = damage.to_vec();
+        self.commit.increment();
+    }
+
+    /// Record the regions of the output scanned out by hardware planes in the last frame.
+    ///
+    /// Regions not covered by any assignment are expected to be part of the primary plane.
+    pub fn set_plane_assignments(&mut self, planes: Vec<(Rectangle<i32, Physical>, PlaneKind)>) {
+        if self.planes != planes {
+            self.planes = planes;
+            self.commit.increment();
+        }
+    }
+
+    /// Average frames per second over the recorded frame times
+    pub fn fps(&self) -> f64 {
+        let total = self.frame_times.iter().sum::<Duration>();
+        if total.is_zero() {
+            0.0
+        } else {
+            self.frame_times.len() as f64 / total.as_secs_f64()
+        }
+    }
+
+    /// Average frame time over the recorded frame times
+    pub fn avg_frame_time(&self) -> Duration {
+        if self.frame_times.is_empty() {
+            Duration::ZERO
+        } else {
+            self.frame_times.iter().sum::<Duration>() / self.frame_times.len() as u32
+        }
+    }
+
+    /// Set the location of the frame time graph
+    pub fn set_location(&mut self, location: impl Into<Point<i32, Physical>>) {
+        self.location = location.into();
+    }
+
+    /// Show or hide the frame time graph and FPS readout
+    pub fn set_fps_visible(&mut self, visible: bool) {
+        self.fps_visible = visible;
+    }
+
+    /// Returns if the frame time graph and FPS readout are visible
+    pub fn fps_visible(&self) -> bool {
+        self.fps_visible
+    }
+
+    /// Show or hide the damage outlines
+    pub fn set_damage_visible(&mut self, visible: bool) {
+        self.damage_visible = visible;
+    }
+
+    /// Returns if the damage outlines are visible
+    pub fn damage_visible(&self) -> bool {
+        self.damage_visible
+    }
+
+    /// Show or hide the outlines of the plane assignments
+    pub fn set_planes_visible(&mut self, visible: bool) {
+        self.planes_visible = visible;
+    }
+
+    /// Returns if the outlines of the plane assignments are visible
+    pub fn planes_visible(&self) -> bool {
+        self.planes_visible
+    }
+
+    /// Create the elements of the currently visible parts of the overlay.
+    ///
+    /// The elements are ordered front to back and should be rendered on top of
+    /// all other elements.
+    pub fn render_elements(&self, scale: f64) -> Vec<DebugElement> {
+        let mut elements = Vec::with_capacity(3);
+        let line_width = scale.ceil() as i32;
+        if self.damage_visible && !self.damage.is_empty() {
+            let outlines = self
+                .damage
+                .iter()
+                .flat_map(|rect| outline(*rect, line_width))
+                .collect::<Vec<_>>();
+            let geometry = outlines
+                .iter()
+                .copied()
+                .reduce(|a, b| a.merge(b))
+                .unwrap_or_default();
+            elements.push(DebugElement {
+                id: self.damage_id.clone(),
+                commit: self.commit,
+                geometry,
+                rects: outlines.into_iter().map(|rect| (rect, DAMAGE_COLOR)).collect(),
+            });
+        }
+        if self.planes_visible && !self.planes.is_empty() {
+            let outlines = self
+                .planes
+                .iter()
+                .flat_map(|(rect, kind)| {
+                    IntoIterator::into_iter(outline(*rect, line_width)).map(move |rect| (rect, kind.color()))
+                })
+                .collect::<Vec<_>>();
+            let geometry = outlines
+                .iter()
+                .map(|(rect, _)| *rect)
+                .reduce(|a, b| a.merge(b))
+                .unwrap_or_default();
+            elements.push(DebugElement {
+                id: self.planes_id.clone(),
+                commit: self.commit,
+                geometry,
+                rects: outlines,
+            });
+        }
+        if self.fps_visible {
+            elements.push(self.graph_element(scale));
+        }
+        elements
+    }
+
+    fn graph_element(&self, scale: f64) -> DebugElement {
+        let bar_width = (BAR_WIDTH as f64 * scale).round() as i32;
+        let height = (GRAPH_HEIGHT as f64 * scale).round() as i32;
+        let geometry = Rectangle::from_loc_and_size(self.location, (bar_width * GRAPH_FRAMES as i32, height));
+
+        let bar_height = |frame_time: Duration| {
+            let ratio = frame_time.as_secs_f64() / GRAPH_MAX_FRAME_TIME.as_secs_f64();
+            ((ratio.min(1.0) * height as f64).round() as i32).max(1)
+        };
+
+        let mut rects = vec![(geometry, BACKGROUND_COLOR)];
+        let target_y = geometry.loc.y + height - bar_height(Duration::from_micros(16_667));
+        rects.push((
+            Rectangle::from_loc_and_size((geometry.loc.x, target_y), (geometry.size.w, 1)),
+            TARGET_LINE_COLOR,
+        ));
+        for (i, frame_time) in self.frame_times.iter().enumerate() {
+            let bar_height = bar_height(*frame_time);
+            rects.push((
+                Rectangle::from_loc_and_size(
+                    (
+                        geometry.loc.x + i as i32 * bar_width,
+                        geometry.loc.y + height - bar_height,
+                    ),
+                    (bar_width, bar_height),
+                ),
+                frame_time_color(*frame_time),
+            ));
+        }
+
+        // the readout is drawn on top of the graph
+        let spacing = (DIGIT_SPACING as f64 * scale).round() as i32;
+        let mut digit_loc = geometry.loc + Point::from((spacing, spacing));
+        for digit in format!("{:.0}", self.fps()).bytes() {
+            rects.extend(
+                digit_segments(digit - b'0', digit_loc, scale)
+                    .into_iter()
+                    .map(|rect| (rect, READOUT_COLOR)),
+            );
+            digit_loc.x += (DIGIT_WIDTH as f64 * scale).round() as i32 + spacing;
+        }
+
+        DebugElement {
+            id: self.graph_id.clone(),
+            commit: self.commit,
+            geometry,
+            rects,
+        }
+    }
+}
+
+fn frame_time_color(frame_time: Duration) -> [f32; 4] {
+    if frame_time <= Duration::from_micros(16_667) {
+        [0.0, 0.8, 0.0, 1.0]
+    } else if frame_time <= Duration::from_micros(33_334) {
+        [0.8, 0.8, 0.0, 1.0]
+    } else {
+        [0.8, 0.0, 0.0, 1.0]
+    }
+}
+
+fn digit_segments(digit: u8, loc: Point<i32, Physical>, scale: f64) -> Vec<Rectangle<i32, Physical>> {
+    let w = (DIGIT_WIDTH as f64 * scale).round() as i32;
+    let h = (DIGIT_HEIGHT as f64 * scale).round() as i32;
+    let t = (DIGIT_SEGMENT as f64 * scale).round() as i32;
+    let mid = (h - t) / 2;
+    let Point { x, y, .. } = loc;
+    let segments = [
+        Rectangle::from_loc_and_size((x, y), (w, t)),
+        Rectangle::from_loc_and_size((x + w - t, y), (t, mid + t)),
+        Rectangle::from_loc_and_size((x + w - t, y + mid), (t, h - mid)),
+        Rectangle::from_loc_and_size((x, y + h - t), (w, t)),
+        Rectangle::from_loc_and_size((x, y + mid), (t, h - mid)),
+        Rectangle::from_loc_and_size((x, y), (t, mid + t)),
+        Rectangle::from_loc_and_size((x, y + mid), (w, t)),
+    ];
+    let mask = DIGIT_SEGMENTS[digit as usize];
+    segments
+        .iter()
+        .copied()
+        .enumerate()
+        .filter(|(i, _)| mask & (1 << i) != 0)
+        .map(|(_, rect)| rect)
+        .collect()
+}
+
+fn outline(rect: Rectangle<i32, Physical>, width: i32) -> [Rectangle<i32, Physical>; 4] {
+    let Point { x, y, .. } = rect.loc;
+    let Size { w, h, .. } = rect.size;
+    [
+        Rectangle::from_loc_and_size((x, y), (w, width)),
+        Rectangle::from_loc_and_size((x, y + h - width), (w, width)),
+        Rectangle::from_loc_and_size((x, y), (width, h)),
+        Rectangle::from_loc_and_size((x + w - width, y), (width, h)),
+    ]
+}
+
+/// Element drawing a part of a [`DebugOverlay`]
+#[derive(Debug, Clone)]
+pub struct DebugElement {
+    id: Id,
+    commit: CommitCounter,
+    geometry: Rectangle<i32, Physical>,
+    rects: Vec<(Rectangle<i32, Physical>, [f32; 4])>,
+}
+
+impl Element for DebugElement {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.commit
+    }
+
+    fn geometry(&self, _scale: f64) -> Rectangle<i32, Physical> {
+        self.geometry
+    }
+}
+
+impl<R: Renderer> RenderElement<R> for DebugElement {
+    fn draw(
+        &self,
+        _renderer: &mut R,
+        frame: &mut <R as Renderer>::Frame,
+        _scale: f64,
+        damage: &[Rectangle<i32, Physical>],
+        _log: &slog::Logger,
+    ) -> Result<(), <R as Renderer>::Error> {
+        for (rect, color) in &self.rects {
+            let rect_damage = damage
+                .iter()
+                .filter_map(|damage| damage.intersection(*rect))
+                .collect::<Vec<_>>();
+            if !rect_damage.is_empty() {
+                frame.clear(*color, &rect_damage)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{DebugOverlay, PlaneKind, READOUT_COLOR};
+    use crate::{
+        backend::renderer::element::Element,
+        utils::{Physical, Rectangle},
+    };
+    use std::time::Duration;
+
+    #[test]
+    fn fps_from_frame_times() {
+        let mut overlay = DebugOverlay::new((0, 0));
+        assert_eq!(overlay.fps(), 0.0);
+        for _ in 0..10 {
+            overlay.add_frame_time(Duration::from_millis(20));
+        }
+        assert!((overlay.fps() - 50.0).abs() < 0.001);
+        assert_eq!(overlay.avg_frame_time(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn fps_readout_is_drawn() {
+        let mut overlay = DebugOverlay::new((0, 0));
+        for _ in 0..10 {
+            overlay.add_frame_time(Duration::from_millis(20));
+        }
+        let elements = overlay.render_elements(1.0);
+        assert_eq!(elements.len(), 1);
+        // "50": five segments for the 5, six for the 0
+        let segments = elements[0]
+            .rects
+            .iter()
+            .filter(|(_, color)| *color == READOUT_COLOR)
+            .count();
+        assert_eq!(segments, 11);
+    }
+
+    #[test]
+    fn plane_assignments_are_outlined() {
+        let mut overlay = DebugOverlay::new((0, 0));
+        overlay.set_fps_visible(false);
+        let cursor = Rectangle::<i32, Physical>::from_loc_and_size((100, 100), (64, 64));
+        overlay.set_plane_assignments(vec![(cursor, PlaneKind::Cursor)]);
+        assert!(overlay.render_elements(1.0).is_empty());
+
+        overlay.set_planes_visible(true);
+        let elements = overlay.render_elements(1.0);
+        assert_eq!(elements.len(), 1);
+        assert_eq!(elements[0].geometry(1.0), cursor);
+        assert!(elements[0]
+            .rects
+            .iter()
+            .all(|(_, color)| *color == PlaneKind::Cursor.color()));
+
+        // unchanged assignments don't cause damage
+        let commit = elements[0].current_commit();
+        overlay.set_plane_assignments(vec![(cursor, PlaneKind::Cursor)]);
+        assert_eq!(overlay.render_elements(1.0)[0].current_commit(), commit);
+    }
+}
@@ -0,0 +1,143 @@
+//! Abstractions over elements rendered onto an output
+//!
+//! An element is anything occupying a rectangular area of an output, that can be drawn
+//! by a given [`Renderer`] and is able to tell, which parts of itself changed since
+//! a previous state. This is what allows the [`OutputDamageTracker`](super::damage::OutputDamageTracker)
+//! to only redraw the parts of an output that actually changed.
+//!
+//! Elements are usually short-lived and re-created for every frame, their state
+//! is tracked through their [`Id`] and [`CommitCounter`].
+
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::utils::{Physical, Rectangle};
+
+use super::Renderer;
+
+pub mod crop;
+pub mod debug;
+pub mod texture;
+
+static ELEMENT_COUNTER: AtomicUsize = AtomicUsize::new(0);
+
+/// Unique identifier of an element
+///
+/// The id has to stay the same for every frame the element is rendered in,
+/// as it is used to match elements between frames.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Id(usize);
+
+impl Id {
+    /// Create a new unique id
+    #[allow(clippy::new_without_default)]
+    pub fn new() -> Id {
+        Id(ELEMENT_COUNTER.fetch_add(1, Ordering::SeqCst))
+    }
+}
+
+/// Counter tracking the changes of an element
+///
+/// Every change to the contents of an element should increment its counter.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct CommitCounter(usize);
+
+impl CommitCounter {
+    /// Increment the commit counter
+    pub fn increment(&mut self) {
+        self.0 = self.0.wrapping_add(1);
+    }
+
+    /// Returns the number of commits since a previous counter.
+    ///
+    /// Returns `None` if no previous counter is given or it is ahead of this one.
+    pub fn distance(&self, previous: Option<CommitCounter>) -> Option<usize> {
+        previous.and_then(|previous| self.0.checked_sub(previous.0))
+    }
+}
+
+/// An element that can be placed on an output
+pub trait Element {
+    /// Unique id of this element
+    fn id(&self) -> &Id;
+
+    /// Current commit of this element
+    fn current_commit(&self) -> CommitCounter;
+
+    /// Geometry of this element in the coordinate space of the output with the given scale
+    fn geometry(&self, scale: f64) -> Rectangle<i32, Physical>;
+
+    /// Damage of this element since the given commit.
+    ///
+    /// The damage is relative to the location of the element.
+    /// If the commit is `None` or unknown to the element, it should be fully damaged.
+    fn damage_since(&self, scale: f64, commit: Option<CommitCounter>) -> Vec<Rectangle<i32, Physical>> {
+        if commit != Some(self.current_commit()) {
+            vec![Rectangle::from_loc_and_size((0, 0), self.geometry(scale).size)]
+        } else {
+            vec![]
+        }
+    }
+}
+
+/// An element that can be drawn by a given [`Renderer`]
+pub trait RenderElement<R: Renderer>: Element {
+    /// Draw this element into the given frame.
+    ///
+    /// Only the given `damage`, relative to the output, needs to be redrawn.
+    fn draw(
+        &self,
+        renderer: &mut R,
+        frame: &mut <R as Renderer>::Frame,
+        scale: f64,
+        damage: &[Rectangle<i32, Physical>],
+        log: &slog::Logger,
+    ) -> Result<(), <R as Renderer>::Error>;
+}
+
+impl<R: Renderer, E: RenderElement<R> + ?Sized> RenderElement<R> for &E {
+    fn draw(
+        &self,
+        renderer: &mut R,
+        frame: &mut <R as Renderer>::Frame,
+        scale: f64,
+        damage: &[Rectangle<i32, Physical>],
+        log: &slog::Logger,
+    ) -> Result<(), <R as Renderer>::Error> {
+        (*self).draw(renderer, frame, scale, damage, log)
+    }
+}
+
+impl<E: Element + ?Sized> Element for &E {
+    fn id(&self) -> &Id {
+        (*self).id()
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        (*self).current_commit()
+    }
+
+    fn geometry(&self, scale: f64) -> Rectangle<i32, Physical> {
+        (*self).geometry(scale)
+    }
+
+    fn damage_since(&self, scale: f64, commit: Option<CommitCounter>) -> Vec<Rectangle<i32, Physical>> {
+        (*self).damage_since(scale, commit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::CommitCounter;
+
+    #[test]
+    fn commit_counter_distance() {
+        let previous = CommitCounter::default();
+        let mut current = previous;
+        assert_eq!(current.distance(Some(previous)), Some(0));
+        current.increment();
+        current.increment();
+        assert_eq!(current.distance(Some(previous)), Some(2));
+        assert_eq!(previous.distance(Some(current)), None);
+        assert_eq!(current.distance(None), None);
+    }
+}
@@ -0,0 +1,231 @@
+//! Render elements for textures not backed by a wayland buffer
+//!
+//! A [`TextureBuffer`] wraps a texture created by the compositor itself (e.g. via
+//! [`ImportMem`]) and keeps track of its updates, so that it can be rendered through
+//! [`TextureRenderElement`]s without redrawing it in every frame.
+//!
+//! ```no_run
+//! # use smithay::backend::renderer::{ImportMem, Renderer, element::texture::TextureBuffer, Transform};
+//! # fn test<R: ImportMem>(renderer: &mut R) where <R as Renderer>::TextureId: Clone {
+//! let mut pixels = vec![0u8; 64 * 64 * 4];
+//! let mut buffer = TextureBuffer::from_memory(renderer, &pixels, (64, 64), 1, Transform::Normal).unwrap();
+//!
+//! // update a part of the texture
+//! pixels[0] = 0xff;
+//! buffer.update_from_memory(renderer, &pixels, smithay::utils::Rectangle::from_loc_and_size((0, 0), (1, 1))).unwrap();
+//!
+//! // create an element to render the buffer at a given location
+//! let element = buffer.render_element((100.0, 100.0), 1.0);
+//! # }
+//! ```
+
+use std::collections::VecDeque;
+
+use crate::{
+    backend::renderer::{Frame, ImportMem, Renderer, Texture, Transform},
+    utils::{Buffer, Physical, Point, Rectangle, Size},
+};
+
+use super::{CommitCounter, Element, Id, RenderElement};
+
+/// Number of updates the damage is kept for
+const MAX_DAMAGE: usize = 4;
+
+/// A texture with tracked damage
+#[derive(Debug, Clone)]
+pub struct TextureBuffer<T> {
+    id: Id,
+    commit: CommitCounter,
+    texture: T,
+    scale: i32,
+    transform: Transform,
+    damage: VecDeque<Vec<Rectangle<i32, Buffer>>>,
+}
+
+impl<T: Texture + Clone> TextureBuffer<T> {
+    /// Wrap an existing texture
+    pub fn from_texture(texture: T, scale: i32, transform: Transform) -> TextureBuffer<T> {
+        TextureBuffer {
+            id: Id::new(),
+            commit: CommitCounter::default(),
+            texture,
+            scale,
+            transform,
+            damage: VecDeque::new(),
+        }
+    }
+
+    /// Create a new texture buffer from a RGBA bitmap (see [`ImportMem::import_memory`])
+    pub fn from_memory<R>(
+        renderer: &mut R,
+        data: &[u8],
+        size: impl Into<Size<i32, Buffer>>,
+        scale: i32,
+        transform: Transform,
+    ) -> Result<TextureBuffer<T>, <R as Renderer>::Error>
+    where
+        R: ImportMem + Renderer<TextureId = T>,
+    {
+        let texture = renderer.import_memory(data, size.into(), false)?;
+        Ok(TextureBuffer::from_texture(texture, scale, transform))
+    }
+
+    /// Update a region of the texture from a RGBA bitmap (see [`ImportMem::update_memory`])
+    pub fn update_from_memory<R>(
+        &mut self,
+        renderer: &mut R,
+        data: &[u8],
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<(), <R as Renderer>::Error>
+    where
+        R: ImportMem + Renderer<TextureId = T>,
+    {
+        renderer.update_memory(&self.texture, data, region)?;
+        self.damage(vec![region]);
+        Ok(())
+    }
+
+    /// Mark regions of the texture as changed, e.g. after rendering into it.
+    pub fn damage(&mut self, damage: Vec<Rectangle<i32, Buffer>>) {
+        self.commit.increment();
+        self.damage.push_front(damage);
+        self.damage.truncate(MAX_DAMAGE);
+    }
+
+    /// Returns the underlying texture
+    pub fn texture(&self) -> &T {
+        &self.texture
+    }
+
+    /// Create a render element for this buffer at the given location
+    pub fn render_element(
+        &self,
+        location: impl Into<Point<f64, Physical>>,
+        alpha: f32,
+    ) -> TextureRenderElement<T> {
+        TextureRenderElement {
+            buffer: self.clone(),
+            location: location.into(),
+            alpha,
+        }
+    }
+
+    fn damage_since(&self, commit: Option<CommitCounter>) -> Vec<Rectangle<i32, Buffer>> {
+        match self.commit.distance(commit) {
+            Some(distance) if distance <= self.damage.len() => {
+                self.damage.iter().take(distance).flatten().copied().collect()
+            }
+            _ => vec![Rectangle::from_loc_and_size((0, 0), self.texture.size())],
+        }
+    }
+}
+
+/// A render element for a [`TextureBuffer`]
+#[derive(Debug)]
+pub struct TextureRenderElement<T> {
+    buffer: TextureBuffer<T>,
+    location: Point<f64, Physical>,
+    alpha: f32,
+}
+
+impl<T: Texture + Clone> Element for TextureRenderElement<T> {
+    fn id(&self) -> &Id {
+        &self.buffer.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.buffer.commit
+    }
+
+    fn geometry(&self, scale: f64) -> Rectangle<i32, Physical> {
+        let size = self.buffer.texture.size();
+        let size = self
+            .buffer
+            .transform
+            .transform_size(size)
+            .to_logical(self.buffer.scale)
+            .to_f64()
+            .to_physical(scale);
+        Rectangle::from_loc_and_size(self.location.to_i32_round(), size.to_i32_round())
+    }
+
+    fn damage_since(&self, scale: f64, commit: Option<CommitCounter>) -> Vec<Rectangle<i32, Physical>> {
+        // the texture is rendered with the inverse of the buffer transformation
+        let transform = self.buffer.transform.invert();
+        let size = self.buffer.texture.size();
+        self.buffer
+            .damage_since(commit)
+            .into_iter()
+            .map(|rect| {
+                let rect = transform
+                    .transform_rect_in(rect, &size)
+                    .to_logical(self.buffer.scale)
+                    .to_f64()
+                    .to_physical(scale);
+                Rectangle::from_extemities(rect.loc.to_i32_floor(), (rect.loc + rect.size).to_i32_ceil())
+            })
+            .collect()
+    }
+}
+
+impl<R, T> RenderElement<R> for TextureRenderElement<T>
+where
+    R: Renderer<TextureId = T>,
+    T: Texture + Clone,
+{
+    fn draw(
+        &self,
+        _renderer: &mut R,
+        frame: &mut <R as Renderer>::Frame,
+        scale: f64,
+        damage: &[Rectangle<i32, Physical>],
+        _log: &slog::Logger,
+    ) -> Result<(), <R as Renderer>::Error> {
+        frame.render_texture_at(
+            &self.buffer.texture,
+            self.location.to_i32_round::<i32>().to_f64(),
+            self.buffer.scale,
+            scale,
+            self.buffer.transform,
+            damage,
+            self.alpha,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::TextureBuffer;
+    use crate::backend::renderer::{element::Element, Texture, Transform};
+    use crate::utils::Rectangle;
+
+    #[derive(Debug, Clone)]
+    struct DummyTexture;
+
+    impl Texture for DummyTexture {
+        fn width(&self) -> u32 {
+            4
+        }
+        fn height(&self) -> u32 {
+            2
+        }
+    }
+
+    #[test]
+    fn damage_follows_buffer_transform() {
+        let mut buffer = TextureBuffer::from_texture(DummyTexture, 1, Transform::_90);
+        let element = buffer.render_element((0.0, 0.0), 1.0);
+        let commit = element.current_commit();
+
+        buffer.damage(vec![Rectangle::from_loc_and_size((0, 0), (1, 1))]);
+        let element = buffer.render_element((0.0, 0.0), 1.0);
+        assert_eq!(
+            element.geometry(1.0),
+            Rectangle::from_loc_and_size((0, 0), (2, 4))
+        );
+        assert_eq!(
+            element.damage_since(1.0, Some(commit)),
+            vec![Rectangle::from_loc_and_size((1, 0), (1, 1))]
+        );
+    }
+}
@@ -0,0 +1,97 @@
+//! Render elements specific to the [`Gles2Renderer`]
+
+use crate::{
+    backend::renderer::element::{CommitCounter, Element, Id, RenderElement},
+    utils::{Logical, Physical, Rectangle},
+};
+
+use super::{Gles2Error, Gles2Frame, Gles2PixelProgram, Gles2Renderer, Uniform};
+
+/// Render element drawing a custom pixel shader
+///
+/// The shader is drawn into the area of the element, see
+/// [`Gles2Renderer::compile_custom_pixel_shader`] for the inputs available to the shader.
+///
+/// Elements are only redrawn, if they changed. Animated shaders therefor need to call
+/// [`PixelShaderElement::damage`] for every frame they should be redrawn.
+#[derive(Debug, Clone)]
+pub struct PixelShaderElement {
+    shader: Gles2PixelProgram,
+    id: Id,
+    commit_counter: CommitCounter,
+    area: Rectangle<i32, Logical>,
+    alpha: f32,
+    additional_uniforms: Vec<Uniform<'static>>,
+}
+
+impl PixelShaderElement {
+    /// Create a new element drawing the given shader into `area`
+    pub fn new(
+        shader: Gles2PixelProgram,
+        area: Rectangle<i32, Logical>,
+        alpha: f32,
+        additional_uniforms: Vec<Uniform<'static>>,
+    ) -> Self {
+        PixelShaderElement {
+            shader,
+            id: Id::new(),
+            commit_counter: CommitCounter::default(),
+            area,
+            alpha,
+            additional_uniforms,
+        }
+    }
+
+    /// Move or resize the element
+    pub fn resize(&mut self, area: Rectangle<i32, Logical>) {
+        if self.area != area {
+            self.area = area;
+            self.commit_counter.increment();
+        }
+    }
+
+    /// Update the values of the additional uniforms
+    pub fn update_uniforms(&mut self, additional_uniforms: Vec<Uniform<'static>>) {
+        self.additional_uniforms = additional_uniforms;
+        self.commit_counter.increment();
+    }
+
+    /// Mark the element as changed, causing it to be redrawn
+    pub fn damage(&mut self) {
+        self.commit_counter.increment();
+    }
+}
+
+impl Element for PixelShaderElement {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.commit_counter
+    }
+
+    fn geometry(&self, scale: f64) -> Rectangle<i32, Physical> {
+        let area = self.area.to_f64().to_physical(scale);
+        Rectangle::from_extemities(area.loc.to_i32_round(), (area.loc + area.size).to_i32_round())
+    }
+}
+
+impl RenderElement<Gles2Renderer> for PixelShaderElement {
+    fn draw(
+        &self,
+        _renderer: &mut Gles2Renderer,
+        frame: &mut Gles2Frame,
+        scale: f64,
+        damage: &[Rectangle<i32, Physical>],
+        _log: &slog::Logger,
+    ) -> Result<(), Gles2Error> {
+        frame.render_pixel_shader_to(
+            &self.shader,
+            self.geometry(scale),
+            damage,
+            self.alpha,
+            &self.additional_uniforms,
+        )
+    }
+}
@@ -8,22 +8,35 @@ use std::rc::Rc;
 use std::sync::{
     atomic::{AtomicUsize, Ordering},
     mpsc::{channel, Receiver, Sender},
+    Arc, Mutex,
+};
+use std::time::{Duration, Instant};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    os::raw::c_char,
 };
-use std::{collections::HashSet, os::raw::c_char};
 
 use cgmath::{prelude::*, Matrix3, Vector2, Vector3};
 
+pub mod element;
 mod shaders;
+mod uniform;
 mod version;
 
-use super::{Bind, Frame, Renderer, Texture, TextureFilter, Transform, Unbind};
+pub use self::uniform::*;
+
+use super::{
+    Bind, ExportMem, Frame, ImportMem, Offscreen, Renderer, Texture, TextureFilter, TextureMapping,
+    Transform, Unbind, WaitFence,
+};
+use crate::backend::allocator::fence::SyncFile;
 use crate::backend::allocator::{
     dmabuf::{Dmabuf, WeakDmabuf},
-    Format,
+    Format, Fourcc,
 };
 use crate::backend::egl::{
     ffi::egl::{self as ffi_egl, types::EGLImage},
-    EGLContext, EGLSurface, MakeCurrentError,
+    EGLContext, EGLFence, EGLSurface, MakeCurrentError,
 };
 use crate::backend::SwapBuffersError;
 use crate::utils::{Buffer, Physical, Rectangle, Size};
@@ -61,9 +74,35 @@ struct Gles2Program {
     attrib_tex_coords: ffi::types::GLint,
 }
 
+/// A compiled custom pixel shader
+///
+/// See [`Gles2Renderer::compile_custom_pixel_shader`] and [`Gles2Frame::render_pixel_shader_to`].
+#[derive(Debug, Clone)]
+pub struct Gles2PixelProgram(Rc<Gles2PixelProgramInternal>);
+
+#[derive(Debug)]
+struct Gles2PixelProgramInternal {
+    program: ffi::types::GLuint,
+    uniform_matrix: ffi::types::GLint,
+    uniform_size: ffi::types::GLint,
+    uniform_alpha: ffi::types::GLint,
+    uniform_time: ffi::types::GLint,
+    attrib_vert: ffi::types::GLint,
+    additional_uniforms: HashMap<String, (ffi::types::GLint, UniformType)>,
+    destruction_callback_sender: Sender<CleanupResource>,
+}
+
+impl Drop for Gles2PixelProgramInternal {
+    fn drop(&mut self) {
+        let _ = self
+            .destruction_callback_sender
+            .send(CleanupResource::Program(self.program));
+    }
+}
+
 /// A handle to a GLES2 texture
 #[derive(Debug, Clone)]
-pub struct Gles2Texture(Rc<Gles2TextureInternal>);
+pub struct Gles2Texture(Arc<Gles2TextureInternal>);
 
 impl Gles2Texture {
     /// Create a Gles2Texture from a raw gl texture id.
@@ -82,14 +121,14 @@ impl Gles2Texture {
         tex: ffi::types::GLuint,
         size: Size<i32, Buffer>,
     ) -> Gles2Texture {
-        Gles2Texture(Rc::new(Gles2TextureInternal {
+        Gles2Texture(Arc::new(Gles2TextureInternal {
             texture: tex,
             texture_kind: 0,
             is_external: false,
             y_inverted: false,
             size,
             egl_images: None,
-            destruction_callback_sender: renderer.destruction_callback_sender.clone(),
+            destruction_callback_sender: Mutex::new(renderer.destruction_callback_sender.clone()),
         }))
     }
 
@@ -109,19 +148,27 @@ struct Gles2TextureInternal {
     y_inverted: bool,
     size: Size<i32, Buffer>,
     egl_images: Option<Vec<EGLImage>>,
-    destruction_callback_sender: Sender<CleanupResource>,
+    destruction_callback_sender: Mutex<Sender<CleanupResource>>,
 }
 
+// The texture and images are only ever used through a `Gles2Renderer` owning a context
+// they were created on (or one sharing with it), which is required to be current anyway.
+// Their destruction is deferred to the renderer through the channel and never happens on
+// the dropping thread, so handles can be freely passed between the threads of renderers
+// sharing their resources. The sender is wrapped in a `Mutex`, as it is not `Sync` itself.
+unsafe impl Send for Gles2TextureInternal {}
+unsafe impl Sync for Gles2TextureInternal {}
+
 impl Drop for Gles2TextureInternal {
     fn drop(&mut self) {
-        let _ = self
+        let sender = self
             .destruction_callback_sender
-            .send(CleanupResource::Texture(self.texture));
+            .get_mut()
+            .unwrap_or_else(|err| err.into_inner());
+        let _ = sender.send(CleanupResource::Texture(self.texture));
         if let Some(images) = self.egl_images.take() {
             for image in images {
-                let _ = self
-                    .destruction_callback_sender
-                    .send(CleanupResource::EGLImage(image));
+                let _ = sender.send(CleanupResource::EGLImage(image));
             }
         }
     }
@@ -129,9 +176,13 @@ impl Drop for Gles2TextureInternal {
 
 enum CleanupResource {
     Texture(ffi::types::GLuint),
+    Program(ffi::types::GLuint),
     EGLImage(EGLImage),
 }
 
+// Only sent to the renderer owning the resources, which is the only one destroying them.
+unsafe impl Send for CleanupResource {}
+
 impl Texture for Gles2Texture {
     fn width(&self) -> u32 {
         self.0.size.w as u32
@@ -158,6 +209,38 @@ struct Gles2Buffer {
     _dmabuf: Dmabuf,
 }
 
+#[derive(Debug)]
+struct Gles2TextureTarget {
+    texture: Gles2Texture,
+    fbo: ffi::types::GLuint,
+}
+
+/// Contents of a framebuffer or texture copied into memory via [`ExportMem`]
+#[derive(Debug)]
+pub struct Gles2Mapping {
+    size: Size<i32, Buffer>,
+    format: Fourcc,
+    data: Vec<u8>,
+}
+
+impl Texture for Gles2Mapping {
+    fn width(&self) -> u32 {
+        self.size.w as u32
+    }
+    fn height(&self) -> u32 {
+        self.size.h as u32
+    }
+    fn size(&self) -> Size<i32, Buffer> {
+        self.size
+    }
+}
+
+impl TextureMapping for Gles2Mapping {
+    fn format(&self) -> Fourcc {
+        self.format
+    }
+}
+
 #[cfg(feature = "wayland_frontend")]
 struct BufferEntry {
     id: u32,
@@ -180,11 +263,17 @@ impl PartialEq for BufferEntry {
 impl Eq for BufferEntry {}
 
 /// A renderer utilizing OpenGL ES 2
+///
+/// The renderer is bound to the thread it was created on, as its context is kept current there.
+/// To render on another thread (e.g. one per gpu), move an [`EGLContext`] created with
+/// [`EGLContext::new_shared`] to that thread and create the renderer there.
+/// Textures of renderers with shared contexts can be passed between them.
 pub struct Gles2Renderer {
     id: usize,
     buffers: Vec<WeakGles2Buffer>,
     target_buffer: Option<Gles2Buffer>,
     target_surface: Option<Rc<EGLSurface>>,
+    target_texture: Option<Gles2TextureTarget>,
     extensions: Vec<String>,
     programs: [Gles2Program; shaders::FRAGMENT_COUNT],
     #[cfg(feature = "wayland_frontend")]
@@ -199,20 +288,30 @@ pub struct Gles2Renderer {
     destruction_callback_sender: Sender<CleanupResource>,
     logger_ptr: Option<*mut ::slog::Logger>,
     logger: ::slog::Logger,
+    start_time: Instant,
+    profiling: bool,
+    pending_timer_queries: VecDeque<ffi::types::GLuint>,
+    last_gpu_render_time: Option<Duration>,
+    explicit_sync: bool,
+    render_fence: Option<EGLFence>,
     _not_send: *mut (),
 }
 
 /// Handle to the currently rendered frame during [`Gles2Renderer::render`](Renderer::render)
 pub struct Gles2Frame {
     current_projection: Matrix3<f32>,
+    size: Size<i32, Physical>,
     gl: ffi::Gles2,
     programs: [Gles2Program; shaders::FRAGMENT_COUNT],
+    start_time: Instant,
+    debug_groups: bool,
 }
 
 impl fmt::Debug for Gles2Frame {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("Gles2Frame")
             .field("current_projection", &self.current_projection)
+            .field("size", &self.size)
             .field("programs", &self.programs)
             .finish_non_exhaustive()
     }
@@ -225,6 +324,7 @@ impl fmt::Debug for Gles2Renderer {
             .field("buffers", &self.buffers)
             .field("target_buffer", &self.target_buffer)
             .field("target_surface", &self.target_surface)
+            .field("target_texture", &self.target_texture)
             .field("extensions", &self.extensions)
             .field("programs", &self.programs)
             // ffi::Gles2 does not implement Debug
@@ -273,6 +373,44 @@ pub enum Gles2Error {
     /// This rendering operation was called without a previous `begin`-call
     #[error("Call begin before doing any rendering operations")]
     UnconstraintRenderingOperation,
+    /// No target is currently bound
+    #[error("No rendering target is currently bound")]
+    NoTargetBound,
+    /// The requested region is out of bounds of the source
+    #[error("The requested region {0:?} is out of bounds")]
+    RegionOutOfBounds(Rectangle<i32, Buffer>),
+    /// The requested format is not supported for exporting
+    #[error("Unsupported export format: {0:?}")]
+    UnsupportedExportFormat(Fourcc),
+    /// External textures can not be read back
+    #[error("External textures can not be read back")]
+    ExternalTextureExport,
+    /// The given memory does not match the size of the texture
+    #[error("Expected {expected} bytes of memory, got {got}")]
+    UnexpectedSize {
+        /// Expected size in bytes
+        expected: usize,
+        /// Actual size in bytes
+        got: usize,
+    },
+    /// External textures can not be updated from memory
+    #[error("External textures can not be updated from memory")]
+    ExternalTextureUpdate,
+    /// External textures can not be bound as a rendering target
+    #[error("External textures can not be bound as a rendering target")]
+    ExternalTextureBind,
+    /// A custom shader could not be compiled
+    #[error("Failed to compile custom shader: {0}")]
+    CustomShaderCompileError(String),
+    /// A uniform passed to a custom shader was not declared or does not match the declared type
+    #[error("Uniform `{0}` is not declared or has a different type")]
+    UnknownUniform(String),
+    /// A fence could not be created, exported or waited for
+    #[error("Fence operation failed: {0}")]
+    FenceError(#[source] crate::backend::egl::Error),
+    /// Waiting for a sync file failed
+    #[error("Failed to wait for sync file: {0}")]
+    SyncFileError(#[source] std::io::Error),
 }
 
 impl From<Gles2Error> for SwapBuffersError {
@@ -287,6 +425,17 @@ impl From<Gles2Error> for SwapBuffersError {
             Gles2Error::ContextActivationError(err) => err.into(),
             x @ Gles2Error::FramebufferBindingError
             | x @ Gles2Error::BindBufferEGLError(_)
+            | x @ Gles2Error::NoTargetBound
+            | x @ Gles2Error::RegionOutOfBounds(_)
+            | x @ Gles2Error::UnsupportedExportFormat(_)
+            | x @ Gles2Error::ExternalTextureExport
+            | x @ Gles2Error::UnexpectedSize { .. }
+            | x @ Gles2Error::ExternalTextureUpdate
+            | x @ Gles2Error::ExternalTextureBind
+            | x @ Gles2Error::CustomShaderCompileError(_)
+            | x @ Gles2Error::UnknownUniform(_)
+            | x @ Gles2Error::FenceError(_)
+            | x @ Gles2Error::SyncFileError(_)
             | x @ Gles2Error::UnsupportedPixelFormat(_)
             | x @ Gles2Error::BufferAccessError(_)
             | x @ Gles2Error::EGLBufferAccessError(_) => SwapBuffersError::TemporaryFailure(Box::new(x)),
@@ -301,9 +450,19 @@ impl From<Gles2Error> for SwapBuffersError {
             | x @ Gles2Error::GLExtensionNotSupported(_)
             | x @ Gles2Error::UnconstraintRenderingOperation => SwapBuffersError::ContextLost(Box::new(x)),
             Gles2Error::ContextActivationError(err) => err.into(),
-            x @ Gles2Error::FramebufferBindingError | x @ Gles2Error::BindBufferEGLError(_) => {
-                SwapBuffersError::TemporaryFailure(Box::new(x))
-            }
+            x @ Gles2Error::FramebufferBindingError
+            | x @ Gles2Error::BindBufferEGLError(_)
+            | x @ Gles2Error::NoTargetBound
+            | x @ Gles2Error::RegionOutOfBounds(_)
+            | x @ Gles2Error::UnsupportedExportFormat(_)
+            | x @ Gles2Error::ExternalTextureExport
+            | x @ Gles2Error::UnexpectedSize { .. }
+            | x @ Gles2Error::ExternalTextureUpdate
+            | x @ Gles2Error::ExternalTextureBind
+            | x @ Gles2Error::CustomShaderCompileError(_)
+            | x @ Gles2Error::UnknownUniform(_)
+            | x @ Gles2Error::FenceError(_)
+            | x @ Gles2Error::SyncFileError(_) => SwapBuffersError::TemporaryFailure(Box::new(x)),
         }
     }
 }
@@ -332,11 +491,12 @@ extern "system" fn gl_debug_log(
     });
 }
 
+// Compiles a shader, returning the info log on failure
 unsafe fn compile_shader(
     gl: &ffi::Gles2,
     variant: ffi::types::GLuint,
-    src: &'static str,
-) -> Result<ffi::types::GLuint, Gles2Error> {
+    src: &str,
+) -> Result<ffi::types::GLuint, String> {
     let shader = gl.CreateShader(variant);
     gl.ShaderSource(
         shader,
@@ -349,8 +509,20 @@ unsafe fn compile_shader(
     let mut status = ffi::FALSE as i32;
     gl.GetShaderiv(shader, ffi::COMPILE_STATUS, &mut status as *mut _);
     if status == ffi::FALSE as i32 {
+        let mut len = 0;
+        gl.GetShaderiv(shader, ffi::INFO_LOG_LENGTH, &mut len as *mut _);
+        let mut log = vec![0u8; len.max(1) as usize];
+        gl.GetShaderInfoLog(
+            shader,
+            log.len() as i32,
+            ptr::null_mut(),
+            log.as_mut_ptr() as *mut ffi::types::GLchar,
+        );
         gl.DeleteShader(shader);
-        return Err(Gles2Error::ShaderCompileError(src));
+        return Err(String::from_utf8_lossy(&log)
+            .trim_end_matches('\0')
+            .trim()
+            .to_string());
     }
 
     Ok(shader)
@@ -361,8 +533,24 @@ unsafe fn link_program(
     vert_src: &'static str,
     frag_src: &'static str,
 ) -> Result<ffi::types::GLuint, Gles2Error> {
-    let vert = compile_shader(gl, ffi::VERTEX_SHADER, vert_src)?;
-    let frag = compile_shader(gl, ffi::FRAGMENT_SHADER, frag_src)?;
+    let vert = compile_shader(gl, ffi::VERTEX_SHADER, vert_src)
+        .map_err(|_| Gles2Error::ShaderCompileError(vert_src))?;
+    let frag = match compile_shader(gl, ffi::FRAGMENT_SHADER, frag_src) {
+        Ok(frag) => frag,
+        Err(_) => {
+            gl.DeleteShader(vert);
+            return Err(Gles2Error::ShaderCompileError(frag_src));
+        }
+    };
+    link_shaders(gl, vert, frag)
+}
+
+// Links the given shaders into a program, the shaders are deleted afterwards
+unsafe fn link_shaders(
+    gl: &ffi::Gles2,
+    vert: ffi::types::GLuint,
+    frag: ffi::types::GLuint,
+) -> Result<ffi::types::GLuint, Gles2Error> {
     let program = gl.CreateProgram();
     gl.AttachShader(program, vert);
     gl.AttachShader(program, frag);
@@ -382,6 +570,28 @@ unsafe fn link_program(
     Ok(program)
 }
 
+// Debug group visible in GPU debugging and profiling tools, closed again when dropped
+struct DebugGroup(Option<ffi::Gles2>);
+
+impl DebugGroup {
+    unsafe fn new(gl: &ffi::Gles2, enabled: bool, name: &'static [u8]) -> DebugGroup {
+        if !enabled {
+            return DebugGroup(None);
+        }
+        let name = CStr::from_bytes_with_nul(name).expect("NULL terminated");
+        gl.PushDebugGroup(ffi::DEBUG_SOURCE_APPLICATION, 0, -1, name.as_ptr());
+        DebugGroup(Some(gl.clone()))
+    }
+}
+
+impl Drop for DebugGroup {
+    fn drop(&mut self) {
+        if let Some(gl) = self.0.as_ref() {
+            unsafe { gl.PopDebugGroup() };
+        }
+    }
+}
+
 unsafe fn texture_program(gl: &ffi::Gles2, frag: &'static str) -> Result<Gles2Program, Gles2Error> {
     let program = link_program(gl, shaders::VERTEX_SHADER, frag)?;
 
@@ -504,6 +714,7 @@ impl Gles2Renderer {
             programs,
             target_buffer: None,
             target_surface: None,
+            target_texture: None,
             buffers: Vec::new(),
             #[cfg(feature = "wayland_frontend")]
             dmabuf_cache: std::collections::HashMap::new(),
@@ -511,6 +722,12 @@ impl Gles2Renderer {
             destruction_callback_sender: tx,
             logger_ptr,
             logger: log,
+            start_time: Instant::now(),
+            profiling: false,
+            pending_timer_queries: VecDeque::new(),
+            last_gpu_render_time: None,
+            explicit_sync: false,
+            render_fence: None,
             _not_send: std::ptr::null_mut(),
         };
         renderer.downscale_filter(TextureFilter::Nearest)?;
@@ -539,6 +756,9 @@ impl Gles2Renderer {
                 CleanupResource::Texture(texture) => unsafe {
                     self.gl.DeleteTextures(1, &texture);
                 },
+                CleanupResource::Program(program) => unsafe {
+                    self.gl.DeleteProgram(program);
+                },
                 CleanupResource::EGLImage(image) => unsafe {
                     ffi_egl::DestroyImageKHR(**self.egl.display.display, image);
                 },
@@ -546,6 +766,146 @@ impl Gles2Renderer {
         }
         Ok(())
     }
+
+    fn debug_groups(&self) -> bool {
+        self.profiling && self.extensions.iter().any(|ext| ext == "GL_KHR_debug")
+    }
+
+    fn timer_queries(&self) -> bool {
+        self.profiling
+            && self
+                .extensions
+                .iter()
+                .any(|ext| ext == "GL_EXT_disjoint_timer_query")
+    }
+
+    // Collects the results of finished timer queries, expects the context to be current
+    unsafe fn poll_timer_queries(&mut self) {
+        if self.pending_timer_queries.is_empty() {
+            return;
+        }
+
+        // results are meaningless, if the gpu was interrupted (e.g. by a frequency change)
+        let mut disjoint = 0;
+        self.gl.GetIntegerv(ffi::GPU_DISJOINT_EXT, &mut disjoint);
+
+        while let Some(query) = self.pending_timer_queries.front().copied() {
+            let mut available = 0;
+            self.gl
+                .GetQueryObjectuivEXT(query, ffi::QUERY_RESULT_AVAILABLE_EXT, &mut available);
+            if available == 0 {
+                break;
+            }
+            let mut elapsed = 0;
+            self.gl
+                .GetQueryObjectui64vEXT(query, ffi::QUERY_RESULT_EXT, &mut elapsed);
+            self.gl.DeleteQueriesEXT(1, &query);
+            self.pending_timer_queries.pop_front();
+            if disjoint == 0 {
+                self.last_gpu_render_time = Some(Duration::from_nanos(elapsed));
+            }
+        }
+    }
+}
+
+impl ImportMem for Gles2Renderer {
+    fn import_memory(
+        &mut self,
+        data: &[u8],
+        size: Size<i32, Buffer>,
+        flipped: bool,
+    ) -> Result<Gles2Texture, Gles2Error> {
+        let expected = (size.w * size.h * 4) as usize;
+        if data.len() != expected {
+            return Err(Gles2Error::UnexpectedSize {
+                expected,
+                got: data.len(),
+            });
+        }
+
+        self.make_current()?;
+        let _group = unsafe { DebugGroup::new(&self.gl, self.debug_groups(), b"import_memory\0") };
+        let texture = unsafe {
+            let mut tex = 0;
+            self.gl.GenTextures(1, &mut tex);
+            self.gl.BindTexture(ffi::TEXTURE_2D, tex);
+            self.gl
+                .TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
+            self.gl
+                .TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_T, ffi::CLAMP_TO_EDGE as i32);
+            self.gl.TexImage2D(
+                ffi::TEXTURE_2D,
+                0,
+                ffi::RGBA as i32,
+                size.w,
+                size.h,
+                0,
+                ffi::RGBA,
+                ffi::UNSIGNED_BYTE,
+                data.as_ptr() as *const _,
+            );
+            self.gl.BindTexture(ffi::TEXTURE_2D, 0);
+            tex
+        };
+
+        Ok(Gles2Texture(Arc::new(Gles2TextureInternal {
+            texture,
+            texture_kind: 0,
+            is_external: false,
+            y_inverted: flipped,
+            size,
+            egl_images: None,
+            destruction_callback_sender: Mutex::new(self.destruction_callback_sender.clone()),
+        })))
+    }
+
+    fn update_memory(
+        &mut self,
+        texture: &Gles2Texture,
+        data: &[u8],
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<(), Gles2Error> {
+        if texture.0.is_external {
+            return Err(Gles2Error::ExternalTextureUpdate);
+        }
+        let size = texture.size();
+        let expected = (size.w * size.h * 4) as usize;
+        if data.len() != expected {
+            return Err(Gles2Error::UnexpectedSize {
+                expected,
+                got: data.len(),
+            });
+        }
+        if !region_in_bounds(region, size) {
+            return Err(Gles2Error::RegionOutOfBounds(region));
+        }
+
+        self.make_current()?;
+        let _group = unsafe { DebugGroup::new(&self.gl, self.debug_groups(), b"update_memory\0") };
+        unsafe {
+            self.gl.BindTexture(ffi::TEXTURE_2D, texture.0.texture);
+            self.gl.PixelStorei(ffi::UNPACK_ROW_LENGTH, size.w);
+            self.gl.PixelStorei(ffi::UNPACK_SKIP_PIXELS, region.loc.x);
+            self.gl.PixelStorei(ffi::UNPACK_SKIP_ROWS, region.loc.y);
+            self.gl.TexSubImage2D(
+                ffi::TEXTURE_2D,
+                0,
+                region.loc.x,
+                region.loc.y,
+                region.size.w,
+                region.size.h,
+                ffi::RGBA,
+                ffi::UNSIGNED_BYTE,
+                data.as_ptr() as *const _,
+            );
+            self.gl.PixelStorei(ffi::UNPACK_ROW_LENGTH, 0);
+            self.gl.PixelStorei(ffi::UNPACK_SKIP_PIXELS, 0);
+            self.gl.PixelStorei(ffi::UNPACK_SKIP_ROWS, 0);
+            self.gl.BindTexture(ffi::TEXTURE_2D, 0);
+        }
+
+        Ok(())
+    }
 }
 
 #[cfg(feature = "wayland_frontend")]
@@ -560,6 +920,7 @@ impl ImportShm for Gles2Renderer {
 
         with_buffer_contents(buffer, |slice, data| {
             self.make_current()?;
+            let _group = unsafe { DebugGroup::new(&self.gl, self.debug_groups(), b"import_shm\0") };
 
             let offset = data.offset as i32;
             let width = data.width as i32;
@@ -583,27 +944,43 @@ impl ImportShm for Gles2Renderer {
 
             let mut upload_full = false;
 
-            let texture = Gles2Texture(
-                // why not store a `Gles2Texture`? because the user might do so.
-                // this is guaranteed a non-public internal type, so we are good.
+            // The texture of the last import is kept per surface and renderer, so only the damaged
+            // parts of new buffers need to be uploaded, as long as size and format stay the same.
+            // why not store a `Gles2Texture`? because the user might do so.
+            // this is guaranteed a non-public internal type, so we are good.
+            let cache = surface.map(|surface| {
+                surface.data_map.insert_if_missing_threadsafe(|| {
+                    Mutex::new(HashMap::<usize, Arc<Gles2TextureInternal>>::new())
+                });
                 surface
-                    .and_then(|surface| surface.data_map.get::<Rc<Gles2TextureInternal>>().cloned())
-                    .unwrap_or_else(|| {
-                        let mut tex = 0;
-                        unsafe { self.gl.GenTextures(1, &mut tex) };
-                        // new texture, upload in full
-                        upload_full = true;
-                        Rc::new(Gles2TextureInternal {
-                            texture: tex,
-                            texture_kind: shader_idx,
-                            is_external: false,
-                            y_inverted: false,
-                            size: (width, height).into(),
-                            egl_images: None,
-                            destruction_callback_sender: self.destruction_callback_sender.clone(),
-                        })
-                    }),
-            );
+                    .data_map
+                    .get::<Mutex<HashMap<usize, Arc<Gles2TextureInternal>>>>()
+                    .unwrap()
+            });
+            let cached = cache
+                .and_then(|cache| cache.lock().unwrap().get(&self.id).cloned())
+                .filter(|texture| {
+                    texture.size == (width, height).into() && texture.texture_kind == shader_idx
+                });
+            let texture = Gles2Texture(cached.unwrap_or_else(|| {
+                let mut tex = 0;
+                unsafe { self.gl.GenTextures(1, &mut tex) };
+                // new texture, upload in full
+                upload_full = true;
+                let texture = Arc::new(Gles2TextureInternal {
+                    texture: tex,
+                    texture_kind: shader_idx,
+                    is_external: false,
+                    y_inverted: false,
+                    size: (width, height).into(),
+                    egl_images: None,
+                    destruction_callback_sender: Mutex::new(self.destruction_callback_sender.clone()),
+                });
+                if let Some(cache) = cache {
+                    cache.lock().unwrap().insert(self.id, texture.clone());
+                }
+                texture
+            }));
 
             unsafe {
                 self.gl.BindTexture(ffi::TEXTURE_2D, texture.0.texture);
@@ -628,8 +1005,14 @@ impl ImportShm for Gles2Renderer {
                         slice.as_ptr().offset(offset as isize) as *const _,
                     );
                 } else {
-                    for region in damage.iter() {
-                        trace!(self.logger, "Uploading partial shm texture for {:?}", buffer);
+                    let bounds = Rectangle::from_loc_and_size((0, 0), (width, height));
+                    for region in damage.iter().filter_map(|region| region.intersection(bounds)) {
+                        trace!(
+                            self.logger,
+                            "Uploading partial shm texture for {:?}: {:?}",
+                            buffer,
+                            region
+                        );
                         self.gl.PixelStorei(ffi::UNPACK_SKIP_PIXELS, region.loc.x);
                         self.gl.PixelStorei(ffi::UNPACK_SKIP_ROWS, region.loc.y);
                         self.gl.TexSubImage2D(
@@ -707,6 +1090,7 @@ impl ImportEgl for Gles2Renderer {
         // is_alive check will always return true and the cache entry
         // will never be cleaned up.
         self.make_current()?;
+        let _group = unsafe { DebugGroup::new(&self.gl, self.debug_groups(), b"import_egl\0") };
 
         let egl = self
             .egl_reader
@@ -717,7 +1101,7 @@ impl ImportEgl for Gles2Renderer {
 
         let tex = self.import_egl_image(egl.image(0).unwrap(), egl.format == EGLFormat::External, None)?;
 
-        let texture = Gles2Texture(Rc::new(Gles2TextureInternal {
+        let texture = Gles2Texture(Arc::new(Gles2TextureInternal {
             texture: tex,
             texture_kind: match egl.format {
                 EGLFormat::RGB => 1,
@@ -729,13 +1113,19 @@ impl ImportEgl for Gles2Renderer {
             y_inverted: egl.y_inverted,
             size: egl.size,
             egl_images: Some(egl.into_images()),
-            destruction_callback_sender: self.destruction_callback_sender.clone(),
+            destruction_callback_sender: Mutex::new(self.destruction_callback_sender.clone()),
         }));
 
         Ok(texture)
     }
 }
 
+impl WaitFence for Gles2Renderer {
+    fn wait_for_fence(&mut self, fence: SyncFile) -> Result<(), Gles2Error> {
+        Gles2Renderer::wait_for_fence(self, fence)
+    }
+}
+
 #[cfg(feature = "wayland_frontend")]
 impl ImportDma for Gles2Renderer {
     fn import_dmabuf(&mut self, buffer: &Dmabuf) -> Result<Gles2Texture, Gles2Error> {
@@ -748,6 +1138,7 @@ impl ImportDma for Gles2Renderer {
             let is_external = !self.egl.dmabuf_render_formats().contains(&buffer.format());
 
             self.make_current()?;
+            let _group = unsafe { DebugGroup::new(&self.gl, self.debug_groups(), b"import_dmabuf\0") };
             let image = self
                 .egl
                 .display
@@ -755,14 +1146,14 @@ impl ImportDma for Gles2Renderer {
                 .map_err(Gles2Error::BindBufferEGLError)?;
 
             let tex = self.import_egl_image(image, is_external, None)?;
-            let texture = Gles2Texture(Rc::new(Gles2TextureInternal {
+            let texture = Gles2Texture(Arc::new(Gles2TextureInternal {
                 texture: tex,
                 texture_kind: if is_external { 2 } else { 0 },
                 is_external,
                 y_inverted: buffer.y_inverted(),
                 size: buffer.size(),
                 egl_images: Some(vec![image]),
-                destruction_callback_sender: self.destruction_callback_sender.clone(),
+                destruction_callback_sender: Mutex::new(self.destruction_callback_sender.clone()),
             }));
             self.egl.unbind()?;
             self.dmabuf_cache.insert(buffer.weak(), texture.clone());
@@ -954,6 +1345,9 @@ impl Unbind for Gles2Renderer {
             self.egl.make_current()?;
         }
         unsafe { self.gl.BindFramebuffer(ffi::FRAMEBUFFER, 0) };
+        if let Some(target) = self.target_texture.take() {
+            unsafe { self.gl.DeleteFramebuffers(1, &target.fbo as *const _) };
+        }
         self.target_buffer = None;
         self.target_surface = None;
         self.egl.unbind()?;
@@ -961,6 +1355,236 @@ impl Unbind for Gles2Renderer {
     }
 }
 
+impl Bind<Gles2Texture> for Gles2Renderer {
+    fn bind(&mut self, texture: Gles2Texture) -> Result<(), Gles2Error> {
+        if texture.0.is_external {
+            return Err(Gles2Error::ExternalTextureBind);
+        }
+        self.unbind()?;
+        unsafe {
+            self.egl.make_current()?;
+        }
+
+        let fbo = unsafe {
+            let mut fbo = 0;
+            self.gl.GenFramebuffers(1, &mut fbo as *mut _);
+            self.gl.BindFramebuffer(ffi::FRAMEBUFFER, fbo);
+            self.gl.FramebufferTexture2D(
+                ffi::FRAMEBUFFER,
+                ffi::COLOR_ATTACHMENT0,
+                ffi::TEXTURE_2D,
+                texture.0.texture,
+                0,
+            );
+            let status = self.gl.CheckFramebufferStatus(ffi::FRAMEBUFFER);
+            if status != ffi::FRAMEBUFFER_COMPLETE {
+                self.gl.BindFramebuffer(ffi::FRAMEBUFFER, 0);
+                self.gl.DeleteFramebuffers(1, &fbo as *const _);
+                return Err(Gles2Error::FramebufferBindingError);
+            }
+            fbo
+        };
+
+        self.target_texture = Some(Gles2TextureTarget { texture, fbo });
+        Ok(())
+    }
+}
+
+impl Offscreen<Gles2Texture> for Gles2Renderer {
+    fn create_buffer(&mut self, size: Size<i32, Buffer>) -> Result<Gles2Texture, Gles2Error> {
+        self.make_current()?;
+        let tex = unsafe {
+            let mut tex = 0;
+            self.gl.GenTextures(1, &mut tex);
+            self.gl.BindTexture(ffi::TEXTURE_2D, tex);
+            self.gl
+                .TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_S, ffi::CLAMP_TO_EDGE as i32);
+            self.gl
+                .TexParameteri(ffi::TEXTURE_2D, ffi::TEXTURE_WRAP_T, ffi::CLAMP_TO_EDGE as i32);
+            self.gl.TexImage2D(
+                ffi::TEXTURE_2D,
+                0,
+                ffi::RGBA as i32,
+                size.w,
+                size.h,
+                0,
+                ffi::RGBA,
+                ffi::UNSIGNED_BYTE,
+                ptr::null(),
+            );
+            self.gl.BindTexture(ffi::TEXTURE_2D, 0);
+            tex
+        };
+
+        Ok(Gles2Texture(Arc::new(Gles2TextureInternal {
+            texture: tex,
+            texture_kind: 0,
+            is_external: false,
+            // rendering happens bottom-up in gl
+            y_inverted: true,
+            size,
+            egl_images: None,
+            destruction_callback_sender: Mutex::new(self.destruction_callback_sender.clone()),
+        })))
+    }
+}
+
+impl Gles2Renderer {
+    fn target_size(&self) -> Option<Size<i32, Buffer>> {
+        if let Some(target) = self.target_texture.as_ref() {
+            Some(target.texture.size())
+        } else if let Some(buffer) = self.target_buffer.as_ref() {
+            buffer
+                .internal
+                .dmabuf
+                .upgrade()
+                .map(|dmabuf| crate::backend::allocator::Buffer::size(&dmabuf))
+        } else if let Some(surface) = self.target_surface.as_ref() {
+            surface.get_size().map(|size| Size::from((size.w, size.h)))
+        } else {
+            None
+        }
+    }
+
+    // Reads the given region of the currently bound read framebuffer of height `height`.
+    // If `flipped` the contents are stored bottom-up and need to be reordered.
+    fn read_pixels(
+        &self,
+        region: Rectangle<i32, Buffer>,
+        height: i32,
+        flipped: bool,
+        format: Fourcc,
+    ) -> Result<Gles2Mapping, Gles2Error> {
+        let swizzle = match format {
+            Fourcc::Abgr8888 | Fourcc::Xbgr8888 => false,
+            Fourcc::Argb8888 | Fourcc::Xrgb8888 => true,
+            format => return Err(Gles2Error::UnsupportedExportFormat(format)),
+        };
+
+        let y = if flipped {
+            height - region.loc.y - region.size.h
+        } else {
+            region.loc.y
+        };
+        let mut data = vec![0u8; (region.size.w * region.size.h * 4) as usize];
+        unsafe {
+            self.gl.PixelStorei(ffi::PACK_ALIGNMENT, 4);
+            self.gl.ReadPixels(
+                region.loc.x,
+                y,
+                region.size.w,
+                region.size.h,
+                ffi::RGBA,
+                ffi::UNSIGNED_BYTE,
+                data.as_mut_ptr() as *mut _,
+            );
+        }
+        convert_pixels(&mut data, region.size.w as usize, flipped, swizzle);
+
+        Ok(Gles2Mapping {
+            size: region.size,
+            format,
+            data,
+        })
+    }
+}
+
+/// Converts tightly packed RGBA pixels in place, optionally reversing
+/// the row order and swapping the red and blue channels.
+fn convert_pixels(data: &mut [u8], width: usize, flip: bool, swizzle: bool) {
+    let stride = width * 4;
+    if stride == 0 {
+        return;
+    }
+    if flip {
+        let rows = data.len() / stride;
+        for row in 0..rows / 2 {
+            let (upper, lower) = data.split_at_mut((rows - row - 1) * stride);
+            upper[row * stride..(row + 1) * stride].swap_with_slice(&mut lower[..stride]);
+        }
+    }
+    if swizzle {
+        for pixel in data.chunks_exact_mut(4) {
+            pixel.swap(0, 2);
+        }
+    }
+}
+
+fn region_in_bounds(region: Rectangle<i32, Buffer>, size: Size<i32, Buffer>) -> bool {
+    region.loc.x >= 0
+        && region.loc.y >= 0
+        && region.size.w >= 0
+        && region.size.h >= 0
+        && region.loc.x + region.size.w <= size.w
+        && region.loc.y + region.size.h <= size.h
+}
+
+impl ExportMem for Gles2Renderer {
+    type TextureMapping = Gles2Mapping;
+
+    fn copy_framebuffer(
+        &mut self,
+        region: Rectangle<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<Gles2Mapping, Gles2Error> {
+        self.make_current()?;
+        let size = self.target_size().ok_or(Gles2Error::NoTargetBound)?;
+        if !region_in_bounds(region, size) {
+            return Err(Gles2Error::RegionOutOfBounds(region));
+        }
+        // all targets are rendered bottom-up
+        self.read_pixels(region, size.h, true, format)
+    }
+
+    fn copy_texture(
+        &mut self,
+        texture: &Gles2Texture,
+        region: Rectangle<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<Gles2Mapping, Gles2Error> {
+        if texture.0.is_external {
+            return Err(Gles2Error::ExternalTextureExport);
+        }
+        let size = texture.size();
+        if !region_in_bounds(region, size) {
+            return Err(Gles2Error::RegionOutOfBounds(region));
+        }
+
+        self.make_current()?;
+        let previous_fbo = self
+            .target_texture
+            .as_ref()
+            .map(|target| target.fbo)
+            .or_else(|| self.target_buffer.as_ref().map(|buffer| buffer.internal.fbo))
+            .unwrap_or(0);
+        unsafe {
+            let mut fbo = 0;
+            self.gl.GenFramebuffers(1, &mut fbo as *mut _);
+            self.gl.BindFramebuffer(ffi::FRAMEBUFFER, fbo);
+            self.gl.FramebufferTexture2D(
+                ffi::FRAMEBUFFER,
+                ffi::COLOR_ATTACHMENT0,
+                ffi::TEXTURE_2D,
+                texture.0.texture,
+                0,
+            );
+            let status = self.gl.CheckFramebufferStatus(ffi::FRAMEBUFFER);
+            let result = if status == ffi::FRAMEBUFFER_COMPLETE {
+                self.read_pixels(region, size.h, texture.0.y_inverted, format)
+            } else {
+                Err(Gles2Error::FramebufferBindingError)
+            };
+            self.gl.BindFramebuffer(ffi::FRAMEBUFFER, previous_fbo);
+            self.gl.DeleteFramebuffers(1, &fbo as *const _);
+            result
+        }
+    }
+
+    fn map_texture<'a>(&mut self, texture_mapping: &'a Gles2Mapping) -> Result<&'a [u8], Gles2Error> {
+        Ok(&texture_mapping.data)
+    }
+}
+
 impl Drop for Gles2Renderer {
     fn drop(&mut self) {
         unsafe {
@@ -969,6 +1593,9 @@ impl Drop for Gles2Renderer {
                 for program in &self.programs {
                     self.gl.DeleteProgram(program.program);
                 }
+                for query in self.pending_timer_queries.drain(..) {
+                    self.gl.DeleteQueriesEXT(1, &query);
+                }
 
                 if self.extensions.iter().any(|ext| ext == "GL_KHR_debug") {
                     self.gl.Disable(ffi::DEBUG_OUTPUT);
@@ -1001,6 +1628,145 @@ impl Gles2Renderer {
         let gl = self.gl.clone();
         Ok(func(self, &gl))
     }
+
+    /// Enable or disable profiling support.
+    ///
+    /// While enabled, the renderer annotates importing, drawing and submitting with
+    /// debug groups (if `GL_KHR_debug` is supported), which show up in GPU debugging
+    /// and profiling tools, and measures the time the gpu takes to render each frame
+    /// (if `GL_EXT_disjoint_timer_query` is supported), see [`Gles2Renderer::last_gpu_render_time`].
+    ///
+    /// Profiling is disabled by default.
+    pub fn set_profiling(&mut self, enabled: bool) {
+        self.profiling = enabled;
+    }
+
+    /// Returns if profiling support is enabled
+    pub fn profiling(&self) -> bool {
+        self.profiling
+    }
+
+    /// Enable or disable explicit synchronization.
+    ///
+    /// By default the renderer blocks at the end of [`Renderer::render`] until the gpu finished
+    /// rendering. With explicit synchronization enabled and native fences supported by the
+    /// underlying EGL implementation (`EGL_ANDROID_native_fence_sync`), rendering is not awaited.
+    /// Instead a fence is created, that needs to be retrieved with [`Gles2Renderer::take_render_fence`]
+    /// and waited on before using the rendered buffer, e.g. by passing it to
+    /// [`RenderSurface::queue_buffer_with_fence`](crate::backend::drm::RenderSurface::queue_buffer_with_fence).
+    ///
+    /// Returns if native fences are supported. If they are not, the renderer keeps blocking.
+    pub fn set_explicit_sync(&mut self, enabled: bool) -> bool {
+        self.explicit_sync = enabled;
+        EGLFence::is_supported(&self.egl.display)
+    }
+
+    /// Returns the fence of the last rendered frame as [`SyncFile`], signaled once rendering
+    /// is finished.
+    ///
+    /// Returns `None` if explicit synchronization is disabled or not supported.
+    /// In that case rendering was already finished, when [`Renderer::render`] returned.
+    pub fn take_render_fence(&mut self) -> Result<Option<SyncFile>, Gles2Error> {
+        match self.render_fence.take() {
+            Some(fence) => fence.export().map(Some).map_err(Gles2Error::FenceError),
+            None => Ok(None),
+        }
+    }
+
+    /// Let the gpu wait for the given fence, before executing any further commands.
+    ///
+    /// This is useful to wait for clients to finish rendering into a buffer, before it is
+    /// sampled from. If the EGL implementation does not support waiting for fences on the
+    /// gpu, this blocks until the fence is signaled.
+    pub fn wait_for_fence(&mut self, fence: SyncFile) -> Result<(), Gles2Error> {
+        self.make_current()?;
+        let display = &self.egl.display;
+        if EGLFence::is_supported(display)
+            && display
+                .get_extensions()
+                .iter()
+                .any(|ext| ext == "EGL_KHR_wait_sync")
+        {
+            let fence = EGLFence::import(display, fence).map_err(Gles2Error::FenceError)?;
+            fence.wait().map_err(Gles2Error::FenceError)
+        } else {
+            fence.wait(None).map(|_| ()).map_err(Gles2Error::SyncFileError)
+        }
+    }
+
+    /// Returns the time the gpu spent rendering the most recent frame, whose measurement
+    /// is available.
+    ///
+    /// Measurements are asynchronous and may lag behind by a few frames. Returns `None`,
+    /// if profiling is disabled, timer queries are not supported or no measurement
+    /// finished yet.
+    pub fn last_gpu_render_time(&mut self) -> Result<Option<Duration>, Gles2Error> {
+        self.make_current()?;
+        unsafe { self.poll_timer_queries() };
+        Ok(self.last_gpu_render_time)
+    }
+
+    /// Compile a custom pixel shader for rendering with [`Gles2Frame::render_pixel_shader_to`].
+    ///
+    /// `src` is the source of a GLSL ES 1.0 fragment shader, which has access to the following
+    /// inputs, that are provided for every draw:
+    ///
+    /// - `varying vec2 v_coords`: position inside the rendered area, ranging from `(0.0, 0.0)`
+    ///   at the top-left to `(1.0, 1.0)` at the bottom-right corner
+    /// - `uniform vec2 size`: size of the rendered area in physical pixels
+    /// - `uniform float alpha`: alpha value the output is expected to be multiplied with
+    /// - `uniform float time`: seconds since the creation of the renderer, useful for animations
+    ///
+    /// Any additional uniforms used by the shader need to be declared in `additional_uniforms`
+    /// and can then be set for every draw.
+    ///
+    /// The output is expected to use premultiplied alpha.
+    pub fn compile_custom_pixel_shader(
+        &mut self,
+        src: impl AsRef<str>,
+        additional_uniforms: &[UniformName<'_>],
+    ) -> Result<Gles2PixelProgram, Gles2Error> {
+        self.make_current()?;
+
+        unsafe {
+            let vert = compile_shader(&self.gl, ffi::VERTEX_SHADER, shaders::VERTEX_SHADER_PIXEL)
+                .map_err(|_| Gles2Error::ShaderCompileError(shaders::VERTEX_SHADER_PIXEL))?;
+            let frag = match compile_shader(&self.gl, ffi::FRAGMENT_SHADER, src.as_ref()) {
+                Ok(frag) => frag,
+                Err(log) => {
+                    self.gl.DeleteShader(vert);
+                    return Err(Gles2Error::CustomShaderCompileError(log));
+                }
+            };
+            let program = link_shaders(&self.gl, vert, frag)?;
+
+            let gl = &self.gl;
+            let location = |name: &str| {
+                let name = std::ffi::CString::new(name).unwrap_or_default();
+                gl.GetUniformLocation(program, name.as_ptr() as *const ffi::types::GLchar)
+            };
+            let vert = CStr::from_bytes_with_nul(b"vert\0").expect("NULL terminated");
+
+            Ok(Gles2PixelProgram(Rc::new(Gles2PixelProgramInternal {
+                program,
+                uniform_matrix: location("matrix"),
+                uniform_size: location("size"),
+                uniform_alpha: location("alpha"),
+                uniform_time: location("time"),
+                attrib_vert: gl.GetAttribLocation(program, vert.as_ptr() as *const ffi::types::GLchar),
+                additional_uniforms: additional_uniforms
+                    .iter()
+                    .map(|uniform| {
+                        (
+                            uniform.name.clone().into_owned(),
+                            (location(&uniform.name), uniform.type_),
+                        )
+                    })
+                    .collect(),
+                destruction_callback_sender: self.destruction_callback_sender.clone(),
+            })))
+        }
+    }
 }
 
 impl Renderer for Gles2Renderer {
@@ -1050,6 +1816,19 @@ impl Renderer for Gles2Renderer {
         // delayed destruction until the next frame rendering.
         self.cleanup()?;
 
+        let timer_query = unsafe {
+            self.poll_timer_queries();
+            if self.timer_queries() {
+                let mut query = 0;
+                self.gl.GenQueriesEXT(1, &mut query);
+                self.gl.BeginQueryEXT(ffi::TIME_ELAPSED_EXT, query);
+                Some(query)
+            } else {
+                None
+            }
+        };
+        let render_group = unsafe { DebugGroup::new(&self.gl, self.debug_groups(), b"render\0") };
+
         unsafe {
             self.gl.Viewport(0, 0, size.w, size.h);
 
@@ -1060,49 +1839,58 @@ impl Renderer for Gles2Renderer {
             self.gl.BlendFunc(ffi::ONE, ffi::ONE_MINUS_SRC_ALPHA);
         }
 
+        // the frame is addressed in the coordinate space of the output,
+        // which is the transformed size of the framebuffer
+        let size = transform.transform_size(size);
+
         // replicate https://www.khronos.org/registry/OpenGL-Refpages/gl2.1/xhtml/glOrtho.xml
-        // glOrtho(0, width, 0, height, 1, 1);
+        // glOrtho(0, width, height, 0, 1, 1);
+        // but keeping the y-axis pointing downwards for now, so the output transformation
+        // can be applied in the same coordinate space as surface transformations.
         let mut renderer = Matrix3::<f32>::identity();
-        let t = Matrix3::<f32>::identity();
-        let x = 2.0 / (size.w as f32);
-        let y = 2.0 / (size.h as f32);
-
-        // Rotation & Reflection
-        renderer[0][0] = x * t[0][0];
-        renderer[1][0] = x * t[0][1];
-        renderer[0][1] = y * -t[1][0];
-        renderer[1][1] = y * -t[1][1];
+        renderer[0][0] = 2.0 / (size.w as f32);
+        renderer[1][1] = 2.0 / (size.h as f32);
+        renderer[2][0] = -1.0;
+        renderer[2][1] = -1.0;
 
-        //Translation
-        renderer[2][0] = -(1.0f32.copysign(renderer[0][0] + renderer[1][0]));
-        renderer[2][1] = -(1.0f32.copysign(renderer[0][1] + renderer[1][1]));
+        // opengl's y-axis points upwards
+        let flip_y = Matrix3::new(1.0, 0.0, 0.0, 0.0, -1.0, 0.0, 0.0, 0.0, 1.0);
 
         let mut frame = Gles2Frame {
             gl: self.gl.clone(),
             programs: self.programs.clone(),
+            start_time: self.start_time,
+            debug_groups: self.debug_groups(),
             // output transformation passed in by the user
-            current_projection: transform.matrix() * renderer,
+            current_projection: flip_y * transform.matrix() * renderer,
+            size,
         };
 
         let result = rendering(self, &mut frame);
+        std::mem::drop(render_group);
 
+        let _submit_group = unsafe { DebugGroup::new(&self.gl, self.debug_groups(), b"submit\0") };
         unsafe {
+            if let Some(query) = timer_query {
+                self.gl.EndQueryEXT(ffi::TIME_ELAPSED_EXT);
+                self.pending_timer_queries.push_back(query);
+            }
+            self.render_fence = None;
+            if self.explicit_sync && EGLFence::is_supported(&self.egl.display) {
+                // the fence is signaled, once rendering is finished. It is only inserted
+                // into the command stream by the flush, so it has to be created before.
+                match EGLFence::create(&self.egl.display) {
+                    Ok(fence) => self.render_fence = Some(fence),
+                    Err(err) => warn!(self.logger, "Failed to create render fence: {}", err),
+                }
+            }
             self.gl.Flush();
-            // We need to wait for the previously submitted GL commands to complete
-            // or otherwise the buffer could be submitted to the drm surface while
-            // still writing to the buffer which results in flickering on the screen.
-            // The proper solution would be to create a fence just before calling
-            // glFlush that the backend can use to wait for the commands to be finished.
-            // In case of a drm atomic backend the fence could be supplied by using the
-            // IN_FENCE_FD property.
-            // See https://01.org/linuxgraphics/gfx-docs/drm/gpu/drm-kms.html#explicit-fencing-properties for
-            // the topic on submitting a IN_FENCE_FD and the mesa kmskube example
-            // https://gitlab.freedesktop.org/mesa/kmscube/-/blob/9f63f359fab1b5d8e862508e4e51c9dfe339ccb0/drm-atomic.c
-            // especially here
-            // https://gitlab.freedesktop.org/mesa/kmscube/-/blob/9f63f359fab1b5d8e862508e4e51c9dfe339ccb0/drm-atomic.c#L147
-            // and here
-            // https://gitlab.freedesktop.org/mesa/kmscube/-/blob/9f63f359fab1b5d8e862508e4e51c9dfe339ccb0/drm-atomic.c#L235
-            self.gl.Finish();
+            if self.render_fence.is_none() {
+                // Without a fence we need to wait for the previously submitted GL commands to complete
+                // or otherwise the buffer could be submitted to the drm surface while
+                // still writing to the buffer which results in flickering on the screen.
+                self.gl.Finish();
+            }
             self.gl.Disable(ffi::BLEND);
         }
 
@@ -1121,10 +1909,15 @@ impl Frame for Gles2Frame {
     type Error = Gles2Error;
     type TextureId = Gles2Texture;
 
-    fn clear(&mut self, color: [f32; 4]) -> Result<(), Self::Error> {
+    fn clear(&mut self, color: [f32; 4], at: &[Rectangle<i32, Physical>]) -> Result<(), Self::Error> {
+        let _group = unsafe { DebugGroup::new(&self.gl, self.debug_groups, b"clear\0") };
         unsafe {
             self.gl.ClearColor(color[0], color[1], color[2], color[3]);
-            self.gl.Clear(ffi::COLOR_BUFFER_BIT);
+            for rect in at {
+                self.scissor(*rect);
+                self.gl.Clear(ffi::COLOR_BUFFER_BIT);
+            }
+            self.reset_scissor();
         }
 
         Ok(())
@@ -1135,6 +1928,7 @@ impl Frame for Gles2Frame {
         texture: &Self::TextureId,
         src: Rectangle<i32, Buffer>,
         dest: Rectangle<f64, Physical>,
+        damage: &[Rectangle<i32, Physical>],
         transform: Transform,
         alpha: f32,
     ) -> Result<(), Self::Error> {
@@ -1170,11 +1964,113 @@ impl Frame for Gles2Frame {
             .truncate(), // bottom-right
             (texture_mat * Vector3::new(src.loc.x as f32, (src.loc.y + src.size.h) as f32, 0.0)).truncate(), // bottom-left
         ];
-        self.render_texture(texture, mat, verts, alpha)
+
+        let dest_bounds = Rectangle::<i32, Physical>::from_extemities(
+            dest.loc.to_i32_floor(),
+            (dest.loc + dest.size).to_i32_ceil(),
+        );
+        let mut result = Ok(());
+        for rect in damage.iter().filter_map(|rect| rect.intersection(dest_bounds)) {
+            unsafe { self.scissor(rect) };
+            result = self.render_texture(texture, mat, verts, alpha);
+            if result.is_err() {
+                break;
+            }
+        }
+        unsafe { self.reset_scissor() };
+        result
     }
 }
 
 impl Gles2Frame {
+    // Limits rendering operations to the given rectangle in frame coordinates
+    unsafe fn scissor(&self, rect: Rectangle<i32, Physical>) {
+        // map the rectangle through the projection into framebuffer coordinates,
+        // this takes care of the output transformation and the flipped y-axis.
+        let corners = [
+            self.current_projection * Vector3::new(rect.loc.x as f32, rect.loc.y as f32, 1.0),
+            self.current_projection
+                * Vector3::new(
+                    (rect.loc.x + rect.size.w) as f32,
+                    (rect.loc.y + rect.size.h) as f32,
+                    1.0,
+                ),
+        ];
+        let to_fb = |ndc: f32, len: i32| (ndc + 1.0) / 2.0 * len as f32;
+        let x1 = to_fb(corners[0].x.min(corners[1].x), self.size.w).round() as i32;
+        let x2 = to_fb(corners[0].x.max(corners[1].x), self.size.w).round() as i32;
+        let y1 = to_fb(corners[0].y.min(corners[1].y), self.size.h).round() as i32;
+        let y2 = to_fb(corners[0].y.max(corners[1].y), self.size.h).round() as i32;
+        self.gl.Scissor(x1, y1, x2 - x1, y2 - y1);
+    }
+
+    unsafe fn reset_scissor(&self) {
+        self.gl.Scissor(0, 0, self.size.w, self.size.h);
+    }
+
+    /// Render a custom pixel shader into the given area of the current target.
+    ///
+    /// Only the parts of `dest` intersecting `damage` are touched. `additional_uniforms` need
+    /// to be declared with matching types, when compiling the shader.
+    pub fn render_pixel_shader_to(
+        &mut self,
+        pixel_shader: &Gles2PixelProgram,
+        dest: Rectangle<i32, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        alpha: f32,
+        additional_uniforms: &[Uniform<'_>],
+    ) -> Result<(), Gles2Error> {
+        let program = &pixel_shader.0;
+        let uniforms = additional_uniforms
+            .iter()
+            .map(|uniform| match program.additional_uniforms.get(&*uniform.name) {
+                Some((location, type_)) if *type_ == uniform.value.type_() => Ok((*location, uniform.value)),
+                _ => Err(Gles2Error::UnknownUniform(uniform.name.to_string())),
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut matrix = Matrix3::from_translation(Vector2::new(dest.loc.x as f32, dest.loc.y as f32));
+        matrix = matrix * Matrix3::from_nonuniform_scale(dest.size.w as f32, dest.size.h as f32);
+        //apply output transformation
+        matrix = self.current_projection * matrix;
+
+        let _group = unsafe { DebugGroup::new(&self.gl, self.debug_groups, b"draw_pixel_shader\0") };
+        unsafe {
+            self.gl.UseProgram(program.program);
+
+            self.gl
+                .UniformMatrix3fv(program.uniform_matrix, 1, ffi::FALSE, matrix.as_ptr());
+            self.gl
+                .Uniform2f(program.uniform_size, dest.size.w as f32, dest.size.h as f32);
+            self.gl.Uniform1f(program.uniform_alpha, alpha);
+            self.gl
+                .Uniform1f(program.uniform_time, self.start_time.elapsed().as_secs_f32());
+            for (location, value) in uniforms {
+                value.set(&self.gl, location);
+            }
+
+            self.gl.VertexAttribPointer(
+                program.attrib_vert as u32,
+                2,
+                ffi::FLOAT,
+                ffi::FALSE,
+                0,
+                VERTS.as_ptr() as *const _,
+            );
+            self.gl.EnableVertexAttribArray(program.attrib_vert as u32);
+
+            for rect in damage.iter().filter_map(|rect| rect.intersection(dest)) {
+                self.scissor(rect);
+                self.gl.DrawArrays(ffi::TRIANGLE_STRIP, 0, 4);
+            }
+            self.reset_scissor();
+
+            self.gl.DisableVertexAttribArray(program.attrib_vert as u32);
+        }
+
+        Ok(())
+    }
+
     /// Render a texture to the current target using given projection matrix and alpha.
     /// The given vertices are used to source the texture. This is mostly useful for cropping the texture.    
     pub fn render_texture(
@@ -1187,6 +2083,7 @@ impl Gles2Frame {
         //apply output transformation
         matrix = self.current_projection * matrix;
 
+        let _group = unsafe { DebugGroup::new(&self.gl, self.debug_groups, b"draw_texture\0") };
         let target = if tex.0.is_external {
             ffi::TEXTURE_EXTERNAL_OES
         } else {
@@ -1251,3 +2148,29 @@ impl Gles2Frame {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::convert_pixels;
+
+    #[test]
+    fn convert_pixels_flip() {
+        let mut data = vec![1, 1, 1, 1, 2, 2, 2, 2, 3, 3, 3, 3];
+        convert_pixels(&mut data, 1, true, false);
+        assert_eq!(data, vec![3, 3, 3, 3, 2, 2, 2, 2, 1, 1, 1, 1]);
+    }
+
+    #[test]
+    fn convert_pixels_swizzle() {
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        convert_pixels(&mut data, 2, false, true);
+        assert_eq!(data, vec![3, 2, 1, 4, 7, 6, 5, 8]);
+    }
+
+    #[test]
+    fn convert_pixels_flip_and_swizzle() {
+        let mut data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        convert_pixels(&mut data, 1, true, true);
+        assert_eq!(data, vec![7, 6, 5, 8, 3, 2, 1, 4]);
+    }
+}
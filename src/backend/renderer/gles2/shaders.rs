@@ -52,3 +52,13 @@ void main() {
     gl_FragColor = texture2D(tex, v_tex_coords) * alpha;
 }
 "#;
+
+pub const VERTEX_SHADER_PIXEL: &str = r#"
+#version 100
+uniform mat3 matrix;
+attribute vec2 vert;
+varying vec2 v_coords;
+void main() {
+    v_coords = vert;
+    gl_Position = vec4(matrix * vec3(vert, 1.0), 1.0);
+}"#;
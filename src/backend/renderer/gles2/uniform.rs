@@ -0,0 +1,124 @@
+use std::borrow::Cow;
+
+use super::ffi;
+
+/// Type of a custom uniform of a pixel shader
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum UniformType {
+    /// `float`
+    Float,
+    /// `vec2`
+    Vec2,
+    /// `vec3`
+    Vec3,
+    /// `vec4`
+    Vec4,
+    /// `int`
+    Int,
+}
+
+/// Declaration of a custom uniform of a pixel shader
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct UniformName<'a> {
+    /// Name of the uniform as used in the shader source
+    pub name: Cow<'a, str>,
+    /// Type of the uniform
+    pub type_: UniformType,
+}
+
+impl<'a> UniformName<'a> {
+    /// Declare a new uniform
+    pub fn new(name: impl Into<Cow<'a, str>>, type_: UniformType) -> Self {
+        UniformName {
+            name: name.into(),
+            type_,
+        }
+    }
+}
+
+/// Value of a custom uniform
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum UniformValue {
+    /// `float`
+    Float(f32),
+    /// `vec2`
+    Vec2([f32; 2]),
+    /// `vec3`
+    Vec3([f32; 3]),
+    /// `vec4`
+    Vec4([f32; 4]),
+    /// `int`
+    Int(i32),
+}
+
+impl UniformValue {
+    /// Type of this value
+    pub fn type_(&self) -> UniformType {
+        match self {
+            UniformValue::Float(_) => UniformType::Float,
+            UniformValue::Vec2(_) => UniformType::Vec2,
+            UniformValue::Vec3(_) => UniformType::Vec3,
+            UniformValue::Vec4(_) => UniformType::Vec4,
+            UniformValue::Int(_) => UniformType::Int,
+        }
+    }
+
+    pub(super) unsafe fn set(&self, gl: &ffi::Gles2, location: ffi::types::GLint) {
+        match self {
+            UniformValue::Float(x) => gl.Uniform1f(location, *x),
+            UniformValue::Vec2([x, y]) => gl.Uniform2f(location, *x, *y),
+            UniformValue::Vec3([x, y, z]) => gl.Uniform3f(location, *x, *y, *z),
+            UniformValue::Vec4([x, y, z, w]) => gl.Uniform4f(location, *x, *y, *z, *w),
+            UniformValue::Int(x) => gl.Uniform1i(location, *x),
+        }
+    }
+}
+
+impl From<f32> for UniformValue {
+    fn from(value: f32) -> Self {
+        UniformValue::Float(value)
+    }
+}
+
+impl From<[f32; 2]> for UniformValue {
+    fn from(value: [f32; 2]) -> Self {
+        UniformValue::Vec2(value)
+    }
+}
+
+impl From<[f32; 3]> for UniformValue {
+    fn from(value: [f32; 3]) -> Self {
+        UniformValue::Vec3(value)
+    }
+}
+
+impl From<[f32; 4]> for UniformValue {
+    fn from(value: [f32; 4]) -> Self {
+        UniformValue::Vec4(value)
+    }
+}
+
+impl From<i32> for UniformValue {
+    fn from(value: i32) -> Self {
+        UniformValue::Int(value)
+    }
+}
+
+/// A custom uniform of a pixel shader together with its value
+#[derive(Debug, Clone, PartialEq)]
+pub struct Uniform<'a> {
+    /// Name of the uniform as used in the shader source
+    pub name: Cow<'a, str>,
+    /// Value of the uniform
+    pub value: UniformValue,
+}
+
+impl<'a> Uniform<'a> {
+    /// Create a new uniform value
+    pub fn new(name: impl Into<Cow<'a, str>>, value: impl Into<UniformValue>) -> Self {
+        Uniform {
+            name: name.into(),
+            value: value.into(),
+        }
+    }
+}
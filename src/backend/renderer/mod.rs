@@ -6,11 +6,32 @@
 //! Supported rendering apis:
 //!
 //! - Raw OpenGL ES 2
+//!
+//! ## Rendering on other threads
+//!
+//! Renderers are bound to the thread they were created on, but everything needed to render
+//! an output can be handed to a dedicated thread (e.g. one per gpu) owning its own renderer:
+//!
+//! - Create the renderer on the render thread, for the [`Gles2Renderer`](gles2::Gles2Renderer)
+//!   from an [`EGLContext`](crate::backend::egl::EGLContext), which is `Send`.
+//! - Textures of the [`Gles2Renderer`](gles2::Gles2Renderer),
+//!   [`Dmabuf`](crate::backend::allocator::dmabuf::Dmabuf)s and
+//!   [`WaylandSurfaceRenderElement`](utils::WaylandSurfaceRenderElement)s are `Send`
+//!   and can be passed to the render thread. The renderer state of client surfaces tracked by
+//!   [`on_commit_buffer_handler`](utils::on_commit_buffer_handler) is thread-safe, so surface
+//!   trees can be imported and drawn on any thread.
+//! - Buffers of surfaces, that stay on the main thread (like the
+//!   [`GbmBufferedSurface`](crate::backend::drm::GbmBufferedSurface)), are handed out as
+//!   [`Dmabuf`](crate::backend::allocator::dmabuf::Dmabuf) to be bound and rendered to.
+//!   Once done, the render thread sends back a fence (see
+//!   [`Gles2Renderer::take_render_fence`](gles2::Gles2Renderer::take_render_fence))
+//!   and the main thread queues the buffer for scan-out.
 
 use std::collections::HashSet;
 use std::error::Error;
 
-use crate::utils::{Buffer, Physical, Point, Rectangle, Size};
+use crate::backend::allocator::{fence::SyncFile, Fourcc};
+use crate::utils::{Buffer, Coordinate, Physical, Point, Rectangle, Size};
 
 #[cfg(feature = "wayland_frontend")]
 use crate::wayland::compositor::SurfaceData;
@@ -18,8 +39,13 @@ use cgmath::Matrix3;
 #[cfg(feature = "wayland_frontend")]
 use wayland_server::protocol::{wl_buffer, wl_shm};
 
+pub mod damage;
+pub mod element;
 #[cfg(feature = "renderer_gl")]
 pub mod gles2;
+pub mod screenshot;
+#[cfg(feature = "wayland_frontend")]
+pub mod utils;
 #[cfg(feature = "wayland_frontend")]
 use crate::backend::allocator::{dmabuf::Dmabuf, Format};
 #[cfg(all(
@@ -79,7 +105,8 @@ impl Transform {
 
     /// Inverts any 90-degree transformation into 270-degree transformations and vise versa.
     ///
-    /// Flipping is preserved and 180/Normal transformation are uneffected.
+    /// 180/Normal transformations are uneffected. Flipped transformations are their own inverse,
+    /// as the flip happens before the rotation.
     pub fn invert(&self) -> Transform {
         match self {
             Transform::Normal => Transform::Normal,
@@ -87,22 +114,67 @@ impl Transform {
             Transform::_90 => Transform::_270,
             Transform::_180 => Transform::_180,
             Transform::_270 => Transform::_90,
-            Transform::Flipped90 => Transform::Flipped270,
+            Transform::Flipped90 => Transform::Flipped90,
             Transform::Flipped180 => Transform::Flipped180,
-            Transform::Flipped270 => Transform::Flipped90,
+            Transform::Flipped270 => Transform::Flipped270,
         }
     }
 
+    /// Applies this transformation to a point inside an area of the given size.
+    ///
+    /// The point is expected in the untransformed coordinate space of the area and is returned
+    /// in the coordinate space of the transformed area (see [`Transform::transform_size`]).
+    /// E.g. a point in the coordinate space of an output can be converted into the coordinate
+    /// space of its framebuffer by using the output transformation and the size of the output,
+    /// while absolute input events of a rotated touchscreen can be converted into the coordinate
+    /// space of the output by using the inverted transformation and the size of the screen.
+    pub fn transform_point_in<N: Coordinate, Kind>(
+        &self,
+        point: Point<N, Kind>,
+        area: &Size<N, Kind>,
+    ) -> Point<N, Kind> {
+        let (x, y) = (point.x, point.y);
+        let (w, h) = (area.w, area.h);
+        let transformed = match self {
+            Transform::Normal => (x, y),
+            Transform::_90 => (y, w - x),
+            Transform::_180 => (w - x, h - y),
+            Transform::_270 => (h - y, x),
+            Transform::Flipped => (w - x, y),
+            Transform::Flipped90 => (y, x),
+            Transform::Flipped180 => (x, h - y),
+            Transform::Flipped270 => (h - y, w - x),
+        };
+        Point::from(transformed)
+    }
+
+    /// Applies this transformation to a rectangle inside an area of the given size.
+    ///
+    /// See [`Transform::transform_point_in`] for the coordinate spaces involved. This can be used
+    /// to convert damage between the coordinate spaces of a buffer and a surface, or an output and
+    /// its framebuffer.
+    pub fn transform_rect_in<N: Coordinate, Kind>(
+        &self,
+        rect: Rectangle<N, Kind>,
+        area: &Size<N, Kind>,
+    ) -> Rectangle<N, Kind> {
+        let a = self.transform_point_in(rect.loc, area);
+        let b = self.transform_point_in(rect.loc + rect.size, area);
+        let min = |a: N, b: N| if a < b { a } else { b };
+        let max = |a: N, b: N| if a > b { a } else { b };
+        Rectangle::from_extemities((min(a.x, b.x), min(a.y, b.y)), (max(a.x, b.x), max(a.y, b.y)))
+    }
+
     /// Transformed size after applying this transformation.
-    pub fn transform_size(&self, width: u32, height: u32) -> (u32, u32) {
+    pub fn transform_size<N: Coordinate, Kind>(&self, size: Size<N, Kind>) -> Size<N, Kind> {
         if *self == Transform::_90
             || *self == Transform::_270
             || *self == Transform::Flipped90
             || *self == Transform::Flipped270
         {
-            (height, width)
+            (size.h, size.w).into()
         } else {
-            (width, height)
+            size
         }
     }
 }
@@ -147,6 +219,62 @@ pub trait Unbind: Renderer {
     fn unbind(&mut self) -> Result<(), <Self as Renderer>::Error>;
 }
 
+/// Functionality to create offscreen rendering targets
+///
+/// The `Gles2Renderer` creates offscreen `Gles2Texture`s.
+/// Renderers do not allocate [`Dmabuf`](crate::backend::allocator::dmabuf::Dmabuf)s, to render into
+/// a dmabuf, allocate it through an [`Allocator`](crate::backend::allocator::Allocator) and bind it.
+pub trait Offscreen<Target>: Renderer + Bind<Target> {
+    /// Create a new instance of a framebuffer with the given size.
+    ///
+    /// The returned target can be bound via [`Bind::bind`] and rendered into
+    /// like any other target, without ever being displayed.
+    fn create_buffer(&mut self, size: Size<i32, Buffer>) -> Result<Target, <Self as Renderer>::Error>;
+}
+
+/// Functionality to read back rendering results into cpu-accessible memory
+pub trait ExportMem: Renderer {
+    /// Handle to the copied contents
+    type TextureMapping: TextureMapping;
+
+    /// Copies a region of the currently bound framebuffer.
+    ///
+    /// The `region` is given in buffer coordinates of the bound target with the origin
+    /// in the top-left corner. The contents are converted into the requested `format`,
+    /// if supported by the renderer.
+    fn copy_framebuffer(
+        &mut self,
+        region: Rectangle<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<Self::TextureMapping, <Self as Renderer>::Error>;
+
+    /// Copies a region of the given texture.
+    ///
+    /// The `region` is given in buffer coordinates of the texture with the origin
+    /// in the top-left corner. The contents are converted into the requested `format`,
+    /// if supported by the renderer.
+    fn copy_texture(
+        &mut self,
+        texture: &Self::TextureId,
+        region: Rectangle<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<Self::TextureMapping, <Self as Renderer>::Error>;
+
+    /// Returns a read-only pointer to the contents of a previously copied region.
+    ///
+    /// Rows are tightly packed and ordered from top to bottom.
+    fn map_texture<'a>(
+        &mut self,
+        texture_mapping: &'a Self::TextureMapping,
+    ) -> Result<&'a [u8], <Self as Renderer>::Error>;
+}
+
+/// A region of a framebuffer or texture copied into cpu-accessible memory (see [`ExportMem`])
+pub trait TextureMapping: Texture {
+    /// Pixel format of the copied contents
+    fn format(&self) -> Fourcc;
+}
+
 /// A two dimensional texture
 pub trait Texture {
     /// Size of the texture plane
@@ -167,15 +295,20 @@ pub trait Frame {
     /// Texture Handle type used by this renderer.
     type TextureId: Texture;
 
-    /// Clear the complete current target with a single given color.
+    /// Clear the given regions of the current target with a single given color.
+    ///
+    /// The regions are given in the coordinate space of the current frame.
     ///
     /// This operation is only valid in between a `begin` and `finish`-call.
     /// If called outside this operation may error-out, do nothing or modify future rendering results in any way.
-    fn clear(&mut self, color: [f32; 4]) -> Result<(), Self::Error>;
+    fn clear(&mut self, color: [f32; 4], at: &[Rectangle<i32, Physical>]) -> Result<(), Self::Error>;
 
     /// Render a texture to the current target as a flat 2d-plane at a given
     /// position and applying the given transformation with the given alpha value.
     /// (Meaning `src_transform` should match the orientation of surface being rendered).
+    ///
+    /// Only the parts of the target inside the given `damage` regions are touched.
+    #[allow(clippy::too_many_arguments)]
     fn render_texture_at(
         &mut self,
         texture: &Self::TextureId,
@@ -183,19 +316,18 @@ pub trait Frame {
         texture_scale: i32,
         output_scale: f64,
         src_transform: Transform,
+        damage: &[Rectangle<i32, Physical>],
         alpha: f32,
     ) -> Result<(), Self::Error> {
+        let size = src_transform.transform_size(texture.size());
         self.render_texture_from_to(
             texture,
             Rectangle::from_loc_and_size(Point::<i32, Buffer>::from((0, 0)), texture.size()),
             Rectangle::from_loc_and_size(
                 pos,
-                texture
-                    .size()
-                    .to_logical(texture_scale)
-                    .to_f64()
-                    .to_physical(output_scale),
+                size.to_logical(texture_scale).to_f64().to_physical(output_scale),
             ),
+            damage,
             src_transform,
             alpha,
         )
@@ -204,11 +336,15 @@ pub trait Frame {
     /// Render part of a texture as given by src to the current target into the rectangle described by dst
     /// as a flat 2d-plane after applying the inverse of the given transformation.
     /// (Meaning `src_transform` should match the orientation of surface being rendered).
+    ///
+    /// Only the parts of the target inside the given `damage` regions are touched.
+    /// The damage is given in the coordinate space of the current frame, like `dst`.
     fn render_texture_from_to(
         &mut self,
         texture: &Self::TextureId,
         src: Rectangle<i32, Buffer>,
         dst: Rectangle<f64, Physical>,
+        damage: &[Rectangle<i32, Physical>],
         src_transform: Transform,
         alpha: f32,
     ) -> Result<(), Self::Error>;
@@ -230,6 +366,11 @@ pub trait Renderer {
 
     /// Initialize a rendering context on the current rendering target with given dimensions and transformation.
     ///
+    /// `size` is the size of the rendering target in pixels and `dst_transform` the transformation
+    /// of the output it is displayed on. The frame is addressed in the coordinate space of the output,
+    /// which is the transformed `size` (see [`Transform::transform_size`]), so elements can be positioned
+    /// independently of the transformation.
+    ///
     /// This function *may* error, if:
     /// - The given dimensions are unsupported (too large) for this renderer
     /// - The given Transformation is not supported by the renderer (`Transform::Normal` is always supported).
@@ -245,6 +386,39 @@ pub trait Renderer {
         F: FnOnce(&mut Self, &mut Self::Frame) -> R;
 }
 
+/// Trait for Renderers supporting importing bitmaps from memory.
+pub trait ImportMem: Renderer {
+    /// Import a given bitmap into the renderer.
+    ///
+    /// The `data` is expected to contain tightly packed pixels in RGBA order with 8 bits per channel
+    /// (`Abgr8888` in drm-fourcc terms) with rows ordered from top to bottom, if not `flipped`.
+    /// Its length needs to match the given `size`.
+    ///
+    /// Returns a texture_id, which can be used with [`Frame::render_texture_at`]
+    /// or implementation-specific functions.
+    ///
+    /// This operation needs no bound or default rendering target.
+    fn import_memory(
+        &mut self,
+        data: &[u8],
+        size: Size<i32, Buffer>,
+        flipped: bool,
+    ) -> Result<<Self as Renderer>::TextureId, <Self as Renderer>::Error>;
+
+    /// Update a portion of a given texture from memory.
+    ///
+    /// The `data` is expected to contain the contents of the whole texture in the same
+    /// layout as for [`ImportMem::import_memory`], but only the given `region` will be uploaded.
+    ///
+    /// This operation needs no bound or default rendering target.
+    fn update_memory(
+        &mut self,
+        texture: &<Self as Renderer>::TextureId,
+        data: &[u8],
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<(), <Self as Renderer>::Error>;
+}
+
 #[cfg(feature = "wayland_frontend")]
 /// Trait for Renderers supporting importing shm-based buffers.
 pub trait ImportShm: Renderer {
@@ -263,6 +437,8 @@ pub trait ImportShm: Renderer {
     ///
     /// The `damage` argument provides a list of rectangle locating parts of the buffer that need to be updated. When provided
     /// with an empty list `&[]`, the renderer is allowed to not update the texture at all.
+    /// When caching textures per surface, the damage needs to cover all changes since the last import
+    /// of a buffer of the same surface, as only the damaged parts might be uploaded again.
     fn import_shm_buffer(
         &mut self,
         buffer: &wl_buffer::WlBuffer,
@@ -380,6 +556,15 @@ pub trait ImportDma: Renderer {
     ) -> Result<<Self as Renderer>::TextureId, <Self as Renderer>::Error>;
 }
 
+/// Trait for renderers able to synchronize with work of other gpu contexts through fences
+pub trait WaitFence: Renderer {
+    /// Let the renderer wait for the given fence, before executing any further commands.
+    ///
+    /// This is used to wait for clients to finish rendering into a buffer, before it is sampled from.
+    /// Implementations unable to wait on the gpu block until the fence is signaled.
+    fn wait_for_fence(&mut self, fence: SyncFile) -> Result<(), <Self as Renderer>::Error>;
+}
+
 // TODO: Replace this with a trait_alias, once that is stabilized.
 // pub type ImportAll = Renderer + ImportShm + ImportEgl;
 
@@ -403,6 +588,10 @@ pub trait ImportAll: Renderer {
     /// The `damage` argument provides a list of rectangle locating parts of the buffer that need to be updated. When provided
     /// with an empty list `&[]`, the renderer is allowed to not update the texture at all.
     ///
+    /// Before importing a dmabuf the renderer waits for its acquire fence, which is the fence
+    /// attached by the client through [`explicit_synchronization`](crate::wayland::explicit_synchronization),
+    /// if `surface` is provided and has one, or the implicit fence of the dmabuf otherwise.
+    ///
     /// Returns `None`, if the buffer type cannot be determined.
     fn import_buffer(
         &mut self,
@@ -418,7 +607,7 @@ pub trait ImportAll: Renderer {
     feature = "backend_egl",
     feature = "use_system_lib"
 ))]
-impl<R: Renderer + ImportShm + ImportEgl + ImportDma> ImportAll for R {
+impl<R: Renderer + ImportShm + ImportEgl + ImportDma + WaitFence> ImportAll for R {
     fn import_buffer(
         &mut self,
         buffer: &wl_buffer::WlBuffer,
@@ -428,7 +617,9 @@ impl<R: Renderer + ImportShm + ImportEgl + ImportDma> ImportAll for R {
         match buffer_type(buffer) {
             Some(BufferType::Shm) => Some(self.import_shm_buffer(buffer, surface, damage)),
             Some(BufferType::Egl) => Some(self.import_egl_buffer(buffer)),
-            Some(BufferType::Dma) => Some(self.import_dma_buffer(buffer)),
+            Some(BufferType::Dma) => Some(
+                wait_for_acquire_fence(self, buffer, surface).and_then(|_| self.import_dma_buffer(buffer)),
+            ),
             _ => None,
         }
     }
@@ -438,7 +629,7 @@ impl<R: Renderer + ImportShm + ImportEgl + ImportDma> ImportAll for R {
     feature = "wayland_frontend",
     not(all(feature = "backend_egl", feature = "use_system_lib"))
 ))]
-impl<R: Renderer + ImportShm + ImportDma> ImportAll for R {
+impl<R: Renderer + ImportShm + ImportDma + WaitFence> ImportAll for R {
     fn import_buffer(
         &mut self,
         buffer: &wl_buffer::WlBuffer,
@@ -447,12 +638,42 @@ impl<R: Renderer + ImportShm + ImportDma> ImportAll for R {
     ) -> Option<Result<<Self as Renderer>::TextureId, <Self as Renderer>::Error>> {
         match buffer_type(buffer) {
             Some(BufferType::Shm) => Some(self.import_shm_buffer(buffer, surface, damage)),
-            Some(BufferType::Dma) => Some(self.import_dma_buffer(buffer)),
+            Some(BufferType::Dma) => Some(
+                wait_for_acquire_fence(self, buffer, surface).and_then(|_| self.import_dma_buffer(buffer)),
+            ),
             _ => None,
         }
     }
 }
 
+#[cfg(feature = "wayland_frontend")]
+fn wait_for_acquire_fence<R: WaitFence>(
+    renderer: &mut R,
+    buffer: &wl_buffer::WlBuffer,
+    surface: Option<&SurfaceData>,
+) -> Result<(), <R as Renderer>::Error> {
+    use crate::wayland::explicit_synchronization::ExplicitSyncState;
+    use std::os::unix::io::FromRawFd;
+
+    let explicit = surface
+        .filter(|states| states.cached_state.has::<ExplicitSyncState>())
+        .and_then(|states| states.cached_state.current::<ExplicitSyncState>().acquire.take());
+    let fence = match explicit {
+        // the fd was handed over to us by the client
+        Some(fd) => Some(unsafe { SyncFile::from_raw_fd(fd) }),
+        // older kernels cannot export implicit fences, their drivers synchronize on their own
+        None => buffer
+            .as_ref()
+            .user_data()
+            .get::<Dmabuf>()
+            .and_then(|dmabuf| dmabuf.export_sync_file().ok()),
+    };
+    match fence {
+        Some(fence) => renderer.wait_for_fence(fence),
+        None => Ok(()),
+    }
+}
+
 #[cfg(feature = "wayland_frontend")]
 #[non_exhaustive]
 /// Buffer type of a given wl_buffer, if managed by smithay
@@ -520,3 +741,47 @@ pub fn buffer_dimensions(buffer: &wl_buffer::WlBuffer) -> Option<Size<i32, Physi
 
     crate::wayland::shm::with_buffer_contents(buffer, |_, data| (data.width, data.height).into()).ok()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::Transform;
+    use crate::utils::{Logical, Point, Rectangle, Size};
+
+    const TRANSFORMS: [Transform; 8] = [
+        Transform::Normal,
+        Transform::_90,
+        Transform::_180,
+        Transform::_270,
+        Transform::Flipped,
+        Transform::Flipped90,
+        Transform::Flipped180,
+        Transform::Flipped270,
+    ];
+
+    #[test]
+    fn transform_rect_round_trip() {
+        let area = Size::<i32, Logical>::from((30, 20));
+        let rect = Rectangle::<i32, Logical>::from_loc_and_size((2, 3), (10, 5));
+        for transform in TRANSFORMS {
+            let transformed = transform.transform_rect_in(rect, &area);
+            assert_eq!(
+                transform
+                    .invert()
+                    .transform_rect_in(transformed, &transform.transform_size(area)),
+                rect,
+                "{:?}",
+                transform
+            );
+        }
+    }
+
+    #[test]
+    fn transform_point_matches_matrix() {
+        // rotate the top-right corner counter-clockwise, like the projection matrix
+        let area = Size::<i32, Logical>::from((4, 2));
+        let point = Point::<i32, Logical>::from((4, 0));
+        assert_eq!(Transform::_90.transform_point_in(point, &area), (0, 0).into());
+        assert_eq!(Transform::_270.transform_point_in(point, &area), (2, 4).into());
+        assert_eq!(Transform::Flipped.transform_point_in(point, &area), (0, 0).into());
+    }
+}
@@ -0,0 +1,158 @@
+//! Helpers to render outputs or surfaces into memory
+//!
+//! These functions render a set of [`RenderElement`]s into an offscreen buffer
+//! and read back the result, e.g. for taking screenshots or to implement screen
+//! capturing protocols.
+//!
+//! *Note*: The functions bind their own offscreen target and unbind it afterwards.
+//! Any previously bound target has to be bound again, before rendering to it.
+
+use crate::{
+    backend::allocator::Fourcc,
+    utils::{Buffer, Physical, Rectangle, Size},
+};
+
+#[cfg(feature = "wayland_frontend")]
+use super::utils::{import_surface_tree, surface_tree_render_elements};
+#[cfg(feature = "wayland_frontend")]
+use super::{element::Element, ImportAll};
+use super::{element::RenderElement, ExportMem, Frame, Offscreen, Renderer, Transform};
+#[cfg(feature = "wayland_frontend")]
+use wayland_server::protocol::wl_surface::WlSurface;
+
+/// Contents of a rendered screenshot
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Screenshot {
+    /// Size of the screenshot in pixels
+    pub size: Size<i32, Buffer>,
+    /// Pixel data in [`Fourcc::Abgr8888`] format (RGBA byte order),
+    /// ordered row by row from the top-left corner
+    pub data: Vec<u8>,
+}
+
+/// Render the given elements into memory.
+///
+/// `elements` are expected to be ordered front to back and to be located
+/// in an area of the given `size` at the given `scale`.
+pub fn render_elements_to_memory<R, E>(
+    renderer: &mut R,
+    size: Size<i32, Physical>,
+    scale: f64,
+    elements: &[E],
+    clear_color: [f32; 4],
+    log: &slog::Logger,
+) -> Result<Screenshot, <R as Renderer>::Error>
+where
+    R: Offscreen<<R as Renderer>::TextureId> + ExportMem,
+    E: RenderElement<R>,
+{
+    let buffer_size = Size::<i32, Buffer>::from((size.w, size.h));
+    let buffer = renderer.create_buffer(buffer_size)?;
+    renderer.bind(buffer)?;
+
+    let area = Rectangle::from_loc_and_size((0, 0), size);
+    let result = renderer
+        .render(size, Transform::Normal, |renderer, frame| {
+            frame.clear(clear_color, &[area])?;
+            for element in elements.iter().rev() {
+                if let Some(damage) = element.geometry(scale).intersection(area) {
+                    element.draw(renderer, frame, scale, &[damage], log)?;
+                }
+            }
+            Ok(())
+        })
+        .and_then(|result| result)
+        .and_then(|_| {
+            renderer.copy_framebuffer(
+                Rectangle::from_loc_and_size((0, 0), buffer_size),
+                Fourcc::Abgr8888,
+            )
+        })
+        .and_then(|mapping| renderer.map_texture(&mapping).map(|data| data.to_vec()));
+
+    let unbind = renderer.unbind();
+    // the rendering error is more relevant than a failed unbind
+    let data = result?;
+    unbind?;
+    Ok(Screenshot {
+        size: buffer_size,
+        data,
+    })
+}
+
+/// Render the contents of an output into memory.
+///
+/// `size`, `scale` and `transform` describe the current mode of the output, `elements`
+/// are expected to be ordered front to back in the coordinate space of the output
+/// (see [`OutputDamageTracker::output_geometry`](super::damage::OutputDamageTracker::output_geometry)).
+///
+/// The screenshot is upright, as the contents are presented to the user, so the output
+/// transformation is not applied.
+pub fn render_output_to_memory<R, E>(
+    renderer: &mut R,
+    size: Size<i32, Physical>,
+    scale: f64,
+    transform: Transform,
+    elements: &[E],
+    clear_color: [f32; 4],
+    log: &slog::Logger,
+) -> Result<Screenshot, <R as Renderer>::Error>
+where
+    R: Offscreen<<R as Renderer>::TextureId> + ExportMem,
+    E: RenderElement<R>,
+{
+    render_elements_to_memory(
+        renderer,
+        transform.transform_size(size),
+        scale,
+        elements,
+        clear_color,
+        log,
+    )
+}
+
+/// Render a surface tree, e.g. of a single window, into memory.
+///
+/// The screenshot covers the bounding box of all mapped surfaces of the tree
+/// on a transparent background.
+///
+/// Requires [`on_commit_buffer_handler`](super::utils::on_commit_buffer_handler)
+/// to be used for the surfaces.
+#[cfg(feature = "wayland_frontend")]
+pub fn render_surface_tree_to_memory<R>(
+    renderer: &mut R,
+    surface: &WlSurface,
+    scale: f64,
+    log: &slog::Logger,
+) -> Result<Screenshot, <R as Renderer>::Error>
+where
+    R: Offscreen<<R as Renderer>::TextureId> + ExportMem + ImportAll,
+    <R as Renderer>::TextureId: Send + 'static,
+{
+    import_surface_tree(renderer, surface, log);
+
+    let bounding_box = surface_tree_render_elements(surface, (0, 0).into())
+        .iter()
+        .map(|element| element.geometry(scale))
+        .reduce(|a, b| a.merge(b));
+    let bounding_box = match bounding_box {
+        Some(bbox) if bbox.size.w > 0 && bbox.size.h > 0 => bbox,
+        _ => {
+            return Ok(Screenshot {
+                size: (0, 0).into(),
+                data: Vec::new(),
+            })
+        }
+    };
+
+    // move subsurfaces with negative offsets into the visible area
+    let elements = surface_tree_render_elements(surface, (-bounding_box.loc.x, -bounding_box.loc.y).into());
+    render_elements_to_memory(
+        renderer,
+        bounding_box.size,
+        scale,
+        &elements,
+        [0.0, 0.0, 0.0, 0.0],
+        log,
+    )
+}
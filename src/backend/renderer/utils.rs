@@ -0,0 +1,524 @@
+//! Utility functions for rendering wayland surfaces
+//!
+//! To use the [`OutputDamageTracker`](super::damage::OutputDamageTracker) with client surfaces,
+//! call [`on_commit_buffer_handler`] in your commit handler to keep track of the attached buffers
+//! and their damage. Afterwards the buffers of a surface tree can be imported via [`import_surface_tree`]
+//! and turned into [`RenderElement`]s via [`surface_tree_render_elements`].
+
+use std::{
+    any::{Any, TypeId},
+    collections::{HashMap, VecDeque},
+    sync::Mutex,
+};
+
+use wayland_server::protocol::{wl_buffer::WlBuffer, wl_surface::WlSurface};
+
+use crate::{
+    backend::renderer::{
+        buffer_dimensions,
+        element::{CommitCounter, Element, Id, RenderElement},
+        Frame, ImportAll, Renderer, Texture, Transform,
+    },
+    utils::{Buffer, Logical, Physical, Point, Rectangle, Region, Size},
+    wayland::{
+        compositor::{
+            with_states, with_surface_tree_located_upward, with_surface_tree_upward, BufferAssignment,
+            Damage, SurfaceAttributes, TraversalAction,
+        },
+        data_device::DnDIconAttributes,
+    },
+};
+
+use slog::warn;
+
+/// Number of commits of damage kept per surface
+const MAX_DAMAGE: usize = 4;
+
+/// Maximum number of rectangles the accumulated damage of a surface is simplified to,
+/// bounding the number of partial uploads of shm buffers
+const MAX_DAMAGE_RECTS: usize = 8;
+
+/// Renderer related state of a surface, tracked by [`on_commit_buffer_handler`]
+///
+/// It is stored as `Mutex<RendererSurfaceState>` in the `data_map` of the surface,
+/// so surfaces can be imported and drawn from any thread.
+#[derive(Debug)]
+pub struct RendererSurfaceState {
+    id: Id,
+    commit_count: CommitCounter,
+    buffer: Option<WlBuffer>,
+    buffer_dimensions: Option<Size<i32, Buffer>>,
+    buffer_scale: i32,
+    buffer_transform: Transform,
+    damage: VecDeque<Vec<Rectangle<i32, Buffer>>>,
+    textures: HashMap<TypeId, Box<dyn Any + Send>>,
+    renderer_seen: HashMap<TypeId, CommitCounter>,
+}
+
+impl RendererSurfaceState {
+    fn new() -> RendererSurfaceState {
+        RendererSurfaceState {
+            id: Id::new(),
+            commit_count: CommitCounter::default(),
+            buffer: None,
+            buffer_dimensions: None,
+            buffer_scale: 1,
+            buffer_transform: Transform::Normal,
+            damage: VecDeque::new(),
+            textures: HashMap::new(),
+            renderer_seen: HashMap::new(),
+        }
+    }
+
+    fn update_buffer(&mut self, attrs: &mut SurfaceAttributes) {
+        match attrs.buffer.take() {
+            Some(BufferAssignment::NewBuffer { buffer, .. }) => {
+                self.buffer_dimensions = buffer_dimensions(&buffer).map(|dim| Size::from((dim.w, dim.h)));
+                self.buffer_scale = attrs.buffer_scale;
+                self.buffer_transform = attrs.buffer_transform.into();
+                if let Some(old_buffer) = self.buffer.replace(buffer) {
+                    if Some(&old_buffer) != self.buffer.as_ref() {
+                        old_buffer.release();
+                    }
+                }
+                self.textures.clear();
+                self.commit_count.increment();
+
+                let buffer_scale = self.buffer_scale;
+                let buffer_transform = self.buffer_transform;
+                // the size of the surface in buffer coordinates, before applying the buffer transformation
+                let surface_size = self
+                    .buffer_dimensions
+                    .map(|dim| buffer_transform.transform_size(dim));
+                let damage = attrs
+                    .damage
+                    .drain(..)
+                    .filter_map(|dmg| match dmg {
+                        Damage::Buffer(rect) => Some(rect),
+                        Damage::Surface(rect) => match surface_size {
+                            Some(size) => {
+                                let bounds =
+                                    Rectangle::from_loc_and_size((0, 0), size.to_logical(buffer_scale));
+                                rect.intersection(bounds).map(|rect| {
+                                    buffer_transform.transform_rect_in(rect.to_buffer(buffer_scale), &size)
+                                })
+                            }
+                            None => Some(rect.to_buffer(buffer_scale)),
+                        },
+                    })
+                    .collect::<Vec<_>>();
+                self.damage.push_front(damage);
+                self.damage.truncate(MAX_DAMAGE);
+            }
+            Some(BufferAssignment::Removed) => {
+                if let Some(buffer) = self.buffer.take() {
+                    buffer.release();
+                }
+                self.buffer_dimensions = None;
+                self.textures.clear();
+                self.commit_count.increment();
+                self.damage.clear();
+            }
+            None => {}
+        }
+    }
+
+    /// Unique id of this surface, used for its render elements
+    pub fn id(&self) -> &Id {
+        &self.id
+    }
+
+    /// Current commit of the surface contents
+    pub fn current_commit(&self) -> CommitCounter {
+        self.commit_count
+    }
+
+    /// Returns the currently attached buffer, if any
+    pub fn wl_buffer(&self) -> Option<&WlBuffer> {
+        self.buffer.as_ref()
+    }
+
+    /// Dimensions of the currently attached buffer
+    pub fn buffer_dimensions(&self) -> Option<Size<i32, Buffer>> {
+        self.buffer_dimensions
+    }
+
+    /// Scale of the currently attached buffer
+    pub fn buffer_scale(&self) -> i32 {
+        self.buffer_scale
+    }
+
+    /// Transform of the currently attached buffer
+    pub fn buffer_transform(&self) -> Transform {
+        self.buffer_transform
+    }
+
+    /// Size of the surface in logical coordinates, if a buffer is attached
+    pub fn surface_size(&self) -> Option<Size<i32, Logical>> {
+        self.buffer_dimensions.map(|dim| {
+            self.buffer_transform
+                .transform_size(dim)
+                .to_logical(self.buffer_scale)
+        })
+    }
+
+    /// Accumulated buffer damage since a given commit.
+    ///
+    /// The damage is clipped to the buffer and does not overlap.
+    /// Returns the whole buffer as damaged, if the commit is too old to be tracked.
+    pub fn damage_since(&self, commit: Option<CommitCounter>) -> Vec<Rectangle<i32, Buffer>> {
+        let dimensions = match self.buffer_dimensions {
+            Some(dim) => dim,
+            None => return Vec::new(),
+        };
+        let bounds = Rectangle::from_loc_and_size((0, 0), dimensions);
+        match self.commit_count.distance(commit) {
+            Some(distance) if distance <= self.damage.len() => {
+                let mut damage = Region::from_rects(self.damage.iter().take(distance).flatten().copied());
+                damage.intersect(bounds);
+                damage.simplify(MAX_DAMAGE_RECTS);
+                damage.into_rects()
+            }
+            _ => vec![bounds],
+        }
+    }
+
+    /// Returns the texture imported for the current buffer by a renderer with the given texture type
+    pub fn texture<T: 'static>(&self) -> Option<&T> {
+        self.textures
+            .get(&TypeId::of::<T>())
+            .and_then(|texture| texture.downcast_ref::<T>())
+    }
+}
+
+/// Handler to let smithay take over buffer management.
+///
+/// Needs to be called on every commit of a surface (from the handler given to
+/// [`compositor_init`](crate::wayland::compositor::compositor_init)). It takes
+/// the newly attached buffers of the surface tree of the committed surface and
+/// tracks their damage in a [`RendererSurfaceState`].
+///
+/// Previously attached buffers are released, when they are replaced.
+pub fn on_commit_buffer_handler(surface: &WlSurface) {
+    with_surface_tree_upward(
+        surface,
+        (),
+        |_, _, _| TraversalAction::DoChildren(()),
+        |_, states, _| {
+            states
+                .data_map
+                .insert_if_missing_threadsafe(|| Mutex::new(RendererSurfaceState::new()));
+            let mut data = states
+                .data_map
+                .get::<Mutex<RendererSurfaceState>>()
+                .unwrap()
+                .lock()
+                .unwrap();
+            data.update_buffer(&mut states.cached_state.current::<SurfaceAttributes>());
+        },
+        |_, _, _| true,
+    );
+}
+
+/// Imports the buffers of a surface tree into the given renderer.
+///
+/// Requires [`on_commit_buffer_handler`] to be used for the surfaces.
+/// Already imported buffers are not imported again. Buffers that fail to import are
+/// logged and skipped, the surface will not be drawn until a new buffer is attached.
+pub fn import_surface_tree<R>(renderer: &mut R, surface: &WlSurface, log: &slog::Logger)
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: Send + 'static,
+{
+    with_surface_tree_upward(
+        surface,
+        (),
+        |_, _, _| TraversalAction::DoChildren(()),
+        |_, states, _| {
+            if let Some(data) = states.data_map.get::<Mutex<RendererSurfaceState>>() {
+                let mut data = data.lock().unwrap();
+                let type_id = TypeId::of::<<R as Renderer>::TextureId>();
+                if data.textures.contains_key(&type_id) {
+                    return;
+                }
+                let buffer = match data.buffer.as_ref() {
+                    Some(buffer) => buffer.clone(),
+                    None => return,
+                };
+                // everything changed since the last import has to be uploaded again
+                let damage = data.damage_since(data.renderer_seen.get(&type_id).copied());
+                match renderer.import_buffer(&buffer, Some(states), &damage) {
+                    Some(Ok(texture)) => {
+                        data.textures.insert(type_id, Box::new(texture));
+                        let commit = data.commit_count;
+                        data.renderer_seen.insert(type_id, commit);
+                    }
+                    Some(Err(err)) => {
+                        warn!(log, "Error loading buffer: {}", err);
+                    }
+                    None => {
+                        warn!(log, "Unknown buffer format for: {:?}", buffer);
+                    }
+                }
+            }
+        },
+        |_, _, _| true,
+    );
+}
+
+/// Creates the [`RenderElement`]s for a surface tree located at `location` on an output.
+///
+/// The elements are ordered from front to back, as expected by
+/// the [`OutputDamageTracker`](super::damage::OutputDamageTracker).
+/// Surfaces without a buffer (and their children) are skipped.
+pub fn surface_tree_render_elements(
+    surface: &WlSurface,
+    location: Point<i32, Physical>,
+) -> Vec<WaylandSurfaceRenderElement> {
+    let mut elements = Vec::new();
+    with_surface_tree_located_upward(
+        surface,
+        (0, 0),
+        |_, states, _| {
+            let mapped = states
+                .data_map
+                .get::<Mutex<RendererSurfaceState>>()
+                .map(|data| data.lock().unwrap().buffer.is_some())
+                .unwrap_or(false);
+            if mapped {
+                TraversalAction::DoChildren(())
+            } else {
+                TraversalAction::SkipChildren
+            }
+        },
+        |surface, states, offset| {
+            if let Some(data) = states.data_map.get::<Mutex<RendererSurfaceState>>() {
+                let data = data.lock().unwrap();
+                if data.buffer.is_none() {
+                    return true;
+                }
+                elements.push(WaylandSurfaceRenderElement {
+                    id: data.id.clone(),
+                    surface: surface.clone(),
+                    location,
+                    offset,
+                    commit: data.commit_count,
+                    size: data.surface_size().unwrap_or_default(),
+                });
+            }
+            true
+        },
+    );
+    elements.reverse();
+    elements
+}
+
+/// Creates the [`RenderElement`]s for a drag'n'drop icon, anchored to the pointer at `pointer_location`
+/// on an output.
+///
+/// The icon is offset by its [`DnDIconAttributes`](crate::wayland::data_device::DnDIconAttributes),
+/// see [`current_dnd_icon`](crate::wayland::data_device::current_dnd_icon) to get the icon of a seat.
+pub fn dnd_icon_render_elements(
+    surface: &WlSurface,
+    pointer_location: Point<f64, Logical>,
+    scale: f64,
+) -> Vec<WaylandSurfaceRenderElement> {
+    let offset = with_states(surface, |states| {
+        states
+            .data_map
+            .get::<Mutex<DnDIconAttributes>>()
+            .map(|attributes| attributes.lock().unwrap().offset)
+    })
+    .ok()
+    .flatten()
+    .unwrap_or_default();
+    let location = (pointer_location + offset.to_f64())
+        .to_physical(scale)
+        .to_i32_round();
+    surface_tree_render_elements(surface, location)
+}
+
+/// A [`RenderElement`] of a single wayland surface
+#[derive(Debug)]
+pub struct WaylandSurfaceRenderElement {
+    id: Id,
+    surface: WlSurface,
+    location: Point<i32, Physical>,
+    offset: Point<i32, Logical>,
+    commit: CommitCounter,
+    size: Size<i32, Logical>,
+}
+
+impl WaylandSurfaceRenderElement {
+    /// The underlying surface of this element
+    pub fn surface(&self) -> &WlSurface {
+        &self.surface
+    }
+}
+
+fn to_physical_bounds(rect: Rectangle<f64, Logical>, scale: f64) -> Rectangle<i32, Physical> {
+    let rect = rect.to_physical(scale);
+    Rectangle::from_extemities(rect.loc.to_i32_floor(), (rect.loc + rect.size).to_i32_ceil())
+}
+
+impl Element for WaylandSurfaceRenderElement {
+    fn id(&self) -> &Id {
+        &self.id
+    }
+
+    fn current_commit(&self) -> CommitCounter {
+        self.commit
+    }
+
+    fn geometry(&self, scale: f64) -> Rectangle<i32, Physical> {
+        let loc = self.location + self.offset.to_f64().to_physical(scale).to_i32_round();
+        Rectangle::from_loc_and_size(loc, self.size.to_f64().to_physical(scale).to_i32_round())
+    }
+
+    fn damage_since(&self, scale: f64, commit: Option<CommitCounter>) -> Vec<Rectangle<i32, Physical>> {
+        with_states(&self.surface, |states| {
+            states
+                .data_map
+                .get::<Mutex<RendererSurfaceState>>()
+                .map(|data| {
+                    let data = data.lock().unwrap();
+                    let buffer_scale = data.buffer_scale;
+                    // the compositor applies the inverse of the buffer transformation
+                    let transform = data.buffer_transform.invert();
+                    let dimensions = data.buffer_dimensions.unwrap_or_default();
+                    data.damage_since(commit)
+                        .into_iter()
+                        .map(|rect| {
+                            let rect = transform.transform_rect_in(rect, &dimensions);
+                            to_physical_bounds(rect.to_logical(buffer_scale).to_f64(), scale)
+                        })
+                        .collect()
+                })
+                .unwrap_or_default()
+        })
+        .unwrap_or_default()
+    }
+}
+
+impl<R> RenderElement<R> for WaylandSurfaceRenderElement
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: Send + 'static,
+{
+    fn draw(
+        &self,
+        _renderer: &mut R,
+        frame: &mut <R as Renderer>::Frame,
+        scale: f64,
+        damage: &[Rectangle<i32, Physical>],
+        log: &slog::Logger,
+    ) -> Result<(), <R as Renderer>::Error> {
+        with_states(&self.surface, |states| {
+            if let Some(data) = states.data_map.get::<Mutex<RendererSurfaceState>>() {
+                let data = data.lock().unwrap();
+                match data.texture::<<R as Renderer>::TextureId>() {
+                    Some(texture) => {
+                        // fill the rounded geometry, so fractional scales do not leave gaps
+                        // in between the surface and its damage.
+                        let src = Rectangle::from_loc_and_size((0, 0), texture.size());
+                        frame.render_texture_from_to(
+                            texture,
+                            src,
+                            self.geometry(scale).to_f64(),
+                            damage,
+                            data.buffer_transform,
+                            1.0,
+                        )?;
+                    }
+                    None => warn!(log, "Surface {:?} has no imported texture", self.surface),
+                }
+            }
+            Ok(())
+        })
+        .unwrap_or(Ok(()))
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::{import_surface_tree, surface_tree_render_elements, RendererSurfaceState};
+    use crate::{
+        backend::{
+            allocator::Fourcc,
+            renderer::{damage::OutputDamageTracker, Bind, ExportMem, Offscreen, Transform},
+        },
+        testing::{test_setup, SoftwareRenderer},
+        utils::{Rectangle, Size},
+        wayland::compositor::with_states,
+    };
+    use std::sync::Mutex;
+    use wayland_client::protocol::wl_output;
+
+    #[test]
+    fn surface_damage_is_transformed_into_the_buffer() {
+        let (mut server, mut client, _) = test_setup(|_| ());
+        let surface = client.create_surface().unwrap();
+        let buffer = client.create_shm_buffer((32, 16), [0, 0, 255, 255]).unwrap();
+        surface
+            .wl_surface()
+            .set_buffer_transform(wl_output::Transform::_90);
+        surface.attach(Some(&buffer));
+        surface.commit();
+        server.roundtrip(&mut client, &mut ()).unwrap();
+
+        let wl_surface = server.wl_surface(&client, &surface).unwrap();
+        let commit = with_states(&wl_surface, |states| {
+            let data = states.data_map.get::<Mutex<RendererSurfaceState>>().unwrap();
+            let data = data.lock().unwrap();
+            assert_eq!(data.surface_size(), Some((16, 32).into()));
+            data.current_commit()
+        })
+        .unwrap();
+
+        // damage the top-left corner of the rotated surface
+        surface.wl_surface().attach(Some(buffer.wl_buffer()), 0, 0);
+        surface.wl_surface().damage(0, 0, 4, 8);
+        surface.commit();
+        server.roundtrip(&mut client, &mut ()).unwrap();
+        let damage = with_states(&wl_surface, |states| {
+            let data = states.data_map.get::<Mutex<RendererSurfaceState>>().unwrap();
+            let damage = data.lock().unwrap().damage_since(Some(commit));
+            damage
+        })
+        .unwrap();
+        assert_eq!(damage, vec![Rectangle::from_loc_and_size((0, 12), (8, 4))]);
+    }
+
+    #[test]
+    fn client_surface_is_rendered_on_another_thread() {
+        let (mut server, mut client, _) = test_setup(|_| ());
+        let surface = client.create_surface().unwrap();
+        let buffer = client.create_shm_buffer((32, 32), [0, 0, 255, 255]).unwrap();
+        surface.attach(Some(&buffer));
+        surface.commit();
+        server.roundtrip(&mut client, &mut ()).unwrap();
+
+        let wl_surface = server.wl_surface(&client, &surface).unwrap();
+        let log = server.log().clone();
+        let data = std::thread::spawn(move || {
+            let mut renderer = SoftwareRenderer::new(log.clone());
+            import_surface_tree(&mut renderer, &wl_surface, &log);
+            let elements = surface_tree_render_elements(&wl_surface, (0, 0).into());
+
+            let size = Size::from((32, 32));
+            let target = renderer.create_buffer(size).unwrap();
+            renderer.bind(target).unwrap();
+            let mut damage_tracker = OutputDamageTracker::new((32, 32), 1.0, Transform::Normal);
+            damage_tracker
+                .render_output(&mut renderer, 0, &elements, [0.0, 0.0, 0.0, 1.0], &log)
+                .unwrap();
+            let mapping = renderer
+                .copy_framebuffer(Rectangle::from_loc_and_size((0, 0), size), Fourcc::Abgr8888)
+                .unwrap();
+            renderer.map_texture(&mapping).unwrap().to_vec()
+        })
+        .join()
+        .unwrap();
+        assert_eq!(&data[..4], &[0, 0, 255, 255]);
+    }
+}
@@ -29,7 +29,8 @@
 //! for notifications are the [`Libinput`](input::Libinput) context or the [`DrmDevice`](crate::backend::drm::DrmDevice).
 //!
 //! The [`AutoSessionNotifier`] is to be inserted into
-//! a calloop event source to have its events processed.
+//! a calloop event source to have its events processed. Every [`Signal`](crate::backend::session::Signal)
+//! is delivered to the callback of the event source, after it has been sent to the linked objects.
 
 #[cfg(feature = "backend_session_libseat")]
 use super::libseat::{LibSeatSession, LibSeatSessionNotifier};
@@ -200,13 +201,13 @@ impl AutoSessionNotifier {
 }
 
 impl EventSource for AutoSessionNotifier {
-    type Event = ();
+    type Event = SessionSignal;
     type Metadata = ();
     type Ret = ();
 
     fn process_events<F>(&mut self, readiness: Readiness, token: Token, callback: F) -> io::Result<PostAction>
     where
-        F: FnMut((), &mut ()),
+        F: FnMut(SessionSignal, &mut ()),
     {
         match self {
             #[cfg(feature = "backend_session_logind")]
@@ -32,7 +32,8 @@
 //! for notifications are the [`Libinput`](input::Libinput) context or the [`DrmDevice`](crate::backend::drm::DrmDevice).
 //!
 //! The [`LogindSessionNotifier`] is to be inserted into
-//! a calloop event source to have its events processed.
+//! a calloop event source to have its events processed. Every [`Signal`](crate::backend::session::Signal)
+//! is delivered to the callback of the event source, after it has been sent to the linked objects.
 
 use crate::{
     backend::session::{AsErrno, Session, Signal as SessionSignal},
@@ -274,7 +275,18 @@ impl LogindSessionImpl {
         }
     }
 
-    fn handle_message(&self, message: dbus::Message) -> Result<(), Error> {
+    fn signal<F>(&self, signal: SessionSignal, callback: &mut F)
+    where
+        F: FnMut(SessionSignal, &mut ()),
+    {
+        self.signaler.signal(signal);
+        callback(signal, &mut ());
+    }
+
+    fn handle_message<F>(&self, message: dbus::Message, callback: &mut F) -> Result<(), Error>
+    where
+        F: FnMut(SessionSignal, &mut ()),
+    {
         if &*message.interface().unwrap() == "org.freedesktop.login1.Manager"
             && &*message.member().unwrap() == "SessionRemoved"
             && message.get1::<String>().unwrap() == self.session_id
@@ -283,7 +295,7 @@ impl LogindSessionImpl {
             //Ok... now what?
             //This session will never live again, but the user maybe has other sessions open
             //So lets just put it to sleep.. forever
-            self.signaler.signal(SessionSignal::PauseSession);
+            self.signal(SessionSignal::PauseSession, callback);
             self.active.store(false, Ordering::SeqCst);
             warn!(self.logger, "Session is now considered inactive");
         } else if &*message.interface().unwrap() == "org.freedesktop.login1.Session" {
@@ -310,7 +322,7 @@ impl LogindSessionImpl {
                 // notifications about it.
                 // This is handled via udev and is not part of our session api.
                 if pause_type != "gone" {
-                    self.signaler.signal(SessionSignal::PauseDevice { major, minor });
+                    self.signal(SessionSignal::PauseDevice { major, minor }, callback);
                 }
                 // the other possible types are "force" or "gone" (unplugged),
                 // both expect no acknowledgement (note even this is not *really* necessary,
@@ -332,11 +344,14 @@ impl LogindSessionImpl {
                 let minor = minor.ok_or(Error::UnexpectedMethodReturn)?;
                 let fd = fd.ok_or(Error::UnexpectedMethodReturn)?.into_fd();
                 debug!(self.logger, "Reactivating device ({},{})", major, minor);
-                self.signaler.signal(SessionSignal::ActivateDevice {
-                    major,
-                    minor,
-                    new_fd: Some(fd),
-                });
+                self.signal(
+                    SessionSignal::ActivateDevice {
+                        major,
+                        minor,
+                        new_fd: Some(fd),
+                    },
+                    callback,
+                );
             }
         } else if &*message.interface().unwrap() == "org.freedesktop.DBus.Properties"
             && &*message.member().unwrap() == "PropertiesChanged"
@@ -456,13 +471,18 @@ impl Drop for LogindSessionNotifier {
 }
 
 impl EventSource for LogindSessionNotifier {
-    type Event = ();
+    type Event = SessionSignal;
     type Metadata = ();
     type Ret = ();
 
-    fn process_events<F>(&mut self, readiness: Readiness, token: Token, _: F) -> std::io::Result<PostAction>
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> std::io::Result<PostAction>
     where
-        F: FnMut((), &mut ()),
+        F: FnMut(SessionSignal, &mut ()),
     {
         // Accumulate the messages, and then process them, as we can't keep the borrow on the `DBusConnection`
         // while processing the messages
@@ -473,7 +493,7 @@ impl EventSource for LogindSessionNotifier {
             .process_events(readiness, token, |msg, _| messages.push(msg))?;
 
         for msg in messages {
-            if let Err(err) = self.internal.handle_message(msg) {
+            if let Err(err) = self.internal.handle_message(msg, &mut callback) {
                 error!(self.internal.logger, "Error handling dbus messages: {}", err);
             }
         }
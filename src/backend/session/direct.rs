@@ -44,7 +44,8 @@
 //! for notifications are the [`Libinput`](input::Libinput) context or the [`DrmDevice`](crate::backend::drm::DrmDevice).
 //!
 //! The [`DirectSessionNotifier`] is to be inserted into
-//! a calloop event source to have its events processed.
+//! a calloop event source to have its events processed. Every [`Signal`](crate::backend::session::Signal)
+//! is delivered to the callback of the event source, after it has been sent to the linked objects.
 
 use super::{AsErrno, Session, Signal as SessionSignal};
 use crate::utils::signaling::Signaler;
@@ -412,7 +413,7 @@ impl Drop for DirectSession {
 pub struct Id(usize);
 
 impl DirectSessionNotifier {
-    fn signal_received(&mut self) {
+    fn signal_received(&mut self) -> SessionSignal {
         if self.active.load(Ordering::SeqCst) {
             info!(self.logger, "Session shall become inactive.");
             self.signaler.signal(SessionSignal::PauseSession);
@@ -421,6 +422,7 @@ impl DirectSessionNotifier {
                 tty::vt_rel_disp(self.tty, 1).expect("Unable to release tty lock");
             }
             debug!(self.logger, "Session is now inactive");
+            SessionSignal::PauseSession
         } else {
             debug!(self.logger, "Session will become active again");
             unsafe {
@@ -429,6 +431,7 @@ impl DirectSessionNotifier {
             self.signaler.signal(SessionSignal::ActivateSession);
             self.active.store(true, Ordering::SeqCst);
             info!(self.logger, "Session is now active again");
+            SessionSignal::ActivateSession
         }
     }
 
@@ -441,7 +444,7 @@ impl DirectSessionNotifier {
 }
 
 impl calloop::EventSource for DirectSessionNotifier {
-    type Event = ();
+    type Event = SessionSignal;
     type Metadata = ();
     type Ret = ();
 
@@ -449,14 +452,17 @@ impl calloop::EventSource for DirectSessionNotifier {
         &mut self,
         readiness: calloop::Readiness,
         token: calloop::Token,
-        _: F,
+        mut callback: F,
     ) -> std::io::Result<calloop::PostAction>
     where
-        F: FnMut((), &mut ()),
+        F: FnMut(SessionSignal, &mut ()),
     {
         let mut source = self.source.take();
         if let Some(ref mut source) = source {
-            source.process_events(readiness, token, |_, _| self.signal_received())?;
+            source.process_events(readiness, token, |_, _| {
+                let signal = self.signal_received();
+                callback(signal, &mut ());
+            })?;
         }
         self.source = source;
         Ok(calloop::PostAction::Continue)
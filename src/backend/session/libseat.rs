@@ -210,13 +210,18 @@ impl LibSeatSessionNotifier {
 }
 
 impl EventSource for LibSeatSessionNotifier {
-    type Event = ();
+    type Event = SessionSignal;
     type Metadata = ();
     type Ret = ();
 
-    fn process_events<F>(&mut self, readiness: Readiness, token: Token, _: F) -> std::io::Result<PostAction>
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> std::io::Result<PostAction>
     where
-        F: FnMut((), &mut ()),
+        F: FnMut(SessionSignal, &mut ()),
     {
         if token == self.token {
             self.internal.seat.borrow_mut().dispatch(0).unwrap();
@@ -230,10 +235,12 @@ impl EventSource for LibSeatSessionNotifier {
                 SeatEvent::Enable => {
                     internal.active.store(true, Ordering::SeqCst);
                     signaler.signal(SessionSignal::ActivateSession);
+                    callback(SessionSignal::ActivateSession, &mut ());
                 }
                 SeatEvent::Disable => {
                     internal.active.store(false, Ordering::SeqCst);
                     signaler.signal(SessionSignal::PauseSession);
+                    callback(SessionSignal::PauseSession, &mut ());
                     internal.seat.borrow_mut().disable().unwrap();
                 }
             },
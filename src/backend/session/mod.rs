@@ -17,11 +17,13 @@
 //! of devices, a VT change, or information about the session state.
 //!
 //! The second is a notifier which informs you when the session is enabled or disabled by the system.
-//! This notifier takes the form of a [`calloop`] event source, but it does not generates any event
-//! directly. Instead, it gives you a [`Signaler`](crate::utils::signaling::Signaler) that you can
-//! pass around to other backend modules that need to be notified of the new session state, to stop
-//! accessing the devices while the session is disabled. Notable examples are the
-//! [`libinput`](super::libinput) and [`drm`](super::drm) backends.
+//! This notifier takes the form of a [`calloop`] event source, generating a [`Signal`] event whenever
+//! the session or one of its devices is paused or activated. Additionally it gives you a
+//! [`Signaler`](crate::utils::signaling::Signaler) that you can pass around to other backend modules
+//! that need to be notified of the new session state, to stop accessing the devices while the session
+//! is disabled. Notable examples are the [`libinput`](super::libinput) and [`drm`](super::drm) backends.
+//! The events of the notifier are generated after the linked modules have been notified, so the
+//! compositor can for example resume rendering directly from the event callback.
 //!
 //! ## Available providers
 //!
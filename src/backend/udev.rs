@@ -142,6 +142,7 @@ impl EventSource for UdevBackend {
         if token != self.token {
             return Ok(PostAction::Continue);
         }
+        trace_span!(TRACE, "udev::process_events");
         let monitor = self.monitor.clone();
         for event in monitor {
             debug!(
@@ -155,7 +156,8 @@ impl EventSource for UdevBackend {
                 // New device
                 EventType::Add => {
                     if let (Some(path), Some(devnum)) = (event.devnode(), event.devnum()) {
-                        info!(self.logger, "New device: #{} at {}", devnum, path.display());
+                        info!(self.logger, "New device"; "devnum" => devnum, "path" => %path.display());
+                        trace_event!(INFO, devnum, path = %path.display(), "New device");
                         if self.devices.insert(devnum, path.to_path_buf()).is_none() {
                             callback(
                                 UdevEvent::Added {
@@ -170,7 +172,8 @@ impl EventSource for UdevBackend {
                 // Device removed
                 EventType::Remove => {
                     if let Some(devnum) = event.devnum() {
-                        info!(self.logger, "Device removed: #{}", devnum);
+                        info!(self.logger, "Device removed"; "devnum" => devnum);
+                        trace_event!(INFO, devnum, "Device removed");
                         if self.devices.remove(&devnum).is_some() {
                             callback(UdevEvent::Removed { device_id: devnum }, &mut ());
                         }
@@ -179,7 +182,8 @@ impl EventSource for UdevBackend {
                 // New connector
                 EventType::Change => {
                     if let Some(devnum) = event.devnum() {
-                        info!(self.logger, "Device changed: #{}", devnum);
+                        info!(self.logger, "Device changed"; "devnum" => devnum);
+                        trace_event!(INFO, devnum, "Device changed");
                         if self.devices.contains_key(&devnum) {
                             callback(UdevEvent::Changed { device_id: devnum }, &mut ());
                         }
@@ -0,0 +1,164 @@
+//! Buffers shared with the host compositor.
+
+use std::{
+    cell::Cell,
+    convert::TryFrom,
+    fs::File,
+    io,
+    os::unix::{fs::FileExt, io::AsRawFd},
+    rc::Rc,
+};
+
+use wayland_client::{
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_shm::{self, WlShm},
+    },
+    Main,
+};
+use wayland_protocols::unstable::linux_dmabuf::v1::client::{
+    zwp_linux_buffer_params_v1::Flags, zwp_linux_dmabuf_v1::ZwpLinuxDmabufV1,
+};
+
+use crate::{
+    backend::allocator::{
+        dmabuf::{Dmabuf, WeakDmabuf},
+        Buffer as _, Fourcc,
+    },
+    utils::{Buffer, Size},
+};
+
+/// A `wl_buffer` attached to the window, tracking whether the host compositor still uses it.
+#[derive(Debug)]
+pub(super) struct HostBuffer {
+    buffer: Main<WlBuffer>,
+    busy: Rc<Cell<bool>>,
+}
+
+impl HostBuffer {
+    fn new(buffer: Main<WlBuffer>) -> HostBuffer {
+        let busy = Rc::new(Cell::new(false));
+        let busy_clone = busy.clone();
+        buffer.quick_assign(move |_, event, _| {
+            if let wl_buffer::Event::Release = event {
+                busy_clone.set(false);
+            }
+        });
+        HostBuffer { buffer, busy }
+    }
+
+    /// Returns the buffer to attach and marks it as used by the host compositor
+    pub(super) fn acquire(&self) -> &WlBuffer {
+        self.busy.set(true);
+        &self.buffer
+    }
+
+    /// Returns if the host compositor may still read from this buffer
+    pub(super) fn is_busy(&self) -> bool {
+        self.busy.get()
+    }
+}
+
+impl Drop for HostBuffer {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+    }
+}
+
+/// A shared memory buffer managed by the backend
+#[derive(Debug)]
+pub(super) struct ShmBuffer {
+    file: File,
+    pub(super) size: Size<i32, Buffer>,
+    pub(super) format: wl_shm::Format,
+    pub(super) buffer: HostBuffer,
+}
+
+impl ShmBuffer {
+    pub(super) fn new(shm: &WlShm, size: Size<i32, Buffer>, format: wl_shm::Format) -> io::Result<ShmBuffer> {
+        let stride = size.w * 4;
+        let len = stride * size.h;
+        let file = tempfile::tempfile()?;
+        file.set_len(len as u64)?;
+
+        let pool = shm.create_pool(file.as_raw_fd(), len);
+        let buffer = pool.create_buffer(0, size.w, size.h, stride, format);
+        // the buffer keeps the memory of the pool alive
+        pool.destroy();
+
+        Ok(ShmBuffer {
+            file,
+            size,
+            format,
+            buffer: HostBuffer::new(buffer),
+        })
+    }
+
+    /// Copy the given pixel data into the buffer
+    pub(super) fn write(&self, data: &[u8]) -> io::Result<()> {
+        self.file.write_all_at(data, 0)
+    }
+}
+
+/// A dmabuf imported into the host compositor
+#[derive(Debug)]
+pub(super) struct DmabufBuffer {
+    pub(super) dmabuf: WeakDmabuf,
+    pub(super) buffer: HostBuffer,
+}
+
+impl DmabufBuffer {
+    pub(super) fn new(linux_dmabuf: &ZwpLinuxDmabufV1, dmabuf: &Dmabuf) -> DmabufBuffer {
+        let params = linux_dmabuf.create_params();
+        let modifier: u64 = dmabuf.format().modifier.into();
+        for (idx, ((fd, offset), stride)) in dmabuf
+            .handles()
+            .zip(dmabuf.offsets())
+            .zip(dmabuf.strides())
+            .enumerate()
+        {
+            params.add(
+                fd,
+                idx as u32,
+                offset,
+                stride,
+                (modifier >> 32) as u32,
+                (modifier & 0xFFFF_FFFF) as u32,
+            );
+        }
+
+        let flags = if dmabuf.y_inverted() {
+            Flags::YInvert
+        } else {
+            Flags::empty()
+        };
+        let size = dmabuf.size();
+        let buffer = params.create_immed(size.w, size.h, dmabuf.format().code as u32, flags);
+        params.destroy();
+
+        DmabufBuffer {
+            dmabuf: dmabuf.weak(),
+            buffer: HostBuffer::new(buffer),
+        }
+    }
+}
+
+// wl_shm uses the fourcc codes, except for the two mandatory formats
+
+/// Converts a fourcc code into the matching `wl_shm` format
+pub(super) fn shm_format(format: Fourcc) -> Option<wl_shm::Format> {
+    match format {
+        Fourcc::Argb8888 => Some(wl_shm::Format::Argb8888),
+        Fourcc::Xrgb8888 => Some(wl_shm::Format::Xrgb8888),
+        format => wl_shm::Format::from_raw(format as u32),
+    }
+}
+
+/// Converts a `wl_shm` format into the matching fourcc code
+pub(super) fn fourcc(format: wl_shm::Format) -> Option<Fourcc> {
+    match format {
+        wl_shm::Format::Argb8888 => Some(Fourcc::Argb8888),
+        wl_shm::Format::Xrgb8888 => Some(Fourcc::Xrgb8888),
+        format => Fourcc::try_from(format.to_raw()).ok(),
+    }
+}
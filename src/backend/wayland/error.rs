@@ -0,0 +1,57 @@
+use std::io;
+
+use wayland_client::{ConnectError, GlobalError};
+
+use crate::backend::allocator::Format;
+
+/// An error emitted by the Wayland backend during setup.
+#[derive(Debug, thiserror::Error)]
+pub enum WaylandError {
+    /// Connecting to the host compositor failed.
+    #[error("Connecting to the host compositor failed")]
+    ConnectionFailed(#[from] ConnectError),
+
+    /// A required global is not advertised by the host compositor or has a too low version.
+    #[error("The host compositor does not provide the required global \"{name}\"")]
+    MissingGlobal {
+        /// The interface name of the global
+        name: &'static str,
+        /// The underlying error
+        #[source]
+        source: GlobalError,
+    },
+
+    /// Communicating with the host compositor failed.
+    #[error("Communicating with the host compositor failed")]
+    Io(#[from] io::Error),
+}
+
+/// An error which may occur when presenting a buffer to the window.
+#[derive(Debug, thiserror::Error)]
+pub enum PresentError {
+    /// The window was not configured by the host compositor yet.
+    ///
+    /// Wait for the first [`WaylandEvent::Refresh`](super::WaylandEvent::Refresh) before presenting.
+    #[error("The window was not configured by the host compositor yet")]
+    NotConfigured,
+
+    /// The host compositor does not support the format of the buffer.
+    #[error("The host compositor does not support the format {0:?}")]
+    UnsupportedFormat(Format),
+
+    /// The host compositor does not support the `zwp_linux_dmabuf_v1` protocol.
+    #[error("The host compositor does not support dmabufs")]
+    DmabufUnsupported,
+
+    /// The provided pixel data does not match the size of the buffer.
+    #[error("The provided pixel data does not match the size of the buffer")]
+    InvalidSize,
+
+    /// Allocating or writing the shared memory buffer failed.
+    #[error("Allocating the shared memory buffer failed")]
+    Shm(#[source] io::Error),
+
+    /// Communicating with the host compositor failed.
+    #[error("Communicating with the host compositor failed")]
+    Io(#[from] io::Error),
+}
@@ -0,0 +1,494 @@
+//! Input backend implementation for the Wayland backend.
+
+use crate::{
+    backend::input::{
+        self, Axis, AxisSource, ButtonState, Device, DeviceCapability, InputBackend, InputEvent, KeyState,
+        KeyboardKeyEvent, PointerAxisEvent, PointerButtonEvent, PointerMotionAbsoluteEvent, UnusedEvent,
+    },
+    utils::{Logical, Size},
+};
+use wayland_client::protocol::{wl_keyboard, wl_pointer};
+
+/// Marker used to define the `InputBackend` types for the Wayland backend.
+#[derive(Debug)]
+pub struct WaylandInput;
+
+/// Virtual input device used by the backend to associate input events.
+#[derive(Debug, PartialEq, Eq, Hash)]
+pub struct WaylandVirtualDevice;
+
+impl Device for WaylandVirtualDevice {
+    fn id(&self) -> String {
+        "wayland".to_owned()
+    }
+
+    fn name(&self) -> String {
+        "wayland virtual input".to_owned()
+    }
+
+    fn has_capability(&self, capability: DeviceCapability) -> bool {
+        matches!(capability, DeviceCapability::Keyboard | DeviceCapability::Pointer)
+    }
+
+    fn usb_id(&self) -> Option<(u32, u32)> {
+        None
+    }
+
+    fn syspath(&self) -> Option<std::path::PathBuf> {
+        None
+    }
+}
+
+/// Wayland-Backend internal event wrapping `wl_keyboard` events into a [`KeyboardKeyEvent`].
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WaylandKeyboardInputEvent {
+    pub(crate) time: u32,
+    pub(crate) key: u32,
+    pub(crate) count: u32,
+    pub(crate) state: KeyState,
+}
+
+impl input::Event<WaylandInput> for WaylandKeyboardInputEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> WaylandVirtualDevice {
+        WaylandVirtualDevice
+    }
+}
+
+impl KeyboardKeyEvent<WaylandInput> for WaylandKeyboardInputEvent {
+    fn key_code(&self) -> u32 {
+        self.key
+    }
+
+    fn state(&self) -> KeyState {
+        self.state
+    }
+
+    fn count(&self) -> u32 {
+        self.count
+    }
+}
+
+/// Wayland-Backend internal event wrapping `wl_pointer` events into a [`PointerAxisEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WaylandMouseWheelEvent {
+    pub(crate) time: u32,
+    pub(crate) axis: Axis,
+    pub(crate) amount: f64,
+    pub(crate) discrete: Option<i32>,
+    pub(crate) source: AxisSource,
+}
+
+impl input::Event<WaylandInput> for WaylandMouseWheelEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> WaylandVirtualDevice {
+        WaylandVirtualDevice
+    }
+}
+
+impl PointerAxisEvent<WaylandInput> for WaylandMouseWheelEvent {
+    fn amount(&self, axis: Axis) -> Option<f64> {
+        if self.axis == axis {
+            Some(self.amount)
+        } else {
+            Some(0.0)
+        }
+    }
+
+    fn amount_discrete(&self, axis: Axis) -> Option<f64> {
+        self.discrete
+            .map(|discrete| if self.axis == axis { discrete as f64 } else { 0.0 })
+    }
+
+    fn source(&self) -> AxisSource {
+        self.source
+    }
+}
+
+/// Wayland-Backend internal event wrapping `wl_pointer` events into a [`PointerButtonEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct WaylandMouseInputEvent {
+    pub(crate) time: u32,
+    pub(crate) button: u32,
+    pub(crate) state: ButtonState,
+}
+
+impl input::Event<WaylandInput> for WaylandMouseInputEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> WaylandVirtualDevice {
+        WaylandVirtualDevice
+    }
+}
+
+impl PointerButtonEvent<WaylandInput> for WaylandMouseInputEvent {
+    fn button_code(&self) -> u32 {
+        // wl_pointer already uses the linux input event codes
+        self.button
+    }
+
+    fn state(&self) -> ButtonState {
+        self.state
+    }
+}
+
+/// Wayland-Backend internal event wrapping `wl_pointer` events into a [`PointerMotionAbsoluteEvent`]
+#[allow(missing_docs)]
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct WaylandMouseMovedEvent {
+    pub(crate) time: u32,
+    pub(crate) x: f64,
+    pub(crate) y: f64,
+    pub(crate) size: Size<i32, Logical>,
+}
+
+impl input::Event<WaylandInput> for WaylandMouseMovedEvent {
+    fn time(&self) -> u32 {
+        self.time
+    }
+
+    fn device(&self) -> WaylandVirtualDevice {
+        WaylandVirtualDevice
+    }
+}
+
+impl PointerMotionAbsoluteEvent<WaylandInput> for WaylandMouseMovedEvent {
+    fn x(&self) -> f64 {
+        self.x
+    }
+
+    fn y(&self) -> f64 {
+        self.y
+    }
+
+    fn x_transformed(&self, width: i32) -> f64 {
+        f64::max(self.x * width as f64 / self.size.w as f64, 0.0)
+    }
+
+    fn y_transformed(&self, height: i32) -> f64 {
+        f64::max(self.y * height as f64 / self.size.h as f64, 0.0)
+    }
+}
+
+impl InputBackend for WaylandInput {
+    type Device = WaylandVirtualDevice;
+    type KeyboardKeyEvent = WaylandKeyboardInputEvent;
+    type PointerAxisEvent = WaylandMouseWheelEvent;
+    type PointerButtonEvent = WaylandMouseInputEvent;
+
+    type PointerMotionEvent = UnusedEvent;
+
+    type PointerMotionAbsoluteEvent = WaylandMouseMovedEvent;
+
+    type TouchDownEvent = UnusedEvent;
+    type TouchUpEvent = UnusedEvent;
+    type TouchMotionEvent = UnusedEvent;
+    type TouchCancelEvent = UnusedEvent;
+    type TouchFrameEvent = UnusedEvent;
+    type TabletToolAxisEvent = UnusedEvent;
+    type TabletToolProximityEvent = UnusedEvent;
+    type TabletToolTipEvent = UnusedEvent;
+    type TabletToolButtonEvent = UnusedEvent;
+
+    type SpecialEvent = UnusedEvent;
+}
+
+/// Translates `wl_pointer` events of the window into input events.
+///
+/// Keeps track of the state spread over multiple events of a `wl_pointer.frame`.
+#[derive(Debug)]
+pub(crate) struct PointerTranslator {
+    last_time: u32,
+    axis_source: AxisSource,
+    discrete: [Option<i32>; 2],
+}
+
+impl Default for PointerTranslator {
+    fn default() -> PointerTranslator {
+        PointerTranslator {
+            last_time: 0,
+            axis_source: AxisSource::Wheel,
+            discrete: [None, None],
+        }
+    }
+}
+
+impl PointerTranslator {
+    /// Translate the next event, `size` is the current size of the window
+    pub(crate) fn translate(
+        &mut self,
+        event: wl_pointer::Event,
+        size: Size<i32, Logical>,
+    ) -> Option<InputEvent<WaylandInput>> {
+        let (time, x, y) = match event {
+            wl_pointer::Event::Enter {
+                surface_x, surface_y, ..
+            } => (self.last_time, surface_x, surface_y),
+            wl_pointer::Event::Motion {
+                time,
+                surface_x,
+                surface_y,
+            } => (time, surface_x, surface_y),
+            wl_pointer::Event::Button {
+                time,
+                button,
+                state: button_state,
+                ..
+            } => {
+                self.last_time = time;
+                let button_state = match button_state {
+                    wl_pointer::ButtonState::Pressed => ButtonState::Pressed,
+                    _ => ButtonState::Released,
+                };
+                return Some(InputEvent::PointerButton {
+                    event: WaylandMouseInputEvent {
+                        time,
+                        button,
+                        state: button_state,
+                    },
+                });
+            }
+            wl_pointer::Event::AxisSource { axis_source: source } => {
+                self.axis_source = match source {
+                    wl_pointer::AxisSource::Finger => AxisSource::Finger,
+                    wl_pointer::AxisSource::Continuous => AxisSource::Continuous,
+                    wl_pointer::AxisSource::WheelTilt => AxisSource::WheelTilt,
+                    _ => AxisSource::Wheel,
+                };
+                return None;
+            }
+            wl_pointer::Event::AxisDiscrete {
+                axis,
+                discrete: steps,
+            } => {
+                self.discrete[axis.to_raw() as usize % 2] = Some(steps);
+                return None;
+            }
+            wl_pointer::Event::Axis { time, axis, value } => {
+                self.last_time = time;
+                return Some(InputEvent::PointerAxis {
+                    event: WaylandMouseWheelEvent {
+                        time,
+                        axis: match axis {
+                            wl_pointer::Axis::HorizontalScroll => Axis::Horizontal,
+                            _ => Axis::Vertical,
+                        },
+                        amount: value,
+                        discrete: self.discrete[axis.to_raw() as usize % 2].take(),
+                        source: self.axis_source,
+                    },
+                });
+            }
+            wl_pointer::Event::Frame => {
+                // the axis source is only valid for a single frame
+                self.axis_source = AxisSource::Wheel;
+                return None;
+            }
+            _ => return None,
+        };
+
+        self.last_time = time;
+        Some(InputEvent::PointerMotionAbsolute {
+            event: WaylandMouseMovedEvent { time, x, y, size },
+        })
+    }
+}
+
+/// Translates `wl_keyboard` events of the window into input events.
+#[derive(Debug, Default)]
+pub(crate) struct KeyboardTranslator {
+    key_counter: u32,
+}
+
+impl KeyboardTranslator {
+    /// Translate the next event
+    pub(crate) fn translate(&mut self, event: wl_keyboard::Event) -> Option<InputEvent<WaylandInput>> {
+        match event {
+            wl_keyboard::Event::Keymap { fd, .. } => {
+                // The compositor uses its own keymap, the keycodes are not affected by it.
+                let _ = nix::unistd::close(fd);
+                None
+            }
+            wl_keyboard::Event::Key {
+                time,
+                key,
+                state: key_state,
+                ..
+            } => {
+                let key_state = match key_state {
+                    wl_keyboard::KeyState::Pressed => {
+                        self.key_counter += 1;
+                        KeyState::Pressed
+                    }
+                    _ => {
+                        self.key_counter = self.key_counter.saturating_sub(1);
+                        KeyState::Released
+                    }
+                };
+                Some(InputEvent::Keyboard {
+                    event: WaylandKeyboardInputEvent {
+                        time,
+                        // wl_keyboard already uses the linux input event codes
+                        key,
+                        count: self.key_counter,
+                        state: key_state,
+                    },
+                })
+            }
+            _ => None,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{KeyboardTranslator, PointerTranslator};
+    use crate::backend::input::{
+        Axis, AxisSource, ButtonState, InputEvent, KeyState, KeyboardKeyEvent, PointerAxisEvent,
+        PointerButtonEvent, PointerMotionAbsoluteEvent,
+    };
+    use wayland_client::protocol::{wl_keyboard, wl_pointer};
+
+    #[test]
+    fn pointer_motion_is_scaled_to_the_output() {
+        let mut translator = PointerTranslator::default();
+        let event = translator.translate(
+            wl_pointer::Event::Motion {
+                time: 10,
+                surface_x: 400.0,
+                surface_y: 150.0,
+            },
+            (800, 600).into(),
+        );
+        match event {
+            Some(InputEvent::PointerMotionAbsolute { event }) => {
+                assert_eq!(event.x_transformed(1600), 800.0);
+                assert_eq!(event.y_transformed(1200), 300.0);
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn pointer_button() {
+        let mut translator = PointerTranslator::default();
+        let event = translator.translate(
+            wl_pointer::Event::Button {
+                serial: 1,
+                time: 10,
+                button: 0x110,
+                state: wl_pointer::ButtonState::Pressed,
+            },
+            (800, 600).into(),
+        );
+        match event {
+            Some(InputEvent::PointerButton { event }) => {
+                assert_eq!(event.button_code(), 0x110);
+                assert_eq!(event.state(), ButtonState::Pressed);
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn pointer_axis_frame() {
+        let mut translator = PointerTranslator::default();
+        let size = (800, 600).into();
+        assert!(translator
+            .translate(
+                wl_pointer::Event::AxisSource {
+                    axis_source: wl_pointer::AxisSource::Finger,
+                },
+                size,
+            )
+            .is_none());
+        assert!(translator
+            .translate(
+                wl_pointer::Event::AxisDiscrete {
+                    axis: wl_pointer::Axis::VerticalScroll,
+                    discrete: 2,
+                },
+                size,
+            )
+            .is_none());
+        match translator.translate(
+            wl_pointer::Event::Axis {
+                time: 10,
+                axis: wl_pointer::Axis::VerticalScroll,
+                value: 30.0,
+            },
+            size,
+        ) {
+            Some(InputEvent::PointerAxis { event }) => {
+                assert_eq!(event.amount(Axis::Vertical), Some(30.0));
+                assert_eq!(event.amount(Axis::Horizontal), Some(0.0));
+                assert_eq!(event.amount_discrete(Axis::Vertical), Some(2.0));
+                assert_eq!(event.source(), AxisSource::Finger);
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+        assert!(translator.translate(wl_pointer::Event::Frame, size).is_none());
+
+        // the source and discrete steps are reset for the next frame
+        match translator.translate(
+            wl_pointer::Event::Axis {
+                time: 20,
+                axis: wl_pointer::Axis::VerticalScroll,
+                value: 15.0,
+            },
+            size,
+        ) {
+            Some(InputEvent::PointerAxis { event }) => {
+                assert_eq!(event.amount_discrete(Axis::Vertical), None);
+                assert_eq!(event.source(), AxisSource::Wheel);
+            }
+            event => panic!("unexpected event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn keyboard_counts_pressed_keys() {
+        let mut translator = KeyboardTranslator::default();
+        let mut key = |key, state| match translator.translate(wl_keyboard::Event::Key {
+            serial: 1,
+            time: 10,
+            key,
+            state,
+        }) {
+            Some(InputEvent::Keyboard { event }) => (event.key_code(), event.state(), event.count()),
+            event => panic!("unexpected event: {:?}", event),
+        };
+        assert_eq!(
+            key(30, wl_keyboard::KeyState::Pressed),
+            (30, KeyState::Pressed, 1)
+        );
+        assert_eq!(
+            key(31, wl_keyboard::KeyState::Pressed),
+            (31, KeyState::Pressed, 2)
+        );
+        assert_eq!(
+            key(30, wl_keyboard::KeyState::Released),
+            (30, KeyState::Released, 1)
+        );
+        assert_eq!(
+            key(31, wl_keyboard::KeyState::Released),
+            (31, KeyState::Released, 0)
+        );
+        // releasing keys pressed before the window got focus does not underflow
+        assert_eq!(
+            key(32, wl_keyboard::KeyState::Released),
+            (32, KeyState::Released, 0)
+        );
+    }
+}
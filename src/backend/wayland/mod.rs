@@ -0,0 +1,711 @@
+//! Implementation of the backend types using a host Wayland compositor.
+//!
+//! This backend provides the appropriate backend implementations to run a Wayland compositor as a
+//! client of another Wayland compositor. This is useful for nested testing or to run a compositor
+//! inside of a window, without requiring an X server.
+//!
+//! The backend is initialized using [`WaylandBackend::new`](self::WaylandBackend::new). It connects
+//! to the compositor given by the environment (`WAYLAND_DISPLAY`), creates an `xdg_toplevel` window
+//! and forwards the pointer and keyboard input the window receives.
+//!
+//! - The [`WaylandBackend`] is inserted into an [`EventLoop`](calloop::EventLoop) to process
+//!   events from the host compositor.
+//! - A [`Window`], obtained from [`WaylandBackend::window`], is used to present buffers to the window.
+//!
+//! ## Presentation
+//!
+//! Buffers can be presented to the window in two ways:
+//!
+//! - [`Dmabuf`]s are imported into the host compositor using the `zwp_linux_dmabuf_v1` protocol,
+//!   see [`Window::present_dmabuf`]. The formats supported by the host compositor are
+//!   returned by [`Window::dmabuf_formats`].
+//! - Pixel data, e.g. read back from a renderer using [`ExportMem`](crate::backend::renderer::ExportMem),
+//!   is copied into shared memory buffers managed by the backend, see [`Window::present_shm`].
+//!
+//! The window can only be presented to after it was configured by the host compositor, which is signaled
+//! by the first [`WaylandEvent::Refresh`]. After presenting a buffer, a [`WaylandEvent::PresentCompleted`] is
+//! emitted once the host compositor considers it a good time to draw the next frame.
+//!
+//! ## Example usage
+//!
+//! ```rust,no_run
+//! # use std::error::Error;
+//! use smithay::backend::wayland::{WaylandBackend, WaylandEvent};
+//!
+//! # struct CompositorState;
+//! fn init_wayland_backend(
+//!    handle: calloop::LoopHandle<CompositorState>,
+//!    logger: slog::Logger
+//! ) -> Result<(), Box<dyn Error>> {
+//!     // Create the backend, which also creates the window on the host compositor.
+//!     let backend = WaylandBackend::new(logger)?;
+//!     // You can get a handle to the window to present buffers later on.
+//!     let window = backend.window();
+//!
+//!     // Insert the backend into the event loop to receive events.
+//!     handle.insert_source(backend, |event, window, state| match event {
+//!         WaylandEvent::Refresh | WaylandEvent::PresentCompleted => {
+//!             // Render a new frame and present it using `window.present_dmabuf` or `window.present_shm`.
+//!         }
+//!         WaylandEvent::Input(event) => {
+//!             // Process the input events of the window.
+//!         }
+//!         WaylandEvent::Resized(size) => {
+//!             // Buffers presented to the window should have the new size.
+//!         }
+//!         WaylandEvent::CloseRequested => {
+//!             // The window should be closed.
+//!         }
+//!     })?;
+//!
+//!     Ok(())
+//! }
+//! ```
+
+mod buffer;
+mod error;
+mod input;
+
+use self::buffer::{DmabufBuffer, ShmBuffer};
+use crate::{
+    backend::{
+        allocator::{dmabuf::Dmabuf, Buffer as _, Format, Fourcc, Modifier},
+        input::InputEvent,
+    },
+    utils::{Buffer, Logical, Rectangle, Size},
+};
+use calloop::{
+    generic::{Fd, Generic},
+    Interest, Mode, Poll, PostAction, Readiness, Token, TokenFactory,
+};
+use slog::{error, info, o, Logger};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{HashSet, VecDeque},
+    convert::TryFrom,
+    io,
+    rc::Rc,
+};
+use wayland_client::{
+    protocol::{
+        wl_buffer::WlBuffer,
+        wl_callback,
+        wl_compositor::WlCompositor,
+        wl_keyboard::WlKeyboard,
+        wl_pointer::WlPointer,
+        wl_seat::{self, WlSeat},
+        wl_shm::{self, WlShm},
+        wl_surface::WlSurface,
+    },
+    Display, EventQueue, GlobalError, GlobalManager, Main,
+};
+use wayland_protocols::{
+    unstable::{
+        linux_dmabuf::v1::client::zwp_linux_dmabuf_v1::{self, ZwpLinuxDmabufV1},
+        xdg_decoration::v1::client::{
+            zxdg_decoration_manager_v1::ZxdgDecorationManagerV1,
+            zxdg_toplevel_decoration_v1::{self, ZxdgToplevelDecorationV1},
+        },
+    },
+    xdg_shell::client::{
+        xdg_surface::{self, XdgSurface},
+        xdg_toplevel::{self, XdgToplevel},
+        xdg_wm_base::{self, XdgWmBase},
+    },
+};
+
+pub use self::error::*;
+pub use self::input::*;
+
+/// An event emitted by the Wayland backend.
+#[derive(Debug)]
+pub enum WaylandEvent {
+    /// The host compositor has configured the window, its contents should be redrawn.
+    Refresh,
+
+    /// An input event occurred.
+    Input(InputEvent<WaylandInput>),
+
+    /// The window was resized.
+    Resized(Size<i32, Logical>),
+
+    /// The host compositor requests the next frame to be drawn.
+    ///
+    /// This event is emitted after presenting a buffer, once it is a good time to start drawing
+    /// the next frame.
+    PresentCompleted,
+
+    /// The window has received a request to be closed.
+    CloseRequested,
+}
+
+/// Represents an active connection to a host compositor to manage events on the window provided by the backend.
+///
+/// Every backend creates exactly one `xdg_toplevel` window, representing a single output of the
+/// nested compositor. There is no way to open additional windows on the same connection,
+/// compositors wanting to expose multiple outputs need to create multiple backends.
+#[derive(Debug)]
+pub struct WaylandBackend {
+    log: Logger,
+    display: Display,
+    event_queue: EventQueue,
+    source: Generic<Fd>,
+    window: Window,
+    _seat: Main<WlSeat>,
+}
+
+impl WaylandBackend {
+    /// Initializes the Wayland backend.
+    ///
+    /// This connects to the host compositor and configures the window using the default options.
+    pub fn new<L>(logger: L) -> Result<WaylandBackend, WaylandError>
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        Self::with_size_and_title((1280, 800).into(), "Smithay", logger)
+    }
+
+    /// Initializes the Wayland backend.
+    ///
+    /// This connects to the host compositor and configures the window using the default size and the
+    /// specified window title.
+    pub fn with_title<L>(title: &str, logger: L) -> Result<WaylandBackend, WaylandError>
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        Self::with_size_and_title((1280, 800).into(), title, logger)
+    }
+
+    /// Initializes the Wayland backend.
+    ///
+    /// This connects to the host compositor and configures the window using the default window title
+    /// and the specified window size.
+    pub fn with_size<L>(size: Size<i32, Logical>, logger: L) -> Result<WaylandBackend, WaylandError>
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        Self::with_size_and_title(size, "Smithay", logger)
+    }
+
+    /// Initializes the Wayland backend.
+    ///
+    /// This connects to the host compositor and configures the window using the specified window size and title.
+    ///
+    /// The size is only a hint, the host compositor may choose a different size for the window,
+    /// which is notified by [`WaylandEvent::Resized`].
+    pub fn with_size_and_title<L>(
+        size: Size<i32, Logical>,
+        title: &str,
+        logger: L,
+    ) -> Result<WaylandBackend, WaylandError>
+    where
+        L: Into<Option<slog::Logger>>,
+    {
+        let logger = crate::slog_or_fallback(logger).new(o!("smithay_module" => "backend_wayland"));
+
+        info!(logger, "Connecting to the host compositor");
+
+        let display = Display::connect_to_env()?;
+        let mut event_queue = display.create_event_queue();
+        let attached_display = (*display).clone().attach(event_queue.token());
+        let globals = GlobalManager::new(&attached_display);
+        event_queue.sync_roundtrip(&mut (), |_, _, _| {})?;
+
+        let state = Rc::new(WindowState::new(size));
+
+        let compositor = globals
+            .instantiate_range::<WlCompositor>(4, 4)
+            .map_err(missing_global("wl_compositor"))?;
+
+        let shm = globals
+            .instantiate_exact::<WlShm>(1)
+            .map_err(missing_global("wl_shm"))?;
+        {
+            let state = state.clone();
+            shm.quick_assign(move |_, event, _| {
+                if let wl_shm::Event::Format { format } = event {
+                    if let Some(format) = buffer::fourcc(format) {
+                        state.shm_formats.borrow_mut().push(format);
+                    }
+                }
+            });
+        }
+
+        let wm_base = globals
+            .instantiate_range::<XdgWmBase>(1, 2)
+            .map_err(missing_global("xdg_wm_base"))?;
+        wm_base.quick_assign(|wm_base, event, _| {
+            if let xdg_wm_base::Event::Ping { serial } = event {
+                wm_base.pong(serial);
+            }
+        });
+
+        let seat = globals
+            .instantiate_range::<WlSeat>(1, 5)
+            .map_err(missing_global("wl_seat"))?;
+        {
+            let state = state.clone();
+            let mut pointer: Option<Main<WlPointer>> = None;
+            let mut keyboard: Option<Main<WlKeyboard>> = None;
+            seat.quick_assign(move |seat, event, _| {
+                if let wl_seat::Event::Capabilities { capabilities } = event {
+                    let has_pointer = capabilities.contains(wl_seat::Capability::Pointer);
+                    if has_pointer && pointer.is_none() {
+                        let new_pointer = seat.get_pointer();
+                        assign_pointer(&new_pointer, state.clone());
+                        pointer = Some(new_pointer);
+                    } else if !has_pointer {
+                        if let Some(pointer) = pointer.take() {
+                            if pointer.as_ref().version() >= 3 {
+                                pointer.release();
+                            }
+                        }
+                    }
+
+                    let has_keyboard = capabilities.contains(wl_seat::Capability::Keyboard);
+                    if has_keyboard && keyboard.is_none() {
+                        let new_keyboard = seat.get_keyboard();
+                        assign_keyboard(&new_keyboard, state.clone());
+                        keyboard = Some(new_keyboard);
+                    } else if !has_keyboard {
+                        if let Some(keyboard) = keyboard.take() {
+                            if keyboard.as_ref().version() >= 3 {
+                                keyboard.release();
+                            }
+                        }
+                    }
+                }
+            });
+        }
+
+        let linux_dmabuf = match globals.instantiate_range::<ZwpLinuxDmabufV1>(3, 3) {
+            Ok(linux_dmabuf) => {
+                let state = state.clone();
+                linux_dmabuf.quick_assign(move |_, event, _| {
+                    if let zwp_linux_dmabuf_v1::Event::Modifier {
+                        format,
+                        modifier_hi,
+                        modifier_lo,
+                    } = event
+                    {
+                        if let Ok(code) = Fourcc::try_from(format) {
+                            let modifier = Modifier::from(((modifier_hi as u64) << 32) | modifier_lo as u64);
+                            state
+                                .dmabuf_formats
+                                .borrow_mut()
+                                .insert(Format { code, modifier });
+                        }
+                    }
+                });
+                Some(linux_dmabuf)
+            }
+            Err(err) => {
+                info!(
+                    logger,
+                    "Host compositor does not support dmabufs, only shm buffers can be presented: {}", err
+                );
+                None
+            }
+        };
+
+        // Receive the supported formats
+        event_queue.sync_roundtrip(&mut (), |_, _, _| {})?;
+
+        let surface = compositor.create_surface();
+        let xdg_surface = wm_base.get_xdg_surface(&surface);
+        let toplevel = xdg_surface.get_toplevel();
+        toplevel.set_title(title.into());
+        toplevel.set_app_id("smithay".into());
+        toplevel.set_min_size(1, 1);
+        {
+            let state = state.clone();
+            toplevel.quick_assign(move |_, event, _| match event {
+                // A size of zero means the client should decide
+                xdg_toplevel::Event::Configure { width, height, .. } if width > 0 && height > 0 => {
+                    state.pending_size.set(Some((width, height).into()));
+                }
+                xdg_toplevel::Event::Close => state.push(WaylandEvent::CloseRequested),
+                _ => {}
+            });
+        }
+        {
+            let state = state.clone();
+            xdg_surface.quick_assign(move |xdg_surface, event, _| {
+                if let xdg_surface::Event::Configure { serial } = event {
+                    xdg_surface.ack_configure(serial);
+
+                    if let Some(size) = state.pending_size.take() {
+                        if size != state.size.get() {
+                            state.size.set(size);
+                            state.push(WaylandEvent::Resized(size));
+                        }
+                    }
+
+                    if !state.configured.replace(true) {
+                        state.push(WaylandEvent::Refresh);
+                    }
+                }
+            });
+        }
+
+        // Prefer decorations drawn by the host compositor
+        let decoration = globals
+            .instantiate_exact::<ZxdgDecorationManagerV1>(1)
+            .ok()
+            .map(|manager| {
+                let decoration = manager.get_toplevel_decoration(&toplevel);
+                decoration.quick_assign(|_, _, _| {});
+                decoration.set_mode(zxdg_toplevel_decoration_v1::Mode::ServerSide);
+                manager.destroy();
+                decoration
+            });
+
+        // The initial commit without a buffer causes the host compositor to configure the window
+        surface.commit();
+        display.flush()?;
+
+        let source = Generic::from_fd(display.get_connection_fd(), Interest::READ, Mode::Level);
+
+        info!(logger, "Window created");
+
+        let window = Window(Rc::new(WindowInner {
+            display: display.clone(),
+            surface,
+            xdg_surface,
+            toplevel,
+            decoration,
+            shm,
+            linux_dmabuf,
+            state,
+            shm_buffers: RefCell::new(Vec::new()),
+            dmabuf_buffers: RefCell::new(Vec::new()),
+        }));
+
+        Ok(WaylandBackend {
+            log: logger,
+            display,
+            event_queue,
+            source,
+            window,
+            _seat: seat,
+        })
+    }
+
+    /// Returns the underlying connection to the host compositor.
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// Returns a handle to the window created by the backend.
+    pub fn window(&self) -> Window {
+        self.window.clone()
+    }
+}
+
+fn missing_global(name: &'static str) -> impl FnOnce(GlobalError) -> WaylandError {
+    move |source| WaylandError::MissingGlobal { name, source }
+}
+
+/// State of the window updated by the event handlers
+#[derive(Debug)]
+struct WindowState {
+    size: Cell<Size<i32, Logical>>,
+    pending_size: Cell<Option<Size<i32, Logical>>>,
+    configured: Cell<bool>,
+    shm_formats: RefCell<Vec<Fourcc>>,
+    dmabuf_formats: RefCell<HashSet<Format>>,
+    events: RefCell<VecDeque<WaylandEvent>>,
+}
+
+impl WindowState {
+    fn new(size: Size<i32, Logical>) -> WindowState {
+        WindowState {
+            size: Cell::new(size),
+            pending_size: Cell::new(None),
+            configured: Cell::new(false),
+            shm_formats: RefCell::new(Vec::new()),
+            dmabuf_formats: RefCell::new(HashSet::new()),
+            events: RefCell::new(VecDeque::new()),
+        }
+    }
+
+    fn push(&self, event: WaylandEvent) {
+        self.events.borrow_mut().push_back(event);
+    }
+}
+
+fn assign_pointer(pointer: &Main<WlPointer>, state: Rc<WindowState>) {
+    let mut translator = PointerTranslator::default();
+    pointer.quick_assign(move |_, event, _| {
+        if let Some(event) = translator.translate(event, state.size.get()) {
+            state.push(WaylandEvent::Input(event));
+        }
+    });
+}
+
+fn assign_keyboard(keyboard: &Main<WlKeyboard>, state: Rc<WindowState>) {
+    let mut translator = KeyboardTranslator::default();
+    keyboard.quick_assign(move |_, event, _| {
+        if let Some(event) = translator.translate(event) {
+            state.push(WaylandEvent::Input(event));
+        }
+    });
+}
+
+#[derive(Debug)]
+struct WindowInner {
+    display: Display,
+    surface: Main<WlSurface>,
+    xdg_surface: Main<XdgSurface>,
+    toplevel: Main<XdgToplevel>,
+    decoration: Option<Main<ZxdgToplevelDecorationV1>>,
+    shm: Main<WlShm>,
+    linux_dmabuf: Option<Main<ZwpLinuxDmabufV1>>,
+    state: Rc<WindowState>,
+    shm_buffers: RefCell<Vec<ShmBuffer>>,
+    dmabuf_buffers: RefCell<Vec<DmabufBuffer>>,
+}
+
+impl WindowInner {
+    fn commit(&self, buffer: &WlBuffer, damage: &[Rectangle<i32, Buffer>]) -> Result<(), PresentError> {
+        self.surface.attach(Some(buffer), 0, 0);
+        if damage.is_empty() {
+            self.surface.damage_buffer(0, 0, i32::MAX, i32::MAX);
+        }
+        for rect in damage {
+            self.surface
+                .damage_buffer(rect.loc.x, rect.loc.y, rect.size.w, rect.size.h);
+        }
+
+        let callback = self.surface.frame();
+        let state = self.state.clone();
+        callback.quick_assign(move |_, event, _| {
+            if let wl_callback::Event::Done { .. } = event {
+                state.push(WaylandEvent::PresentCompleted);
+            }
+        });
+
+        self.surface.commit();
+        match self.display.flush() {
+            Err(err) if err.kind() != io::ErrorKind::WouldBlock => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
+
+impl Drop for WindowInner {
+    fn drop(&mut self) {
+        if let Some(decoration) = self.decoration.take() {
+            decoration.destroy();
+        }
+        self.toplevel.destroy();
+        self.xdg_surface.destroy();
+        self.surface.destroy();
+        let _ = self.display.flush();
+    }
+}
+
+/// Handle to the window of the backend, used to present buffers.
+#[derive(Debug, Clone)]
+pub struct Window(Rc<WindowInner>);
+
+impl Window {
+    /// Sets the title of the window.
+    pub fn set_title(&self, title: &str) {
+        self.0.toplevel.set_title(title.into());
+    }
+
+    /// Returns the size of this window.
+    ///
+    /// Buffers presented to the window should have this size.
+    pub fn size(&self) -> Size<i32, Logical> {
+        self.0.state.size.get()
+    }
+
+    /// Returns if the window was configured by the host compositor and may be presented to.
+    pub fn is_configured(&self) -> bool {
+        self.0.state.configured.get()
+    }
+
+    /// Returns the formats supported by [`Window::present_shm`].
+    pub fn shm_formats(&self) -> Vec<Fourcc> {
+        self.0.state.shm_formats.borrow().clone()
+    }
+
+    /// Returns the dmabuf formats supported by the host compositor.
+    ///
+    /// Returns an empty set, if the host compositor does not support dmabufs.
+    pub fn dmabuf_formats(&self) -> HashSet<Format> {
+        self.0.state.dmabuf_formats.borrow().clone()
+    }
+
+    /// Presents the given pixel data to the window.
+    ///
+    /// The data is copied into a shared memory buffer and has to consist of 4 bytes per pixel
+    /// without any padding between rows. The `format` has to be one of [`Window::shm_formats`].
+    ///
+    /// `damage` denotes the changed parts of the data compared to the previously presented
+    /// buffer. If it is empty, the whole buffer is considered damaged.
+    pub fn present_shm(
+        &self,
+        data: &[u8],
+        size: Size<i32, Buffer>,
+        format: Fourcc,
+        damage: &[Rectangle<i32, Buffer>],
+    ) -> Result<(), PresentError> {
+        if !self.is_configured() {
+            return Err(PresentError::NotConfigured);
+        }
+        let shm_format = buffer::shm_format(format)
+            .filter(|_| self.0.state.shm_formats.borrow().contains(&format))
+            .ok_or(PresentError::UnsupportedFormat(Format {
+                code: format,
+                modifier: Modifier::Linear,
+            }))?;
+        if size.w <= 0 || size.h <= 0 || data.len() != size.w as usize * size.h as usize * 4 {
+            return Err(PresentError::InvalidSize);
+        }
+
+        let mut buffers = self.0.shm_buffers.borrow_mut();
+        buffers.retain(|buffer| buffer.size == size && buffer.format == shm_format);
+        let buffer = match buffers.iter().position(|buffer| !buffer.buffer.is_busy()) {
+            Some(idx) => &buffers[idx],
+            None => {
+                let buffer = ShmBuffer::new(&self.0.shm, size, shm_format).map_err(PresentError::Shm)?;
+                buffers.push(buffer);
+                buffers.last().unwrap()
+            }
+        };
+        buffer.write(data).map_err(PresentError::Shm)?;
+
+        self.0.commit(buffer.buffer.acquire(), damage)
+    }
+
+    /// Presents a dmabuf to the window.
+    ///
+    /// The format of the dmabuf has to be one of [`Window::dmabuf_formats`]. The dmabuf is imported
+    /// into the host compositor once and reused for later presentations. It must not be rendered to,
+    /// while it is still in use by the host compositor (see [`Window::is_dmabuf_busy`]).
+    ///
+    /// `damage` denotes the changed parts of the dmabuf compared to the previously presented
+    /// buffer. If it is empty, the whole buffer is considered damaged.
+    pub fn present_dmabuf(
+        &self,
+        dmabuf: &Dmabuf,
+        damage: &[Rectangle<i32, Buffer>],
+    ) -> Result<(), PresentError> {
+        if !self.is_configured() {
+            return Err(PresentError::NotConfigured);
+        }
+        let linux_dmabuf = self
+            .0
+            .linux_dmabuf
+            .as_ref()
+            .ok_or(PresentError::DmabufUnsupported)?;
+        let format = dmabuf.format();
+        if !self.0.state.dmabuf_formats.borrow().contains(&format) {
+            return Err(PresentError::UnsupportedFormat(format));
+        }
+
+        let mut buffers = self.0.dmabuf_buffers.borrow_mut();
+        // forget about buffers, which were destroyed in the meantime
+        buffers.retain(|buffer| buffer.dmabuf.upgrade().is_some());
+        let weak = dmabuf.weak();
+        let buffer = match buffers.iter().position(|buffer| buffer.dmabuf == weak) {
+            Some(idx) => &buffers[idx],
+            None => {
+                buffers.push(DmabufBuffer::new(linux_dmabuf, dmabuf));
+                buffers.last().unwrap()
+            }
+        };
+
+        self.0.commit(buffer.buffer.acquire(), damage)
+    }
+
+    /// Returns if the given dmabuf is still in use by the host compositor.
+    ///
+    /// The dmabuf should not be rendered to, until the host compositor released it.
+    pub fn is_dmabuf_busy(&self, dmabuf: &Dmabuf) -> bool {
+        let weak = dmabuf.weak();
+        self.0
+            .dmabuf_buffers
+            .borrow()
+            .iter()
+            .any(|buffer| buffer.dmabuf == weak && buffer.buffer.is_busy())
+    }
+}
+
+impl PartialEq for Window {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl calloop::EventSource for WaylandBackend {
+    type Event = WaylandEvent;
+
+    /// The window the incoming events are applicable to.
+    type Metadata = Window;
+
+    type Ret = ();
+
+    fn process_events<F>(
+        &mut self,
+        readiness: Readiness,
+        token: Token,
+        mut callback: F,
+    ) -> io::Result<PostAction>
+    where
+        F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
+    {
+        let display = &self.display;
+        let event_queue = &mut self.event_queue;
+        let log = &self.log;
+
+        let post_action = self.source.process_events(readiness, token, |_, _| {
+            if let Some(guard) = event_queue.prepare_read() {
+                if let Err(err) = guard.read_events() {
+                    if err.kind() != io::ErrorKind::WouldBlock {
+                        return Err(err);
+                    }
+                }
+            }
+
+            if let Err(err) = event_queue.dispatch_pending(&mut (), |_, _, _| {}) {
+                if let Some(protocol_error) = display.protocol_error() {
+                    error!(log, "Protocol error of the host compositor: {}", protocol_error);
+                }
+                return Err(err);
+            }
+
+            Ok(PostAction::Continue)
+        })?;
+
+        let mut window = self.window.clone();
+        loop {
+            // Intentionally release the borrow before invoking the callback
+            let event = self.window.0.state.events.borrow_mut().pop_front();
+            match event {
+                Some(event) => callback(event, &mut window),
+                None => break,
+            }
+        }
+
+        // Flush the connection so changes to the window state during callbacks are sent.
+        let _ = self.display.flush();
+
+        Ok(post_action)
+    }
+
+    fn register(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+        self.source.register(poll, token_factory)
+    }
+
+    fn reregister(&mut self, poll: &mut Poll, token_factory: &mut TokenFactory) -> io::Result<()> {
+        self.source.reregister(poll, token_factory)
+    }
+
+    fn unregister(&mut self, poll: &mut Poll) -> io::Result<()> {
+        self.source.unregister(poll)
+    }
+}
@@ -281,26 +281,51 @@ impl WinitGraphicsBackend {
         &mut self.renderer
     }
 
+    /// Bind the window as the rendering target of the underlying renderer.
+    ///
+    /// Use this instead of [`WinitGraphicsBackend::render`] to drive the renderer directly,
+    /// e.g. through an [`OutputDamageTracker`](crate::backend::renderer::damage::OutputDamageTracker)
+    /// together with [`WinitGraphicsBackend::buffer_age`]. Finish the frame with [`WinitGraphicsBackend::submit`].
+    pub fn bind(&mut self) -> Result<(), crate::backend::SwapBuffersError> {
+        // Were we told to resize?
+        if let Some(size) = self.resize_notification.take() {
+            self.egl.resize(size.w, size.h, 0, 0);
+        }
+
+        self.renderer.bind(self.egl.clone())?;
+        Ok(())
+    }
+
+    /// Age of the current back buffer of the window.
+    ///
+    /// Needs to be called after [`WinitGraphicsBackend::bind`]. Returns `0`, if the age
+    /// is unknown (e.g. `EGL_EXT_buffer_age` is not supported), requiring a full redraw.
+    pub fn buffer_age(&self) -> usize {
+        self.egl.buffer_age().unwrap_or(0).max(0) as usize
+    }
+
+    /// Present the contents rendered since [`WinitGraphicsBackend::bind`] and unbind the window.
+    pub fn submit(&mut self) -> Result<(), crate::backend::SwapBuffersError> {
+        self.egl.swap_buffers()?;
+        self.renderer.unbind()?;
+        Ok(())
+    }
+
     /// Shortcut to `Renderer::render` with the current window dimensions
     /// and this window set as the rendering target.
     pub fn render<F, R>(&mut self, rendering: F) -> Result<R, crate::backend::SwapBuffersError>
     where
         F: FnOnce(&mut Gles2Renderer, &mut Gles2Frame) -> R,
     {
-        // Were we told to resize?
-        if let Some(size) = self.resize_notification.take() {
-            self.egl.resize(size.w, size.h, 0, 0);
-        }
+        self.bind()?;
 
         let size = {
             let size = self.size.borrow();
             size.physical_size
         };
 
-        self.renderer.bind(self.egl.clone())?;
         let result = self.renderer.render(size, Transform::Normal, rendering)?;
-        self.egl.swap_buffers()?;
-        self.renderer.unbind()?;
+        self.submit()?;
         Ok(result)
     }
 }
@@ -332,6 +357,8 @@ impl WinitEventLoop {
         F: FnMut(WinitEvent),
     {
         use self::WinitEvent::*;
+        profile_scope!("winit::dispatch_new_events");
+        trace_span!(TRACE, "winit::dispatch_new_events");
 
         let mut closed = false;
 
@@ -348,6 +348,8 @@ pub struct X11Surface {
     height: u16,
     current: BufferObject<Dmabuf>,
     next: BufferObject<Dmabuf>,
+    // number of frames presented since the buffers were allocated, up to 2
+    presented: u8,
 }
 
 #[derive(Debug, thiserror::Error)]
@@ -487,6 +489,7 @@ impl X11Surface {
             height: size.h,
             current,
             next,
+            presented: 0,
             resize: recv,
         })
     }
@@ -542,6 +545,7 @@ impl X11Surface {
         self.height = size.h;
         self.current = current;
         self.next = next;
+        self.presented = 0;
 
         Ok(())
     }
@@ -584,6 +588,18 @@ impl Present<'_> {
             .map(|dmabuf| dmabuf.cloned())
             .map(Option::unwrap)?)
     }
+
+    /// Returns the age of the next buffer.
+    ///
+    /// The age is the number of frames since the contents of the buffer were presented,
+    /// `0` denotes unknown contents (e.g. after a resize), requiring a full redraw.
+    pub fn age(&self) -> u8 {
+        if self.surface.presented >= 2 {
+            2
+        } else {
+            0
+        }
+    }
 }
 
 impl Drop for Present<'_> {
@@ -596,10 +612,17 @@ impl Drop for Present<'_> {
 
             match surface.current.userdata().map(Option::unwrap) {
                 Ok(dmabuf) => {
-                    if let Ok(pixmap) = PixmapWrapper::with_dmabuf(&*connection, &surface.window, dmabuf) {
+                    let presented = PixmapWrapper::with_dmabuf(&*connection, &surface.window, dmabuf)
+                        .ok()
                         // Now present the current buffer
-                        let _ = pixmap.present(&*connection, &surface.window);
-                    }
+                        .map(|pixmap| pixmap.present(&*connection, &surface.window).is_ok())
+                        .unwrap_or(false);
+                    // the buffer ages are unknown, if a frame got lost
+                    surface.presented = if presented {
+                        surface.presented.saturating_add(1).min(2)
+                    } else {
+                        0
+                    };
                 }
                 Err(_err) => {
                     todo!("Log error")
@@ -700,6 +723,8 @@ impl EventSource for X11Backend {
         F: FnMut(Self::Event, &mut Self::Metadata) -> Self::Ret,
     {
         use self::X11Event::Input;
+        profile_scope!("x11::process_events");
+        trace_span!(TRACE, "x11::process_events");
 
         let connection = self.connection.clone();
         let window = self.window.clone();
@@ -0,0 +1,533 @@
+//! Interactive move and resize of windows
+//!
+//! See the [module-level documentation](super) for an overview.
+
+use std::{convert::TryFrom, sync::Mutex};
+
+use wayland_protocols::xdg_shell::server::xdg_toplevel;
+use wayland_server::protocol::{wl_pointer::ButtonState, wl_shell_surface, wl_surface::WlSurface};
+
+use crate::{
+    utils::{Logical, Point, Size},
+    wayland::{
+        compositor::{self, with_states},
+        seat::{AxisFrame, GrabStartData, PointerGrab, PointerHandle, PointerInnerHandle, Seat},
+        shell::xdg::{SurfaceCachedState, XdgToplevelSurfaceRoleAttributes},
+        Serial,
+    },
+};
+
+use super::{Kind, Space, Window};
+
+bitflags::bitflags! {
+    /// The edges of a window dragged during an interactive resize
+    pub struct ResizeEdge: u32 {
+        /// No edge
+        const NONE = 0;
+        /// The top edge
+        const TOP = 1;
+        /// The bottom edge
+        const BOTTOM = 2;
+        /// The left edge
+        const LEFT = 4;
+        /// The top left corner
+        const TOP_LEFT = 5;
+        /// The bottom left corner
+        const BOTTOM_LEFT = 6;
+        /// The right edge
+        const RIGHT = 8;
+        /// The top right corner
+        const TOP_RIGHT = 9;
+        /// The bottom right corner
+        const BOTTOM_RIGHT = 10;
+    }
+}
+
+impl From<wl_shell_surface::Resize> for ResizeEdge {
+    #[inline]
+    fn from(x: wl_shell_surface::Resize) -> Self {
+        Self::from_bits(x.bits()).unwrap()
+    }
+}
+
+impl From<ResizeEdge> for wl_shell_surface::Resize {
+    #[inline]
+    fn from(x: ResizeEdge) -> Self {
+        Self::from_bits(x.bits()).unwrap()
+    }
+}
+
+impl From<xdg_toplevel::ResizeEdge> for ResizeEdge {
+    #[inline]
+    fn from(x: xdg_toplevel::ResizeEdge) -> Self {
+        Self::from_bits(x.to_raw()).unwrap()
+    }
+}
+
+/// The edges have no matching [`xdg_toplevel::ResizeEdge`], e.g. `TOP | BOTTOM`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("The edges {0:?} have no matching xdg_toplevel resize edge")]
+pub struct UnsupportedResizeEdge(pub ResizeEdge);
+
+impl TryFrom<ResizeEdge> for xdg_toplevel::ResizeEdge {
+    type Error = UnsupportedResizeEdge;
+
+    #[inline]
+    fn try_from(x: ResizeEdge) -> Result<Self, Self::Error> {
+        Self::from_raw(x.bits()).ok_or(UnsupportedResizeEdge(x))
+    }
+}
+
+/// Errors thrown by [`Space::start_move_grab`] and [`Space::start_resize_grab`]
+#[derive(Debug, thiserror::Error)]
+pub enum InteractiveGrabError {
+    /// The shell surface of the window was already destroyed
+    #[error("The window was already destroyed")]
+    DeadWindow,
+    /// The window is not mapped onto the space
+    #[error("The window is not mapped onto the space")]
+    UnmappedWindow,
+    /// The seat has no pointer, or the serial does not match its current implicit grab
+    #[error("The serial does not match an implicit grab of the pointer")]
+    InvalidSerial,
+    /// The implicit grab of the pointer was not started on a surface of the client of the window
+    #[error("The pointer grab was not started on the window")]
+    InvalidFocus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ResizeData {
+    edges: ResizeEdge,
+    initial_window_location: Point<i32, Logical>,
+    initial_window_size: Size<i32, Logical>,
+}
+
+impl ResizeData {
+    /// Location of the window keeping the edges opposite to the resized ones in place
+    fn window_location(&self, size: Size<i32, Logical>) -> Point<i32, Logical> {
+        let mut location = self.initial_window_location;
+        if self.edges.intersects(ResizeEdge::LEFT) {
+            location.x += self.initial_window_size.w - size.w;
+        }
+        if self.edges.intersects(ResizeEdge::TOP) {
+            location.y += self.initial_window_size.h - size.h;
+        }
+        location
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ResizeState {
+    /// The window is currently being resized
+    Resizing(ResizeData),
+    /// The resize has finished, the client has to ack the configure without the resizing state
+    WaitingForFinalAck(ResizeData),
+    /// The resize has finished, the client has to commit its final size
+    WaitingForCommit(ResizeData),
+    /// The final size was committed, the window is placed a last time on the next refresh
+    Done(ResizeData),
+}
+
+/// State of the interactive grabs of a window, stored in the data map of its surface
+#[derive(Debug, Default)]
+struct InteractiveState {
+    /// Location the window was moved to since the last refresh
+    location: Option<Point<i32, Logical>>,
+    /// State of the resize of the window, if it is being resized
+    resize: Option<ResizeState>,
+}
+
+fn init_interactive_state(surface: &WlSurface) {
+    let inserted = with_states(surface, |states| {
+        states
+            .data_map
+            .insert_if_missing_threadsafe(|| Mutex::new(InteractiveState::default()))
+    })
+    .unwrap_or(false);
+    if inserted {
+        compositor::add_commit_hook(surface, interactive_commit_hook);
+    }
+}
+
+fn with_interactive_state<T>(surface: &WlSurface, f: impl FnOnce(&mut InteractiveState) -> T) -> Option<T> {
+    with_states(surface, |states| {
+        states
+            .data_map
+            .get::<Mutex<InteractiveState>>()
+            .map(|state| f(&mut state.lock().unwrap()))
+    })
+    .ok()
+    .flatten()
+}
+
+fn interactive_commit_hook(surface: &WlSurface) {
+    let _ = with_states(surface, |states| {
+        let mut state = match states.data_map.get::<Mutex<InteractiveState>>() {
+            Some(state) => state.lock().unwrap(),
+            None => return,
+        };
+        match state.resize {
+            Some(ResizeState::WaitingForCommit(data)) => state.resize = Some(ResizeState::Done(data)),
+            Some(ResizeState::WaitingForFinalAck(data)) => {
+                // the xdg_shell commit hook already applied the last acked state
+                let resizing = states
+                    .data_map
+                    .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                    .map(|attributes| {
+                        attributes
+                            .lock()
+                            .unwrap()
+                            .current
+                            .states
+                            .contains(xdg_toplevel::State::Resizing)
+                    })
+                    .unwrap_or(false);
+                if !resizing {
+                    state.resize = Some(ResizeState::Done(data));
+                }
+            }
+            _ => {}
+        }
+    });
+}
+
+/// Location the window requests to be moved to by an interactive move or resize
+pub(super) fn take_requested_location(window: &Window) -> Option<Point<i32, Logical>> {
+    let surface = window.toplevel().get_surface()?;
+    let (location, resize) = with_interactive_state(surface, |state| {
+        let resize = match state.resize {
+            None => None,
+            Some(ResizeState::Done(data)) => {
+                state.resize = None;
+                Some(data)
+            }
+            Some(ResizeState::Resizing(data))
+            | Some(ResizeState::WaitingForFinalAck(data))
+            | Some(ResizeState::WaitingForCommit(data)) => Some(data),
+        };
+        (state.location.take(), resize)
+    })?;
+    match resize {
+        Some(data) => Some(data.window_location(window.geometry().size)),
+        None => location,
+    }
+}
+
+fn check_grab(
+    window: &Window,
+    seat: &Seat,
+    serial: Serial,
+) -> Result<(PointerHandle, GrabStartData), InteractiveGrabError> {
+    let surface = window
+        .toplevel()
+        .get_surface()
+        .ok_or(InteractiveGrabError::DeadWindow)?;
+    let pointer = seat
+        .get_pointer()
+        .filter(|pointer| pointer.has_grab(serial))
+        .ok_or(InteractiveGrabError::InvalidSerial)?;
+    let start_data = pointer
+        .grab_start_data()
+        .ok_or(InteractiveGrabError::InvalidSerial)?;
+    match start_data.focus {
+        Some((ref focus, _)) if focus.as_ref().same_client_as(surface.as_ref()) => Ok((pointer, start_data)),
+        _ => Err(InteractiveGrabError::InvalidFocus),
+    }
+}
+
+impl Space<Window> {
+    /// Starts an interactive move of a mapped window with the pointer of `seat`
+    ///
+    /// This is meant to be called in response to a move request of a client, e.g.
+    /// [`XdgRequest::Move`](crate::wayland::shell::xdg::XdgRequest::Move), with the serial of
+    /// the request. The window follows the pointer until all its buttons are released, its new
+    /// location is applied on [`Space::refresh`].
+    ///
+    /// Only pointer grabs are supported, as seats do not provide touch input yet.
+    pub fn start_move_grab(
+        &self,
+        window: &Window,
+        seat: &Seat,
+        serial: Serial,
+    ) -> Result<(), InteractiveGrabError> {
+        let (pointer, start_data) = check_grab(window, seat, serial)?;
+        let initial_window_location = self
+            .element_location(window)
+            .ok_or(InteractiveGrabError::UnmappedWindow)?;
+        // checked by `check_grab`
+        init_interactive_state(window.toplevel().get_surface().unwrap());
+
+        pointer.set_grab(
+            MoveSurfaceGrab {
+                start_data,
+                window: window.clone(),
+                initial_window_location,
+            },
+            serial,
+        );
+        Ok(())
+    }
+
+    /// Starts an interactive resize of a mapped window with the pointer of `seat`
+    ///
+    /// This is meant to be called in response to a resize request of a client, e.g.
+    /// [`XdgRequest::Resize`](crate::wayland::shell::xdg::XdgRequest::Resize), with the serial
+    /// and edges of the request. Until all buttons of the pointer are released, the window is
+    /// configured with the size following the pointer, bounded by the minimum and maximum size
+    /// of `xdg_toplevel` surfaces, and the `resizing` state. When resizing from the top or left
+    /// edges, the window is moved on [`Space::refresh`] to keep the opposite edges in place,
+    /// until the client committed the final size.
+    ///
+    /// Only pointer grabs are supported, as seats do not provide touch input yet.
+    pub fn start_resize_grab(
+        &self,
+        window: &Window,
+        seat: &Seat,
+        serial: Serial,
+        edges: ResizeEdge,
+    ) -> Result<(), InteractiveGrabError> {
+        let (pointer, start_data) = check_grab(window, seat, serial)?;
+        let initial_window_location = self
+            .element_location(window)
+            .ok_or(InteractiveGrabError::UnmappedWindow)?;
+        let initial_window_size = window.geometry().size;
+        // checked by `check_grab`
+        let surface = window.toplevel().get_surface().unwrap();
+        init_interactive_state(surface);
+        with_interactive_state(surface, |state| {
+            state.resize = Some(ResizeState::Resizing(ResizeData {
+                edges,
+                initial_window_location,
+                initial_window_size,
+            }))
+        });
+
+        pointer.set_grab(
+            ResizeSurfaceGrab {
+                start_data,
+                window: window.clone(),
+                edges,
+                initial_window_size,
+                last_window_size: initial_window_size,
+            },
+            serial,
+        );
+        Ok(())
+    }
+}
+
+struct MoveSurfaceGrab {
+    start_data: GrabStartData,
+    window: Window,
+    initial_window_location: Point<i32, Logical>,
+}
+
+impl PointerGrab for MoveSurfaceGrab {
+    fn motion(
+        &mut self,
+        handle: &mut PointerInnerHandle<'_>,
+        location: Point<f64, Logical>,
+        _focus: Option<(WlSurface, Point<i32, Logical>)>,
+        serial: Serial,
+        time: u32,
+    ) {
+        let surface = match self.window.toplevel().get_surface() {
+            Some(surface) => surface,
+            None => {
+                handle.unset_grab(serial, time);
+                return;
+            }
+        };
+        let delta = location - self.start_data.location;
+        let new_location = (self.initial_window_location.to_f64() + delta).to_i32_round();
+        with_interactive_state(surface, |state| state.location = Some(new_location));
+    }
+
+    fn button(
+        &mut self,
+        handle: &mut PointerInnerHandle<'_>,
+        button: u32,
+        state: ButtonState,
+        serial: Serial,
+        time: u32,
+    ) {
+        handle.button(button, state, serial, time);
+        if handle.current_pressed().is_empty() {
+            // No more buttons are pressed, release the grab.
+            handle.unset_grab(serial, time);
+        }
+    }
+
+    fn axis(&mut self, handle: &mut PointerInnerHandle<'_>, details: AxisFrame) {
+        handle.axis(details)
+    }
+
+    fn start_data(&self) -> &GrabStartData {
+        &self.start_data
+    }
+}
+
+struct ResizeSurfaceGrab {
+    start_data: GrabStartData,
+    window: Window,
+    edges: ResizeEdge,
+    initial_window_size: Size<i32, Logical>,
+    last_window_size: Size<i32, Logical>,
+}
+
+impl ResizeSurfaceGrab {
+    fn new_window_size(&self, delta: Point<f64, Logical>, surface: &WlSurface) -> Size<i32, Logical> {
+        let (mut dx, mut dy) = delta.into();
+        if self.edges.intersects(ResizeEdge::LEFT) {
+            dx = -dx;
+        }
+        if self.edges.intersects(ResizeEdge::TOP) {
+            dy = -dy;
+        }
+
+        let mut size = self.initial_window_size;
+        if self.edges.intersects(ResizeEdge::LEFT | ResizeEdge::RIGHT) {
+            size.w = (size.w as f64 + dx) as i32;
+        }
+        if self.edges.intersects(ResizeEdge::TOP | ResizeEdge::BOTTOM) {
+            size.h = (size.h as f64 + dy) as i32;
+        }
+
+        let (min_size, max_size) = match self.window.toplevel() {
+            Kind::Xdg(_) => with_states(surface, |states| {
+                let data = states.cached_state.current::<SurfaceCachedState>();
+                (data.min_size, data.max_size)
+            })
+            .unwrap_or_default(),
+            Kind::Wl(_) => Default::default(),
+        };
+        // a maximum size of 0 means unbounded
+        let bound = |max: i32| if max == 0 { i32::MAX } else { max };
+        (
+            size.w.max(min_size.w.max(1)).min(bound(max_size.w)),
+            size.h.max(min_size.h.max(1)).min(bound(max_size.h)),
+        )
+            .into()
+    }
+}
+
+impl PointerGrab for ResizeSurfaceGrab {
+    fn motion(
+        &mut self,
+        handle: &mut PointerInnerHandle<'_>,
+        location: Point<f64, Logical>,
+        _focus: Option<(WlSurface, Point<i32, Logical>)>,
+        serial: Serial,
+        time: u32,
+    ) {
+        // the size constraints of a dead toplevel are unknown
+        let surface = match self.window.toplevel().get_surface() {
+            Some(surface) => surface,
+            None => {
+                handle.unset_grab(serial, time);
+                return;
+            }
+        };
+
+        self.last_window_size = self.new_window_size(location - self.start_data.location, surface);
+        match self.window.toplevel() {
+            Kind::Xdg(toplevel) => {
+                let ret = toplevel.with_pending_state(|state| {
+                    state.states.set(xdg_toplevel::State::Resizing);
+                    state.size = Some(self.last_window_size);
+                });
+                if ret.is_ok() {
+                    toplevel.send_configure();
+                }
+            }
+            Kind::Wl(shell_surface) => shell_surface.send_configure(self.last_window_size, self.edges.into()),
+        }
+    }
+
+    fn button(
+        &mut self,
+        handle: &mut PointerInnerHandle<'_>,
+        button: u32,
+        state: ButtonState,
+        serial: Serial,
+        time: u32,
+    ) {
+        handle.button(button, state, serial, time);
+        if !handle.current_pressed().is_empty() {
+            return;
+        }
+        // No more buttons are pressed, release the grab.
+        handle.unset_grab(serial, time);
+
+        let surface = match self.window.toplevel().get_surface() {
+            Some(surface) => surface,
+            None => return,
+        };
+        let waiting_for_ack = match self.window.toplevel() {
+            Kind::Xdg(toplevel) => {
+                let ret = toplevel.with_pending_state(|state| {
+                    state.states.unset(xdg_toplevel::State::Resizing);
+                    state.size = Some(self.last_window_size);
+                });
+                if ret.is_ok() {
+                    toplevel.send_configure();
+                }
+                true
+            }
+            Kind::Wl(_) => false,
+        };
+        with_interactive_state(surface, |state| {
+            if let Some(ResizeState::Resizing(data)) = state.resize {
+                state.resize = Some(if waiting_for_ack {
+                    ResizeState::WaitingForFinalAck(data)
+                } else {
+                    ResizeState::WaitingForCommit(data)
+                });
+            }
+        });
+    }
+
+    fn axis(&mut self, handle: &mut PointerInnerHandle<'_>, details: AxisFrame) {
+        handle.axis(details)
+    }
+
+    fn start_data(&self) -> &GrabStartData {
+        &self.start_data
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ResizeData, ResizeEdge, UnsupportedResizeEdge};
+    use std::convert::TryFrom;
+    use wayland_protocols::xdg_shell::server::xdg_toplevel;
+
+    #[test]
+    fn resize_keeps_opposite_edges_in_place() {
+        let data = ResizeData {
+            edges: ResizeEdge::TOP_LEFT,
+            initial_window_location: (100, 100).into(),
+            initial_window_size: (200, 100).into(),
+        };
+        assert_eq!(data.window_location((250, 80).into()), (50, 120).into());
+
+        let data = ResizeData {
+            edges: ResizeEdge::BOTTOM_RIGHT,
+            ..data
+        };
+        assert_eq!(data.window_location((250, 80).into()), (100, 100).into());
+    }
+
+    #[test]
+    fn only_protocol_edges_convert_to_xdg() {
+        assert_eq!(
+            xdg_toplevel::ResizeEdge::try_from(ResizeEdge::TOP_LEFT),
+            Ok(xdg_toplevel::ResizeEdge::TopLeft)
+        );
+        let edges = ResizeEdge::TOP | ResizeEdge::BOTTOM;
+        assert_eq!(
+            xdg_toplevel::ResizeEdge::try_from(edges),
+            Err(UnsupportedResizeEdge(edges))
+        );
+    }
+}
@@ -0,0 +1,577 @@
+//! Arrangement of layer-shell surfaces
+//!
+//! See the [module-level documentation](super) for an overview.
+
+use std::{rc::Rc, sync::Mutex, time::Duration};
+
+use slog::{o, trace};
+use wayland_server::protocol::wl_surface::WlSurface;
+
+use crate::{
+    backend::renderer::{
+        utils::{import_surface_tree, surface_tree_render_elements, WaylandSurfaceRenderElement},
+        ImportAll, Renderer,
+    },
+    utils::{user_data::UserDataMap, Logical, Point, Rectangle, Size},
+    wayland::{
+        compositor::{with_states, with_surface_tree_downward, TraversalAction},
+        output::Output,
+        shell::wlr_layer::{
+            self, Anchor, ExclusiveZone, KeyboardInteractivity, Layer, LayerSurfaceAttributes,
+            LayerSurfaceCachedState,
+        },
+    },
+};
+
+use super::{
+    popup::PopupManager,
+    space::output_size,
+    utils::{send_frames_surface_tree, under_from_surface_tree},
+};
+
+#[derive(Debug)]
+struct LayerSurfaceInner {
+    surface: wlr_layer::LayerSurface,
+    namespace: String,
+    user_data: UserDataMap,
+}
+
+/// A layer-shell surface, that can be mapped onto the [`LayerMap`] of an output
+///
+/// Cloning a layer surface creates a new handle to the same surface.
+/// Requires [`on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler)
+/// to be used for its surfaces.
+#[derive(Debug, Clone)]
+pub struct LayerSurface(Rc<LayerSurfaceInner>);
+
+impl PartialEq for LayerSurface {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for LayerSurface {}
+
+impl LayerSurface {
+    /// Create a new layer surface, e.g. on [`LayerShellRequest::NewLayerSurface`](crate::wayland::shell::wlr_layer::LayerShellRequest::NewLayerSurface)
+    pub fn new(surface: wlr_layer::LayerSurface, namespace: String) -> LayerSurface {
+        LayerSurface(Rc::new(LayerSurfaceInner {
+            surface,
+            namespace,
+            user_data: UserDataMap::new(),
+        }))
+    }
+
+    /// Access the underlying layer-shell surface
+    pub fn layer_surface(&self) -> &wlr_layer::LayerSurface {
+        &self.0.surface
+    }
+
+    /// Access the underlying `wl_surface`
+    ///
+    /// Returns `None` if the layer surface no longer exists.
+    pub fn get_surface(&self) -> Option<&WlSurface> {
+        self.0.surface.get_surface()
+    }
+
+    /// Is the underlying layer surface still alive?
+    pub fn alive(&self) -> bool {
+        self.0.surface.alive()
+    }
+
+    /// Returns the namespace of the layer surface, defining its purpose
+    pub fn namespace(&self) -> &str {
+        &self.0.namespace
+    }
+
+    /// Returns the currently committed state of the layer surface
+    pub fn cached_state(&self) -> LayerSurfaceCachedState {
+        self.get_surface()
+            .and_then(|surface| {
+                with_states(surface, |states| {
+                    *states.cached_state.current::<LayerSurfaceCachedState>()
+                })
+                .ok()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Returns the layer this surface is currently placed on
+    pub fn layer(&self) -> Layer {
+        self.cached_state().layer
+    }
+
+    /// Returns if the layer surface is interested in keyboard focus
+    pub fn can_receive_keyboard_focus(&self) -> bool {
+        !matches!(
+            self.cached_state().keyboard_interactivity,
+            KeyboardInteractivity::None
+        )
+    }
+
+    /// Returns the bounding box of the surface tree of the layer surface and all its popups
+    pub fn bbox_with_popups(&self) -> Rectangle<i32, Logical> {
+        self.get_surface()
+            .map(|surface| PopupManager::bbox_with_popups(surface, (0, 0)))
+            .unwrap_or_default()
+    }
+
+    /// Finds the topmost surface of the layer surface or its popups accepting input at the given point
+    ///
+    /// Returns the surface and its location relative to the layer surface.
+    pub fn surface_under<P>(&self, point: P) -> Option<(WlSurface, Point<i32, Logical>)>
+    where
+        P: Into<Point<f64, Logical>>,
+    {
+        let point = point.into();
+        let surface = self.get_surface()?;
+        PopupManager::surface_under(surface, point, (0, 0))
+            .or_else(|| under_from_surface_tree(surface, point, (0, 0)))
+    }
+
+    /// Sends the pending frame callbacks of the surfaces of the layer surface and its popups, after it was
+    /// presented on `output` at `time`
+    ///
+    /// See [`send_frames_surface_tree`](super::utils::send_frames_surface_tree) for details on the throttling.
+    pub fn send_frame<T>(&self, output: &Output, time: T, throttle: Option<Duration>)
+    where
+        T: Into<Duration>,
+    {
+        let time = time.into();
+        if let Some(surface) = self.get_surface() {
+            send_frames_surface_tree(surface, output, time, throttle);
+            for (popup, _) in PopupManager::popups_for_surface(surface) {
+                if let Some(surface) = popup.get_surface() {
+                    send_frames_surface_tree(surface, output, time, throttle);
+                }
+            }
+        }
+    }
+
+    /// Access the user data associated with this layer surface
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.0.user_data
+    }
+
+    fn with_surfaces(&self, mut f: impl FnMut(&WlSurface)) {
+        if let Some(surface) = self.get_surface() {
+            with_surface_tree_downward(
+                surface,
+                (),
+                |_, _, _| TraversalAction::DoChildren(()),
+                |surface, _, _| f(surface),
+                |_, _, _| true,
+            );
+        }
+    }
+
+    /// Returns if the client already committed the initial state of the surface
+    fn initial_commit_done(&self) -> bool {
+        self.get_surface()
+            .and_then(|surface| {
+                with_states(surface, |states| {
+                    states
+                        .data_map
+                        .get::<Mutex<LayerSurfaceAttributes>>()
+                        .map(|attributes| attributes.lock().unwrap().initial_configure_sent)
+                })
+                .ok()
+                .flatten()
+            })
+            .unwrap_or(false)
+    }
+}
+
+/// Errors thrown by [`LayerMap::map_layer`]
+#[derive(Debug, thiserror::Error)]
+pub enum LayerError {
+    /// The layer surface is already mapped onto a layer map
+    #[error("The layer surface is already mapped")]
+    AlreadyMapped,
+}
+
+/// The layer surfaces of a single output, arranged according to their anchors, margins and exclusive zones
+///
+/// All coordinates are relative to the output.
+#[derive(Debug)]
+pub struct LayerMap {
+    output: Output,
+    layers: Vec<(LayerSurface, Rectangle<i32, Logical>)>,
+    output_size: Size<i32, Logical>,
+    zone: Rectangle<i32, Logical>,
+    logger: ::slog::Logger,
+}
+
+impl LayerMap {
+    /// Create a new, empty layer map for the given output
+    pub fn new<L>(output: Output, log: L) -> LayerMap
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let output_size = output_size(&output).unwrap_or_default();
+        LayerMap {
+            output,
+            layers: Vec::new(),
+            output_size,
+            zone: Rectangle::from_loc_and_size((0, 0), output_size),
+            logger: crate::slog_or_fallback(log).new(o!("smithay_module" => "desktop_layer_map")),
+        }
+    }
+
+    /// The output of this layer map
+    pub fn output(&self) -> &Output {
+        &self.output
+    }
+
+    /// Map a layer surface onto the output and re-arrange all layer surfaces
+    ///
+    /// Within each [`Layer`], newly mapped surfaces are placed on top.
+    pub fn map_layer(&mut self, layer: &LayerSurface) -> Result<(), LayerError> {
+        if self.layers.iter().any(|(l, _)| l == layer) {
+            return Err(LayerError::AlreadyMapped);
+        }
+        trace!(self.logger, "Mapping layer surface"; "namespace" => layer.namespace());
+        layer.with_surfaces(|surface| self.output.enter(surface));
+        self.layers.push((layer.clone(), Rectangle::default()));
+        self.arrange();
+        Ok(())
+    }
+
+    /// Unmap a layer surface from the output and re-arrange the remaining layer surfaces
+    pub fn unmap_layer(&mut self, layer: &LayerSurface) {
+        if let Some(idx) = self.layers.iter().position(|(l, _)| l == layer) {
+            trace!(self.logger, "Unmapping layer surface"; "namespace" => layer.namespace());
+            self.layers.remove(idx);
+            layer.with_surfaces(|surface| self.output.leave(surface));
+            self.arrange();
+        }
+    }
+
+    /// Iterate over all mapped layer surfaces from bottom to top
+    pub fn layers(&self) -> impl DoubleEndedIterator<Item = &LayerSurface> {
+        Self::stacking_order()
+            .iter()
+            .flat_map(move |layer| self.layers_on(*layer))
+    }
+
+    /// Iterate over the layer surfaces on the given layer from bottom to top
+    pub fn layers_on(&self, layer: Layer) -> impl DoubleEndedIterator<Item = &LayerSurface> {
+        self.layers
+            .iter()
+            .map(|(l, _)| l)
+            .filter(move |l| l.layer() == layer)
+    }
+
+    fn stacking_order() -> &'static [Layer] {
+        &[Layer::Background, Layer::Bottom, Layer::Top, Layer::Overlay]
+    }
+
+    /// Find the layer surface of the given `wl_surface`
+    pub fn layer_for_surface(&self, surface: &WlSurface) -> Option<&LayerSurface> {
+        self.layers
+            .iter()
+            .map(|(l, _)| l)
+            .find(|l| l.get_surface() == Some(surface))
+    }
+
+    /// Returns the arranged geometry of a mapped layer surface
+    pub fn layer_geometry(&self, layer: &LayerSurface) -> Option<Rectangle<i32, Logical>> {
+        self.layers
+            .iter()
+            .find(|(l, _)| l == layer)
+            .map(|(_, geometry)| *geometry)
+    }
+
+    /// Find the topmost layer surface on the given layer accepting input at the given point
+    ///
+    /// Returns the layer surface and its location.
+    pub fn layer_under<P>(&self, layer: Layer, point: P) -> Option<(&LayerSurface, Point<i32, Logical>)>
+    where
+        P: Into<Point<f64, Logical>>,
+    {
+        let point = point.into();
+        self.layers
+            .iter()
+            .rev()
+            .filter(|(l, _)| l.layer() == layer)
+            .find(|(l, geometry)| l.surface_under(point - geometry.loc.to_f64()).is_some())
+            .map(|(l, geometry)| (l, geometry.loc))
+    }
+
+    /// Returns the area of the output not covered by the exclusive zones of the layer surfaces
+    ///
+    /// This is the area available to e.g. maximized or tiled windows.
+    pub fn non_exclusive_zone(&self) -> Rectangle<i32, Logical> {
+        self.zone
+    }
+
+    /// Arrange the layer surfaces according to their current state and the current
+    /// mode of the output, configuring them if their size changed
+    ///
+    /// Needs to be called after every commit of a mapped layer surface.
+    pub fn arrange(&mut self) {
+        self.output_size = output_size(&self.output).unwrap_or_default();
+        let output_rect = Rectangle::from_loc_and_size((0, 0), self.output_size);
+        let mut zone = output_rect;
+        trace!(self.logger, "Arranging layers in {:?}", output_rect);
+
+        // surfaces with an exclusive zone are arranged first, from the topmost layer,
+        // the remaining surfaces have to avoid their zones
+        let states = self
+            .layers
+            .iter()
+            .map(|(layer, _)| layer.cached_state())
+            .collect::<Vec<_>>();
+        let mut order = (0..self.layers.len()).collect::<Vec<_>>();
+        order.sort_by_key(|&idx| {
+            let state = &states[idx];
+            let stacking = Self::stacking_order()
+                .iter()
+                .position(|layer| *layer == state.layer);
+            (exclusive_edge(state).is_none(), std::cmp::Reverse(stacking))
+        });
+
+        for idx in order {
+            let state = &states[idx];
+            let bounds = match state.exclusive_zone {
+                ExclusiveZone::DontCare => output_rect,
+                _ => zone,
+            };
+            let geometry = arrange_geometry(bounds, state);
+            if let Some((edge, amount)) = exclusive_edge(state) {
+                zone = shrink_zone(zone, edge, amount);
+            }
+
+            let (layer, layer_geometry) = &mut self.layers[idx];
+            *layer_geometry = geometry;
+            // the client has to wait for the initial configure before committing a buffer
+            if geometry.size != state.size || !layer.initial_commit_done() {
+                let _ = layer
+                    .layer_surface()
+                    .with_pending_state(|pending| pending.size = Some(geometry.size));
+                layer.layer_surface().send_configure();
+            }
+        }
+        self.zone = zone;
+    }
+
+    /// Remove destroyed layer surfaces and re-arrange the remaining surfaces, if necessary
+    ///
+    /// Takes care of re-arranging the layer surfaces on a mode change of the output.
+    /// Should be called periodically.
+    pub fn refresh(&mut self) {
+        let count = self.layers.len();
+        self.layers.retain(|(layer, _)| layer.alive());
+        if count != self.layers.len() || output_size(&self.output).unwrap_or_default() != self.output_size {
+            self.arrange();
+        }
+    }
+
+    /// Sends the pending frame callbacks of all mapped layer surfaces, after the output was presented at `time`
+    ///
+    /// See [`LayerSurface::send_frame`].
+    pub fn send_frames<T>(&self, time: T, throttle: Option<Duration>)
+    where
+        T: Into<Duration>,
+    {
+        let time = time.into();
+        for (layer, _) in &self.layers {
+            layer.send_frame(&self.output, time, throttle);
+        }
+    }
+
+    /// Creates the render elements of the layer surfaces on the given layer, including their popups
+    ///
+    /// The elements are ordered from front to back and located relative to the output with the
+    /// given scale. The [`Layer::Overlay`] and [`Layer::Top`] layers are expected to be rendered above
+    /// the windows of the output, the [`Layer::Bottom`] and [`Layer::Background`] layers below.
+    pub fn render_elements<R>(
+        &self,
+        renderer: &mut R,
+        layer: Layer,
+        scale: f64,
+        log: &slog::Logger,
+    ) -> Vec<WaylandSurfaceRenderElement>
+    where
+        R: Renderer + ImportAll,
+        <R as Renderer>::TextureId: Send + 'static,
+    {
+        self.layers
+            .iter()
+            .rev()
+            .filter(|(l, _)| l.layer() == layer)
+            .flat_map(|(l, geometry)| match l.get_surface() {
+                Some(surface) => {
+                    let location = geometry.loc.to_f64().to_physical(scale).to_i32_round();
+                    let mut elements =
+                        PopupManager::render_elements_for_surface(renderer, surface, location, scale, log);
+                    import_surface_tree(renderer, surface, log);
+                    elements.extend(surface_tree_render_elements(surface, location));
+                    elements
+                }
+                None => Vec::new(),
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Edge {
+    Top,
+    Bottom,
+    Left,
+    Right,
+}
+
+/// Returns the edge and amount a layer surface claims exclusively, including its margin
+fn exclusive_edge(state: &LayerSurfaceCachedState) -> Option<(Edge, i32)> {
+    let amount = match state.exclusive_zone {
+        ExclusiveZone::Exclusive(amount) => amount as i32,
+        _ => return None,
+    };
+    let anchor = state.anchor;
+    let horizontal = Anchor::LEFT | Anchor::RIGHT;
+    let vertical = Anchor::TOP | Anchor::BOTTOM;
+    // only meaningful if anchored to a single edge, optionally stretched along it
+    let edge = if anchor == Anchor::TOP || anchor == Anchor::TOP | horizontal {
+        Edge::Top
+    } else if anchor == Anchor::BOTTOM || anchor == Anchor::BOTTOM | horizontal {
+        Edge::Bottom
+    } else if anchor == Anchor::LEFT || anchor == Anchor::LEFT | vertical {
+        Edge::Left
+    } else if anchor == Anchor::RIGHT || anchor == Anchor::RIGHT | vertical {
+        Edge::Right
+    } else {
+        return None;
+    };
+    let margin = match edge {
+        Edge::Top => state.margin.top,
+        Edge::Bottom => state.margin.bottom,
+        Edge::Left => state.margin.left,
+        Edge::Right => state.margin.right,
+    };
+    Some((edge, amount + margin))
+}
+
+fn shrink_zone(mut zone: Rectangle<i32, Logical>, edge: Edge, amount: i32) -> Rectangle<i32, Logical> {
+    match edge {
+        Edge::Top => {
+            zone.loc.y += amount;
+            zone.size.h -= amount;
+        }
+        Edge::Bottom => zone.size.h -= amount,
+        Edge::Left => {
+            zone.loc.x += amount;
+            zone.size.w -= amount;
+        }
+        Edge::Right => zone.size.w -= amount,
+    }
+    zone.size.w = zone.size.w.max(0);
+    zone.size.h = zone.size.h.max(0);
+    zone
+}
+
+/// Places a layer surface inside the given bounds according to its anchors and margins
+fn arrange_geometry(
+    bounds: Rectangle<i32, Logical>,
+    state: &LayerSurfaceCachedState,
+) -> Rectangle<i32, Logical> {
+    let anchor = state.anchor;
+    let margin = state.margin;
+    let mut size = state.size;
+    let available_w = (bounds.size.w - margin.left - margin.right).max(0);
+    let available_h = (bounds.size.h - margin.top - margin.bottom).max(0);
+    // a size of zero stretches the surface between the anchored edges
+    if size.w == 0 {
+        size.w = available_w;
+    }
+    if size.h == 0 {
+        size.h = available_h;
+    }
+
+    let x = if anchor.anchored_horizontally() {
+        bounds.loc.x + margin.left + (available_w - size.w) / 2
+    } else if anchor.contains(Anchor::LEFT) {
+        bounds.loc.x + margin.left
+    } else if anchor.contains(Anchor::RIGHT) {
+        bounds.loc.x + bounds.size.w - size.w - margin.right
+    } else {
+        bounds.loc.x + (bounds.size.w - size.w) / 2
+    };
+    let y = if anchor.anchored_vertically() {
+        bounds.loc.y + margin.top + (available_h - size.h) / 2
+    } else if anchor.contains(Anchor::TOP) {
+        bounds.loc.y + margin.top
+    } else if anchor.contains(Anchor::BOTTOM) {
+        bounds.loc.y + bounds.size.h - size.h - margin.bottom
+    } else {
+        bounds.loc.y + (bounds.size.h - size.h) / 2
+    };
+
+    Rectangle::from_loc_and_size((x, y), size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{arrange_geometry, exclusive_edge, shrink_zone, Edge};
+    use crate::{
+        utils::Rectangle,
+        wayland::shell::wlr_layer::{Anchor, ExclusiveZone, LayerSurfaceCachedState, Margins},
+    };
+
+    #[test]
+    fn arrange_panel_and_centered_surface() {
+        let output = Rectangle::from_loc_and_size((0, 0), (1920, 1080));
+        let panel = LayerSurfaceCachedState {
+            size: (0, 30).into(),
+            anchor: Anchor::TOP | Anchor::LEFT | Anchor::RIGHT,
+            exclusive_zone: ExclusiveZone::Exclusive(30),
+            margin: Margins {
+                top: 5,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            arrange_geometry(output, &panel),
+            Rectangle::from_loc_and_size((0, 5), (1920, 30))
+        );
+        assert_eq!(exclusive_edge(&panel), Some((Edge::Top, 35)));
+        let zone = shrink_zone(output, Edge::Top, 35);
+        assert_eq!(zone, Rectangle::from_loc_and_size((0, 35), (1920, 1045)));
+
+        let dialog = LayerSurfaceCachedState {
+            size: (200, 100).into(),
+            ..Default::default()
+        };
+        assert_eq!(
+            arrange_geometry(zone, &dialog),
+            Rectangle::from_loc_and_size((860, 507), (200, 100))
+        );
+        assert_eq!(exclusive_edge(&dialog), None);
+    }
+
+    #[test]
+    fn exclusive_zone_needs_single_edge() {
+        let corner = LayerSurfaceCachedState {
+            size: (100, 100).into(),
+            anchor: Anchor::TOP | Anchor::LEFT,
+            exclusive_zone: ExclusiveZone::Exclusive(100),
+            ..Default::default()
+        };
+        assert_eq!(exclusive_edge(&corner), None);
+
+        let dock = LayerSurfaceCachedState {
+            size: (64, 0).into(),
+            anchor: Anchor::RIGHT | Anchor::TOP | Anchor::BOTTOM,
+            exclusive_zone: ExclusiveZone::Exclusive(64),
+            ..Default::default()
+        };
+        assert_eq!(exclusive_edge(&dock), Some((Edge::Right, 64)));
+        let output = Rectangle::from_loc_and_size((0, 0), (1920, 1080));
+        assert_eq!(
+            arrange_geometry(output, &dock),
+            Rectangle::from_loc_and_size((1856, 0), (64, 1080))
+        );
+    }
+}
@@ -0,0 +1,65 @@
+//! Desktop management helpers
+//!
+//! This module contains helpers to organize and interact with the windows and outputs of
+//! your compositor. They build upon the lower-level [`wayland`](crate::wayland) and
+//! [`renderer`](crate::backend::renderer) modules and are entirely optional, you may still
+//! implement your own window management instead.
+//!
+//! ## Space
+//!
+//! A [`Space`] represents a two-dimensional plane in logical coordinates, that elements
+//! (usually windows) and outputs can be mapped onto. It keeps track of the stacking order
+//! of the mapped elements, which outputs they are visible on and provides the means to find
+//! the element under a given point for input handling. Outputs can also
+//! [mirror](Space::mirror_output) another output, showing the same area of the space rescaled
+//! to their own mode.
+//!
+//! Rendering a space onto an output is done by converting the visible elements into
+//! [`RenderElement`](crate::backend::renderer::element::RenderElement)s through
+//! [`Space::render_elements_for_output`] and passing them to an
+//! [`OutputDamageTracker`](crate::backend::renderer::damage::OutputDamageTracker), which
+//! [`Space::render_output`] does for you.
+//!
+//! ## Window
+//!
+//! A [`Window`] wraps the toplevel surfaces of the different shells (currently `xdg_toplevel` and
+//! `wl_shell_surface`) behind a common interface, providing their geometry, title and app id,
+//! activation state and finding the surface under a point taking input regions into account.
+//! Windows can be directly mapped onto a [`Space`].
+//!
+//! ## Interactive move and resize
+//!
+//! The move and resize requests of windows are handled by [`Space::start_move_grab`] and
+//! [`Space::start_resize_grab`], which validate the request and set a grab on the pointer of the
+//! seat. The grabs move the window with the pointer, or configure it with a size following the
+//! pointer, bounded by the minimum and maximum size of the client, while keeping the edges
+//! opposite to the dragged ones in place. The new locations are applied on [`Space::refresh`],
+//! so the compositor only has to decide which requests to grant.
+//!
+//! ## Popups
+//!
+//! The [`PopupManager`] keeps track of the popups of your surfaces, organized in trees below the
+//! toplevel (or layer) surface they belong to. It computes the location of the popups relative to
+//! their parents from the state of their positioners and handles explicit popup grabs, dismissing
+//! the grabbing popups in the correct order. Popups of a [`Window`] are part of its bounding box,
+//! input handling and render elements, given they are tracked by a [`PopupManager`].
+//!
+//! ## Layer shell
+//!
+//! A [`LayerMap`] arranges the [`LayerSurface`]s of a single output according to their layer,
+//! anchors, margins and exclusive zones. It provides the area left for your windows after
+//! subtracting the exclusive zones, re-arranges the surfaces on mode changes of the output and
+//! creates the render elements of each layer, to be rendered around the windows of the output.
+
+mod grabs;
+mod layer;
+mod popup;
+pub mod space;
+pub mod utils;
+mod window;
+
+pub use self::grabs::{InteractiveGrabError, ResizeEdge, UnsupportedResizeEdge};
+pub use self::layer::{LayerError, LayerMap, LayerSurface};
+pub use self::popup::{PopupGrabError, PopupKind, PopupManager};
+pub use self::space::{AsRenderElements, RenderError, RenderOutputResult, Space, SpaceElement};
+pub use self::window::{Kind, Window};
@@ -0,0 +1,891 @@
+//! Popup tracking and grabs
+//!
+//! See the [module-level documentation](super) for an overview.
+
+use std::{cell::RefCell, rc::Rc, sync::Mutex};
+
+use slog::{o, trace};
+use wayland_server::protocol::{wl_pointer::ButtonState, wl_surface::WlSurface};
+
+use crate::{
+    backend::renderer::{
+        utils::{import_surface_tree, surface_tree_render_elements, WaylandSurfaceRenderElement},
+        ImportAll, Renderer,
+    },
+    utils::{DeadResource, Logical, Physical, Point, Rectangle},
+    wayland::{
+        compositor::{get_role, with_states},
+        seat::{AxisFrame, GrabStartData, KeyboardHandle, PointerGrab, PointerInnerHandle, Seat},
+        shell::xdg::{PopupSurface, SurfaceCachedState, XdgPopupSurfaceRoleAttributes, XDG_POPUP_ROLE},
+        Serial, SERIAL_COUNTER,
+    },
+};
+
+use super::utils::{bbox_from_surface_tree, under_from_surface_tree};
+
+/// A popup surface
+#[derive(Debug, Clone, PartialEq)]
+pub enum PopupKind {
+    /// An `xdg_popup` surface
+    Xdg(PopupSurface),
+}
+
+impl PopupKind {
+    /// Is the underlying popup surface still alive?
+    pub fn alive(&self) -> bool {
+        match self {
+            PopupKind::Xdg(popup) => popup.alive(),
+        }
+    }
+
+    /// Access the underlying `wl_surface`
+    ///
+    /// Returns `None` if the popup surface no longer exists.
+    pub fn get_surface(&self) -> Option<&WlSurface> {
+        match self {
+            PopupKind::Xdg(popup) => popup.get_surface(),
+        }
+    }
+
+    /// Returns the parent surface of the popup, if any was set
+    pub fn parent(&self) -> Option<WlSurface> {
+        match self {
+            PopupKind::Xdg(popup) => popup.get_parent_surface(),
+        }
+    }
+
+    /// Returns the geometry of the popup
+    ///
+    /// This is the geometry set by the client through `xdg_surface.set_window_geometry`,
+    /// falling back to the bounding box of the popup if none was set.
+    pub fn geometry(&self) -> Rectangle<i32, Logical> {
+        self.get_surface().map(surface_geometry).unwrap_or_default()
+    }
+
+    /// Returns the location of the popup relative to the geometry of its parent
+    ///
+    /// This is the location computed from the positioner of the popup, as currently
+    /// committed by the client.
+    pub fn location(&self) -> Point<i32, Logical> {
+        let surface = match self.get_surface() {
+            Some(surface) => surface,
+            None => return (0, 0).into(),
+        };
+        with_states(surface, |states| {
+            states
+                .data_map
+                .get::<Mutex<XdgPopupSurfaceRoleAttributes>>()
+                .map(|attributes| attributes.lock().unwrap().current.geometry.loc)
+        })
+        .ok()
+        .flatten()
+        .unwrap_or_default()
+    }
+
+    fn send_done(&self) {
+        match self {
+            PopupKind::Xdg(popup) => popup.send_popup_done(),
+        }
+    }
+}
+
+/// Geometry of a surface as defined by `xdg_surface.set_window_geometry`, or its bounding box
+fn surface_geometry(surface: &WlSurface) -> Rectangle<i32, Logical> {
+    with_states(surface, |states| {
+        states.cached_state.current::<SurfaceCachedState>().geometry
+    })
+    .ok()
+    .flatten()
+    .unwrap_or_else(|| bbox_from_surface_tree(surface, (0, 0)))
+}
+
+fn is_popup(surface: &WlSurface) -> bool {
+    get_role(surface) == Some(XDG_POPUP_ROLE)
+}
+
+/// Popups of a root surface, stored in its data map
+#[derive(Debug, Default)]
+struct PopupTree(RefCell<Vec<PopupNode>>);
+
+#[derive(Debug)]
+struct PopupNode {
+    popup: PopupKind,
+    children: Vec<PopupNode>,
+}
+
+impl PopupNode {
+    fn new(popup: PopupKind) -> PopupNode {
+        PopupNode {
+            popup,
+            children: Vec::new(),
+        }
+    }
+
+    fn surface_is(&self, surface: &WlSurface) -> bool {
+        self.popup.get_surface() == Some(surface)
+    }
+
+    fn find(&self, surface: &WlSurface) -> Option<&PopupNode> {
+        if self.surface_is(surface) {
+            return Some(self);
+        }
+        self.children.iter().find_map(|child| child.find(surface))
+    }
+
+    fn find_mut(&mut self, surface: &WlSurface) -> Option<&mut PopupNode> {
+        if self.surface_is(surface) {
+            return Some(self);
+        }
+        self.children.iter_mut().find_map(|child| child.find_mut(surface))
+    }
+
+    fn cleanup(&mut self) {
+        self.children.retain(|child| child.popup.alive());
+        for child in &mut self.children {
+            child.cleanup();
+        }
+    }
+
+    /// Collects the popups from bottom to top with the location of their surface,
+    /// given the location of the geometry of the parent surface.
+    fn collect(
+        &self,
+        parent_geometry: Point<i32, Logical>,
+        popups: &mut Vec<(PopupKind, Point<i32, Logical>)>,
+    ) {
+        let geometry = self.popup.geometry();
+        let location = parent_geometry + self.popup.location() - geometry.loc;
+        popups.push((self.popup.clone(), location));
+        for child in &self.children {
+            child.collect(location + geometry.loc, popups);
+        }
+    }
+}
+
+fn with_popup_tree<T>(surface: &WlSurface, f: impl FnOnce(&mut Vec<PopupNode>) -> T) -> Option<T> {
+    with_states(surface, |states| {
+        states.data_map.insert_if_missing(PopupTree::default);
+        let tree = states.data_map.get::<PopupTree>().unwrap();
+        let mut nodes = tree.0.borrow_mut();
+        f(&mut nodes)
+    })
+    .ok()
+}
+
+/// Errors thrown by [`PopupManager::grab_popup`]
+#[derive(Debug, thiserror::Error)]
+pub enum PopupGrabError {
+    /// The popup surface was already destroyed
+    #[error("The popup surface was already destroyed")]
+    DeadPopup,
+    /// The parent of the popup is a popup without an explicit grab, which is a protocol error
+    #[error("The parent of the popup did not take a grab")]
+    InvalidGrab,
+    /// The parent of the popup was already dismissed, the popup should be dismissed as well
+    #[error("The parent of the popup was already dismissed")]
+    ParentDismissed,
+}
+
+/// Tracks the popups of the surfaces of the compositor
+///
+/// Popups are organized in trees below the non-popup surface (e.g. a toplevel or a layer surface)
+/// they ultimately belong to, their root surface. The popups of a root surface can be
+/// queried through [`PopupManager::popups_for_surface`], a [`Window`](super::Window) takes its
+/// popups into account automatically.
+#[derive(Debug)]
+pub struct PopupManager {
+    unmapped_popups: Vec<PopupKind>,
+    popup_roots: Vec<WlSurface>,
+    logger: ::slog::Logger,
+}
+
+impl PopupManager {
+    /// Create a new popup manager
+    pub fn new<L>(log: L) -> PopupManager
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        PopupManager {
+            unmapped_popups: Vec::new(),
+            popup_roots: Vec::new(),
+            logger: crate::slog_or_fallback(log).new(o!("smithay_module" => "desktop_popup")),
+        }
+    }
+
+    /// Start tracking a new popup
+    ///
+    /// Should be called for every newly created popup, e.g. on [`XdgRequest::NewPopup`](crate::wayland::shell::xdg::XdgRequest::NewPopup).
+    /// Popups without a parent are tracked once their parent is set, see [`PopupManager::commit`].
+    pub fn track_popup(&mut self, popup: PopupKind) -> Result<(), DeadResource> {
+        if !popup.alive() {
+            return Err(DeadResource);
+        }
+        if popup.parent().is_some() {
+            self.add_popup(popup)
+        } else {
+            trace!(self.logger, "Popup has no parent yet, deferring tracking");
+            self.unmapped_popups.push(popup);
+            Ok(())
+        }
+    }
+
+    /// Needs to be called on every commit of a surface, to track popups whose parent
+    /// was set after their creation
+    pub fn commit(&mut self, surface: &WlSurface) {
+        if let Some(idx) = self
+            .unmapped_popups
+            .iter()
+            .position(|popup| popup.get_surface() == Some(surface) && popup.parent().is_some())
+        {
+            let popup = self.unmapped_popups.remove(idx);
+            let _ = self.add_popup(popup);
+        }
+    }
+
+    fn add_popup(&mut self, popup: PopupKind) -> Result<(), DeadResource> {
+        let parent = popup.parent().ok_or(DeadResource)?;
+        let root = find_root(&parent).ok_or(DeadResource)?;
+        trace!(self.logger, "Tracking new popup"; "root" => ?root);
+
+        let inserted = with_popup_tree(&root, |nodes| {
+            let node = PopupNode::new(popup);
+            if parent == root {
+                nodes.push(node);
+                true
+            } else if let Some(parent) = nodes.iter_mut().find_map(|child| child.find_mut(&parent)) {
+                parent.children.push(node);
+                true
+            } else {
+                false
+            }
+        })
+        .unwrap_or(false);
+        if !inserted {
+            // the parent popup is not tracked
+            return Err(DeadResource);
+        }
+        if !self.popup_roots.contains(&root) {
+            self.popup_roots.push(root);
+        }
+        Ok(())
+    }
+
+    /// Find the tracked popup of the given surface
+    pub fn find_popup(&self, surface: &WlSurface) -> Option<PopupKind> {
+        if let Some(popup) = self
+            .unmapped_popups
+            .iter()
+            .find(|popup| popup.get_surface() == Some(surface))
+        {
+            return Some(popup.clone());
+        }
+        let root = find_root(surface)?;
+        with_popup_tree(&root, |nodes| {
+            nodes
+                .iter()
+                .find_map(|node| node.find(surface))
+                .map(|node| node.popup.clone())
+        })
+        .flatten()
+    }
+
+    /// Returns the root surface, the non-popup surface the given popup ultimately belongs to
+    pub fn get_root_surface(popup: &PopupKind) -> Option<WlSurface> {
+        find_root(&popup.parent()?)
+    }
+
+    /// Returns the location of the surface of a popup relative to the surface of its root
+    pub fn get_popup_toplevel_coords(popup: &PopupKind) -> Point<i32, Logical> {
+        let surface = match popup.get_surface() {
+            Some(surface) => surface,
+            None => return (0, 0).into(),
+        };
+        Self::get_root_surface(popup)
+            .and_then(|root| {
+                Self::popups_for_surface(&root)
+                    .into_iter()
+                    .find(|(popup, _)| popup.get_surface() == Some(surface))
+            })
+            .map(|(_, location)| location)
+            .unwrap_or_default()
+    }
+
+    /// Returns the popups of a root surface from bottom to top, together with the
+    /// location of their surfaces relative to the root surface
+    pub fn popups_for_surface(surface: &WlSurface) -> Vec<(PopupKind, Point<i32, Logical>)> {
+        let geometry = surface_geometry(surface).loc;
+        let mut popups = Vec::new();
+        with_popup_tree(surface, |nodes| {
+            for node in nodes.iter() {
+                node.collect(geometry, &mut popups);
+            }
+        });
+        popups
+    }
+
+    /// Finds the topmost popup surface of a root surface located at `location` accepting input
+    /// at the given point, and the location of that surface
+    pub fn surface_under<P>(
+        surface: &WlSurface,
+        point: Point<f64, Logical>,
+        location: P,
+    ) -> Option<(WlSurface, Point<i32, Logical>)>
+    where
+        P: Into<Point<i32, Logical>>,
+    {
+        let location = location.into();
+        Self::popups_for_surface(surface)
+            .into_iter()
+            .rev()
+            .find_map(|(popup, popup_location)| {
+                let popup_surface = popup.get_surface()?;
+                under_from_surface_tree(popup_surface, point, location + popup_location)
+            })
+    }
+
+    /// Returns the bounding box of a root surface located at `location` and all its popups
+    pub fn bbox_with_popups<P>(surface: &WlSurface, location: P) -> Rectangle<i32, Logical>
+    where
+        P: Into<Point<i32, Logical>>,
+    {
+        let location = location.into();
+        Self::popups_for_surface(surface).into_iter().fold(
+            bbox_from_surface_tree(surface, location),
+            |bbox, (popup, popup_location)| match popup.get_surface() {
+                Some(popup_surface) => {
+                    bbox.merge(bbox_from_surface_tree(popup_surface, location + popup_location))
+                }
+                None => bbox,
+            },
+        )
+    }
+
+    /// Creates the [`RenderElement`](crate::backend::renderer::element::RenderElement)s of the
+    /// popups of a root surface located at `location` on an output with the given scale
+    ///
+    /// The elements are ordered from front to back.
+    pub fn render_elements_for_surface<R>(
+        renderer: &mut R,
+        surface: &WlSurface,
+        location: Point<i32, Physical>,
+        scale: f64,
+        log: &slog::Logger,
+    ) -> Vec<WaylandSurfaceRenderElement>
+    where
+        R: Renderer + ImportAll,
+        <R as Renderer>::TextureId: Send + 'static,
+    {
+        Self::popups_for_surface(surface)
+            .into_iter()
+            .rev()
+            .flat_map(|(popup, popup_location)| match popup.get_surface() {
+                Some(popup_surface) => {
+                    import_surface_tree(renderer, popup_surface, log);
+                    let location = location + popup_location.to_f64().to_physical(scale).to_i32_round();
+                    surface_tree_render_elements(popup_surface, location)
+                }
+                None => Vec::new(),
+            })
+            .collect()
+    }
+
+    /// Take an explicit grab for a popup, e.g. on [`XdgRequest::Grab`](crate::wayland::shell::xdg::XdgRequest::Grab)
+    ///
+    /// While grabbed, the keyboard of the seat is focused on the topmost grabbing popup and the
+    /// pointer only delivers events to surfaces of the client owning the popups. Clicking anywhere
+    /// else dismisses all grabbing popups, from top to bottom. Popups grabbing on top of a grabbing
+    /// parent popup are nested into its grab.
+    ///
+    /// The `serial` has to be the one of an implicit pointer grab (see
+    /// [`PointerHandle::has_grab`](crate::wayland::seat::PointerHandle::has_grab)) or of the last
+    /// key press (see [`KeyboardHandle::is_last_key_press`]). While a grab is active,
+    /// the serial of any input event received after the grab was taken is accepted as well.
+    /// A grab for another root surface replaces the active grab, dismissing its popups.
+    ///
+    /// If the grab is denied, the popup should be dismissed by the compositor, in case of
+    /// [`PopupGrabError::InvalidGrab`] the client should be sent a protocol error as well.
+    pub fn grab_popup(
+        &mut self,
+        popup: PopupKind,
+        seat: &Seat,
+        serial: Serial,
+    ) -> Result<(), PopupGrabError> {
+        if !popup.alive() {
+            return Err(PopupGrabError::DeadPopup);
+        }
+        let parent = popup.parent().ok_or(PopupGrabError::DeadPopup)?;
+        let root = find_root(&parent).ok_or(PopupGrabError::ParentDismissed)?;
+
+        seat.user_data().insert_if_missing(SeatPopupGrab::default);
+        let seat_grab = seat.user_data().get::<SeatPopupGrab>().unwrap();
+        let current = seat_grab.0.borrow().clone();
+
+        let pointer = match seat.get_pointer() {
+            Some(pointer) => pointer,
+            None => return Err(PopupGrabError::InvalidGrab),
+        };
+        let keyboard = seat.get_keyboard();
+        // the grab has to be triggered by an implicit pointer grab or a key press
+        let mut valid_serial = pointer.has_grab(serial)
+            || keyboard
+                .as_ref()
+                .map(|keyboard| keyboard.is_last_key_press(serial))
+                .unwrap_or(false);
+
+        if let Some(current) = current {
+            let mut inner = current.borrow_mut();
+            inner.prune();
+            if !inner.grabs.is_empty() {
+                // while grabbed, the pointer events are delivered through the popup grab instead
+                valid_serial |= serial.is_no_older_than(&inner.serial);
+                if !valid_serial {
+                    return Err(PopupGrabError::InvalidGrab);
+                }
+            }
+            let nested = inner.root.as_ref() == Some(&root) && !inner.grabs.is_empty();
+            if nested {
+                if is_popup(&parent) {
+                    if inner.grabs.last().and_then(|top| top.get_surface()) != Some(&parent) {
+                        return Err(PopupGrabError::InvalidGrab);
+                    }
+                } else {
+                    // a new popup of the root replaces the previous popups
+                    inner.dismiss();
+                }
+                trace!(self.logger, "Nesting popup grab");
+                inner.grabs.push(popup);
+                inner.serial = serial;
+                inner.focus_top();
+                return Ok(());
+            }
+            if !is_popup(&parent) && !inner.grabs.is_empty() {
+                // the grab is replaced, the focus is not returned to the previous root
+                trace!(self.logger, "Replacing popup grab");
+                inner.dismiss();
+                inner.root = None;
+            }
+        }
+
+        if is_popup(&parent) {
+            return Err(if parent.as_ref().is_alive() {
+                PopupGrabError::InvalidGrab
+            } else {
+                PopupGrabError::ParentDismissed
+            });
+        }
+        if !valid_serial {
+            return Err(PopupGrabError::InvalidGrab);
+        }
+
+        trace!(self.logger, "Starting new popup grab");
+        let inner = Rc::new(RefCell::new(PopupGrabInner {
+            root: Some(root),
+            grabs: vec![popup],
+            keyboard,
+            serial,
+        }));
+        *seat_grab.0.borrow_mut() = Some(inner.clone());
+        pointer.set_grab(
+            PopupPointerGrab {
+                inner: inner.clone(),
+                start_data: GrabStartData {
+                    focus: None,
+                    button: 0,
+                    location: pointer.current_location(),
+                },
+            },
+            serial,
+        );
+        // focus the popup after the previous grab was dropped
+        inner.borrow().focus_top();
+        Ok(())
+    }
+
+    /// Cleanup the popups of destroyed surfaces
+    ///
+    /// Should be called periodically.
+    pub fn cleanup(&mut self) {
+        self.unmapped_popups.retain(PopupKind::alive);
+        self.popup_roots.retain(|root| {
+            with_popup_tree(root, |nodes| {
+                nodes.retain(|node| node.popup.alive());
+                for node in nodes.iter_mut() {
+                    node.cleanup();
+                }
+                !nodes.is_empty()
+            })
+            .unwrap_or(false)
+        });
+    }
+}
+
+/// Walks up the parents of a surface until a non-popup surface is found
+fn find_root(surface: &WlSurface) -> Option<WlSurface> {
+    let mut surface = surface.clone();
+    while is_popup(&surface) {
+        surface = with_states(&surface, |states| {
+            states
+                .data_map
+                .get::<Mutex<XdgPopupSurfaceRoleAttributes>>()
+                .and_then(|attributes| attributes.lock().unwrap().parent.clone())
+        })
+        .ok()
+        .flatten()?;
+    }
+    if surface.as_ref().is_alive() {
+        Some(surface)
+    } else {
+        None
+    }
+}
+
+/// Popup grab of a seat, stored in its user data
+#[derive(Debug, Default)]
+struct SeatPopupGrab(RefCell<Option<Rc<RefCell<PopupGrabInner>>>>);
+
+#[derive(Debug)]
+struct PopupGrabInner {
+    root: Option<WlSurface>,
+    /// grabbing popups from bottom to top
+    grabs: Vec<PopupKind>,
+    keyboard: Option<KeyboardHandle>,
+    /// serial of the most recent grab request
+    serial: Serial,
+}
+
+impl PopupGrabInner {
+    fn prune(&mut self) {
+        self.grabs.retain(PopupKind::alive);
+    }
+
+    /// Dismiss all grabbing popups from top to bottom
+    fn dismiss(&mut self) {
+        for popup in self.grabs.drain(..).rev() {
+            popup.send_done();
+        }
+    }
+
+    fn focus_top(&self) {
+        if let (Some(keyboard), Some(surface)) = (
+            self.keyboard.as_ref(),
+            self.grabs.last().and_then(|popup| popup.get_surface()),
+        ) {
+            keyboard.set_focus(Some(surface), SERIAL_COUNTER.next_serial());
+        }
+    }
+
+    /// Returns the keyboard focus to the root surface
+    fn restore_focus(&mut self) {
+        if let (Some(keyboard), Some(root)) = (self.keyboard.as_ref(), self.root.take()) {
+            if root.as_ref().is_alive() {
+                keyboard.set_focus(Some(&root), SERIAL_COUNTER.next_serial());
+            }
+        }
+    }
+
+    fn owns(&self, surface: &WlSurface) -> bool {
+        self.root
+            .as_ref()
+            .map(|root| root.as_ref().same_client_as(surface.as_ref()))
+            .unwrap_or(false)
+    }
+}
+
+struct PopupPointerGrab {
+    inner: Rc<RefCell<PopupGrabInner>>,
+    start_data: GrabStartData,
+}
+
+impl PopupPointerGrab {
+    /// Prunes destroyed popups, returns `false` if no grabbing popups are left
+    fn update(&self) -> bool {
+        let mut inner = self.inner.borrow_mut();
+        inner.prune();
+        if inner.grabs.is_empty() {
+            inner.restore_focus();
+            false
+        } else {
+            inner.focus_top();
+            true
+        }
+    }
+}
+
+impl PointerGrab for PopupPointerGrab {
+    fn motion(
+        &mut self,
+        handle: &mut PointerInnerHandle<'_>,
+        location: Point<f64, Logical>,
+        focus: Option<(WlSurface, Point<i32, Logical>)>,
+        serial: Serial,
+        time: u32,
+    ) {
+        if !self.update() {
+            handle.unset_grab(serial, time);
+            return;
+        }
+        // surfaces of other clients do not receive any pointer events
+        let focus = focus.filter(|(surface, _)| self.inner.borrow().owns(surface));
+        handle.motion(location, focus, serial, time);
+    }
+
+    fn button(
+        &mut self,
+        handle: &mut PointerInnerHandle<'_>,
+        button: u32,
+        state: ButtonState,
+        serial: Serial,
+        time: u32,
+    ) {
+        if !self.update() {
+            handle.unset_grab(serial, time);
+            return;
+        }
+        if state == ButtonState::Pressed && handle.current_focus().is_none() {
+            let mut inner = self.inner.borrow_mut();
+            inner.dismiss();
+            inner.restore_focus();
+            drop(inner);
+            handle.unset_grab(serial, time);
+            return;
+        }
+        handle.button(button, state, serial, time);
+    }
+
+    fn axis(&mut self, handle: &mut PointerInnerHandle<'_>, details: AxisFrame) {
+        handle.axis(details);
+    }
+
+    fn start_data(&self) -> &GrabStartData {
+        &self.start_data
+    }
+}
+
+impl Drop for PopupPointerGrab {
+    fn drop(&mut self) {
+        // the grab was replaced or cancelled, the popups are dismissed
+        let mut inner = self.inner.borrow_mut();
+        if !inner.grabs.is_empty() {
+            inner.dismiss();
+            inner.restore_focus();
+        }
+    }
+}
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::{PopupGrabError, PopupKind, PopupManager};
+    use crate::{
+        testing::{test_setup, TestClient, TestServer, TestSurface},
+        wayland::{
+            seat::{KeyboardHandle, PointerHandle, Seat, XkbConfig},
+            shell::xdg::{xdg_shell_init, PopupSurface, XdgRequest},
+            Serial, SERIAL_COUNTER,
+        },
+    };
+    use std::{cell::RefCell, rc::Rc};
+    use wayland_client::Main;
+    use wayland_protocols::xdg_shell::client::{
+        xdg_popup,
+        xdg_surface::XdgSurface,
+        xdg_wm_base::{self, XdgWmBase},
+    };
+    use wayland_server::protocol::{wl_pointer::ButtonState, wl_surface::WlSurface};
+
+    struct Harness {
+        server: TestServer<()>,
+        client: TestClient,
+        seat: Seat,
+        pointer: PointerHandle,
+        focus: Rc<RefCell<Option<WlSurface>>>,
+        new_popups: Rc<RefCell<Vec<PopupSurface>>>,
+        wm_base: Main<XdgWmBase>,
+        dismissed: Rc<RefCell<Vec<&'static str>>>,
+        manager: PopupManager,
+    }
+
+    impl Harness {
+        fn new() -> (Harness, KeyboardHandle) {
+            let new_popups = Rc::new(RefCell::new(Vec::new()));
+            let focus = Rc::new(RefCell::new(None));
+            let (new_popups_clone, focus_clone) = (new_popups.clone(), focus.clone());
+            let (server, client, (seat, pointer, keyboard)) = test_setup(move |display| {
+                xdg_shell_init(
+                    display,
+                    move |request, _| {
+                        if let XdgRequest::NewPopup { surface, .. } = request {
+                            new_popups_clone.borrow_mut().push(surface);
+                        }
+                    },
+                    None,
+                );
+                let (mut seat, _) = Seat::new(display, "seat-0".into(), None);
+                let pointer = seat.add_pointer(|_| {});
+                let keyboard = seat
+                    .add_keyboard(XkbConfig::default(), 200, 25, move |_, surface| {
+                        *focus_clone.borrow_mut() = surface.cloned();
+                    })
+                    .unwrap();
+                (seat, pointer, keyboard)
+            });
+            let wm_base = client.globals().instantiate_exact::<XdgWmBase>(1).unwrap();
+            wm_base.quick_assign(|wm_base, event, _| {
+                if let xdg_wm_base::Event::Ping { serial } = event {
+                    wm_base.pong(serial);
+                }
+            });
+            let harness = Harness {
+                server,
+                client,
+                seat,
+                pointer,
+                focus,
+                new_popups,
+                wm_base,
+                dismissed: Rc::new(RefCell::new(Vec::new())),
+                manager: PopupManager::new(None),
+            };
+            (harness, keyboard)
+        }
+
+        fn roundtrip(&mut self) {
+            self.server.roundtrip(&mut self.client, &mut ()).unwrap();
+        }
+
+        fn toplevel(&mut self) -> (TestSurface, XdgSurface, WlSurface) {
+            let surface = self.client.create_surface().unwrap();
+            let xdg_surface = self.wm_base.get_xdg_surface(surface.wl_surface());
+            xdg_surface.get_toplevel();
+            surface.commit();
+            self.roundtrip();
+            let wl_surface = self.server.wl_surface(&self.client, &surface).unwrap();
+            (surface, xdg_surface.detach(), wl_surface)
+        }
+
+        fn popup(&mut self, parent: &XdgSurface, name: &'static str) -> (TestSurface, XdgSurface, PopupKind) {
+            let positioner = self.wm_base.create_positioner();
+            positioner.set_size(10, 10);
+            positioner.set_anchor_rect(0, 0, 1, 1);
+            let surface = self.client.create_surface().unwrap();
+            let xdg_surface = self.wm_base.get_xdg_surface(surface.wl_surface());
+            let popup = xdg_surface.get_popup(Some(parent), &positioner);
+            let dismissed = self.dismissed.clone();
+            popup.quick_assign(move |_, event, _| {
+                if let xdg_popup::Event::PopupDone = event {
+                    dismissed.borrow_mut().push(name);
+                }
+            });
+            surface.commit();
+            self.roundtrip();
+            let popup = PopupKind::Xdg(self.new_popups.borrow_mut().pop().unwrap());
+            self.manager.track_popup(popup.clone()).unwrap();
+            (surface, xdg_surface.detach(), popup)
+        }
+
+        /// Presses a button on the given surface, returning the serial of the implicit grab
+        fn press(&mut self, surface: Option<&WlSurface>) -> Serial {
+            let location = if surface.is_some() { 5.0 } else { 500.0 };
+            self.pointer.motion(
+                (location, location).into(),
+                surface.map(|surface| (surface.clone(), (0, 0).into())),
+                SERIAL_COUNTER.next_serial(),
+                0,
+            );
+            let serial = SERIAL_COUNTER.next_serial();
+            self.pointer.button(0x110, ButtonState::Pressed, serial, 0);
+            serial
+        }
+
+        fn release(&mut self) {
+            self.pointer
+                .button(0x110, ButtonState::Released, SERIAL_COUNTER.next_serial(), 0);
+        }
+
+        fn focus(&self) -> Option<WlSurface> {
+            self.focus.borrow().clone()
+        }
+    }
+
+    #[test]
+    fn grab_requires_valid_serial() {
+        let (mut harness, keyboard) = Harness::new();
+        let (_toplevel, xdg_toplevel, _) = harness.toplevel();
+        let (_popup, _, popup) = harness.popup(&xdg_toplevel, "popup");
+
+        let seat = harness.seat.clone();
+        let stale = SERIAL_COUNTER.next_serial();
+        assert!(matches!(
+            harness.manager.grab_popup(popup.clone(), &seat, stale),
+            Err(PopupGrabError::InvalidGrab)
+        ));
+
+        let serial = SERIAL_COUNTER.next_serial();
+        keyboard.input::<(), _>(30, crate::backend::input::KeyState::Pressed, serial, 0, |_, _| {
+            crate::wayland::seat::FilterResult::Forward
+        });
+        harness.manager.grab_popup(popup.clone(), &seat, serial).unwrap();
+        assert_eq!(harness.focus().as_ref(), popup.get_surface());
+    }
+
+    #[test]
+    fn nested_grabs_are_dismissed_top_to_bottom() {
+        let (mut harness, keyboard) = Harness::new();
+        let (_toplevel, xdg_toplevel, wl_toplevel) = harness.toplevel();
+        keyboard.set_focus(Some(&wl_toplevel), SERIAL_COUNTER.next_serial());
+        let (_parent, xdg_parent, parent) = harness.popup(&xdg_toplevel, "parent");
+        let (_child, _, child) = harness.popup(&xdg_parent, "child");
+        let seat = harness.seat.clone();
+
+        let serial = harness.press(Some(&wl_toplevel));
+        harness.manager.grab_popup(parent.clone(), &seat, serial).unwrap();
+        harness.release();
+        assert_eq!(harness.focus().as_ref(), parent.get_surface());
+        // serials from before the grab are rejected
+        let stale = Serial::from(u32::from(serial).wrapping_sub(1));
+        assert!(matches!(
+            harness.manager.grab_popup(child.clone(), &seat, stale),
+            Err(PopupGrabError::InvalidGrab)
+        ));
+        // the grab of the child is triggered by input on the parent popup
+        let serial = harness.press(parent.get_surface());
+        harness.manager.grab_popup(child.clone(), &seat, serial).unwrap();
+        harness.release();
+        assert_eq!(harness.focus().as_ref(), child.get_surface());
+
+        // clicking outside of the client dismisses all popups, the topmost one first
+        harness.press(None);
+        harness.release();
+        harness.roundtrip();
+        assert_eq!(*harness.dismissed.borrow(), vec!["child", "parent"]);
+        assert_eq!(harness.focus(), Some(wl_toplevel));
+    }
+
+    #[test]
+    fn grab_of_another_root_replaces_the_grab() {
+        let (mut harness, keyboard) = Harness::new();
+        let (_first, xdg_first, wl_first) = harness.toplevel();
+        let (_second, xdg_second, wl_second) = harness.toplevel();
+        keyboard.set_focus(Some(&wl_first), SERIAL_COUNTER.next_serial());
+        let (_popup, _, first_popup) = harness.popup(&xdg_first, "first");
+        let (_popup, _, second_popup) = harness.popup(&xdg_second, "second");
+        let seat = harness.seat.clone();
+
+        let serial = harness.press(Some(&wl_first));
+        harness
+            .manager
+            .grab_popup(first_popup.clone(), &seat, serial)
+            .unwrap();
+        harness.release();
+        let serial = harness.press(Some(&wl_second));
+        harness
+            .manager
+            .grab_popup(second_popup.clone(), &seat, serial)
+            .unwrap();
+        harness.release();
+        harness.roundtrip();
+
+        // the previous popup is dismissed without taking the focus from the new one
+        assert_eq!(*harness.dismissed.borrow(), vec!["first"]);
+        assert_eq!(harness.focus().as_ref(), second_popup.get_surface());
+    }
+}
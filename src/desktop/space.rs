@@ -0,0 +1,862 @@
+//! A two-dimensional plane of windows and outputs
+//!
+//! See the [module-level documentation](super) for an overview.
+
+use slog::{o, trace, warn};
+
+use crate::{
+    backend::renderer::{
+        damage::OutputDamageTracker,
+        element::{crop::CropRenderElement, RenderElement},
+        Renderer, Transform,
+    },
+    utils::{Logical, Physical, Point, Rectangle, Region, Size},
+    wayland::output::Output,
+};
+
+/// An element that can be mapped onto a [`Space`]
+///
+/// All coordinates are relative to the location of the element, the origin of its
+/// surface tree.
+pub trait SpaceElement: PartialEq {
+    /// Geometry of the element
+    ///
+    /// This is the area the user perceives as the element, e.g. a window without the shadows
+    /// drawn by its client-side decorations. The location of the geometry is mapped onto the
+    /// location given to [`Space::map_element`].
+    fn geometry(&self) -> Rectangle<i32, Logical>;
+
+    /// Bounding box of everything the element draws
+    fn bbox(&self) -> Rectangle<i32, Logical>;
+
+    /// Areas of the element, that are guaranteed to be opaque
+    ///
+    /// Elements below these areas are not visible, see [`Space::visible_elements_for_output`].
+    fn opaque_regions(&self) -> Vec<Rectangle<i32, Logical>> {
+        Vec::new()
+    }
+
+    /// Returns if the given point is part of the input region of the element
+    fn is_in_input_region(&self, point: &Point<f64, Logical>) -> bool;
+
+    /// Sets the activation state of the element, e.g. to draw focused window decorations
+    fn set_activate(&self, activated: bool);
+
+    /// The element became visible on the given output
+    fn output_enter(&self, output: &Output);
+
+    /// The element is no longer visible on the given output
+    fn output_leave(&self, output: &Output);
+
+    /// Called on [`Space::refresh`], to update any internal state of the element
+    fn refresh(&self) {}
+
+    /// Location the element requests to be moved to since the last [`Space::refresh`]
+    ///
+    /// Used e.g. by the interactive move and resize of [`Window`](super::Window)s. The element is moved
+    /// on refresh, keeping its position in the stacking order.
+    fn requested_location(&self) -> Option<Point<i32, Logical>> {
+        None
+    }
+}
+
+/// An element that can be turned into [`RenderElement`]s of a given [`Renderer`]
+pub trait AsRenderElements<R: Renderer> {
+    /// Type of the created render elements
+    type RenderElement: RenderElement<R>;
+
+    /// Creates the render elements of this element located at `location` on an output
+    /// with the given scale.
+    ///
+    /// The elements are expected to be ordered from front to back. Implementations may use
+    /// the renderer to import any required buffers.
+    fn render_elements(
+        &self,
+        renderer: &mut R,
+        location: Point<i32, Physical>,
+        scale: f64,
+        log: &slog::Logger,
+    ) -> Vec<Self::RenderElement>;
+}
+
+/// Errors thrown by [`Space::render_output`]
+#[derive(Debug, thiserror::Error)]
+pub enum RenderError<E: std::error::Error> {
+    /// The renderer failed to render the output
+    #[error(transparent)]
+    Rendering(E),
+    /// The output is not mapped onto this space
+    #[error("The output is not mapped onto this space")]
+    UnmappedOutput,
+    /// The output has no current mode set
+    #[error("The output has no current mode")]
+    OutputNoMode,
+}
+
+/// Result of [`Space::render_output`]
+#[derive(Debug)]
+pub struct RenderOutputResult<'a, E> {
+    /// Damage of the rendered frame, `None` if nothing changed and rendering was skipped
+    pub damage: Option<Vec<Rectangle<i32, Physical>>>,
+    /// Elements presented on the output from bottom to top, see [`Space::visible_elements_for_output`]
+    pub elements: Vec<&'a E>,
+}
+
+/// Logical size of an output, taking its current mode, transformation and scale into account
+pub(super) fn output_size(output: &Output) -> Option<Size<i32, Logical>> {
+    let mode = output.current_mode()?;
+    let transform = Transform::from(output.current_transform());
+    Some(
+        transform
+            .transform_size(mode.size)
+            .to_logical(output.current_scale()),
+    )
+}
+
+#[derive(Debug)]
+struct MappedElement<E> {
+    element: E,
+    location: Point<i32, Logical>,
+    outputs: Vec<Output>,
+}
+
+impl<E: SpaceElement> MappedElement<E> {
+    fn render_location(&self) -> Point<i32, Logical> {
+        self.location - self.element.geometry().loc
+    }
+
+    fn bbox(&self) -> Rectangle<i32, Logical> {
+        let mut bbox = self.element.bbox();
+        bbox.loc += self.render_location();
+        bbox
+    }
+}
+
+#[derive(Debug)]
+struct MappedOutput {
+    output: Output,
+    location: Point<i32, Logical>,
+    /// The output this output mirrors, if any
+    source: Option<Output>,
+}
+
+/// A two-dimensional plane elements and outputs can be mapped onto
+///
+/// Elements are kept in stacking order, newly mapped or raised elements are placed
+/// on top of all other elements.
+#[derive(Debug)]
+pub struct Space<E> {
+    elements: Vec<MappedElement<E>>,
+    outputs: Vec<MappedOutput>,
+    logger: ::slog::Logger,
+}
+
+impl<E: SpaceElement> Space<E> {
+    /// Create a new, empty space
+    pub fn new<L>(log: L) -> Space<E>
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        Space {
+            elements: Vec::new(),
+            outputs: Vec::new(),
+            logger: crate::slog_or_fallback(log).new(o!("smithay_module" => "desktop_space")),
+        }
+    }
+
+    /// Map an element onto the space at the given location
+    ///
+    /// The location denotes the position of the [geometry](SpaceElement::geometry) of the element.
+    /// If the element is already mapped, it is moved to the new location.
+    /// In any case, it is raised on top of all other elements.
+    ///
+    /// If `activate` is set, the element is activated and all other elements are deactivated.
+    pub fn map_element(&mut self, element: E, location: impl Into<Point<i32, Logical>>, activate: bool) {
+        let location = location.into();
+        let mapped = match self.elements.iter().position(|e| e.element == element) {
+            Some(idx) => {
+                let mut mapped = self.elements.remove(idx);
+                mapped.location = location;
+                mapped
+            }
+            None => {
+                trace!(self.logger, "Mapping new element at {:?}", location);
+                MappedElement {
+                    element,
+                    location,
+                    outputs: Vec::new(),
+                }
+            }
+        };
+        self.elements.push(mapped);
+        if activate {
+            self.activate_top();
+        }
+    }
+
+    /// Raise a mapped element on top of all other elements
+    ///
+    /// If `activate` is set, the element is activated and all other elements are deactivated.
+    pub fn raise_element(&mut self, element: &E, activate: bool) {
+        if let Some(idx) = self.elements.iter().position(|e| &e.element == element) {
+            let mapped = self.elements.remove(idx);
+            self.elements.push(mapped);
+            if activate {
+                self.activate_top();
+            }
+        }
+    }
+
+    fn activate_top(&self) {
+        if let Some((top, others)) = self.elements.split_last() {
+            for mapped in others {
+                mapped.element.set_activate(false);
+            }
+            top.element.set_activate(true);
+        }
+    }
+
+    /// Unmap an element from the space
+    ///
+    /// The element leaves all outputs it was visible on.
+    pub fn unmap_element(&mut self, element: &E) {
+        if let Some(idx) = self.elements.iter().position(|e| &e.element == element) {
+            trace!(self.logger, "Unmapping element");
+            let mapped = self.elements.remove(idx);
+            for output in &mapped.outputs {
+                mapped.element.output_leave(output);
+            }
+        }
+    }
+
+    /// Iterate over all mapped elements from bottom to top
+    pub fn elements(&self) -> impl DoubleEndedIterator<Item = &E> {
+        self.elements.iter().map(|mapped| &mapped.element)
+    }
+
+    /// Iterate over all elements, that are visible on the given output, from bottom to top
+    ///
+    /// The visibility of the elements is updated by [`Space::refresh`].
+    pub fn elements_for_output<'a>(&'a self, output: &'a Output) -> impl DoubleEndedIterator<Item = &'a E> {
+        self.elements
+            .iter()
+            .filter(move |mapped| mapped.outputs.contains(output))
+            .map(|mapped| &mapped.element)
+    }
+
+    /// Returns the elements presented on the given output from bottom to top
+    ///
+    /// In contrast to [`Space::elements_for_output`] elements completely covered by the
+    /// [opaque regions](SpaceElement::opaque_regions) of the elements above them are left out.
+    /// Frame callbacks should only be sent to these elements, to keep occluded clients from
+    /// rendering.
+    /// Returns an empty list if the output is not mapped or has no current mode.
+    pub fn visible_elements_for_output(&self, output: &Output) -> Vec<&E> {
+        let geometry = match self.output_geometry(output) {
+            Some(geometry) => geometry,
+            None => return Vec::new(),
+        };
+        let mut opaque = Region::<i32, Logical>::new();
+        let mut elements = Vec::new();
+        for mapped in self.elements.iter().rev() {
+            let mut visible = match mapped.bbox().intersection(geometry) {
+                Some(bbox) => Region::from_rects(Some(bbox)),
+                None => continue,
+            };
+            for rect in opaque.rects() {
+                visible.subtract(*rect);
+            }
+            if !visible.is_empty() {
+                elements.push(&mapped.element);
+            }
+            for mut rect in mapped.element.opaque_regions() {
+                rect.loc += mapped.render_location();
+                opaque.add(rect);
+            }
+        }
+        elements.reverse();
+        elements
+    }
+
+    /// Returns the location of the geometry of a mapped element
+    pub fn element_location(&self, element: &E) -> Option<Point<i32, Logical>> {
+        self.mapped_element(element).map(|mapped| mapped.location)
+    }
+
+    /// Returns the geometry of a mapped element in the coordinate space of this space
+    pub fn element_geometry(&self, element: &E) -> Option<Rectangle<i32, Logical>> {
+        self.mapped_element(element)
+            .map(|mapped| Rectangle::from_loc_and_size(mapped.location, mapped.element.geometry().size))
+    }
+
+    /// Returns the bounding box of a mapped element in the coordinate space of this space
+    pub fn element_bbox(&self, element: &E) -> Option<Rectangle<i32, Logical>> {
+        self.mapped_element(element).map(MappedElement::bbox)
+    }
+
+    fn mapped_element(&self, element: &E) -> Option<&MappedElement<E>> {
+        self.elements.iter().find(|mapped| &mapped.element == element)
+    }
+
+    /// Find the topmost element accepting input at the given point
+    ///
+    /// Returns the element and the location of its origin, to allow converting the point
+    /// into the coordinate space of the element.
+    pub fn element_under(&self, point: impl Into<Point<f64, Logical>>) -> Option<(&E, Point<i32, Logical>)> {
+        let point = point.into();
+        self.elements.iter().rev().find_map(|mapped| {
+            let render_location = mapped.render_location();
+            if mapped.bbox().to_f64().contains(point)
+                && mapped
+                    .element
+                    .is_in_input_region(&(point - render_location.to_f64()))
+            {
+                Some((&mapped.element, render_location))
+            } else {
+                None
+            }
+        })
+    }
+
+    /// Map an output onto the space at the given location
+    ///
+    /// The location is also advertised to the clients of the output and the outputs mirroring it.
+    /// If the output is already mapped, it is moved to the new location, an output mirroring
+    /// another one stops doing so.
+    pub fn map_output(&mut self, output: &Output, location: impl Into<Point<i32, Logical>>) {
+        let location = location.into();
+        output.change_current_state(None, None, None, Some(location));
+        match self.outputs.iter_mut().find(|mapped| &mapped.output == output) {
+            Some(mapped) => {
+                mapped.location = location;
+                mapped.source = None;
+            }
+            None => {
+                trace!(self.logger, "Mapping output {} at {:?}", output.name(), location);
+                self.outputs.push(MappedOutput {
+                    output: output.clone(),
+                    location,
+                    source: None,
+                });
+            }
+        }
+        for mapped in &mut self.outputs {
+            if mapped.source.as_ref() == Some(output) {
+                mapped.location = location;
+                mapped
+                    .output
+                    .change_current_state(None, None, None, Some(location));
+            }
+        }
+    }
+
+    /// Map an output as a mirror of another mapped output
+    ///
+    /// The mirror shows the same area of the space as `source`, its geometry is the one of
+    /// `source` and elements visible on `source` are visible on the mirror as well, receiving
+    /// [enter](SpaceElement::output_enter) notifications for both outputs. When the mode or scale
+    /// of the outputs differ, the area is rendered with the largest scale fitting the mode of the
+    /// mirror, centered on it, see [`Space::output_scale`].
+    ///
+    /// As the elements are part of [`Space::visible_elements_for_output`] of both outputs, their
+    /// frame callbacks are sent for the output they were presented on. Use the throttling of
+    /// [`send_frames_surface_tree`](super::utils::send_frames_surface_tree) to keep clients from
+    /// rendering once per output.
+    ///
+    /// If `source` mirrors another output itself, that output is mirrored instead. Mirroring an
+    /// unmapped output or the output itself is ignored. Use [`Space::map_output`] to stop mirroring.
+    pub fn mirror_output(&mut self, output: &Output, source: &Output) {
+        let source = match self.outputs.iter().find(|mapped| &mapped.output == source) {
+            Some(mapped) => mapped.source.clone().unwrap_or_else(|| source.clone()),
+            None => {
+                warn!(self.logger, "Cannot mirror unmapped output {}", source.name());
+                return;
+            }
+        };
+        if &source == output {
+            warn!(self.logger, "Output {} cannot mirror itself", output.name());
+            return;
+        }
+
+        let location = self.output_geometry(&source).map(|geometry| geometry.loc);
+        // outputs mirroring the new mirror mirror the source instead
+        self.map_output(output, location.unwrap_or_default());
+        trace!(
+            self.logger,
+            "Mirroring output {} on {}",
+            source.name(),
+            output.name()
+        );
+        for mapped in &mut self.outputs {
+            if &mapped.output == output || mapped.source.as_ref() == Some(output) {
+                mapped.source = Some(source.clone());
+            }
+        }
+    }
+
+    /// Returns the output a mapped output mirrors, if any
+    pub fn mirrored_output(&self, output: &Output) -> Option<&Output> {
+        self.outputs
+            .iter()
+            .find(|mapped| &mapped.output == output)?
+            .source
+            .as_ref()
+    }
+
+    /// Unmap an output from the space
+    ///
+    /// All elements visible on the output leave it. Outputs mirroring it are unmapped as well.
+    pub fn unmap_output(&mut self, output: &Output) {
+        trace!(self.logger, "Unmapping output {}", output.name());
+        let mirrors = self
+            .outputs
+            .iter()
+            .filter(|mapped| mapped.source.as_ref() == Some(output))
+            .map(|mapped| mapped.output.clone())
+            .collect::<Vec<_>>();
+        self.outputs.retain(|mapped| &mapped.output != output);
+        for mapped in &mut self.elements {
+            if let Some(idx) = mapped.outputs.iter().position(|o| o == output) {
+                mapped.outputs.remove(idx);
+                mapped.element.output_leave(output);
+            }
+        }
+        for mirror in mirrors {
+            self.unmap_output(&mirror);
+        }
+    }
+
+    /// Iterate over all mapped outputs
+    pub fn outputs(&self) -> impl Iterator<Item = &Output> {
+        self.outputs.iter().map(|mapped| &mapped.output)
+    }
+
+    /// Returns the area of a mapped output in the coordinate space of this space
+    ///
+    /// Takes the current mode, transformation and scale of the output into account.
+    /// Mirrors return the area of the output they mirror.
+    /// Returns `None` if the output is not mapped or has no current mode.
+    pub fn output_geometry(&self, output: &Output) -> Option<Rectangle<i32, Logical>> {
+        let mapped = self.outputs.iter().find(|mapped| &mapped.output == output)?;
+        match mapped.source {
+            Some(ref source) => self.output_geometry(source),
+            None => Some(Rectangle::from_loc_and_size(
+                mapped.location,
+                output_size(output)?,
+            )),
+        }
+    }
+
+    /// Returns the scale the elements of the space are rendered with onto a mapped output
+    ///
+    /// This is the scale of the output, or for mirrors the largest scale fitting the area of the
+    /// mirrored output into the mode of the mirror. An [`OutputDamageTracker`] rendering the
+    /// [elements of the output](Space::render_elements_for_output) has to use this scale.
+    /// Returns `None` if the output is not mapped or has no current mode.
+    pub fn output_scale(&self, output: &Output) -> Option<f64> {
+        self.output_render_area(output).map(|(_, scale, _)| scale)
+    }
+
+    /// Area of the space shown on an output, the scale it is rendered with and its offset on the output
+    #[allow(clippy::type_complexity)]
+    fn output_render_area(
+        &self,
+        output: &Output,
+    ) -> Option<(Rectangle<i32, Logical>, f64, Point<i32, Physical>)> {
+        let geometry = self.output_geometry(output)?;
+        if self.mirrored_output(output).is_none() {
+            return Some((geometry, output.current_scale() as f64, (0, 0).into()));
+        }
+
+        let mode = output.current_mode()?;
+        let size = Transform::from(output.current_transform())
+            .transform_size(mode.size)
+            .to_f64();
+        let area = geometry.size.to_f64();
+        let scale = (size.w / area.w).min(size.h / area.h);
+        let offset =
+            Point::<f64, Physical>::from(((size.w - area.w * scale) / 2.0, (size.h - area.h * scale) / 2.0));
+        Some((geometry, scale, offset.to_i32_round()))
+    }
+
+    /// Iterate over all outputs containing the given point
+    ///
+    /// Includes the outputs mirroring the outputs containing the point.
+    pub fn output_under(&self, point: impl Into<Point<f64, Logical>>) -> impl Iterator<Item = &Output> {
+        let point = point.into();
+        self.outputs
+            .iter()
+            .map(|mapped| &mapped.output)
+            .filter(move |output| {
+                self.output_geometry(output)
+                    .map(|geometry| geometry.to_f64().contains(point))
+                    .unwrap_or(false)
+            })
+    }
+
+    /// Returns the outputs a mapped element is visible on
+    ///
+    /// The visibility of the elements is updated by [`Space::refresh`].
+    pub fn outputs_for_element(&self, element: &E) -> Vec<Output> {
+        self.mapped_element(element)
+            .map(|mapped| mapped.outputs.clone())
+            .unwrap_or_default()
+    }
+
+    /// Refresh the state of the space
+    ///
+    /// Moves the mapped elements to their [requested location](SpaceElement::requested_location),
+    /// updates which outputs they are visible on, sending the necessary
+    /// [enter](SpaceElement::output_enter) and [leave](SpaceElement::output_leave) notifications,
+    /// and [refreshes](SpaceElement::refresh) the elements.
+    ///
+    /// Needs to be called periodically, at best before every rendered frame.
+    pub fn refresh(&mut self) {
+        let output_geometries = self
+            .outputs
+            .iter()
+            .filter_map(|mapped| {
+                self.output_geometry(&mapped.output)
+                    .map(|geometry| (&mapped.output, geometry))
+            })
+            .collect::<Vec<_>>();
+
+        for mapped in &mut self.elements {
+            if let Some(location) = mapped.element.requested_location() {
+                mapped.location = location;
+            }
+            let bbox = mapped.bbox();
+            for (output, geometry) in &output_geometries {
+                let visible = bbox.overlaps(*geometry);
+                let entered = mapped.outputs.iter().position(|o| o == *output);
+                match (visible, entered) {
+                    (true, None) => {
+                        mapped.element.output_enter(output);
+                        mapped.outputs.push((*output).clone());
+                    }
+                    (false, Some(idx)) => {
+                        mapped.outputs.remove(idx);
+                        mapped.element.output_leave(output);
+                    }
+                    _ => {}
+                }
+            }
+            mapped.element.refresh();
+        }
+    }
+
+    /// Creates the render elements of all elements visible on the given output
+    ///
+    /// The elements are ordered from front to back and located relative to the output,
+    /// ready to be passed to an [`OutputDamageTracker`] using the [scale](Space::output_scale)
+    /// of the output. They are cropped to the area of the space shown on the output, which
+    /// keeps them out of the letterbox of mirrors.
+    /// Returns `None` if the output is not mapped or has no current mode.
+    #[allow(clippy::type_complexity)]
+    pub fn render_elements_for_output<R>(
+        &self,
+        renderer: &mut R,
+        output: &Output,
+    ) -> Option<Vec<CropRenderElement<<E as AsRenderElements<R>>::RenderElement>>>
+    where
+        R: Renderer,
+        E: AsRenderElements<R>,
+    {
+        profile_scope!("Space::render_elements_for_output");
+        let (geometry, scale, offset) = self.output_render_area(output)?;
+        let crop =
+            Rectangle::from_loc_and_size(offset, geometry.size.to_f64().to_physical(scale).to_i32_round());
+        let elements = self
+            .elements
+            .iter()
+            .rev()
+            .filter(|mapped| mapped.bbox().overlaps(geometry))
+            .flat_map(|mapped| {
+                let location = (mapped.render_location() - geometry.loc)
+                    .to_f64()
+                    .to_physical(scale)
+                    .to_i32_round()
+                    + offset;
+                mapped
+                    .element
+                    .render_elements(renderer, location, scale, &self.logger)
+                    .into_iter()
+                    .map(move |element| CropRenderElement::new(element, crop))
+            })
+            .collect();
+        Some(elements)
+    }
+
+    /// Render the space onto the given output
+    ///
+    /// The renderer needs to have the target of the output bound already, `age` denotes the
+    /// age of the bound buffer. The damage tracker is updated to the current mode of the output.
+    ///
+    /// Returns the damage of the rendered frame together with the elements presented on the output,
+    /// which should be sent their frame callbacks.
+    /// To render additional elements, e.g. a cursor, use [`Space::render_elements_for_output`]
+    /// and render the combined elements with the [`OutputDamageTracker`] directly.
+    pub fn render_output<'a, R>(
+        &'a self,
+        renderer: &mut R,
+        output: &Output,
+        damage_tracker: &mut OutputDamageTracker,
+        age: usize,
+        clear_color: [f32; 4],
+    ) -> Result<RenderOutputResult<'a, E>, RenderError<<R as Renderer>::Error>>
+    where
+        R: Renderer,
+        E: AsRenderElements<R>,
+    {
+        profile_scope!("Space::render_output");
+        if !self.outputs.iter().any(|mapped| &mapped.output == output) {
+            return Err(RenderError::UnmappedOutput);
+        }
+        let mode = output.current_mode().ok_or(RenderError::OutputNoMode)?;
+        let scale = self.output_scale(output).ok_or(RenderError::OutputNoMode)?;
+        damage_tracker.update_mode(mode.size, scale, output.current_transform().into());
+
+        let elements = self
+            .render_elements_for_output(renderer, output)
+            .ok_or(RenderError::OutputNoMode)?;
+        let damage = damage_tracker
+            .render_output(renderer, age, &elements, clear_color, &self.logger)
+            .map_err(RenderError::Rendering)?;
+        Ok(RenderOutputResult {
+            damage,
+            elements: self.visible_elements_for_output(output),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc};
+
+    use wayland_server::{protocol::wl_output::Subpixel, Display};
+
+    use super::{Space, SpaceElement};
+    use crate::{
+        utils::{Logical, Point, Rectangle},
+        wayland::output::{Mode, Output, PhysicalProperties},
+    };
+
+    #[derive(Debug, Default)]
+    struct TestState {
+        activated: bool,
+        outputs: Vec<String>,
+        requested_location: Option<Point<i32, Logical>>,
+        opaque: bool,
+    }
+
+    #[derive(Debug, Clone)]
+    struct TestElement {
+        bbox: Rectangle<i32, Logical>,
+        state: Rc<RefCell<TestState>>,
+    }
+
+    impl TestElement {
+        fn new(w: i32, h: i32) -> TestElement {
+            TestElement {
+                bbox: Rectangle::from_loc_and_size((0, 0), (w, h)),
+                state: Default::default(),
+            }
+        }
+    }
+
+    impl PartialEq for TestElement {
+        fn eq(&self, other: &Self) -> bool {
+            Rc::ptr_eq(&self.state, &other.state)
+        }
+    }
+
+    impl SpaceElement for TestElement {
+        fn geometry(&self) -> Rectangle<i32, Logical> {
+            self.bbox
+        }
+
+        fn bbox(&self) -> Rectangle<i32, Logical> {
+            self.bbox
+        }
+
+        fn opaque_regions(&self) -> Vec<Rectangle<i32, Logical>> {
+            if self.state.borrow().opaque {
+                vec![self.bbox]
+            } else {
+                Vec::new()
+            }
+        }
+
+        fn is_in_input_region(&self, point: &Point<f64, Logical>) -> bool {
+            self.bbox.to_f64().contains(*point)
+        }
+
+        fn set_activate(&self, activated: bool) {
+            self.state.borrow_mut().activated = activated;
+        }
+
+        fn output_enter(&self, output: &Output) {
+            self.state.borrow_mut().outputs.push(output.name());
+        }
+
+        fn output_leave(&self, output: &Output) {
+            self.state
+                .borrow_mut()
+                .outputs
+                .retain(|name| *name != output.name());
+        }
+
+        fn requested_location(&self) -> Option<Point<i32, Logical>> {
+            self.state.borrow_mut().requested_location.take()
+        }
+    }
+
+    fn output(display: &mut Display, name: &str) -> Output {
+        let (output, _global) = Output::new(
+            display,
+            name.into(),
+            PhysicalProperties {
+                size: (0, 0).into(),
+                subpixel: Subpixel::Unknown,
+                make: String::new(),
+                model: String::new(),
+            },
+            None,
+        );
+        output.change_current_state(
+            Some(Mode {
+                size: (800, 600).into(),
+                refresh: 60_000,
+            }),
+            None,
+            Some(2),
+            None,
+        );
+        output
+    }
+
+    #[test]
+    fn stacking_and_hit_testing() {
+        let mut space = Space::new(None);
+        let bottom = TestElement::new(100, 100);
+        let top = TestElement::new(100, 100);
+        space.map_element(bottom.clone(), (0, 0), true);
+        space.map_element(top.clone(), (50, 50), true);
+
+        assert!(!bottom.state.borrow().activated);
+        assert!(top.state.borrow().activated);
+        assert_eq!(space.element_under((75.0, 75.0)), Some((&top, (50, 50).into())));
+        assert_eq!(space.element_under((25.0, 25.0)), Some((&bottom, (0, 0).into())));
+        assert_eq!(space.element_under((200.0, 200.0)), None);
+
+        space.raise_element(&bottom, true);
+        assert!(bottom.state.borrow().activated);
+        assert_eq!(space.element_under((75.0, 75.0)), Some((&bottom, (0, 0).into())));
+    }
+
+    #[test]
+    fn output_enter_leave() {
+        let mut display = Display::new();
+        let left = output(&mut display, "left");
+        let right = output(&mut display, "right");
+        let mut space = Space::new(None);
+        space.map_output(&left, (0, 0));
+        space.map_output(&right, (400, 0));
+        assert_eq!(
+            space.output_geometry(&right),
+            Some(Rectangle::from_loc_and_size((400, 0), (400, 300)))
+        );
+
+        let element = TestElement::new(100, 100);
+        space.map_element(element.clone(), (350, 0), false);
+        space.refresh();
+        assert_eq!(element.state.borrow().outputs, vec!["left", "right"]);
+
+        space.map_element(element.clone(), (500, 0), false);
+        space.refresh();
+        assert_eq!(element.state.borrow().outputs, vec!["right"]);
+        assert_eq!(space.outputs_for_element(&element), vec![right.clone()]);
+
+        space.unmap_output(&right);
+        assert!(element.state.borrow().outputs.is_empty());
+    }
+
+    #[test]
+    fn requested_location_keeps_stacking() {
+        let mut space = Space::new(None);
+        let bottom = TestElement::new(100, 100);
+        let top = TestElement::new(100, 100);
+        space.map_element(bottom.clone(), (0, 0), false);
+        space.map_element(top.clone(), (200, 200), false);
+
+        bottom.state.borrow_mut().requested_location = Some((150, 150).into());
+        space.refresh();
+        assert_eq!(space.element_location(&bottom), Some((150, 150).into()));
+        assert_eq!(
+            space.element_under((225.0, 225.0)),
+            Some((&top, (200, 200).into()))
+        );
+    }
+
+    #[test]
+    fn mirrored_output() {
+        let mut display = Display::new();
+        let source = output(&mut display, "source");
+        let mirror = output(&mut display, "mirror");
+        mirror.change_current_state(
+            Some(Mode {
+                size: (1600, 900).into(),
+                refresh: 60_000,
+            }),
+            None,
+            Some(1),
+            None,
+        );
+        let mut space = Space::new(None);
+        space.map_output(&source, (0, 0));
+        space.mirror_output(&mirror, &source);
+        assert_eq!(space.mirrored_output(&mirror), Some(&source));
+        assert_eq!(space.output_geometry(&mirror), space.output_geometry(&source));
+        // 400x300 logical pixels fit 1600x900 physical pixels with a scale of 3
+        assert_eq!(space.output_scale(&source), Some(2.0));
+        assert_eq!(space.output_scale(&mirror), Some(3.0));
+
+        let element = TestElement::new(100, 100);
+        space.map_element(element.clone(), (0, 0), false);
+        space.refresh();
+        assert_eq!(element.state.borrow().outputs, vec!["source", "mirror"]);
+
+        space.map_output(&source, (1000, 0));
+        space.refresh();
+        assert!(element.state.borrow().outputs.is_empty());
+
+        space.unmap_output(&source);
+        assert_eq!(space.outputs().count(), 0);
+    }
+
+    #[test]
+    fn occluded_elements_are_not_visible() {
+        let mut display = Display::new();
+        let output = output(&mut display, "output");
+        let mut space = Space::new(None);
+        space.map_output(&output, (0, 0));
+        let bottom = TestElement::new(100, 100);
+        let opaque = TestElement::new(100, 100);
+        opaque.state.borrow_mut().opaque = true;
+        let top = TestElement::new(100, 100);
+        let offscreen = TestElement::new(100, 100);
+        space.map_element(bottom.clone(), (0, 0), false);
+        space.map_element(opaque.clone(), (0, 0), false);
+        space.map_element(top.clone(), (50, 50), false);
+        space.map_element(offscreen.clone(), (1000, 0), false);
+
+        assert_eq!(space.visible_elements_for_output(&output), vec![&opaque, &top]);
+        // a partially covered element is still visible
+        space.map_element(opaque.clone(), (10, 10), false);
+        space.raise_element(&top, false);
+        assert_eq!(
+            space.visible_elements_for_output(&output),
+            vec![&bottom, &opaque, &top]
+        );
+    }
+}
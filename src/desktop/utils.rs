@@ -0,0 +1,251 @@
+//! Helper functions to ease dealing with surface trees
+
+use std::{cell::RefCell, sync::Mutex, time::Duration};
+
+use wayland_server::protocol::wl_surface::WlSurface;
+
+use crate::{
+    backend::renderer::utils::RendererSurfaceState,
+    utils::{Logical, Point, Rectangle, Region},
+    wayland::{
+        compositor::{
+            with_surface_tree_downward, with_surface_tree_located_downward, RectangleKind, SurfaceAttributes,
+            SurfaceData, TraversalAction,
+        },
+        output::Output,
+    },
+};
+
+fn surface_size(states: &SurfaceData) -> Option<crate::utils::Size<i32, Logical>> {
+    states
+        .data_map
+        .get::<Mutex<RendererSurfaceState>>()
+        .and_then(|data| data.lock().unwrap().surface_size())
+}
+
+/// Returns the bounding box of a surface tree located at `location`
+///
+/// Requires [`on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler)
+/// to be used for the surfaces. Surfaces without a buffer (and their children) are not taken
+/// into account.
+pub fn bbox_from_surface_tree<P>(surface: &WlSurface, location: P) -> Rectangle<i32, Logical>
+where
+    P: Into<Point<i32, Logical>>,
+{
+    let location = location.into();
+    let mut bbox = Rectangle::from_loc_and_size(location, (0, 0));
+    with_surface_tree_located_downward(
+        surface,
+        location,
+        |_, states, location| match surface_size(states) {
+            Some(size) => {
+                bbox = bbox.merge(Rectangle::from_loc_and_size(location, size));
+                TraversalAction::DoChildren(())
+            }
+            None => TraversalAction::SkipChildren,
+        },
+        |_, _, _| true,
+    );
+    bbox
+}
+
+/// Returns the opaque region of a surface tree located at `location`
+///
+/// The opaque regions set by the clients are clipped to the size of their surfaces. Requires
+/// [`on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler)
+/// to be used for the surfaces. Surfaces without a buffer (and their children) are not taken
+/// into account.
+pub fn opaque_regions_from_surface_tree<P>(surface: &WlSurface, location: P) -> Vec<Rectangle<i32, Logical>>
+where
+    P: Into<Point<i32, Logical>>,
+{
+    let mut opaque = Region::new();
+    with_surface_tree_located_downward(
+        surface,
+        location,
+        |_, states, _| {
+            if surface_size(states).is_some() {
+                TraversalAction::DoChildren(())
+            } else {
+                TraversalAction::SkipChildren
+            }
+        },
+        |_, states, location| {
+            let size = match surface_size(states) {
+                Some(size) => size,
+                None => return true,
+            };
+            if let Some(region) = states
+                .cached_state
+                .current::<SurfaceAttributes>()
+                .opaque_region
+                .as_ref()
+            {
+                let mut surface_opaque = Region::new();
+                for (kind, rect) in &region.rects {
+                    match kind {
+                        RectangleKind::Add => surface_opaque.add(*rect),
+                        RectangleKind::Subtract => surface_opaque.subtract(*rect),
+                    }
+                }
+                surface_opaque.intersect(Rectangle::from_loc_and_size((0, 0), size));
+                for mut rect in surface_opaque.into_rects() {
+                    rect.loc += location;
+                    opaque.add(rect);
+                }
+            }
+            true
+        },
+    );
+    opaque.into_rects()
+}
+
+/// Returns the topmost surface of a surface tree located at `location`, which accepts input at
+/// the given point, and the location of that surface
+///
+/// The input region of the surfaces is taken into account. Requires
+/// [`on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler)
+/// to be used for the surfaces.
+pub fn under_from_surface_tree<P>(
+    surface: &WlSurface,
+    point: Point<f64, Logical>,
+    location: P,
+) -> Option<(WlSurface, Point<i32, Logical>)>
+where
+    P: Into<Point<i32, Logical>>,
+{
+    let mut found = None;
+    with_surface_tree_located_downward(
+        surface,
+        location,
+        |_, states, _| {
+            if surface_size(states).is_some() {
+                TraversalAction::DoChildren(())
+            } else {
+                TraversalAction::SkipChildren
+            }
+        },
+        |surface, states, location| {
+            let size = match surface_size(states) {
+                Some(size) => size,
+                None => return true,
+            };
+            let point = point - location.to_f64();
+            // the input region is always clipped to the surface
+            if !Rectangle::from_loc_and_size((0, 0), size)
+                .to_f64()
+                .contains(point)
+            {
+                return true;
+            }
+            let in_region = states
+                .cached_state
+                .current::<SurfaceAttributes>()
+                .input_region
+                .as_ref()
+                .map(|region| region.contains(point.to_i32_floor()))
+                .unwrap_or(true);
+            if in_region {
+                found = Some((surface.clone(), location));
+            }
+            !in_region
+        },
+    );
+    found
+}
+
+/// The output and time frame callbacks of a surface were last sent for
+#[derive(Debug, Default)]
+struct SurfaceFrameThrottlingState(Option<(String, Duration)>);
+
+impl SurfaceFrameThrottlingState {
+    /// Records sending the frame callbacks for `output` at `time`, if not throttled
+    fn update(&mut self, output: &str, time: Duration, throttle: Option<Duration>) -> bool {
+        let send = match (&self.0, throttle) {
+            (Some((last_output, last_time)), Some(throttle)) if last_output != output => {
+                time.saturating_sub(*last_time) >= throttle
+            }
+            _ => true,
+        };
+        if send {
+            self.0 = Some((output.to_owned(), time));
+        }
+        send
+    }
+}
+
+/// Sends the pending frame callbacks of a surface tree, that was presented on `output` at `time`
+///
+/// Should be called after the surface tree was presented on an output, e.g. for every element
+/// returned by [`Space::render_output`](super::Space::render_output) or
+/// [`Space::visible_elements_for_output`](super::Space::visible_elements_for_output).
+/// Surfaces without a buffer (and their children) do not receive any frame callbacks.
+///
+/// If `throttle` is set, a surface visible on multiple outputs (identified by their name) is
+/// only sent frame callbacks for another output, if none were sent for the last output during
+/// the `throttle` duration. This keeps clients from rendering once per output, while the
+/// surface still receives callbacks if the last output stops presenting it.
+pub fn send_frames_surface_tree<T>(surface: &WlSurface, output: &Output, time: T, throttle: Option<Duration>)
+where
+    T: Into<Duration>,
+{
+    let time = time.into();
+    let output_name = output.name();
+    with_surface_tree_downward(
+        surface,
+        (),
+        |_, states, _| {
+            if surface_size(states).is_some() {
+                TraversalAction::DoChildren(())
+            } else {
+                TraversalAction::SkipChildren
+            }
+        },
+        |_, states, _| {
+            if surface_size(states).is_none() {
+                return;
+            }
+            states
+                .data_map
+                .insert_if_missing(|| RefCell::new(SurfaceFrameThrottlingState::default()));
+            let send = states
+                .data_map
+                .get::<RefCell<SurfaceFrameThrottlingState>>()
+                .unwrap()
+                .borrow_mut()
+                .update(&output_name, time, throttle);
+            if send {
+                for callback in states
+                    .cached_state
+                    .current::<SurfaceAttributes>()
+                    .frame_callbacks
+                    .drain(..)
+                {
+                    callback.done(time.as_millis() as u32);
+                }
+            }
+        },
+        |_, _, _| true,
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SurfaceFrameThrottlingState;
+    use std::time::Duration;
+
+    #[test]
+    fn frame_throttling() {
+        let throttle = Some(Duration::from_millis(100));
+        let mut state = SurfaceFrameThrottlingState::default();
+        assert!(state.update("DP-1", Duration::from_millis(0), throttle));
+        assert!(state.update("DP-1", Duration::from_millis(16), throttle));
+        // another output presenting the surface shortly after is throttled
+        assert!(!state.update("HDMI-A-1", Duration::from_millis(20), throttle));
+        assert!(state.update("DP-1", Duration::from_millis(33), throttle));
+        // ...until the last output stops presenting it
+        assert!(state.update("HDMI-A-1", Duration::from_millis(140), throttle));
+        assert!(!state.update("DP-1", Duration::from_millis(150), throttle));
+        assert!(state.update("DP-1", Duration::from_millis(150), None));
+    }
+}
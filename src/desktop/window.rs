@@ -0,0 +1,359 @@
+//! Shell-agnostic windows
+//!
+//! See the [module-level documentation](super) for an overview.
+
+use std::{rc::Rc, sync::Mutex, time::Duration};
+
+use wayland_protocols::xdg_shell::server::xdg_toplevel;
+use wayland_server::protocol::{wl_shell_surface, wl_surface::WlSurface};
+
+use crate::{
+    backend::renderer::{
+        utils::{import_surface_tree, surface_tree_render_elements, WaylandSurfaceRenderElement},
+        ImportAll, Renderer,
+    },
+    utils::{user_data::UserDataMap, Logical, Physical, Point, Rectangle, Size},
+    wayland::{
+        compositor::{with_states, with_surface_tree_downward, TraversalAction},
+        output::Output,
+        shell::{
+            legacy::{ShellSurface, ShellSurfaceAttributes},
+            xdg::{SurfaceCachedState, ToplevelSurface, XdgToplevelSurfaceRoleAttributes},
+        },
+    },
+};
+
+use super::{
+    grabs::take_requested_location,
+    popup::PopupManager,
+    space::{AsRenderElements, SpaceElement},
+    utils::{
+        bbox_from_surface_tree, opaque_regions_from_surface_tree, send_frames_surface_tree,
+        under_from_surface_tree,
+    },
+};
+
+/// The shell surface backing a [`Window`]
+#[derive(Debug, Clone, PartialEq)]
+#[non_exhaustive]
+pub enum Kind {
+    /// An `xdg_toplevel` surface
+    Xdg(ToplevelSurface),
+    /// A `wl_shell_surface` surface
+    Wl(ShellSurface),
+}
+
+impl Kind {
+    /// Is the underlying shell surface still alive?
+    pub fn alive(&self) -> bool {
+        match self {
+            Kind::Xdg(toplevel) => toplevel.alive(),
+            Kind::Wl(shell_surface) => shell_surface.alive(),
+        }
+    }
+
+    /// Access the underlying `wl_surface`
+    ///
+    /// Returns `None` if the shell surface no longer exists.
+    pub fn get_surface(&self) -> Option<&WlSurface> {
+        match self {
+            Kind::Xdg(toplevel) => toplevel.get_surface(),
+            Kind::Wl(shell_surface) => shell_surface.get_surface(),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct WindowInner {
+    toplevel: Kind,
+    user_data: UserDataMap,
+}
+
+/// A toplevel window, independent of the shell it was created by
+///
+/// Cloning a window creates a new handle to the same window. Windows can be mapped
+/// onto a [`Space`](super::Space).
+///
+/// All coordinates are relative to the origin of the surface tree of the window.
+/// Requires [`on_commit_buffer_handler`](crate::backend::renderer::utils::on_commit_buffer_handler)
+/// to be used for the surfaces of the window.
+#[derive(Debug, Clone)]
+pub struct Window(Rc<WindowInner>);
+
+impl PartialEq for Window {
+    fn eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+}
+
+impl Eq for Window {}
+
+impl Window {
+    /// Create a new window from a shell surface
+    pub fn new(toplevel: Kind) -> Window {
+        Window(Rc::new(WindowInner {
+            toplevel,
+            user_data: UserDataMap::new(),
+        }))
+    }
+
+    /// Access the underlying shell surface
+    pub fn toplevel(&self) -> &Kind {
+        &self.0.toplevel
+    }
+
+    /// Is the underlying shell surface still alive?
+    pub fn alive(&self) -> bool {
+        self.0.toplevel.alive()
+    }
+
+    /// Returns the geometry of the window
+    ///
+    /// This is the geometry set by the client through `xdg_surface.set_window_geometry`,
+    /// falling back to the bounding box of the window if none was set.
+    pub fn geometry(&self) -> Rectangle<i32, Logical> {
+        let geometry = match &self.0.toplevel {
+            Kind::Xdg(toplevel) => toplevel.get_surface().and_then(|surface| {
+                with_states(surface, |states| {
+                    states.cached_state.current::<SurfaceCachedState>().geometry
+                })
+                .ok()
+                .flatten()
+            }),
+            Kind::Wl(_) => None,
+        };
+        geometry.unwrap_or_else(|| self.bbox())
+    }
+
+    /// Returns the bounding box of the surface tree of the window
+    pub fn bbox(&self) -> Rectangle<i32, Logical> {
+        self.0
+            .toplevel
+            .get_surface()
+            .map(|surface| bbox_from_surface_tree(surface, (0, 0)))
+            .unwrap_or_default()
+    }
+
+    /// Returns the bounding box of the surface tree of the window and all its popups
+    pub fn bbox_with_popups(&self) -> Rectangle<i32, Logical> {
+        self.0
+            .toplevel
+            .get_surface()
+            .map(|surface| PopupManager::bbox_with_popups(surface, (0, 0)))
+            .unwrap_or_default()
+    }
+
+    /// Returns the title of the window, if any was set by the client
+    pub fn title(&self) -> Option<String> {
+        let surface = self.0.toplevel.get_surface()?;
+        with_states(surface, |states| match &self.0.toplevel {
+            Kind::Xdg(_) => states
+                .data_map
+                .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                .and_then(|attributes| attributes.lock().unwrap().title.clone()),
+            Kind::Wl(_) => states
+                .data_map
+                .get::<Mutex<ShellSurfaceAttributes>>()
+                .map(|attributes| attributes.lock().unwrap().title.clone())
+                .filter(|title| !title.is_empty()),
+        })
+        .ok()
+        .flatten()
+    }
+
+    /// Returns the application id of the window, if any was set by the client
+    ///
+    /// For `wl_shell` surfaces, this is the class of the surface.
+    pub fn app_id(&self) -> Option<String> {
+        let surface = self.0.toplevel.get_surface()?;
+        with_states(surface, |states| match &self.0.toplevel {
+            Kind::Xdg(_) => states
+                .data_map
+                .get::<Mutex<XdgToplevelSurfaceRoleAttributes>>()
+                .and_then(|attributes| attributes.lock().unwrap().app_id.clone()),
+            Kind::Wl(_) => states
+                .data_map
+                .get::<Mutex<ShellSurfaceAttributes>>()
+                .map(|attributes| attributes.lock().unwrap().class.clone())
+                .filter(|class| !class.is_empty()),
+        })
+        .ok()
+        .flatten()
+    }
+
+    /// Sets the activation state of the window
+    ///
+    /// Returns `true` if the state changed. The new state is sent to the client on the next
+    /// [`configure`](Window::configure). `wl_shell` surfaces have no activation state.
+    pub fn set_activated(&self, activated: bool) -> bool {
+        match &self.0.toplevel {
+            Kind::Xdg(toplevel) => toplevel
+                .with_pending_state(|state| {
+                    if activated {
+                        state.states.set(xdg_toplevel::State::Activated)
+                    } else {
+                        state.states.unset(xdg_toplevel::State::Activated)
+                    }
+                })
+                .unwrap_or(false),
+            Kind::Wl(_) => false,
+        }
+    }
+
+    /// Returns the pending activation state of the window
+    pub fn is_activated(&self) -> bool {
+        match &self.0.toplevel {
+            Kind::Xdg(toplevel) => toplevel
+                .with_pending_state(|state| state.states.contains(xdg_toplevel::State::Activated))
+                .unwrap_or(false),
+            Kind::Wl(_) => false,
+        }
+    }
+
+    /// Requests the window to resize to the given size
+    ///
+    /// For `xdg_toplevel` surfaces, the size is sent on the next [`configure`](Window::configure),
+    /// `wl_shell` surfaces are configured immediately.
+    pub fn request_size(&self, size: Size<i32, Logical>) {
+        match &self.0.toplevel {
+            Kind::Xdg(toplevel) => {
+                let _ = toplevel.with_pending_state(|state| state.size = Some(size));
+            }
+            Kind::Wl(shell_surface) => {
+                shell_surface.send_configure(size, wl_shell_surface::Resize::None);
+            }
+        }
+    }
+
+    /// Sends the pending state of the window to the client, if it changed
+    pub fn configure(&self) {
+        if let Kind::Xdg(toplevel) = &self.0.toplevel {
+            toplevel.send_configure();
+        }
+    }
+
+    /// Asks the client to close the window
+    ///
+    /// `wl_shell` surfaces can not be asked to close.
+    pub fn send_close(&self) {
+        if let Kind::Xdg(toplevel) = &self.0.toplevel {
+            toplevel.send_close();
+        }
+    }
+
+    /// Finds the topmost surface of the window accepting input at the given point
+    ///
+    /// Returns the surface and its location relative to the window. The input regions of
+    /// the surfaces are taken into account, popups are placed above the window.
+    pub fn surface_under<P>(&self, point: P) -> Option<(WlSurface, Point<i32, Logical>)>
+    where
+        P: Into<Point<f64, Logical>>,
+    {
+        let point = point.into();
+        let surface = self.0.toplevel.get_surface()?;
+        PopupManager::surface_under(surface, point, (0, 0))
+            .or_else(|| under_from_surface_tree(surface, point, (0, 0)))
+    }
+
+    /// Sends the pending frame callbacks of the surfaces of the window and its popups, after it was
+    /// presented on `output` at `time`
+    ///
+    /// See [`send_frames_surface_tree`](super::utils::send_frames_surface_tree) for details on the throttling.
+    pub fn send_frame<T>(&self, output: &Output, time: T, throttle: Option<Duration>)
+    where
+        T: Into<Duration>,
+    {
+        let time = time.into();
+        if let Some(surface) = self.0.toplevel.get_surface() {
+            send_frames_surface_tree(surface, output, time, throttle);
+            for (popup, _) in PopupManager::popups_for_surface(surface) {
+                if let Some(surface) = popup.get_surface() {
+                    send_frames_surface_tree(surface, output, time, throttle);
+                }
+            }
+        }
+    }
+
+    /// Access the user data associated with this window
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.0.user_data
+    }
+
+    fn with_surfaces(&self, mut f: impl FnMut(&WlSurface)) {
+        if let Some(surface) = self.0.toplevel.get_surface() {
+            with_surface_tree_downward(
+                surface,
+                (),
+                |_, _, _| TraversalAction::DoChildren(()),
+                |surface, _, _| f(surface),
+                |_, _, _| true,
+            );
+        }
+    }
+}
+
+impl SpaceElement for Window {
+    fn geometry(&self) -> Rectangle<i32, Logical> {
+        Window::geometry(self)
+    }
+
+    fn bbox(&self) -> Rectangle<i32, Logical> {
+        self.bbox_with_popups()
+    }
+
+    fn opaque_regions(&self) -> Vec<Rectangle<i32, Logical>> {
+        match self.0.toplevel.get_surface() {
+            Some(surface) => opaque_regions_from_surface_tree(surface, (0, 0)),
+            None => Vec::new(),
+        }
+    }
+
+    fn is_in_input_region(&self, point: &Point<f64, Logical>) -> bool {
+        self.surface_under(*point).is_some()
+    }
+
+    fn set_activate(&self, activated: bool) {
+        if self.set_activated(activated) {
+            self.configure();
+        }
+    }
+
+    fn output_enter(&self, output: &Output) {
+        self.with_surfaces(|surface| output.enter(surface));
+    }
+
+    fn output_leave(&self, output: &Output) {
+        self.with_surfaces(|surface| output.leave(surface));
+    }
+
+    fn requested_location(&self) -> Option<Point<i32, Logical>> {
+        take_requested_location(self)
+    }
+}
+
+impl<R> AsRenderElements<R> for Window
+where
+    R: Renderer + ImportAll,
+    <R as Renderer>::TextureId: Send + 'static,
+{
+    type RenderElement = WaylandSurfaceRenderElement;
+
+    fn render_elements(
+        &self,
+        renderer: &mut R,
+        location: Point<i32, Physical>,
+        scale: f64,
+        log: &slog::Logger,
+    ) -> Vec<WaylandSurfaceRenderElement> {
+        match self.0.toplevel.get_surface() {
+            Some(surface) => {
+                let mut elements =
+                    PopupManager::render_elements_for_surface(renderer, surface, location, scale, log);
+                import_surface_tree(renderer, surface, log);
+                elements.extend(surface_tree_render_elements(surface, location));
+                elements
+            }
+            None => Vec::new(),
+        }
+    }
+}
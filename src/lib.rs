@@ -15,8 +15,9 @@
 //! the operating system, such as session management, interactions with the graphic stack and input
 //! processing. On the other hand, [`wayland`] contains helpers for interacting with wayland clients
 //! according to the wayland protocol. In addition, the [`xwayland`] module contains helpers for managing
-//! an XWayland instance if you want to support it. See the documentation of these respective modules for
-//! information about their usage.
+//! an XWayland instance if you want to support it. The [`desktop`] module provides higher-level helpers
+//! for managing windows and outputs built on top of these. See the documentation of these respective
+//! modules for information about their usage.
 //!
 //! ## General principles for using Smithay
 //!
@@ -46,18 +47,30 @@
 //! whether the `slog-stdlog` is enabled. If yes, the module will log to the global logger of the
 //! `log` crate. If not, the logs will discarded. This cargo feature is part of the default set of
 //! features of Smithay.
+//!
+//! With the `tracing` cargo feature, smithay additionally emits spans and events through the
+//! [`tracing`](https://docs.rs/tracing) crate: backend event dispatch, drm commits and `wl_surface`
+//! commits are wrapped in spans, while device hotplug, mode sets and protocol errors posted to clients
+//! are reported as events. Install a `tracing` subscriber in your compositor to collect them.
 
 #[doc(hidden)]
 pub extern crate nix;
 
-pub mod backend;
+#[macro_use]
 pub mod utils;
+
+pub mod backend;
+#[cfg(feature = "desktop")]
+pub mod desktop;
 #[cfg(feature = "wayland_frontend")]
 pub mod wayland;
 
 #[cfg(feature = "xwayland")]
 pub mod xwayland;
 
+#[cfg(feature = "testing")]
+pub mod testing;
+
 pub mod reexports;
 
 #[cfg(feature = "slog-stdlog")]
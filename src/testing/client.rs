@@ -0,0 +1,421 @@
+//! In-process wayland clients for tests
+
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    fs::File,
+    io,
+    os::unix::{
+        fs::FileExt,
+        io::{AsRawFd, IntoRawFd},
+        net::UnixStream,
+    },
+    rc::Rc,
+};
+
+use slog::{o, warn};
+use wayland_client::{
+    protocol::{
+        wl_buffer::{self, WlBuffer},
+        wl_compositor::WlCompositor,
+        wl_display::WlDisplay,
+        wl_keyboard, wl_pointer,
+        wl_seat::{self, WlSeat},
+        wl_shm::{self, WlShm},
+        wl_surface::WlSurface,
+        wl_touch,
+    },
+    Attached, ConnectError, EventQueue, GlobalError, GlobalManager, Main, ProtocolError,
+};
+
+use crate::utils::{Buffer, Size};
+
+/// A wayland client living in the same process as the compositor
+///
+/// The client is connected to the compositor through a socket pair. It never blocks: its
+/// requests are sent with [`TestClient::flush`] and its events are processed with
+/// [`TestClient::dispatch_pending`], usually both through [`TestServer::roundtrip`](super::TestServer::roundtrip).
+///
+/// Events are dispatched with `()` as dispatch data.
+pub struct TestClient {
+    display: wayland_client::Display,
+    attached: Attached<WlDisplay>,
+    event_queue: EventQueue,
+    globals: GlobalManager,
+    client: wayland_server::Client,
+    compositor: RefCell<Option<Main<WlCompositor>>>,
+    shm: RefCell<Option<Main<WlShm>>>,
+    log: ::slog::Logger,
+}
+
+impl fmt::Debug for TestClient {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestClient")
+            .field("display", &self.attached)
+            .field("client", &self.client)
+            .finish_non_exhaustive()
+    }
+}
+
+impl TestClient {
+    /// Connects a new client to the given display
+    ///
+    /// `data` is the dispatch data of the compositor, passed to the handlers of the client creation.
+    /// The globals of the compositor are only known to the client after a roundtrip.
+    pub fn connect<T, L>(
+        display: &mut wayland_server::Display,
+        data: &mut T,
+        logger: L,
+    ) -> Result<TestClient, TestClientError>
+    where
+        T: std::any::Any,
+        L: Into<Option<::slog::Logger>>,
+    {
+        let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "testing_client"));
+
+        let (server_end, client_end) = UnixStream::pair()?;
+        client_end.set_nonblocking(true)?;
+        // Safety: both ends of the socket pair are connected unix sockets, whose ownership is handed over
+        let client = unsafe { display.create_client(server_end.into_raw_fd(), data) };
+        let client_display = unsafe { wayland_client::Display::from_fd(client_end.into_raw_fd()) }?;
+
+        let event_queue = client_display.create_event_queue();
+        let attached = (*client_display).clone().attach(event_queue.token());
+        let globals = GlobalManager::new(&attached);
+
+        Ok(TestClient {
+            display: client_display,
+            attached,
+            event_queue,
+            globals,
+            client,
+            compositor: RefCell::new(None),
+            shm: RefCell::new(None),
+            log,
+        })
+    }
+
+    /// The server-side handle of this client
+    pub fn client(&self) -> &wayland_server::Client {
+        &self.client
+    }
+
+    /// The `wl_display` of this client, attached to its event queue
+    pub fn display(&self) -> &Attached<WlDisplay> {
+        &self.attached
+    }
+
+    /// The globals advertised to this client
+    pub fn globals(&self) -> &GlobalManager {
+        &self.globals
+    }
+
+    /// Returns the protocol error this client was killed with, if any
+    pub fn protocol_error(&self) -> Option<ProtocolError> {
+        self.display.protocol_error()
+    }
+
+    /// Sends the pending requests of this client to the compositor
+    pub fn flush(&self) -> io::Result<()> {
+        match self.display.flush() {
+            Err(err) if err.kind() == io::ErrorKind::WouldBlock => Ok(()),
+            result => result,
+        }
+    }
+
+    /// Reads and processes the events sent to this client so far, without blocking
+    ///
+    /// Returns the number of processed events.
+    pub fn dispatch_pending(&mut self) -> io::Result<u32> {
+        if let Some(guard) = self.event_queue.prepare_read() {
+            if let Err(err) = guard.read_events() {
+                if err.kind() != io::ErrorKind::WouldBlock {
+                    return Err(err);
+                }
+            }
+        }
+        let result = self.event_queue.dispatch_pending(&mut (), |_, _, _| {});
+        if let (Err(_), Some(protocol_error)) = (&result, self.display.protocol_error()) {
+            warn!(self.log, "Test client was killed: {}", protocol_error);
+        }
+        result
+    }
+
+    /// Sends a `wl_display.sync` request, whose callback sets the returned flag
+    pub(super) fn sync(&self) -> Rc<Cell<bool>> {
+        let done = Rc::new(Cell::new(false));
+        let done_clone = done.clone();
+        self.attached
+            .sync()
+            .quick_assign(move |_, _, _| done_clone.set(true));
+        done
+    }
+
+    /// Creates a new surface through the `wl_compositor` global
+    pub fn create_surface(&self) -> Result<TestSurface, GlobalError> {
+        let mut compositor = self.compositor.borrow_mut();
+        if compositor.is_none() {
+            *compositor = Some(self.globals.instantiate_range::<WlCompositor>(1, 4)?);
+        }
+        let surface = compositor.as_ref().unwrap().create_surface();
+        surface.quick_assign(|_, _, _| {});
+        Ok(TestSurface {
+            surface,
+            frames_done: Rc::new(Cell::new(0)),
+        })
+    }
+
+    /// Creates a new `wl_shm` buffer of the given size, filled with a single color
+    ///
+    /// The color is given as premultiplied RGBA value.
+    pub fn create_shm_buffer(
+        &self,
+        size: impl Into<Size<i32, Buffer>>,
+        color: [u8; 4],
+    ) -> Result<TestBuffer, TestClientError> {
+        let mut shm = self.shm.borrow_mut();
+        if shm.is_none() {
+            let global = self.globals.instantiate_exact::<WlShm>(1)?;
+            global.quick_assign(|_, _, _| {});
+            *shm = Some(global);
+        }
+        let shm = shm.as_ref().unwrap();
+
+        let size = size.into();
+        let stride = size.w * 4;
+        let len = stride * size.h;
+        let file = tempfile::tempfile()?;
+        let [r, g, b, a] = color;
+        // Argb8888 is little-endian
+        let data = [b, g, r, a].repeat((size.w * size.h) as usize);
+        file.write_all_at(&data, 0)?;
+
+        let pool = shm.create_pool(file.as_raw_fd(), len);
+        let buffer = pool.create_buffer(0, size.w, size.h, stride, wl_shm::Format::Argb8888);
+        // the buffer keeps the memory of the pool alive
+        pool.destroy();
+
+        let released = Rc::new(Cell::new(true));
+        let released_clone = released.clone();
+        buffer.quick_assign(move |_, event, _| {
+            if let wl_buffer::Event::Release = event {
+                released_clone.set(true);
+            }
+        });
+
+        Ok(TestBuffer {
+            buffer,
+            size,
+            released,
+            _file: file,
+        })
+    }
+
+    /// Binds the `wl_seat` global and records the input events sent to it
+    pub fn seat(&self) -> Result<TestSeat, GlobalError> {
+        let seat = self.globals.instantiate_range::<WlSeat>(1, 7)?;
+        let events = Rc::new(RefCell::new(Vec::new()));
+        let events_clone = events.clone();
+        let mut devices = TestSeatDevices::default();
+        seat.quick_assign(move |seat, event, _| {
+            if let wl_seat::Event::Capabilities { capabilities } = event {
+                devices.update(&seat, capabilities, &events_clone);
+            }
+        });
+        Ok(TestSeat { seat, events })
+    }
+}
+
+/// Error of the [`TestClient`]
+#[derive(thiserror::Error, Debug)]
+pub enum TestClientError {
+    /// The client could not connect to the display
+    #[error("Failed to connect to the display: {0}")]
+    Connect(#[from] ConnectError),
+    /// A required global is missing
+    #[error("Failed to bind a global: {0}")]
+    Global(#[from] GlobalError),
+    /// The connection failed or shared memory could not be allocated
+    #[error("I/O error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// A surface of a [`TestClient`]
+#[derive(Debug)]
+pub struct TestSurface {
+    surface: Main<WlSurface>,
+    frames_done: Rc<Cell<u32>>,
+}
+
+impl TestSurface {
+    /// The underlying `wl_surface`
+    pub fn wl_surface(&self) -> &Main<WlSurface> {
+        &self.surface
+    }
+
+    /// Attaches the given buffer and damages all of it
+    ///
+    /// The buffer is marked as used, until the compositor releases it.
+    pub fn attach(&self, buffer: Option<&TestBuffer>) {
+        match buffer {
+            Some(buffer) => {
+                buffer.released.set(false);
+                self.surface.attach(Some(&buffer.buffer), 0, 0);
+                self.surface.damage(0, 0, buffer.size.w, buffer.size.h);
+            }
+            None => self.surface.attach(None, 0, 0),
+        }
+    }
+
+    /// Requests a frame callback for the next commit, see [`TestSurface::frames_done`]
+    pub fn frame(&self) {
+        let frames_done = self.frames_done.clone();
+        self.surface
+            .frame()
+            .quick_assign(move |_, _, _| frames_done.set(frames_done.get() + 1));
+    }
+
+    /// Commits the pending state of the surface
+    pub fn commit(&self) {
+        self.surface.commit();
+    }
+
+    /// The number of frame callbacks, which were requested with [`TestSurface::frame`] and are done
+    pub fn frames_done(&self) -> u32 {
+        self.frames_done.get()
+    }
+}
+
+impl Drop for TestSurface {
+    fn drop(&mut self) {
+        self.surface.destroy();
+    }
+}
+
+/// A `wl_shm` buffer of a [`TestClient`]
+#[derive(Debug)]
+pub struct TestBuffer {
+    buffer: Main<WlBuffer>,
+    size: Size<i32, Buffer>,
+    released: Rc<Cell<bool>>,
+    _file: File,
+}
+
+impl TestBuffer {
+    /// The underlying `wl_buffer`
+    pub fn wl_buffer(&self) -> &Main<WlBuffer> {
+        &self.buffer
+    }
+
+    /// Size of the buffer in pixels
+    pub fn size(&self) -> Size<i32, Buffer> {
+        self.size
+    }
+
+    /// Returns if the compositor released the buffer since it was last attached
+    pub fn is_released(&self) -> bool {
+        self.released.get()
+    }
+}
+
+impl Drop for TestBuffer {
+    fn drop(&mut self) {
+        self.buffer.destroy();
+    }
+}
+
+/// An input event received by a [`TestSeat`]
+#[derive(Debug)]
+pub enum TestInputEvent {
+    /// An event of the `wl_pointer` of the seat
+    Pointer(wl_pointer::Event),
+    /// An event of the `wl_keyboard` of the seat
+    ///
+    /// The file descriptor of [`wl_keyboard::Event::Keymap`] is already closed.
+    Keyboard(wl_keyboard::Event),
+    /// An event of the `wl_touch` of the seat
+    Touch(wl_touch::Event),
+}
+
+/// The `wl_seat` of a [`TestClient`]
+///
+/// The devices of the seat are created, when the compositor advertises them.
+#[derive(Debug)]
+pub struct TestSeat {
+    seat: Main<WlSeat>,
+    events: Rc<RefCell<Vec<TestInputEvent>>>,
+}
+
+impl TestSeat {
+    /// The underlying `wl_seat`
+    pub fn wl_seat(&self) -> &Main<WlSeat> {
+        &self.seat
+    }
+
+    /// Takes the input events received so far
+    pub fn take_events(&self) -> Vec<TestInputEvent> {
+        std::mem::take(&mut *self.events.borrow_mut())
+    }
+}
+
+#[derive(Default)]
+struct TestSeatDevices {
+    pointer: Option<Main<wl_pointer::WlPointer>>,
+    keyboard: Option<Main<wl_keyboard::WlKeyboard>>,
+    touch: Option<Main<wl_touch::WlTouch>>,
+}
+
+impl TestSeatDevices {
+    fn update(
+        &mut self,
+        seat: &Main<WlSeat>,
+        capabilities: wl_seat::Capability,
+        events: &Rc<RefCell<Vec<TestInputEvent>>>,
+    ) {
+        if capabilities.contains(wl_seat::Capability::Pointer) {
+            if self.pointer.is_none() {
+                let events = events.clone();
+                let pointer = seat.get_pointer();
+                pointer.quick_assign(move |_, event, _| {
+                    events.borrow_mut().push(TestInputEvent::Pointer(event))
+                });
+                self.pointer = Some(pointer);
+            }
+        } else if let Some(pointer) = self.pointer.take() {
+            if pointer.as_ref().version() >= 3 {
+                pointer.release();
+            }
+        }
+
+        if capabilities.contains(wl_seat::Capability::Keyboard) {
+            if self.keyboard.is_none() {
+                let events = events.clone();
+                let keyboard = seat.get_keyboard();
+                keyboard.quick_assign(move |_, event, _| {
+                    if let wl_keyboard::Event::Keymap { fd, .. } = event {
+                        let _ = nix::unistd::close(fd);
+                    }
+                    events.borrow_mut().push(TestInputEvent::Keyboard(event))
+                });
+                self.keyboard = Some(keyboard);
+            }
+        } else if let Some(keyboard) = self.keyboard.take() {
+            if keyboard.as_ref().version() >= 3 {
+                keyboard.release();
+            }
+        }
+
+        if capabilities.contains(wl_seat::Capability::Touch) {
+            if self.touch.is_none() {
+                let events = events.clone();
+                let touch = seat.get_touch();
+                touch.quick_assign(move |_, event, _| events.borrow_mut().push(TestInputEvent::Touch(event)));
+                self.touch = Some(touch);
+            }
+        } else if let Some(touch) = self.touch.take() {
+            if touch.as_ref().version() >= 3 {
+                touch.release();
+            }
+        }
+    }
+}
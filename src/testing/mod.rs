@@ -0,0 +1,430 @@
+//! Helpers for end-to-end tests of compositors
+//!
+//! This module combines the [headless backend](crate::backend::headless), a cpu-based
+//! [`SoftwareRenderer`] and in-process wayland clients, so compositors can be tested
+//! without any display hardware, e.g. in CI.
+//!
+//! - [`TestServer`] owns the wayland [`Display`] and an event loop with a [`HeadlessBackend`].
+//!   Its [`VirtualOutput`] is rendered using the [`SoftwareRenderer`] with [`TestServer::render`]
+//!   and the rendered frames are recorded as [`TestFrame`]s. Input is injected with the
+//!   [`VirtualInputHandle`] returned by [`TestServer::input`].
+//! - [`TestClient`] is a wayland client connected to the display through a socket pair. It can
+//!   create [`TestSurface`]s and [`TestBuffer`]s and records the input of its [`TestSeat`].
+//! - [`TestServer::roundtrip`] drives both sides until the compositor processed all requests
+//!   of a client and the client received all resulting events.
+//!
+//! Everything runs on the thread of the test, no request or event is processed unless the
+//! server or client is dispatched.
+//!
+//! This module is only available with the `testing` cargo feature.
+//!
+//! ## Example usage
+//!
+//! ```no_run
+//! use smithay::{
+//!     backend::headless::HeadlessEvent,
+//!     backend::renderer::utils::{import_surface_tree, on_commit_buffer_handler, surface_tree_render_elements},
+//!     testing::{TestClient, TestServer},
+//!     wayland::{compositor::compositor_init, shm::init_shm_global},
+//! };
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! struct State {
+//!     input_events: usize,
+//! }
+//!
+//! let mut server = TestServer::<State>::new((800, 600), None, |event, state: &mut State| {
+//!     if let HeadlessEvent::Input(_) = event {
+//!         state.input_events += 1;
+//!     }
+//! })?;
+//! compositor_init(server.display_mut(), |surface, _| on_commit_buffer_handler(&surface), None);
+//! init_shm_global(server.display_mut(), vec![], None);
+//!
+//! let mut state = State { input_events: 0 };
+//! let mut client = server.connect_client(&mut state)?;
+//!
+//! let surface = client.create_surface()?;
+//! let buffer = client.create_shm_buffer((100, 100), [255, 0, 0, 255])?;
+//! surface.attach(Some(&buffer));
+//! surface.commit();
+//! server.roundtrip(&mut client, &mut state)?;
+//!
+//! // render the surface, as your compositor would
+//! let wl_surface = server.wl_surface(&client, &surface).unwrap();
+//! let log = server.log().clone();
+//! import_surface_tree(server.renderer(), &wl_surface, &log);
+//! let elements = surface_tree_render_elements(&wl_surface, (0, 0).into());
+//! server.render(&elements)?;
+//! assert_eq!(server.last_frame().unwrap().pixel((50, 50)), [255, 0, 0, 255]);
+//! # Ok(())
+//! # }
+//! ```
+
+mod client;
+mod renderer;
+
+pub use self::client::{TestBuffer, TestClient, TestClientError, TestInputEvent, TestSeat, TestSurface};
+pub use self::renderer::{
+    SoftwareBuffer, SoftwareError, SoftwareFrame, SoftwareMapping, SoftwareRenderer, SoftwareTexture,
+};
+
+use std::{cell::RefCell, fmt, io, rc::Rc, time::Duration};
+
+use calloop::{EventLoop, LoopHandle};
+use slog::o;
+use wayland_server::{protocol::wl_surface::WlSurface, Display};
+
+use crate::{
+    backend::{
+        headless::{HeadlessBackend, HeadlessEvent, HeadlessFrame, VirtualInputHandle, VirtualOutput},
+        renderer::element::RenderElement,
+    },
+    utils::{Buffer, Physical, Point, Rectangle, Size},
+};
+
+/// How often [`TestServer::roundtrip`] dispatches both sides before giving up
+const MAX_ROUNDTRIP_ITERATIONS: usize = 64;
+
+/// Color the virtual output is cleared with
+const CLEAR_COLOR: [f32; 4] = [0.0, 0.0, 0.0, 1.0];
+
+/// A frame rendered to the [`VirtualOutput`] of a [`TestServer`]
+#[derive(Debug, Clone)]
+pub struct TestFrame {
+    /// Premultiplied RGBA pixels ordered row by row from the top-left corner
+    pub data: Vec<u8>,
+    /// Size of the frame in pixels
+    pub size: Size<i32, Buffer>,
+    /// The damaged regions compared to the previous frame
+    pub damage: Vec<Rectangle<i32, Physical>>,
+}
+
+impl TestFrame {
+    /// Returns the premultiplied RGBA value of the pixel at the given location
+    ///
+    /// Panics, if the location is out of bounds.
+    pub fn pixel(&self, location: impl Into<Point<i32, Buffer>>) -> [u8; 4] {
+        let location = location.into();
+        assert!(
+            Rectangle::from_loc_and_size((0, 0), self.size).contains(location),
+            "{:?} is out of bounds",
+            location
+        );
+        let offset = ((location.y * self.size.w + location.x) * 4) as usize;
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.data[offset..offset + 4]);
+        pixel
+    }
+}
+
+/// The compositor side of an end-to-end test, see the [module-level docs](self)
+///
+/// `D` is the state of the compositor, passed as dispatch data to the display and
+/// the event loop.
+pub struct TestServer<D: 'static> {
+    display: Display,
+    event_loop: EventLoop<'static, D>,
+    output: VirtualOutput,
+    input: VirtualInputHandle,
+    renderer: SoftwareRenderer,
+    frames: Rc<RefCell<Vec<TestFrame>>>,
+    log: ::slog::Logger,
+}
+
+impl<D: 'static> fmt::Debug for TestServer<D> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("TestServer")
+            .field("output", &self.output)
+            .field("input", &self.input)
+            .field("renderer", &self.renderer)
+            .field("frames", &self.frames.borrow().len())
+            .finish_non_exhaustive()
+    }
+}
+
+impl<D: 'static> TestServer<D> {
+    /// Creates a new test server with a virtual output of the given size
+    ///
+    /// The events of the [`HeadlessBackend`], i.e. injected input and the vblanks of the
+    /// virtual output, are passed to the given callback.
+    pub fn new<F, L>(
+        output_size: impl Into<Size<i32, Physical>>,
+        logger: L,
+        mut callback: F,
+    ) -> io::Result<Self>
+    where
+        F: FnMut(HeadlessEvent, &mut D) + 'static,
+        L: Into<Option<::slog::Logger>>,
+    {
+        let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "testing"));
+
+        let event_loop = EventLoop::try_new()?;
+        let mut backend = HeadlessBackend::new(log.clone())?;
+        let mut output = backend.create_output("test-1", output_size, 60_000);
+        let input = backend.input_handle();
+        event_loop
+            .handle()
+            .insert_source(backend, move |event, _, data| callback(event, data))
+            .map_err(|err| err.error)?;
+
+        let frames = Rc::new(RefCell::new(Vec::new()));
+        let frames_clone = frames.clone();
+        output.set_frame_callback(move |frame| {
+            if let HeadlessFrame::Memory {
+                data, size, damage, ..
+            } = frame
+            {
+                frames_clone.borrow_mut().push(TestFrame {
+                    data: data.to_vec(),
+                    size,
+                    damage: damage.to_vec(),
+                });
+            }
+        });
+
+        Ok(TestServer {
+            display: Display::new(),
+            event_loop,
+            output,
+            input,
+            renderer: SoftwareRenderer::new(log.clone()),
+            frames,
+            log,
+        })
+    }
+
+    /// The wayland display of the compositor, e.g. to initialize globals
+    pub fn display(&self) -> &Display {
+        &self.display
+    }
+
+    /// Mutable access to the wayland display of the compositor
+    pub fn display_mut(&mut self) -> &mut Display {
+        &mut self.display
+    }
+
+    /// A handle to the event loop, e.g. to insert additional event sources
+    pub fn handle(&self) -> LoopHandle<'static, D> {
+        self.event_loop.handle()
+    }
+
+    /// The virtual output rendered to by [`TestServer::render`]
+    pub fn output(&mut self) -> &mut VirtualOutput {
+        &mut self.output
+    }
+
+    /// The handle to inject input, emitted as [`HeadlessEvent::Input`] on the next dispatch
+    pub fn input(&self) -> &VirtualInputHandle {
+        &self.input
+    }
+
+    /// The renderer used by [`TestServer::render`], e.g. to import textures
+    pub fn renderer(&mut self) -> &mut SoftwareRenderer {
+        &mut self.renderer
+    }
+
+    /// The logger of the test server
+    pub fn log(&self) -> &::slog::Logger {
+        &self.log
+    }
+
+    /// Connects a new [`TestClient`] and waits until it received the globals of the compositor
+    pub fn connect_client(&mut self, data: &mut D) -> Result<TestClient, TestClientError> {
+        let mut client = TestClient::connect(&mut self.display, data, self.log.clone())?;
+        self.roundtrip(&mut client, data)?;
+        Ok(client)
+    }
+
+    /// Processes all pending requests of clients and events of the event loop once, without blocking
+    pub fn dispatch(&mut self, data: &mut D) -> io::Result<()> {
+        self.display.dispatch(Duration::ZERO, data)?;
+        self.event_loop.dispatch(Some(Duration::ZERO), data)?;
+        self.display.flush_clients(data);
+        Ok(())
+    }
+
+    /// Dispatches the server and the given client, until the server processed all requests
+    /// the client sent so far and the client received all resulting events
+    ///
+    /// Fails with [`io::ErrorKind::TimedOut`], if that did not happen after a generous number of
+    /// iterations, and with the error of the client, if it was killed.
+    pub fn roundtrip(&mut self, client: &mut TestClient, data: &mut D) -> io::Result<()> {
+        let done = client.sync();
+        for _ in 0..MAX_ROUNDTRIP_ITERATIONS {
+            client.flush()?;
+            self.dispatch(data)?;
+            client.dispatch_pending()?;
+            if done.get() {
+                return Ok(());
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "roundtrip did not complete",
+        ))
+    }
+
+    /// Returns the compositor side of a surface of the given client
+    pub fn wl_surface(&self, client: &TestClient, surface: &TestSurface) -> Option<WlSurface> {
+        client
+            .client()
+            .get_resource::<WlSurface>(surface.wl_surface().as_ref().id())
+    }
+
+    /// Renders the given elements to the virtual output, recording the frame if anything changed
+    ///
+    /// Returns the damage of the frame, see [`VirtualOutput::render_to_memory`].
+    pub fn render<E>(
+        &mut self,
+        elements: &[E],
+    ) -> Result<Option<Vec<Rectangle<i32, Physical>>>, SoftwareError>
+    where
+        E: RenderElement<SoftwareRenderer>,
+    {
+        self.output.render_to_memory::<_, SoftwareBuffer, _>(
+            &mut self.renderer,
+            elements,
+            CLEAR_COLOR,
+            &self.log,
+        )
+    }
+
+    /// The last frame rendered with [`TestServer::render`]
+    pub fn last_frame(&self) -> Option<TestFrame> {
+        self.frames.borrow().last().cloned()
+    }
+
+    /// Takes all frames rendered since the last call
+    pub fn take_frames(&mut self) -> Vec<TestFrame> {
+        std::mem::take(&mut *self.frames.borrow_mut())
+    }
+}
+
+/// Sets up a [`TestServer`] with a connected [`TestClient`] for the unit tests of smithay
+///
+/// The compositor and shm globals are initialized and the buffers of committed surfaces are
+/// tracked through `on_commit_buffer_handler`. `init` initializes further globals, before
+/// the client is connected.
+#[cfg(test)]
+pub(crate) fn test_setup<T>(init: impl FnOnce(&mut Display) -> T) -> (TestServer<()>, TestClient, T) {
+    let mut server = TestServer::new((64, 64), None, |_, _: &mut ()| {}).unwrap();
+    crate::wayland::compositor::compositor_init(
+        server.display_mut(),
+        |surface, _| crate::backend::renderer::utils::on_commit_buffer_handler(&surface),
+        None,
+    );
+    crate::wayland::shm::init_shm_global(server.display_mut(), vec![], None);
+    let data = init(server.display_mut());
+    let client = server.connect_client(&mut ()).unwrap();
+    (server, client, data)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{test_setup, HeadlessEvent, TestInputEvent, TestServer};
+    use crate::{
+        backend::{
+            input::ButtonState,
+            renderer::utils::{import_surface_tree, on_commit_buffer_handler, surface_tree_render_elements},
+        },
+        wayland::{
+            compositor::{compositor_init, with_states, SurfaceAttributes},
+            seat::Seat,
+            shm::init_shm_global,
+            SERIAL_COUNTER,
+        },
+    };
+    use wayland_client::protocol::wl_pointer;
+    use wayland_server::protocol::wl_surface::WlSurface;
+
+    #[derive(Default)]
+    struct State {
+        committed: Vec<WlSurface>,
+        input_events: usize,
+    }
+
+    #[test]
+    fn client_surface_is_rendered() {
+        let mut server = TestServer::<State>::new((64, 64), None, |event, state: &mut State| {
+            if let HeadlessEvent::Input(_) = event {
+                state.input_events += 1;
+            }
+        })
+        .unwrap();
+        compositor_init(
+            server.display_mut(),
+            |surface, mut ddata| {
+                on_commit_buffer_handler(&surface);
+                ddata.get::<State>().unwrap().committed.push(surface);
+            },
+            None,
+        );
+        init_shm_global(server.display_mut(), vec![], None);
+
+        let mut state = State::default();
+        let mut client = server.connect_client(&mut state).unwrap();
+        let surface = client.create_surface().unwrap();
+        let buffer = client.create_shm_buffer((32, 32), [0, 0, 255, 255]).unwrap();
+        surface.attach(Some(&buffer));
+        surface.frame();
+        surface.commit();
+        server.roundtrip(&mut client, &mut state).unwrap();
+
+        let wl_surface = server.wl_surface(&client, &surface).unwrap();
+        assert_eq!(state.committed, vec![wl_surface.clone()]);
+
+        let log = server.log().clone();
+        import_surface_tree(server.renderer(), &wl_surface, &log);
+        let elements = surface_tree_render_elements(&wl_surface, (16, 16).into());
+        server.render(&elements).unwrap();
+        let frame = server.last_frame().unwrap();
+        assert_eq!(frame.pixel((20, 20)), [0, 0, 255, 255]);
+        assert_eq!(frame.pixel((8, 8)), [0, 0, 0, 255]);
+
+        with_states(&wl_surface, |states| {
+            let mut attributes = states.cached_state.current::<SurfaceAttributes>();
+            for callback in attributes.frame_callbacks.drain(..) {
+                callback.done(0);
+            }
+        })
+        .unwrap();
+        server.roundtrip(&mut client, &mut state).unwrap();
+        assert_eq!(surface.frames_done(), 1);
+
+        server
+            .input()
+            .pointer_button(0x110, ButtonState::Pressed)
+            .unwrap();
+        server.dispatch(&mut state).unwrap();
+        assert_eq!(state.input_events, 1);
+    }
+
+    #[test]
+    fn client_seat_records_input() {
+        let (mut server, mut client, (_seat, pointer)) = test_setup(|display| {
+            let (mut seat, _) = Seat::new(display, "seat-0".into(), None);
+            let pointer = seat.add_pointer(|_| {});
+            (seat, pointer)
+        });
+        let surface = client.create_surface().unwrap();
+        let test_seat = client.seat().unwrap();
+        server.roundtrip(&mut client, &mut ()).unwrap();
+        // the pointer is created once the capabilities of the seat are received
+        server.roundtrip(&mut client, &mut ()).unwrap();
+
+        let wl_surface = server.wl_surface(&client, &surface).unwrap();
+        pointer.motion(
+            (8.0, 8.0).into(),
+            Some((wl_surface, (0, 0).into())),
+            SERIAL_COUNTER.next_serial(),
+            0,
+        );
+        server.roundtrip(&mut client, &mut ()).unwrap();
+
+        assert!(test_seat
+            .take_events()
+            .iter()
+            .any(|event| matches!(event, TestInputEvent::Pointer(wl_pointer::Event::Enter { .. }))));
+    }
+}
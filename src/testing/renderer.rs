@@ -0,0 +1,522 @@
+//! A software renderer for tests
+//!
+//! The [`SoftwareRenderer`] renders into cpu memory, so the rendering code of a compositor
+//! can be tested without any gpu. It favours simplicity over speed: textures are sampled
+//! with the nearest filter and dmabufs are not supported.
+
+use std::{
+    fmt,
+    sync::{Arc, Mutex},
+};
+
+use slog::o;
+use wayland_server::protocol::{wl_buffer, wl_shm};
+
+use crate::{
+    backend::{
+        allocator::{dmabuf::Dmabuf, fence::SyncFile, Fourcc},
+        renderer::{
+            Bind, ExportMem, Frame, ImportDma, ImportMem, ImportShm, Offscreen, Renderer, Texture,
+            TextureFilter, TextureMapping, Transform, Unbind, WaitFence,
+        },
+    },
+    utils::{Buffer, Physical, Point, Rectangle, Size},
+    wayland::shm::{self, BufferAccessError},
+};
+
+/// Error returned by the [`SoftwareRenderer`]
+#[derive(thiserror::Error, Debug)]
+pub enum SoftwareError {
+    /// No target is currently bound
+    #[error("No rendering target is currently bound")]
+    NoTargetBound,
+    /// The given buffer has an unsupported pixel format
+    #[error("Unsupported pixel format: {0:?}")]
+    UnsupportedPixelFormat(wl_shm::Format),
+    /// The given buffer was not accessible
+    #[error("Error accessing the buffer ({0:?})")]
+    BufferAccessError(BufferAccessError),
+    /// The requested region is out of bounds of the source
+    #[error("The requested region {0:?} is out of bounds")]
+    RegionOutOfBounds(Rectangle<i32, Buffer>),
+    /// The requested format is not supported for exporting
+    #[error("Unsupported export format: {0:?}")]
+    UnsupportedExportFormat(Fourcc),
+    /// The given memory does not match the size of the texture
+    #[error("Expected {expected} bytes of memory, got {got}")]
+    UnexpectedSize {
+        /// Expected size in bytes
+        expected: usize,
+        /// Actual size in bytes
+        got: usize,
+    },
+    /// Only shm buffers and memory can be imported
+    #[error("Only shm buffers and memory are supported by the software renderer")]
+    UnsupportedBuffer,
+    /// Waiting for a fence failed
+    #[error("Failed to wait for fence: {0}")]
+    FenceError(#[source] std::io::Error),
+}
+
+/// Premultiplied RGBA pixels, ordered row by row from the top-left corner
+#[derive(Debug)]
+struct Image {
+    size: Size<i32, Buffer>,
+    data: Vec<u8>,
+}
+
+impl Image {
+    fn new(size: Size<i32, Buffer>) -> Image {
+        Image {
+            size,
+            data: vec![0; (size.w.max(0) * size.h.max(0) * 4) as usize],
+        }
+    }
+
+    fn bounds(&self) -> Rectangle<i32, Buffer> {
+        Rectangle::from_loc_and_size((0, 0), self.size)
+    }
+
+    fn offset(&self, x: i32, y: i32) -> usize {
+        ((y * self.size.w + x) * 4) as usize
+    }
+
+    fn pixel(&self, x: i32, y: i32) -> [u8; 4] {
+        let offset = self.offset(x, y);
+        let mut pixel = [0; 4];
+        pixel.copy_from_slice(&self.data[offset..offset + 4]);
+        pixel
+    }
+
+    fn set_pixel(&mut self, x: i32, y: i32, pixel: [u8; 4]) {
+        let offset = self.offset(x, y);
+        self.data[offset..offset + 4].copy_from_slice(&pixel);
+    }
+
+    fn blend_pixel(&mut self, x: i32, y: i32, pixel: [u8; 4], alpha: f32) {
+        let offset = self.offset(x, y);
+        let src_alpha = pixel[3] as f32 / 255.0 * alpha;
+        for (dst, src) in self.data[offset..offset + 4].iter_mut().zip(pixel) {
+            let value = src as f32 * alpha + *dst as f32 * (1.0 - src_alpha);
+            *dst = value.round().clamp(0.0, 255.0) as u8;
+        }
+    }
+
+    fn export(
+        &self,
+        region: Rectangle<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<SoftwareMapping, SoftwareError> {
+        if self.bounds().intersection(region) != Some(region) {
+            return Err(SoftwareError::RegionOutOfBounds(region));
+        }
+        let mut data = Vec::with_capacity((region.size.w * region.size.h * 4) as usize);
+        for y in region.loc.y..region.loc.y + region.size.h {
+            for x in region.loc.x..region.loc.x + region.size.w {
+                let [r, g, b, a] = self.pixel(x, y);
+                let pixel = match format {
+                    Fourcc::Abgr8888 => [r, g, b, a],
+                    Fourcc::Xbgr8888 => [r, g, b, 255],
+                    Fourcc::Argb8888 => [b, g, r, a],
+                    Fourcc::Xrgb8888 => [b, g, r, 255],
+                    format => return Err(SoftwareError::UnsupportedExportFormat(format)),
+                };
+                data.extend_from_slice(&pixel);
+            }
+        }
+        Ok(SoftwareMapping {
+            data,
+            size: region.size,
+            format,
+        })
+    }
+}
+
+/// A texture of the [`SoftwareRenderer`]
+///
+/// Cloning a texture creates a new handle to the same contents.
+#[derive(Debug, Clone)]
+pub struct SoftwareTexture(Arc<Mutex<Image>>);
+
+impl Texture for SoftwareTexture {
+    fn size(&self) -> Size<i32, Buffer> {
+        self.0.lock().unwrap().size
+    }
+    fn width(&self) -> u32 {
+        self.0.lock().unwrap().size.w as u32
+    }
+    fn height(&self) -> u32 {
+        self.0.lock().unwrap().size.h as u32
+    }
+}
+
+/// An offscreen rendering target of the [`SoftwareRenderer`]
+///
+/// Cloning a buffer creates a new handle to the same contents.
+#[derive(Debug, Clone)]
+pub struct SoftwareBuffer(Arc<Mutex<Image>>);
+
+impl SoftwareBuffer {
+    /// Size of the buffer in pixels
+    pub fn size(&self) -> Size<i32, Buffer> {
+        self.0.lock().unwrap().size
+    }
+
+    /// Returns the premultiplied RGBA value of the pixel at the given location
+    ///
+    /// Panics, if the location is out of bounds.
+    pub fn pixel(&self, location: impl Into<Point<i32, Buffer>>) -> [u8; 4] {
+        let location = location.into();
+        let image = self.0.lock().unwrap();
+        assert!(
+            image.bounds().contains(location),
+            "{:?} is out of bounds",
+            location
+        );
+        image.pixel(location.x, location.y)
+    }
+}
+
+/// Contents of a [`SoftwareBuffer`] or [`SoftwareTexture`] copied into memory
+#[derive(Debug)]
+pub struct SoftwareMapping {
+    data: Vec<u8>,
+    size: Size<i32, Buffer>,
+    format: Fourcc,
+}
+
+impl Texture for SoftwareMapping {
+    fn size(&self) -> Size<i32, Buffer> {
+        self.size
+    }
+    fn width(&self) -> u32 {
+        self.size.w as u32
+    }
+    fn height(&self) -> u32 {
+        self.size.h as u32
+    }
+}
+
+impl TextureMapping for SoftwareMapping {
+    fn format(&self) -> Fourcc {
+        self.format
+    }
+}
+
+/// A renderer drawing into cpu memory, see the [module-level docs](self)
+pub struct SoftwareRenderer {
+    target: Option<SoftwareBuffer>,
+    log: slog::Logger,
+}
+
+impl fmt::Debug for SoftwareRenderer {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SoftwareRenderer")
+            .field("target", &self.target)
+            .finish_non_exhaustive()
+    }
+}
+
+impl SoftwareRenderer {
+    /// Creates a new software renderer
+    pub fn new<L>(logger: L) -> SoftwareRenderer
+    where
+        L: Into<Option<::slog::Logger>>,
+    {
+        let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "renderer_software"));
+        SoftwareRenderer { target: None, log }
+    }
+}
+
+/// A frame of the [`SoftwareRenderer`]
+#[derive(Debug)]
+pub struct SoftwareFrame {
+    target: SoftwareBuffer,
+    size: Size<i32, Physical>,
+    transform: Transform,
+}
+
+impl SoftwareFrame {
+    fn bounds(&self) -> Rectangle<i32, Physical> {
+        Rectangle::from_loc_and_size((0, 0), self.size)
+    }
+
+    /// Converts a pixel of the frame into the pixel of the target it covers
+    fn target_pixel(&self, x: i32, y: i32) -> (i32, i32) {
+        let center = Point::<f64, Physical>::from((x as f64 + 0.5, y as f64 + 0.5));
+        let point = self.transform.transform_point_in(center, &self.size.to_f64());
+        (point.x.floor() as i32, point.y.floor() as i32)
+    }
+}
+
+impl Frame for SoftwareFrame {
+    type Error = SoftwareError;
+    type TextureId = SoftwareTexture;
+
+    fn clear(&mut self, color: [f32; 4], at: &[Rectangle<i32, Physical>]) -> Result<(), SoftwareError> {
+        let pixel = color.map(|channel| (channel * 255.0).round().clamp(0.0, 255.0) as u8);
+        let mut target = self.target.0.lock().unwrap();
+        for rect in at.iter().filter_map(|rect| rect.intersection(self.bounds())) {
+            for y in rect.loc.y..rect.loc.y + rect.size.h {
+                for x in rect.loc.x..rect.loc.x + rect.size.w {
+                    let (x, y) = self.target_pixel(x, y);
+                    target.set_pixel(x, y, pixel);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn render_texture_from_to(
+        &mut self,
+        texture: &SoftwareTexture,
+        src: Rectangle<i32, Buffer>,
+        dst: Rectangle<f64, Physical>,
+        damage: &[Rectangle<i32, Physical>],
+        src_transform: Transform,
+        alpha: f32,
+    ) -> Result<(), SoftwareError> {
+        let texture = texture.0.lock().unwrap();
+        let src = match src.intersection(texture.bounds()) {
+            Some(src) => src,
+            None => return Ok(()),
+        };
+        let dst_bounds =
+            Rectangle::from_extemities(dst.loc.to_i32_floor(), (dst.loc + dst.size).to_i32_ceil());
+        let dst_bounds = match dst_bounds.intersection(self.bounds()) {
+            Some(bounds) => bounds,
+            None => return Ok(()),
+        };
+        // the size of the source, as it is displayed after applying the inverse of `src_transform`
+        let src_size = src_transform.transform_size(src.size).to_f64();
+
+        let mut target = self.target.0.lock().unwrap();
+        for rect in damage.iter().filter_map(|rect| rect.intersection(dst_bounds)) {
+            for y in rect.loc.y..rect.loc.y + rect.size.h {
+                for x in rect.loc.x..rect.loc.x + rect.size.w {
+                    let u = (x as f64 + 0.5 - dst.loc.x) / dst.size.w;
+                    let v = (y as f64 + 0.5 - dst.loc.y) / dst.size.h;
+                    if !(0.0..1.0).contains(&u) || !(0.0..1.0).contains(&v) {
+                        continue;
+                    }
+                    let point = Point::<f64, Buffer>::from((u * src_size.w, v * src_size.h));
+                    let point = src_transform.transform_point_in(point, &src_size);
+                    let src_x = src.loc.x + (point.x.floor() as i32).clamp(0, src.size.w - 1);
+                    let src_y = src.loc.y + (point.y.floor() as i32).clamp(0, src.size.h - 1);
+                    let (x, y) = self.target_pixel(x, y);
+                    target.blend_pixel(x, y, texture.pixel(src_x, src_y), alpha);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+impl Renderer for SoftwareRenderer {
+    type Error = SoftwareError;
+    type TextureId = SoftwareTexture;
+    type Frame = SoftwareFrame;
+
+    /// Textures are always sampled with the nearest filter
+    fn downscale_filter(&mut self, _filter: TextureFilter) -> Result<(), SoftwareError> {
+        Ok(())
+    }
+
+    /// Textures are always sampled with the nearest filter
+    fn upscale_filter(&mut self, _filter: TextureFilter) -> Result<(), SoftwareError> {
+        Ok(())
+    }
+
+    fn render<F, R>(
+        &mut self,
+        size: Size<i32, Physical>,
+        dst_transform: Transform,
+        rendering: F,
+    ) -> Result<R, SoftwareError>
+    where
+        F: FnOnce(&mut Self, &mut SoftwareFrame) -> R,
+    {
+        let target = self.target.clone().ok_or(SoftwareError::NoTargetBound)?;
+        let target_size = target.size();
+        if (target_size.w, target_size.h) != (size.w, size.h) {
+            slog::warn!(
+                self.log,
+                "Rendering {:?} into a target of size {:?}, the frame is clipped",
+                size,
+                target_size
+            );
+        }
+        let mut frame = SoftwareFrame {
+            target,
+            size: dst_transform
+                .transform_size(Size::from((size.w.min(target_size.w), size.h.min(target_size.h)))),
+            transform: dst_transform,
+        };
+        Ok(rendering(self, &mut frame))
+    }
+}
+
+impl Unbind for SoftwareRenderer {
+    fn unbind(&mut self) -> Result<(), SoftwareError> {
+        self.target = None;
+        Ok(())
+    }
+}
+
+impl Bind<SoftwareBuffer> for SoftwareRenderer {
+    fn bind(&mut self, target: SoftwareBuffer) -> Result<(), SoftwareError> {
+        self.target = Some(target);
+        Ok(())
+    }
+}
+
+impl Offscreen<SoftwareBuffer> for SoftwareRenderer {
+    fn create_buffer(&mut self, size: Size<i32, Buffer>) -> Result<SoftwareBuffer, SoftwareError> {
+        Ok(SoftwareBuffer(Arc::new(Mutex::new(Image::new(size)))))
+    }
+}
+
+impl ExportMem for SoftwareRenderer {
+    type TextureMapping = SoftwareMapping;
+
+    fn copy_framebuffer(
+        &mut self,
+        region: Rectangle<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<SoftwareMapping, SoftwareError> {
+        let target = self.target.as_ref().ok_or(SoftwareError::NoTargetBound)?;
+        let image = target.0.lock().unwrap();
+        image.export(region, format)
+    }
+
+    fn copy_texture(
+        &mut self,
+        texture: &SoftwareTexture,
+        region: Rectangle<i32, Buffer>,
+        format: Fourcc,
+    ) -> Result<SoftwareMapping, SoftwareError> {
+        texture.0.lock().unwrap().export(region, format)
+    }
+
+    fn map_texture<'a>(&mut self, texture_mapping: &'a SoftwareMapping) -> Result<&'a [u8], SoftwareError> {
+        Ok(&texture_mapping.data)
+    }
+}
+
+impl ImportMem for SoftwareRenderer {
+    fn import_memory(
+        &mut self,
+        data: &[u8],
+        size: Size<i32, Buffer>,
+        flipped: bool,
+    ) -> Result<SoftwareTexture, SoftwareError> {
+        let mut image = Image::new(size);
+        if data.len() != image.data.len() {
+            return Err(SoftwareError::UnexpectedSize {
+                expected: image.data.len(),
+                got: data.len(),
+            });
+        }
+        let stride = (size.w * 4) as usize;
+        for (row, line) in data.chunks_exact(stride.max(1)).enumerate() {
+            let row = if flipped { size.h as usize - 1 - row } else { row };
+            image.data[row * stride..(row + 1) * stride].copy_from_slice(line);
+        }
+        Ok(SoftwareTexture(Arc::new(Mutex::new(image))))
+    }
+
+    fn update_memory(
+        &mut self,
+        texture: &SoftwareTexture,
+        data: &[u8],
+        region: Rectangle<i32, Buffer>,
+    ) -> Result<(), SoftwareError> {
+        let mut image = texture.0.lock().unwrap();
+        if data.len() != image.data.len() {
+            return Err(SoftwareError::UnexpectedSize {
+                expected: image.data.len(),
+                got: data.len(),
+            });
+        }
+        let region = region
+            .intersection(image.bounds())
+            .ok_or(SoftwareError::RegionOutOfBounds(region))?;
+        for y in region.loc.y..region.loc.y + region.size.h {
+            let start = image.offset(region.loc.x, y);
+            let end = start + (region.size.w * 4) as usize;
+            image.data[start..end].copy_from_slice(&data[start..end]);
+        }
+        Ok(())
+    }
+}
+
+impl ImportShm for SoftwareRenderer {
+    /// Imports the whole buffer, textures are not cached per surface
+    fn import_shm_buffer(
+        &mut self,
+        buffer: &wl_buffer::WlBuffer,
+        _surface: Option<&crate::wayland::compositor::SurfaceData>,
+        _damage: &[Rectangle<i32, Buffer>],
+    ) -> Result<SoftwareTexture, SoftwareError> {
+        shm::with_buffer_contents(buffer, |slice, data| {
+            let mut image = Image::new((data.width, data.height).into());
+            for y in 0..data.height {
+                for x in 0..data.width {
+                    let offset = (data.offset + y * data.stride + x * 4) as usize;
+                    let [c0, c1, c2, c3] = [
+                        slice[offset],
+                        slice[offset + 1],
+                        slice[offset + 2],
+                        slice[offset + 3],
+                    ];
+                    // shm formats are little-endian
+                    let pixel = match data.format {
+                        wl_shm::Format::Abgr8888 => [c0, c1, c2, c3],
+                        wl_shm::Format::Xbgr8888 => [c0, c1, c2, 255],
+                        wl_shm::Format::Argb8888 => [c2, c1, c0, c3],
+                        wl_shm::Format::Xrgb8888 => [c2, c1, c0, 255],
+                        format => return Err(SoftwareError::UnsupportedPixelFormat(format)),
+                    };
+                    image.set_pixel(x, y, pixel);
+                }
+            }
+            Ok(SoftwareTexture(Arc::new(Mutex::new(image))))
+        })
+        .map_err(SoftwareError::BufferAccessError)?
+    }
+}
+
+impl WaitFence for SoftwareRenderer {
+    fn wait_for_fence(&mut self, fence: SyncFile) -> Result<(), SoftwareError> {
+        // rendering happens on the cpu, so block until the fence is signaled
+        fence.wait(None).map(|_| ()).map_err(SoftwareError::FenceError)
+    }
+}
+
+impl ImportDma for SoftwareRenderer {
+    fn import_dmabuf(&mut self, _dmabuf: &Dmabuf) -> Result<SoftwareTexture, SoftwareError> {
+        Err(SoftwareError::UnsupportedBuffer)
+    }
+}
+
+#[cfg(all(feature = "backend_egl", feature = "use_system_lib"))]
+impl crate::backend::renderer::ImportEgl for SoftwareRenderer {
+    fn bind_wl_display(
+        &mut self,
+        _display: &wayland_server::Display,
+    ) -> Result<(), crate::backend::egl::Error> {
+        Err(crate::backend::egl::Error::EglExtensionNotSupported(&[
+            "EGL_WL_bind_wayland_display",
+        ]))
+    }
+
+    fn unbind_wl_display(&mut self) {}
+
+    fn egl_reader(&self) -> Option<&crate::backend::egl::display::EGLBufferReader> {
+        None
+    }
+
+    fn import_egl_buffer(&mut self, _buffer: &wl_buffer::WlBuffer) -> Result<SoftwareTexture, SoftwareError> {
+        Err(SoftwareError::UnsupportedBuffer)
+    }
+}
@@ -824,6 +824,69 @@ impl<N: Coordinate, Kind> Rectangle<N, Kind> {
     pub fn merge(self, other: Self) -> Self {
         Self::bounding_box([self.loc, self.loc + self.size, other.loc, other.loc + other.size])
     }
+
+    /// Compute the intersection of two [`Rectangle`]s
+    ///
+    /// Returns `None` if the two rectangles do not share any area.
+    pub fn intersection(self, other: Self) -> Option<Self> {
+        // we don't have cmp::{min,max} for f64 :(
+        let max = |a: N, b: N| if a > b { a } else { b };
+        let min = |a: N, b: N| if a < b { a } else { b };
+
+        let topleft: Point<N, Kind> = (max(self.loc.x, other.loc.x), max(self.loc.y, other.loc.y)).into();
+        let bottomright: Point<N, Kind> = (
+            min(self.loc.x + self.size.w, other.loc.x + other.size.w),
+            min(self.loc.y + self.size.h, other.loc.y + other.size.h),
+        )
+            .into();
+
+        if topleft.x < bottomright.x && topleft.y < bottomright.y {
+            Some(Rectangle::from_extemities(topleft, bottomright))
+        } else {
+            None
+        }
+    }
+
+    /// Subtract another [`Rectangle`] from this one
+    ///
+    /// Returns up to four non-overlapping rectangles covering the remaining area.
+    pub fn subtract_rect(self, other: Self) -> Vec<Self> {
+        let intersection = match self.intersection(other) {
+            Some(intersection) => intersection,
+            None => return vec![self],
+        };
+
+        let (top, bottom) = (self.loc.y, self.loc.y + self.size.h);
+        let (left, right) = (self.loc.x, self.loc.x + self.size.w);
+        let inner_topleft = intersection.loc;
+        let inner_bottomright = intersection.loc + intersection.size;
+
+        let mut rects = Vec::with_capacity(4);
+        // full width above and below the intersection
+        if inner_topleft.y > top {
+            rects.push(Rectangle::from_extemities((left, top), (right, inner_topleft.y)));
+        }
+        if bottom > inner_bottomright.y {
+            rects.push(Rectangle::from_extemities(
+                (left, inner_bottomright.y),
+                (right, bottom),
+            ));
+        }
+        // left and right of the intersection
+        if inner_topleft.x > left {
+            rects.push(Rectangle::from_extemities(
+                (left, inner_topleft.y),
+                (inner_topleft.x, inner_bottomright.y),
+            ));
+        }
+        if right > inner_bottomright.x {
+            rects.push(Rectangle::from_extemities(
+                (inner_bottomright.x, inner_topleft.y),
+                (right, inner_bottomright.y),
+            ));
+        }
+        rects
+    }
 }
 
 impl<N: Coordinate> Rectangle<N, Logical> {
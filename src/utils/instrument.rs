@@ -0,0 +1,46 @@
+//! Internal `tracing` instrumentation
+//!
+//! With the `tracing` feature enabled, smithay emits spans and events through the
+//! [`tracing`](https://docs.rs/tracing) crate. Without it, these macros compile to nothing.
+
+/// Enters a tracing span lasting until the end of the enclosing block
+#[allow(unused_macros)]
+macro_rules! trace_span {
+    ($level:ident, $name:expr $(, $($field:tt)+)?) => {
+        #[cfg(feature = "tracing")]
+        let _tracing_span = ::tracing::span!(::tracing::Level::$level, $name $(, $($field)+)?).entered();
+    };
+}
+
+/// Emits a tracing event
+#[allow(unused_macros)]
+macro_rules! trace_event {
+    ($level:ident, $($arg:tt)+) => {
+        #[cfg(feature = "tracing")]
+        ::tracing::event!(::tracing::Level::$level, $($arg)+);
+    };
+}
+
+/// Posts a protocol error on a resource, reporting it as a tracing event
+#[cfg(feature = "wayland_frontend")]
+macro_rules! post_error {
+    ($resource:expr, $code:expr, $msg:expr $(,)?) => {
+        $crate::utils::instrument::post_error($resource, $code, $msg)
+    };
+}
+
+#[cfg(feature = "wayland_frontend")]
+pub(crate) fn post_error<I>(resource: &wayland_server::Resource<I>, code: u32, msg: String)
+where
+    I: wayland_server::Interface + AsRef<wayland_server::Resource<I>> + From<wayland_server::Resource<I>>,
+{
+    trace_event!(
+        WARN,
+        interface = I::NAME,
+        id = resource.id(),
+        code,
+        message = %msg,
+        "Posting protocol error"
+    );
+    resource.post_error(code, msg);
+}
@@ -1,6 +1,12 @@
 //! Various utilities functions and types
 
+#[macro_use]
+pub(crate) mod instrument;
+#[macro_use]
+pub mod profiling;
+
 mod geometry;
+mod region;
 pub mod signaling;
 
 #[cfg(feature = "x11rb_event_source")]
@@ -8,7 +14,9 @@ pub mod x11rb;
 
 pub mod user_data;
 
+pub(crate) use self::geometry::Coordinate;
 pub use self::geometry::{Buffer, Logical, Physical, Point, Raw, Rectangle, Size};
+pub use self::region::Region;
 
 /// This resource is not managed by Smithay
 #[derive(Debug)]
@@ -0,0 +1,184 @@
+//! Frame profiling instrumentation
+//!
+//! With the `profiling` feature enabled, smithay opens profiling scopes around the hot paths
+//! of a frame: input dispatch, surface commit processing, render element collection, drawing
+//! and page-flips. The scopes are reported to the [`Profiler`] registered with [`set_profiler`],
+//! which allows to forward them to a profiler of your choice, e.g. [Tracy] or [puffin], and to
+//! inspect a frame of your compositor end-to-end together with your own scopes.
+//!
+//! Without the feature, the instrumentation compiles to nothing. Without a registered profiler,
+//! a scope only costs an atomic load.
+//!
+//! ```no_run
+//! use smithay::utils::profiling::{set_profiler, Profiler};
+//!
+//! struct PrintProfiler;
+//!
+//! impl Profiler for PrintProfiler {
+//!     fn begin_scope(&self, name: &'static str, location: &'static str) {
+//!         println!("begin {} ({})", name, location);
+//!     }
+//!     fn end_scope(&self) {
+//!         println!("end");
+//!     }
+//! }
+//!
+//! set_profiler(PrintProfiler);
+//! ```
+//!
+//! [Tracy]: https://github.com/wolfpld/tracy
+//! [puffin]: https://github.com/EmbarkStudios/puffin
+
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, RwLock,
+};
+
+/// Receiver of the profiling scopes of smithay
+///
+/// Scopes are strictly nested per thread: every [`begin_scope`](Profiler::begin_scope) is
+/// followed by a matching [`end_scope`](Profiler::end_scope) on the same thread.
+pub trait Profiler: Send + Sync {
+    /// A scope was entered
+    ///
+    /// `name` identifies the instrumented operation, `location` is the source location
+    /// of the scope in the form `file:line`.
+    fn begin_scope(&self, name: &'static str, location: &'static str);
+    /// The innermost scope of the current thread was left
+    fn end_scope(&self);
+    /// A frame was completed, see [`finish_frame`]
+    fn finish_frame(&self) {}
+}
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+static PROFILER: RwLock<Option<Arc<dyn Profiler>>> = RwLock::new(None);
+
+/// Registers the profiler receiving the scopes of smithay, replacing any previous one
+pub fn set_profiler<P: Profiler + 'static>(profiler: P) {
+    *PROFILER.write().unwrap() = Some(Arc::new(profiler));
+    ENABLED.store(true, Ordering::Release);
+}
+
+/// Removes the currently registered profiler
+pub fn clear_profiler() {
+    ENABLED.store(false, Ordering::Release);
+    PROFILER.write().unwrap().take();
+}
+
+/// Marks the end of a frame of your compositor, e.g. after queueing a page-flip
+pub fn finish_frame() {
+    if let Some(profiler) = profiler() {
+        profiler.finish_frame();
+    }
+}
+
+fn profiler() -> Option<Arc<dyn Profiler>> {
+    if !ENABLED.load(Ordering::Acquire) {
+        return None;
+    }
+    PROFILER.read().unwrap().clone()
+}
+
+/// Guard of an open profiling scope, the scope ends when it is dropped
+///
+/// Usually created through the internal `profile_scope!` macro.
+#[derive(Default)]
+pub struct Scope {
+    profiler: Option<Arc<dyn Profiler>>,
+}
+
+impl std::fmt::Debug for Scope {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Scope")
+            .field("active", &self.profiler.is_some())
+            .finish()
+    }
+}
+
+impl Scope {
+    /// Opens a new scope on the registered profiler, if any
+    pub fn new(name: &'static str, location: &'static str) -> Scope {
+        let profiler = profiler();
+        if let Some(profiler) = profiler.as_ref() {
+            profiler.begin_scope(name, location);
+        }
+        Scope { profiler }
+    }
+}
+
+impl Drop for Scope {
+    fn drop(&mut self) {
+        if let Some(profiler) = self.profiler.take() {
+            profiler.end_scope();
+        }
+    }
+}
+
+/// Opens a profiling scope lasting until the end of the enclosing block
+macro_rules! profile_scope {
+    ($name:expr) => {
+        #[cfg(feature = "profiling")]
+        let _profiling_scope = $crate::utils::profiling::Scope::new($name, concat!(file!(), ":", line!()));
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::{
+        sync::Mutex,
+        thread::{self, ThreadId},
+    };
+
+    // The profiler is process-wide, so scopes opened by tests running in parallel
+    // are reported to it as well. Only the scopes of the test thread are recorded.
+    struct Recorder {
+        thread: ThreadId,
+        events: Mutex<Vec<String>>,
+    }
+
+    impl Recorder {
+        fn new() -> Recorder {
+            Recorder {
+                thread: thread::current().id(),
+                events: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn record(&self, event: String) {
+            if thread::current().id() == self.thread {
+                self.events.lock().unwrap().push(event);
+            }
+        }
+    }
+
+    impl Profiler for Arc<Recorder> {
+        fn begin_scope(&self, name: &'static str, _location: &'static str) {
+            self.record(format!("begin {}", name));
+        }
+        fn end_scope(&self) {
+            self.record("end".into());
+        }
+        fn finish_frame(&self) {
+            self.record("frame".into());
+        }
+    }
+
+    #[test]
+    fn scopes_are_nested() {
+        let recorder = Arc::new(Recorder::new());
+        set_profiler(recorder.clone());
+        {
+            let _outer = Scope::new("outer", "");
+            let _inner = Scope::new("inner", "");
+        }
+        finish_frame();
+        clear_profiler();
+        let _ignored = Scope::new("ignored", "");
+
+        assert_eq!(
+            *recorder.events.lock().unwrap(),
+            vec!["begin outer", "begin inner", "end", "end", "frame"]
+        );
+    }
+}
@@ -0,0 +1,274 @@
+use std::{fmt, iter::FromIterator};
+
+use super::geometry::{Coordinate, Point, Rectangle};
+
+/// A set of non-overlapping rectangles, e.g. the damaged area of a buffer or output
+///
+/// Adding, subtracting and intersecting keeps the rectangles disjoint and coalesces
+/// neighbouring rectangles where possible. As the number of rectangles can grow large
+/// for complex shapes, [`Region::simplify`] can be used to bound it at the cost of
+/// covering a slightly larger area.
+pub struct Region<N, Kind> {
+    rects: Vec<Rectangle<N, Kind>>,
+}
+
+impl<N: Coordinate, Kind> Region<N, Kind> {
+    /// Create a new, empty region
+    pub fn new() -> Self {
+        Region { rects: Vec::new() }
+    }
+
+    /// Create a region covering the union of the given rectangles
+    pub fn from_rects(rects: impl IntoIterator<Item = Rectangle<N, Kind>>) -> Self {
+        let mut region = Region::new();
+        for rect in rects {
+            region.add(rect);
+        }
+        region
+    }
+
+    /// The disjoint rectangles making up this region
+    pub fn rects(&self) -> &[Rectangle<N, Kind>] {
+        &self.rects
+    }
+
+    /// Turns the region into its disjoint rectangles
+    pub fn into_rects(self) -> Vec<Rectangle<N, Kind>> {
+        self.rects
+    }
+
+    /// Returns if the region does not cover any area
+    pub fn is_empty(&self) -> bool {
+        self.rects.is_empty()
+    }
+
+    /// Checks whether the given [`Point`] is inside the region
+    pub fn contains<P: Into<Point<N, Kind>>>(&self, point: P) -> bool {
+        let point = point.into();
+        self.rects.iter().any(|rect| rect.contains(point))
+    }
+
+    /// Returns the smallest rectangle containing the whole region
+    pub fn bounding_box(&self) -> Rectangle<N, Kind> {
+        Rectangle::bounding_box(
+            self.rects
+                .iter()
+                .flat_map(|rect| [rect.loc, rect.loc + rect.size]),
+        )
+    }
+
+    /// Adds the area of a rectangle to the region
+    pub fn add(&mut self, rect: Rectangle<N, Kind>) {
+        if is_empty(&rect) {
+            return;
+        }
+        let mut pieces = vec![rect];
+        for existing in &self.rects {
+            pieces = pieces
+                .into_iter()
+                .flat_map(|piece| piece.subtract_rect(*existing))
+                .collect();
+            if pieces.is_empty() {
+                return;
+            }
+        }
+        self.rects.extend(pieces);
+        self.coalesce();
+    }
+
+    /// Adds the area of another region to this region
+    pub fn union(&mut self, other: &Region<N, Kind>) {
+        for rect in &other.rects {
+            self.add(*rect);
+        }
+    }
+
+    /// Removes the area of a rectangle from the region
+    pub fn subtract(&mut self, rect: Rectangle<N, Kind>) {
+        self.rects = self
+            .rects
+            .iter()
+            .flat_map(|existing| existing.subtract_rect(rect))
+            .collect();
+        self.coalesce();
+    }
+
+    /// Limits the region to the area of a rectangle
+    pub fn intersect(&mut self, rect: Rectangle<N, Kind>) {
+        self.rects = self
+            .rects
+            .iter()
+            .filter_map(|existing| existing.intersection(rect))
+            .collect();
+    }
+
+    /// Reduces the region to at most `max_rects` rectangles
+    ///
+    /// Repeatedly replaces the two rectangles adding the least area when merged by their
+    /// bounding box. The resulting region always covers the original region.
+    pub fn simplify(&mut self, max_rects: usize) {
+        let max_rects = max_rects.max(1);
+        while self.rects.len() > max_rects {
+            let count = self.rects.len();
+            let mut best = (0, 1, f64::INFINITY);
+            for i in 0..count {
+                for j in (i + 1)..count {
+                    let waste = area(&self.rects[i].merge(self.rects[j]))
+                        - area(&self.rects[i])
+                        - area(&self.rects[j]);
+                    if waste < best.2 {
+                        best = (i, j, waste);
+                    }
+                }
+            }
+
+            let (i, j, _) = best;
+            let merged = self.rects[i].merge(self.rects[j]);
+            let others = std::mem::take(&mut self.rects);
+            self.rects = others
+                .into_iter()
+                .enumerate()
+                .filter(|(idx, _)| *idx != i && *idx != j)
+                .flat_map(|(_, rect)| rect.subtract_rect(merged))
+                .collect();
+            self.rects.push(merged);
+            self.coalesce();
+
+            // the merged rectangle might split its neighbours, fall back to the bounding box
+            // instead of growing the region further
+            if self.rects.len() >= count {
+                self.rects = vec![self.bounding_box()];
+            }
+        }
+    }
+
+    /// Merges rectangles sharing a complete edge
+    fn coalesce(&mut self) {
+        let mut merged = true;
+        while merged {
+            merged = false;
+            'outer: for i in 0..self.rects.len() {
+                for j in (i + 1)..self.rects.len() {
+                    let (a, b) = (self.rects[i], self.rects[j]);
+                    let horizontal = a.loc.y == b.loc.y
+                        && a.size.h == b.size.h
+                        && (a.loc.x + a.size.w == b.loc.x || b.loc.x + b.size.w == a.loc.x);
+                    let vertical = a.loc.x == b.loc.x
+                        && a.size.w == b.size.w
+                        && (a.loc.y + a.size.h == b.loc.y || b.loc.y + b.size.h == a.loc.y);
+                    if horizontal || vertical {
+                        self.rects.swap_remove(j);
+                        self.rects[i] = a.merge(b);
+                        merged = true;
+                        break 'outer;
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn is_empty<N: Coordinate, Kind>(rect: &Rectangle<N, Kind>) -> bool {
+    !(rect.size.w > N::default() && rect.size.h > N::default())
+}
+
+fn area<N: Coordinate, Kind>(rect: &Rectangle<N, Kind>) -> f64 {
+    rect.size.w.to_f64() * rect.size.h.to_f64()
+}
+
+impl<N: Coordinate, Kind> Default for Region<N, Kind> {
+    fn default() -> Self {
+        Region::new()
+    }
+}
+
+impl<N: Coordinate, Kind> Clone for Region<N, Kind> {
+    fn clone(&self) -> Self {
+        Region {
+            rects: self.rects.clone(),
+        }
+    }
+}
+
+impl<N, Kind> fmt::Debug for Region<N, Kind>
+where
+    Rectangle<N, Kind>: fmt::Debug,
+{
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Region").field("rects", &self.rects).finish()
+    }
+}
+
+impl<N: Coordinate, Kind> FromIterator<Rectangle<N, Kind>> for Region<N, Kind> {
+    fn from_iter<T: IntoIterator<Item = Rectangle<N, Kind>>>(iter: T) -> Self {
+        Region::from_rects(iter)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Region;
+    use crate::utils::{Logical, Rectangle};
+
+    fn covered_area(region: &Region<i32, Logical>) -> i32 {
+        region.rects().iter().map(|rect| rect.size.w * rect.size.h).sum()
+    }
+
+    #[test]
+    fn union_is_disjoint() {
+        let region = Region::<i32, Logical>::from_rects(vec![
+            Rectangle::from_loc_and_size((0, 0), (10, 10)),
+            Rectangle::from_loc_and_size((5, 5), (10, 10)),
+            Rectangle::from_loc_and_size((2, 2), (3, 3)),
+        ]);
+        assert_eq!(covered_area(&region), 175);
+        for (i, a) in region.rects().iter().enumerate() {
+            for b in &region.rects()[i + 1..] {
+                assert_eq!(a.intersection(*b), None);
+            }
+        }
+        assert!(region.contains((12, 12)));
+        assert!(!region.contains((12, 2)));
+    }
+
+    #[test]
+    fn coalesce_neighbours() {
+        let region = Region::<i32, Logical>::from_rects(vec![
+            Rectangle::from_loc_and_size((0, 0), (10, 10)),
+            Rectangle::from_loc_and_size((10, 0), (10, 10)),
+            Rectangle::from_loc_and_size((0, 10), (20, 5)),
+        ]);
+        assert_eq!(region.rects(), &[Rectangle::from_loc_and_size((0, 0), (20, 15))]);
+    }
+
+    #[test]
+    fn subtract_and_intersect() {
+        let mut region =
+            Region::<i32, Logical>::from_rects(vec![Rectangle::from_loc_and_size((0, 0), (30, 30))]);
+        region.subtract(Rectangle::from_loc_and_size((10, 10), (10, 10)));
+        assert_eq!(covered_area(&region), 800);
+        assert!(!region.contains((15, 15)));
+        region.intersect(Rectangle::from_loc_and_size((0, 0), (15, 30)));
+        assert_eq!(covered_area(&region), 400);
+        region.subtract(Rectangle::from_loc_and_size((0, 0), (30, 30)));
+        assert!(region.is_empty());
+    }
+
+    #[test]
+    fn simplify_bounds_rects() {
+        let rects = (0..10)
+            .map(|i| Rectangle::from_loc_and_size((i * 20, (i % 3) * 7), (10, 5)))
+            .collect::<Vec<_>>();
+        let mut region = Region::<i32, Logical>::from_rects(rects.clone());
+        assert_eq!(region.rects().len(), 10);
+        region.simplify(4);
+        assert!(region.rects().len() <= 4);
+        for rect in rects {
+            for x in rect.loc.x..rect.loc.x + rect.size.w {
+                for y in rect.loc.y..rect.loc.y + rect.size.h {
+                    assert!(region.contains((x, y)));
+                }
+            }
+        }
+    }
+}
@@ -129,6 +129,8 @@ impl SurfaceImplem {
                 });
             }
             wl_surface::Request::Commit => {
+                profile_scope!("wl_surface::commit");
+                trace_span!(TRACE, "wl_surface::commit", surface = surface.as_ref().id());
                 let mut user_impl = self.implem.borrow_mut();
                 PrivateSurfaceData::invoke_commit_hooks(&surface);
                 if !surface.as_ref().is_alive() {
@@ -268,7 +270,8 @@ pub(crate) fn implement_subcompositor(
     subcompositor.quick_assign(move |subcompositor, request, _| match request {
         wl_subcompositor::Request::GetSubsurface { id, surface, parent } => {
             if let Err(AlreadyHasRole) = PrivateSurfaceData::set_parent(&surface, &parent) {
-                subcompositor.as_ref().post_error(
+                post_error!(
+                    subcompositor.as_ref(),
                     wl_subcompositor::Error::BadSurface as u32,
                     "Surface already has a role.".into(),
                 );
@@ -364,7 +367,8 @@ fn implement_subsurface(
                     .get::<wl_surface::WlSurface>()
                     .unwrap();
                 if let Err(()) = PrivateSurfaceData::reorder(surface, Location::After, &sibling) {
-                    subsurface.as_ref().post_error(
+                    post_error!(
+                        subsurface.as_ref(),
                         wl_subsurface::Error::BadSurface as u32,
                         "Provided surface is not a sibling or parent.".into(),
                     )
@@ -377,7 +381,8 @@ fn implement_subsurface(
                     .get::<wl_surface::WlSurface>()
                     .unwrap();
                 if let Err(()) = PrivateSurfaceData::reorder(surface, Location::Before, &sibling) {
-                    subsurface.as_ref().post_error(
+                    post_error!(
+                        subsurface.as_ref(),
                         wl_subsurface::Error::BadSurface as u32,
                         "Provided surface is not a sibling or parent.".into(),
                     )
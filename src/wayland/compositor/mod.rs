@@ -82,7 +82,11 @@
 //! on a surface. See [`give_role`] and [`get_role`] for details. This module manages the
 //! subsurface role, which is identified by the string `"subsurface"`.
 
-use std::{cell::RefCell, rc::Rc, sync::Mutex};
+use std::{
+    cell::{Cell, RefCell},
+    rc::Rc,
+    sync::Mutex,
+};
 
 mod cache;
 mod handlers;
@@ -91,14 +95,14 @@ mod tree;
 
 pub use self::cache::{Cacheable, MultiCache};
 pub use self::handlers::SubsurfaceCachedState;
-use self::tree::PrivateSurfaceData;
 pub use self::tree::{AlreadyHasRole, TraversalAction};
-use crate::utils::{Buffer, DeadResource, Logical, Point, Rectangle};
+use self::tree::{PrivateSurfaceData, SUBSURFACE_ROLE};
+use crate::utils::{user_data::UserDataMap, Buffer, DeadResource, Logical, Point, Rectangle};
 use wayland_server::{
     protocol::{
         wl_buffer, wl_callback, wl_compositor, wl_output, wl_region, wl_subcompositor, wl_surface::WlSurface,
     },
-    DispatchData, Display, Filter, Global, UserDataMap,
+    DispatchData, Display, Filter, Global,
 };
 
 /// Description of a part of a surface that
@@ -333,6 +337,111 @@ pub fn with_surface_tree_downward<F1, F2, F3, T>(
     PrivateSurfaceData::map_tree(surface, &initial, filter, processor, post_filter, true);
 }
 
+/// Access the data of a surface tree from bottom to top, together with the location of each surface
+///
+/// Behaves like [`with_surface_tree_upward`], but instead of a custom value the closures receive
+/// the location of the surface they are called on: the root surface is located at `location`,
+/// subsurfaces are offset from their parent by their [`SubsurfaceCachedState::location`].
+///
+/// The `filter` is called on a surface before its children, returning
+/// [`TraversalAction::SkipChildren`] skips its children and [`TraversalAction::Break`] stops the
+/// traversal. The `processor` is called in drawing order, the traversal stops as soon as it returns
+/// `false`. The role of each surface and the role data stored in its `data_map` are available
+/// through the [`SurfaceData`] given to both closures.
+pub fn with_surface_tree_located_upward<F1, F2>(
+    surface: &WlSurface,
+    location: impl Into<Point<i32, Logical>>,
+    filter: F1,
+    processor: F2,
+) where
+    F1: FnMut(&WlSurface, &SurfaceData, Point<i32, Logical>) -> TraversalAction<()>,
+    F2: FnMut(&WlSurface, &SurfaceData, Point<i32, Logical>) -> bool,
+{
+    map_located_tree(surface, location.into(), filter, processor, false);
+}
+
+/// Access the data of a surface tree from top to bottom, together with the location of each surface
+///
+/// Behavior is the same as [`with_surface_tree_located_upward`], but the processing is done in the
+/// reverse order, from the nearest of the screen to the deepest. Returning `false` from the `processor`
+/// once the surface of interest is found, e.g. the surface under the pointer, skips the remaining surfaces.
+pub fn with_surface_tree_located_downward<F1, F2>(
+    surface: &WlSurface,
+    location: impl Into<Point<i32, Logical>>,
+    filter: F1,
+    processor: F2,
+) where
+    F1: FnMut(&WlSurface, &SurfaceData, Point<i32, Logical>) -> TraversalAction<()>,
+    F2: FnMut(&WlSurface, &SurfaceData, Point<i32, Logical>) -> bool,
+{
+    map_located_tree(surface, location.into(), filter, processor, true);
+}
+
+fn map_located_tree<F1, F2>(
+    surface: &WlSurface,
+    location: Point<i32, Logical>,
+    mut filter: F1,
+    mut processor: F2,
+    reverse: bool,
+) where
+    F1: FnMut(&WlSurface, &SurfaceData, Point<i32, Logical>) -> TraversalAction<()>,
+    F2: FnMut(&WlSurface, &SurfaceData, Point<i32, Logical>) -> bool,
+{
+    // the value passed down the tree is the location of the parent, `None` for the root surface
+    let surface_location = |states: &SurfaceData, parent: &Option<Point<i32, Logical>>| match parent {
+        Some(parent) if states.role == Some(SUBSURFACE_ROLE) => {
+            *parent + states.cached_state.current::<SubsurfaceCachedState>().location
+        }
+        Some(parent) => *parent,
+        None => location,
+    };
+    let stop = Cell::new(false);
+    PrivateSurfaceData::map_tree(
+        surface,
+        &None,
+        |surface, states, parent| {
+            if stop.get() {
+                return TraversalAction::Break;
+            }
+            let location = surface_location(states, parent);
+            match filter(surface, states, location) {
+                TraversalAction::DoChildren(()) => TraversalAction::DoChildren(Some(location)),
+                TraversalAction::SkipChildren => TraversalAction::SkipChildren,
+                TraversalAction::Break => TraversalAction::Break,
+            }
+        },
+        |surface, states, parent| {
+            if !stop.get() && !processor(surface, states, surface_location(states, parent)) {
+                stop.set(true);
+            }
+        },
+        |_, _, _| !stop.get(),
+        reverse,
+    );
+}
+
+/// Lists the surfaces of a surface tree located at `location` from bottom to top, with their locations
+///
+/// See [`with_surface_tree_located_upward`] for how the locations are computed.
+pub fn surface_tree_locations(
+    surface: &WlSurface,
+    location: impl Into<Point<i32, Logical>>,
+) -> Vec<(WlSurface, Point<i32, Logical>)> {
+    let mut surfaces = Vec::new();
+    if surface.as_ref().is_alive() {
+        with_surface_tree_located_upward(
+            surface,
+            location,
+            |_, _, _| TraversalAction::DoChildren(()),
+            |surface, _, location| {
+                surfaces.push((surface.clone(), location));
+                true
+            },
+        );
+    }
+    surfaces
+}
+
 /// Retrieve the parent of this surface
 ///
 /// Returns `None` is this surface is a root surface
@@ -411,6 +520,40 @@ pub fn add_commit_hook(surface: &WlSurface, hook: fn(&WlSurface)) {
     PrivateSurfaceData::add_commit_hook(surface, hook)
 }
 
+/// Handler for surface commits
+///
+/// Implement it on your compositor state and initialize the global with
+/// [`delegate_compositor!`](crate::delegate_compositor).
+pub trait CompositorHandler {
+    /// A surface was committed, its new state is available through [`with_states`]
+    fn commit(&mut self, surface: &WlSurface);
+}
+
+/// Initializes the `wl_compositor` global, delegating to the [`CompositorHandler`] implementation of your state
+///
+/// Takes the type of your state, the display and optionally a logger, and returns the result of
+/// [`compositor_init`]. See [delegating to your state](crate::wayland#delegating-to-your-state) for
+/// how the display has to be dispatched.
+#[macro_export]
+macro_rules! delegate_compositor {
+    ($ty:ty, $display:expr) => {
+        $crate::delegate_compositor!($ty, $display, ::core::option::Option::None)
+    };
+    ($ty:ty, $display:expr, $logger:expr) => {{
+        let log = $crate::wayland::delegate_logger($logger);
+        let delegate_log = log.clone();
+        $crate::wayland::compositor::compositor_init(
+            $display,
+            move |surface, mut ddata| {
+                if let Some(state) = $crate::wayland::delegated_state::<$ty>(&mut ddata, &delegate_log) {
+                    <$ty as $crate::wayland::compositor::CompositorHandler>::commit(state, &surface);
+                }
+            },
+            log,
+        )
+    }};
+}
+
 /// Create new [`wl_compositor`](wayland_server::protocol::wl_compositor)
 /// and [`wl_subcompositor`](wayland_server::protocol::wl_subcompositor) globals.
 ///
@@ -500,4 +643,44 @@ mod tests {
         assert_eq!(region.contains((5, 5)), true);
         assert_eq!(region.contains((2, 2)), true);
     }
+
+    #[cfg(feature = "testing")]
+    #[test]
+    fn surface_tree_is_traversed_with_locations() {
+        use crate::testing::test_setup;
+        use wayland_client::protocol::wl_subcompositor::WlSubcompositor;
+
+        let (mut server, mut client, _) = test_setup(|_| ());
+        let parent = client.create_surface().unwrap();
+        let child = client.create_surface().unwrap();
+        let subcompositor = client.globals().instantiate_exact::<WlSubcompositor>(1).unwrap();
+        let subsurface = subcompositor.get_subsurface(child.wl_surface(), parent.wl_surface());
+        subsurface.set_position(10, 20);
+        child.commit();
+        parent.commit();
+        server.roundtrip(&mut client, &mut ()).unwrap();
+
+        let wl_parent = server.wl_surface(&client, &parent).unwrap();
+        let wl_child = server.wl_surface(&client, &child).unwrap();
+        assert_eq!(
+            surface_tree_locations(&wl_parent, (5, 5)),
+            vec![
+                (wl_parent.clone(), (5, 5).into()),
+                (wl_child.clone(), (15, 25).into())
+            ]
+        );
+
+        // the traversal stops once the processor returns false
+        let mut visited = Vec::new();
+        with_surface_tree_located_downward(
+            &wl_parent,
+            (0, 0),
+            |_, _, _| TraversalAction::DoChildren(()),
+            |surface, _, _| {
+                visited.push(surface.clone());
+                false
+            },
+        );
+        assert_eq!(visited, vec![wl_child]);
+    }
 }
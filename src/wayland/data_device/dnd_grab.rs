@@ -48,17 +48,62 @@ impl DnDGrab {
             seat,
         }
     }
+
+    fn source_destroyed(&self) -> bool {
+        self.data_source
+            .as_ref()
+            .map(|source| !source.as_ref().is_alive())
+            .unwrap_or(false)
+    }
+
+    /// Cancels the drag'n'drop after its data source was destroyed
+    fn cancel(&mut self, handle: &mut PointerInnerHandle<'_>, serial: Serial, time: u32) {
+        if let Some(surface) = self.current_focus.take() {
+            let seat_data = self.seat.user_data().get::<RefCell<SeatData>>().unwrap().borrow();
+            for device in &seat_data.known_devices {
+                if device.as_ref().same_client_as(surface.as_ref()) {
+                    device.leave();
+                }
+            }
+        }
+        self.pending_offers.clear();
+        if let Some(offer_data) = self.offer_data.take() {
+            offer_data.borrow_mut().active = false;
+        }
+        (*self.callback.borrow_mut())(super::DataDeviceEvent::DnDDropped);
+        handle.unset_grab(serial, time);
+    }
+}
+
+impl Drop for DnDGrab {
+    fn drop(&mut self) {
+        // the drag'n'drop ended, be it by a drop or by the grab being unset or replaced
+        let mut seat_data = self
+            .seat
+            .user_data()
+            .get::<RefCell<SeatData>>()
+            .unwrap()
+            .borrow_mut();
+        // a replacing drag'n'drop may already have set its own icon
+        if seat_data.dnd_icon == self.icon {
+            seat_data.dnd_icon = None;
+        }
+    }
 }
 
 impl PointerGrab for DnDGrab {
     fn motion(
         &mut self,
-        _handle: &mut PointerInnerHandle<'_>,
+        handle: &mut PointerInnerHandle<'_>,
         location: Point<f64, Logical>,
         focus: Option<(wl_surface::WlSurface, Point<i32, Logical>)>,
         serial: Serial,
         time: u32,
     ) {
+        if self.source_destroyed() {
+            self.cancel(handle, serial, time);
+            return;
+        }
         let seat_data = self
             .seat
             .user_data()
@@ -168,14 +213,13 @@ impl PointerGrab for DnDGrab {
         serial: Serial,
         time: u32,
     ) {
+        if self.source_destroyed() {
+            self.cancel(handle, serial, time);
+            return;
+        }
         if handle.current_pressed().is_empty() {
             // the user dropped, proceed to the drop
-            let seat_data = self
-                .seat
-                .user_data()
-                .get::<RefCell<SeatData>>()
-                .unwrap()
-                .borrow_mut();
+            let seat_data = self.seat.user_data().get::<RefCell<SeatData>>().unwrap().borrow();
             let validated = if let Some(ref data) = self.offer_data {
                 let data = data.borrow();
                 data.accepted && (!data.chosen_action.is_empty())
@@ -210,7 +254,6 @@ impl PointerGrab for DnDGrab {
                 }
             }
             (&mut *self.callback.borrow_mut())(super::DataDeviceEvent::DnDDropped);
-            self.icon = None;
             // in all cases abandon the drop
             // no more buttons are pressed, release the grab
             handle.unset_grab(serial, time);
@@ -269,28 +312,32 @@ fn implement_dnd_data_offer(
             Request::Destroy => {}
             Request::Finish => {
                 if !data.active {
-                    offer.as_ref().post_error(
+                    post_error!(
+                        offer.as_ref(),
                         wl_data_offer::Error::InvalidFinish as u32,
                         "Cannot finish a data offer that is no longer active.".into(),
                     );
                     return;
                 }
                 if !data.accepted {
-                    offer.as_ref().post_error(
+                    post_error!(
+                        offer.as_ref(),
                         wl_data_offer::Error::InvalidFinish as u32,
                         "Cannot finish a data offer that has not been accepted.".into(),
                     );
                     return;
                 }
                 if !data.dropped {
-                    offer.as_ref().post_error(
+                    post_error!(
+                        offer.as_ref(),
                         wl_data_offer::Error::InvalidFinish as u32,
                         "Cannot finish a data offer that has not been dropped.".into(),
                     );
                     return;
                 }
                 if data.chosen_action.is_empty() {
-                    offer.as_ref().post_error(
+                    post_error!(
+                        offer.as_ref(),
                         wl_data_offer::Error::InvalidFinish as u32,
                         "Cannot finish a data offer with no valid action.".into(),
                     );
@@ -309,7 +356,8 @@ fn implement_dnd_data_offer(
                 if ![DndAction::None, DndAction::Move, DndAction::Copy, DndAction::Ask]
                     .contains(&preferred_action)
                 {
-                    offer.as_ref().post_error(
+                    post_error!(
+                        offer.as_ref(),
                         wl_data_offer::Error::InvalidAction as u32,
                         "Invalid preferred action.".into(),
                     );
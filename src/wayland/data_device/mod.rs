@@ -21,11 +21,26 @@
 //! - You can provide a callback closure to [`init_data_device`]
 //!   to peek into the the actions of your clients
 //! - the freestanding function [`set_data_device_selection`]
-//!   allows you to set the contents of the selection for your clients
+//!   allows you to set the contents of the selection for your clients, and
+//!   [`clear_data_device_selection`] to clear it
+//! - the freestanding function [`request_data_device_client_selection`] allows you to read the
+//!   contents of a selection set by a client, whose mime types are given by
+//!   [`data_device_selection_mime_types`]
 //! - the freestanding function [`start_dnd`] allows you to initiate a drag'n'drop event from the compositor
 //!   itself and receive interactions of clients with it via an other dedicated callback.
 //!
 //! The module defines the role `"dnd_icon"` that is assigned to surfaces used as drag'n'drop icons.
+//! The icon of the drag'n'drop a client currently performs is returned by [`current_dnd_icon`],
+//! its offset to the pointer is tracked in its [`DnDIconAttributes`]. It can be rendered with
+//! [`dnd_icon_render_elements`](crate::backend::renderer::utils::dnd_icon_render_elements).
+//!
+//! ## Selection persistence
+//!
+//! The selection of a client is lost, once its client exits. To keep it available, the compositor
+//! can read the contents of a new selection (see [`DataDeviceEvent::NewSelection`]) for each of its
+//! mime types through [`request_data_device_client_selection`] and then replace it with a
+//! compositor-owned selection of the same mime types via [`set_data_device_selection`], answering
+//! [`DataDeviceEvent::SendSelection`] from the stored contents.
 //!
 //! ## Initialization
 //!
@@ -45,7 +60,7 @@
 //! );
 //! ```
 
-use std::{cell::RefCell, ops::Deref as _, os::unix::io::RawFd, rc::Rc};
+use std::{cell::RefCell, ops::Deref as _, os::unix::io::RawFd, rc::Rc, sync::Mutex};
 
 use wayland_server::{
     protocol::{
@@ -58,10 +73,13 @@ use wayland_server::{
 
 use slog::{debug, error, o};
 
-use crate::wayland::{
-    compositor,
-    seat::{GrabStartData, Seat},
-    Serial,
+use crate::{
+    utils::{Logical, Point},
+    wayland::{
+        compositor::{self, BufferAssignment, SurfaceAttributes},
+        seat::{GrabStartData, Seat},
+        Serial,
+    },
 };
 
 mod data_source;
@@ -73,6 +91,29 @@ pub use self::server_dnd_grab::ServerDndEvent;
 
 static DND_ICON_ROLE: &str = "dnd_icon";
 
+/// Attributes of a surface with the `"dnd_icon"` role
+///
+/// They are stored as `Mutex<DnDIconAttributes>` in the `data_map` of the surface.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct DnDIconAttributes {
+    /// Location of the icon relative to the pointer
+    ///
+    /// Accumulates the offsets of the buffers attached to the icon.
+    pub offset: Point<i32, Logical>,
+}
+
+fn dnd_icon_commit_hook(surface: &wl_surface::WlSurface) {
+    let _ = compositor::with_states(surface, |states| {
+        if let Some(BufferAssignment::NewBuffer { delta, .. }) =
+            states.cached_state.pending::<SurfaceAttributes>().buffer
+        {
+            if let Some(attributes) = states.data_map.get::<Mutex<DnDIconAttributes>>() {
+                attributes.lock().unwrap().offset += delta;
+            }
+        }
+    });
+}
+
 /// Events that are generated by interactions of the clients with the data device
 #[derive(Debug)]
 pub enum DataDeviceEvent {
@@ -89,7 +130,8 @@ pub enum DataDeviceEvent {
         /// during the drag'n'drop.
         icon: Option<wl_surface::WlSurface>,
     },
-    /// The drag'n'drop action was finished by the user releasing the buttons
+    /// The drag'n'drop action was finished by the user releasing the buttons, or cancelled
+    /// because its data source was destroyed
     ///
     /// At this point, any pointer icon should be removed.
     ///
@@ -104,6 +146,20 @@ pub enum DataDeviceEvent {
     },
 }
 
+/// Errors that can occur when requesting the contents of the selection
+#[derive(thiserror::Error, Debug)]
+pub enum SelectionRequestError {
+    /// The requested mime type is not offered by the selection
+    #[error("Requested mime type is not available")]
+    InvalidMimetype,
+    /// There is currently no selection
+    #[error("Current selection is empty")]
+    NoSelection,
+    /// The current selection was set by the compositor
+    #[error("Current selection is set by the compositor")]
+    ServerSideSelection,
+}
+
 enum Selection {
     Empty,
     Client(wl_data_source::WlDataSource),
@@ -113,6 +169,7 @@ enum Selection {
 struct SeatData {
     known_devices: Vec<wl_data_device::WlDataDevice>,
     selection: Selection,
+    dnd_icon: Option<wl_surface::WlSurface>,
     log: ::slog::Logger,
     current_focus: Option<Client>,
 }
@@ -247,6 +304,7 @@ impl SeatData {
         SeatData {
             known_devices: Vec::new(),
             selection: Selection::Empty,
+            dnd_icon: None,
             log,
             current_focus: None,
         }
@@ -327,11 +385,84 @@ pub fn set_data_device_selection(seat: &Seat, mime_types: Vec<String>) {
         }));
 }
 
+/// Clear the current selection of this seat
+pub fn clear_data_device_selection(seat: &Seat) {
+    // TODO: same question as in set_data_device_focus
+    seat.user_data().insert_if_missing(|| {
+        RefCell::new(SeatData::new(
+            seat.arc.log.new(o!("smithay_module" => "data_device_mgr")),
+        ))
+    });
+    let seat_data = seat.user_data().get::<RefCell<SeatData>>().unwrap();
+    seat_data.borrow_mut().set_selection(Selection::Empty);
+}
+
+/// Mime types offered by the current selection of this seat, if any
+///
+/// This includes selections set by the compositor through [`set_data_device_selection`].
+pub fn data_device_selection_mime_types(seat: &Seat) -> Option<Vec<String>> {
+    let seat_data = seat.user_data().get::<RefCell<SeatData>>()?.borrow();
+    match seat_data.selection {
+        Selection::Empty => None,
+        Selection::Client(ref source) => with_source_metadata(source, |meta| meta.mime_types.clone())
+            .ok()
+            .filter(|_| source.as_ref().is_alive()),
+        Selection::Compositor(ref meta) => Some(meta.mime_types.clone()),
+    }
+}
+
+/// Request the contents of the current selection of this seat, if it was set by a client
+///
+/// The client writes the contents in the requested mime type into `fd`, typically the write end
+/// of a pipe, whose other end the compositor reads from. As the data is transferred asynchronously,
+/// the clients have to be flushed for the request to reach the client. The fd is closed in any case.
+pub fn request_data_device_client_selection(
+    seat: &Seat,
+    mime_type: String,
+    fd: RawFd,
+) -> Result<(), SelectionRequestError> {
+    let result = match seat.user_data().get::<RefCell<SeatData>>() {
+        Some(seat_data) => match seat_data.borrow().selection {
+            Selection::Client(ref source) if source.as_ref().is_alive() => {
+                let valid = with_source_metadata(source, |meta| meta.mime_types.contains(&mime_type))
+                    .unwrap_or(false);
+                if valid {
+                    source.send(mime_type, fd);
+                    Ok(())
+                } else {
+                    Err(SelectionRequestError::InvalidMimetype)
+                }
+            }
+            Selection::Compositor(_) => Err(SelectionRequestError::ServerSideSelection),
+            _ => Err(SelectionRequestError::NoSelection),
+        },
+        None => Err(SelectionRequestError::NoSelection),
+    };
+    let _ = ::nix::unistd::close(fd);
+    result
+}
+
+/// The icon surface of the drag'n'drop a client currently performs on this seat, if any
+///
+/// The icon is to be drawn at the location of the pointer, offset by its [`DnDIconAttributes`].
+pub fn current_dnd_icon(seat: &Seat) -> Option<wl_surface::WlSurface> {
+    seat.user_data()
+        .get::<RefCell<SeatData>>()?
+        .borrow()
+        .dnd_icon
+        .clone()
+        .filter(|icon| icon.as_ref().is_alive())
+}
+
 /// Start a drag'n'drop from a resource controlled by the compositor
 ///
 /// You'll receive events generated by the interaction of clients with your
 /// drag'n'drop in the provided callback. See [`ServerDndEvent`] for details about
 /// which events can be generated and what response is expected from you to them.
+///
+/// The `metadata` describes the data offered to clients, which is transferred through
+/// [`ServerDndEvent::Send`]. Any icon following the pointer is up to the compositor to draw,
+/// until [`ServerDndEvent::Dropped`] or [`ServerDndEvent::Cancelled`] is received.
 pub fn start_dnd<C>(
     seat: &Seat,
     serial: Serial,
@@ -428,18 +559,27 @@ where
             icon,
             serial,
         } => {
-            /* TODO: handle the icon */
             let serial = Serial::from(serial);
             if let Some(pointer) = seat.get_pointer() {
                 if pointer.has_grab(serial) {
                     if let Some(ref icon) = icon {
                         if compositor::give_role(icon, DND_ICON_ROLE).is_err() {
-                            dd.as_ref().post_error(
+                            post_error!(
+                                dd.as_ref(),
                                 wl_data_device::Error::Role as u32,
                                 "Given surface already has an other role".into(),
                             );
                             return;
                         }
+                        let _ = compositor::with_states(icon, |states| {
+                            states
+                                .data_map
+                                .insert_if_missing_threadsafe(|| Mutex::new(DnDIconAttributes::default()))
+                        });
+                        compositor::add_commit_hook(icon, dnd_icon_commit_hook);
+                    }
+                    if let Some(seat_data) = seat.user_data().get::<RefCell<SeatData>>() {
+                        seat_data.borrow_mut().dnd_icon = icon.clone();
                     }
                     // The StartDrag is in response to a pointer implicit grab, all is good
                     (&mut *callback.borrow_mut())(DataDeviceEvent::DnDStarted {
@@ -520,3 +660,182 @@ pub fn default_action_chooser(available: DndAction, preferred: DndAction) -> Dnd
         DndAction::empty()
     }
 }
+
+#[cfg(all(test, feature = "testing"))]
+mod tests {
+    use super::{
+        current_dnd_icon, data_device_selection_mime_types, default_action_chooser, init_data_device,
+        request_data_device_client_selection, SelectionRequestError,
+    };
+    use crate::{
+        backend::renderer::{element::Element, utils::dnd_icon_render_elements},
+        testing::test_setup,
+        utils::Rectangle,
+        wayland::{
+            seat::{Seat, XkbConfig},
+            SERIAL_COUNTER,
+        },
+    };
+    use std::{
+        fs::File,
+        io::{Read, Write},
+        os::unix::io::FromRawFd,
+    };
+    use wayland_client::protocol::{wl_data_device_manager::WlDataDeviceManager, wl_data_source};
+    use wayland_server::protocol::wl_pointer;
+
+    #[test]
+    fn client_selection_is_read_by_compositor() {
+        let (mut server, mut client, (seat, keyboard)) = test_setup(|display| {
+            init_data_device(display, |_| {}, default_action_chooser, None);
+            let (mut seat, _) = Seat::new(display, "seat-0".into(), None);
+            let keyboard = seat
+                .add_keyboard(XkbConfig::default(), 200, 25, |_, _| {})
+                .unwrap();
+            (seat, keyboard)
+        });
+        let surface = client.create_surface().unwrap();
+        let test_seat = client.seat().unwrap();
+        server.roundtrip(&mut client, &mut ()).unwrap();
+        // only the client with keyboard focus may set the selection
+        let wl_surface = server.wl_surface(&client, &surface).unwrap();
+        keyboard.set_focus(Some(&wl_surface), SERIAL_COUNTER.next_serial());
+
+        let ddm = client
+            .globals()
+            .instantiate_exact::<WlDataDeviceManager>(3)
+            .unwrap();
+        let source = ddm.create_data_source();
+        source.offer("text/plain".into());
+        source.quick_assign(|_, event, _| {
+            if let wl_data_source::Event::Send { fd, .. } = event {
+                let mut file = unsafe { File::from_raw_fd(fd) };
+                file.write_all(b"hello").unwrap();
+            }
+        });
+        let data_device = ddm.get_data_device(test_seat.wl_seat());
+        data_device.set_selection(Some(&source), 0);
+        server.roundtrip(&mut client, &mut ()).unwrap();
+        assert_eq!(
+            data_device_selection_mime_types(&seat),
+            Some(vec![String::from("text/plain")])
+        );
+
+        let (read_fd, write_fd) = nix::unistd::pipe().unwrap();
+        assert!(matches!(
+            request_data_device_client_selection(&seat, "image/png".into(), write_fd),
+            Err(SelectionRequestError::InvalidMimetype)
+        ));
+        let _ = nix::unistd::close(read_fd);
+
+        let (read_fd, write_fd) = nix::unistd::pipe().unwrap();
+        request_data_device_client_selection(&seat, "text/plain".into(), write_fd).unwrap();
+        server.roundtrip(&mut client, &mut ()).unwrap();
+
+        let mut contents = String::new();
+        unsafe { File::from_raw_fd(read_fd) }
+            .read_to_string(&mut contents)
+            .unwrap();
+        assert_eq!(contents, "hello");
+    }
+
+    #[test]
+    fn dnd_icon_follows_pointer() {
+        let (mut server, mut client, (seat, pointer)) = test_setup(|display| {
+            init_data_device(display, |_| {}, default_action_chooser, None);
+            let (mut seat, _) = Seat::new(display, "seat-0".into(), None);
+            let pointer = seat.add_pointer(|_| {});
+            (seat, pointer)
+        });
+        let surface = client.create_surface().unwrap();
+        let icon = client.create_surface().unwrap();
+        let test_seat = client.seat().unwrap();
+        server.roundtrip(&mut client, &mut ()).unwrap();
+
+        // a drag may only be started during an implicit grab of the pointer
+        let wl_surface = server.wl_surface(&client, &surface).unwrap();
+        pointer.motion(
+            (16.0, 16.0).into(),
+            Some((wl_surface, (0, 0).into())),
+            SERIAL_COUNTER.next_serial(),
+            0,
+        );
+        let serial = SERIAL_COUNTER.next_serial();
+        pointer.button(0x110, wl_pointer::ButtonState::Pressed, serial, 0);
+
+        let ddm = client
+            .globals()
+            .instantiate_exact::<WlDataDeviceManager>(3)
+            .unwrap();
+        let source = ddm.create_data_source();
+        source.offer("text/plain".into());
+        let data_device = ddm.get_data_device(test_seat.wl_seat());
+        data_device.start_drag(
+            Some(&source),
+            surface.wl_surface(),
+            Some(icon.wl_surface()),
+            serial.into(),
+        );
+        let buffer = client.create_shm_buffer((8, 8), [0, 0, 255, 255]).unwrap();
+        icon.wl_surface().attach(Some(buffer.wl_buffer()), -4, -4);
+        icon.commit();
+        server.roundtrip(&mut client, &mut ()).unwrap();
+
+        let wl_icon = server.wl_surface(&client, &icon).unwrap();
+        assert_eq!(current_dnd_icon(&seat), Some(wl_icon.clone()));
+        let elements = dnd_icon_render_elements(&wl_icon, (16.0, 16.0).into(), 1.0);
+        assert_eq!(
+            elements[0].geometry(1.0),
+            Rectangle::from_loc_and_size((12, 12), (8, 8))
+        );
+
+        pointer.button(
+            0x110,
+            wl_pointer::ButtonState::Released,
+            SERIAL_COUNTER.next_serial(),
+            0,
+        );
+        assert_eq!(current_dnd_icon(&seat), None);
+
+        // the icon is also removed if the compositor ends the drag
+        let serial = SERIAL_COUNTER.next_serial();
+        pointer.button(0x110, wl_pointer::ButtonState::Pressed, serial, 0);
+        let source = ddm.create_data_source();
+        let icon = client.create_surface().unwrap();
+        data_device.start_drag(
+            Some(&source),
+            surface.wl_surface(),
+            Some(icon.wl_surface()),
+            serial.into(),
+        );
+        server.roundtrip(&mut client, &mut ()).unwrap();
+        assert!(current_dnd_icon(&seat).is_some());
+        pointer.unset_grab();
+        assert_eq!(current_dnd_icon(&seat), None);
+
+        // or if the client destroys the data source during the drag
+        let serial = SERIAL_COUNTER.next_serial();
+        pointer.button(0x111, wl_pointer::ButtonState::Pressed, serial, 0);
+        let source = ddm.create_data_source();
+        let icon = client.create_surface().unwrap();
+        data_device.start_drag(
+            Some(&source),
+            surface.wl_surface(),
+            Some(icon.wl_surface()),
+            serial.into(),
+        );
+        server.roundtrip(&mut client, &mut ()).unwrap();
+        assert!(current_dnd_icon(&seat).is_some());
+        source.destroy();
+        server.roundtrip(&mut client, &mut ()).unwrap();
+        let wl_surface = server.wl_surface(&client, &surface).unwrap();
+        pointer.motion(
+            (20.0, 20.0).into(),
+            Some((wl_surface, (0, 0).into())),
+            SERIAL_COUNTER.next_serial(),
+            0,
+        );
+        assert_eq!(current_dnd_icon(&seat), None);
+        assert!(!pointer.is_grabbed());
+    }
+}
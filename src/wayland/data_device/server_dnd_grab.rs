@@ -264,28 +264,32 @@ where
             Request::Destroy => {}
             Request::Finish => {
                 if !data.active {
-                    offer.as_ref().post_error(
+                    post_error!(
+                        offer.as_ref(),
                         wl_data_offer::Error::InvalidFinish as u32,
                         "Cannot finish a data offer that is no longer active.".into(),
                     );
                     return;
                 }
                 if !data.accepted {
-                    offer.as_ref().post_error(
+                    post_error!(
+                        offer.as_ref(),
                         wl_data_offer::Error::InvalidFinish as u32,
                         "Cannot finish a data offer that has not been accepted.".into(),
                     );
                     return;
                 }
                 if !data.dropped {
-                    offer.as_ref().post_error(
+                    post_error!(
+                        offer.as_ref(),
                         wl_data_offer::Error::InvalidFinish as u32,
                         "Cannot finish a data offer that has not been dropped.".into(),
                     );
                     return;
                 }
                 if data.chosen_action.is_empty() {
-                    offer.as_ref().post_error(
+                    post_error!(
+                        offer.as_ref(),
                         wl_data_offer::Error::InvalidFinish as u32,
                         "Cannot finish a data offer with no valid action.".into(),
                     );
@@ -304,7 +308,8 @@ where
                 if ![DndAction::None, DndAction::Move, DndAction::Copy, DndAction::Ask]
                     .contains(&preferred_action)
                 {
-                    offer.as_ref().post_error(
+                    post_error!(
+                        offer.as_ref(),
                         wl_data_offer::Error::InvalidAction as u32,
                         "Invalid preferred action.".into(),
                     );
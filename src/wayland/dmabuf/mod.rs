@@ -65,6 +65,43 @@ use crate::backend::allocator::{
 
 const DMABUF_VERSION: u32 = 3;
 
+/// Handler for dmabuf imports
+///
+/// Implement it on your compositor state and initialize the global with
+/// [`delegate_dmabuf!`](crate::delegate_dmabuf).
+pub trait DmabufHandler {
+    /// Validate and test the import of a dmabuf created by a client, returns if it was successful
+    fn dmabuf_imported(&mut self, dmabuf: &Dmabuf) -> bool;
+}
+
+/// Initializes the `zwp_linux_dmabuf_v1` global, delegating to the [`DmabufHandler`] implementation of your state
+///
+/// Takes the type of your state, the display, the supported formats and optionally a logger, and
+/// returns the result of [`init_dmabuf_global`]. See [delegating to your
+/// state](crate::wayland#delegating-to-your-state) for how the display has to be dispatched.
+#[macro_export]
+macro_rules! delegate_dmabuf {
+    ($ty:ty, $display:expr, $formats:expr) => {
+        $crate::delegate_dmabuf!($ty, $display, $formats, ::core::option::Option::None)
+    };
+    ($ty:ty, $display:expr, $formats:expr, $logger:expr) => {{
+        let log = $crate::wayland::delegate_logger($logger);
+        let delegate_log = log.clone();
+        $crate::wayland::dmabuf::init_dmabuf_global(
+            $display,
+            $formats,
+            move |dmabuf, mut ddata| {
+                if let Some(state) = $crate::wayland::delegated_state::<$ty>(&mut ddata, &delegate_log) {
+                    <$ty as $crate::wayland::dmabuf::DmabufHandler>::dmabuf_imported(state, dmabuf)
+                } else {
+                    false
+                }
+            },
+            log,
+        )
+    }};
+}
+
 /// Initialize a dmabuf global.
 ///
 /// You need to provide a vector of the supported formats, as well as a closure,
@@ -210,7 +247,8 @@ where
         // protocol checks:
         // Cannot reuse a params:
         if self.used {
-            params.as_ref().post_error(
+            post_error!(
+                params.as_ref(),
                 ParamError::AlreadyUsed as u32,
                 "This buffer_params has already been used to create a buffer.".into(),
             );
@@ -219,7 +257,8 @@ where
         // plane_idx is not too large
         if plane_idx >= self.max_planes {
             // plane_idx starts at 0
-            params.as_ref().post_error(
+            post_error!(
+                params.as_ref(),
                 ParamError::PlaneIdx as u32,
                 format!("Plane index {} is out of bounds.", plane_idx),
             );
@@ -227,7 +266,8 @@ where
         }
         // plane_idx has already been set
         if self.pending_planes.iter().any(|d| d.plane_idx == plane_idx) {
-            params.as_ref().post_error(
+            post_error!(
+                params.as_ref(),
                 ParamError::PlaneSet as u32,
                 format!("Plane index {} is already set.", plane_idx),
             );
@@ -254,7 +294,8 @@ where
     ) {
         // Cannot reuse a params:
         if self.used {
-            params.as_ref().post_error(
+            post_error!(
+                params.as_ref(),
                 ParamError::AlreadyUsed as u32,
                 "This buffer_params has already been used to create a buffer.".into(),
             );
@@ -265,7 +306,8 @@ where
         let format = match Fourcc::try_from(format) {
             Ok(format) => format,
             Err(_) => {
-                params.as_ref().post_error(
+                post_error!(
+                    params.as_ref(),
                     ParamError::InvalidFormat as u32,
                     format!("Format {:x} is not supported", format),
                 );
@@ -293,7 +335,8 @@ where
         let dmabuf = match buf.build() {
             Some(buf) => buf,
             None => {
-                params.as_ref().post_error(
+                post_error!(
+                    params.as_ref(),
                     ParamError::Incomplete as u32,
                     "Provided buffer is incomplete, it has zero planes".to_string(),
                 );
@@ -336,7 +379,8 @@ where
     ) {
         // Cannot reuse a params:
         if self.used {
-            params.as_ref().post_error(
+            post_error!(
+                params.as_ref(),
                 ParamError::AlreadyUsed as u32,
                 "This buffer_params has already been used to create a buffer.".into(),
             );
@@ -347,7 +391,8 @@ where
         let format = match Fourcc::try_from(format) {
             Ok(format) => format,
             Err(_) => {
-                params.as_ref().post_error(
+                post_error!(
+                    params.as_ref(),
                     ParamError::InvalidFormat as u32,
                     format!("Format {:x} is not supported", format),
                 );
@@ -375,7 +420,8 @@ where
         let dmabuf = match buf.build() {
             Some(buf) => buf,
             None => {
-                params.as_ref().post_error(
+                post_error!(
+                    params.as_ref(),
                     ParamError::Incomplete as u32,
                     "Provided buffer is incomplete, it has zero planes".to_string(),
                 );
@@ -393,7 +439,8 @@ where
                 self.log,
                 "Refusing creation of an invalid immediate dma wl_buffer, killing client."
             );
-            params.as_ref().post_error(
+            post_error!(
+                params.as_ref(),
                 ParamError::InvalidWlBuffer as u32,
                 "create_immed resulted in an invalid buffer.".into(),
             );
@@ -414,7 +461,8 @@ fn buffer_basic_checks(
     let _format = match formats.iter().find(|f| f.code == format) {
         Some(f) => f,
         None => {
-            params.as_ref().post_error(
+            post_error!(
+                params.as_ref(),
                 ParamError::InvalidFormat as u32,
                 format!("Format {:?}/{:x} is not supported.", format, format as u32),
             );
@@ -423,7 +471,8 @@ fn buffer_basic_checks(
     };
     // Width and height must be positivie
     if width < 1 || height < 1 {
-        params.as_ref().post_error(
+        post_error!(
+            params.as_ref(),
             ParamError::InvalidDimensions as u32,
             format!("Dimensions ({},{}) are not valid.", width, height),
         );
@@ -438,7 +487,8 @@ fn buffer_basic_checks(
             .and_then(|o| o.checked_add(plane.offset))
         {
             None => {
-                params.as_ref().post_error(
+                post_error!(
+                    params.as_ref(),
                     ParamError::OutOfBounds as u32,
                     format!("Size overflow for plane {}.", plane.plane_idx),
                 );
@@ -450,14 +500,16 @@ fn buffer_basic_checks(
             // reset the seek point
             let _ = ::nix::unistd::lseek(plane.fd.unwrap(), 0, ::nix::unistd::Whence::SeekSet);
             if plane.offset as libc::off_t > size {
-                params.as_ref().post_error(
+                post_error!(
+                    params.as_ref(),
                     ParamError::OutOfBounds as u32,
                     format!("Invalid offset {} for plane {}.", plane.offset, plane.plane_idx),
                 );
                 return false;
             }
             if (plane.offset + plane.stride) as libc::off_t > size {
-                params.as_ref().post_error(
+                post_error!(
+                    params.as_ref(),
                     ParamError::OutOfBounds as u32,
                     format!("Invalid stride {} for plane {}.", plane.stride, plane.plane_idx),
                 );
@@ -466,7 +518,8 @@ fn buffer_basic_checks(
             // Planes > 0 can be subsampled, in which case 'size' will be smaller
             // than expected.
             if plane.plane_idx == 0 && end as libc::off_t > size {
-                params.as_ref().post_error(
+                post_error!(
+                    params.as_ref(),
                     ParamError::OutOfBounds as u32,
                     format!(
                         "Invalid stride ({}) or height ({}) for plane {}.",
@@ -162,15 +162,18 @@ pub fn send_explicit_synchronization_error(attrs: &SurfaceData, error: ExplicitS
     if let Some(data) = attrs.data_map.get::<ESUserData>() {
         if let Some(sync_resource) = data.state.borrow().deref() {
             match error {
-                ExplicitSyncError::InvalidFence => sync_resource.as_ref().post_error(
+                ExplicitSyncError::InvalidFence => post_error!(
+                    sync_resource.as_ref(),
                     zwp_linux_surface_synchronization_v1::Error::InvalidFence as u32,
                     "The fence specified by the client could not be imported.".into(),
                 ),
-                ExplicitSyncError::UnsupportedBuffer => sync_resource.as_ref().post_error(
+                ExplicitSyncError::UnsupportedBuffer => post_error!(
+                    sync_resource.as_ref(),
                     zwp_linux_surface_synchronization_v1::Error::UnsupportedBuffer as u32,
                     "The buffer does not support explicit synchronization.".into(),
                 ),
-                ExplicitSyncError::NoBuffer => sync_resource.as_ref().post_error(
+                ExplicitSyncError::NoBuffer => post_error!(
+                    sync_resource.as_ref(),
                     zwp_linux_surface_synchronization_v1::Error::NoBuffer as u32,
                     "No buffer was attached.".into(),
                 ),
@@ -214,7 +217,8 @@ where
                         })
                         .unwrap_or(false);
                         if exists {
-                            explicit_sync.as_ref().post_error(
+                            post_error!(
+                                explicit_sync.as_ref(),
                                 zwp_linux_explicit_synchronization_v1::Error::SynchronizationExists as u32,
                                 "The surface already has a synchronization object associated.".into(),
                             );
@@ -240,7 +244,8 @@ fn implement_surface_sync(
     id.quick_assign(move |surface_sync, req, _| match req {
         zwp_linux_surface_synchronization_v1::Request::SetAcquireFence { fd } => {
             if !surface.as_ref().is_alive() {
-                surface_sync.as_ref().post_error(
+                post_error!(
+                    surface_sync.as_ref(),
                     zwp_linux_surface_synchronization_v1::Error::NoSurface as u32,
                     "The associated wl_surface was destroyed.".into(),
                 )
@@ -248,7 +253,8 @@ fn implement_surface_sync(
             with_states(&surface, |states| {
                 let mut pending = states.cached_state.pending::<ExplicitSyncState>();
                 if pending.acquire.is_some() {
-                    surface_sync.as_ref().post_error(
+                    post_error!(
+                        surface_sync.as_ref(),
                         zwp_linux_surface_synchronization_v1::Error::DuplicateFence as u32,
                         "Multiple fences added for a single surface commit.".into(),
                     )
@@ -260,7 +266,8 @@ fn implement_surface_sync(
         }
         zwp_linux_surface_synchronization_v1::Request::GetRelease { release } => {
             if !surface.as_ref().is_alive() {
-                surface_sync.as_ref().post_error(
+                post_error!(
+                    surface_sync.as_ref(),
                     zwp_linux_surface_synchronization_v1::Error::NoSurface as u32,
                     "The associated wl_surface was destroyed.".into(),
                 )
@@ -268,7 +275,8 @@ fn implement_surface_sync(
             with_states(&surface, |states| {
                 let mut pending = states.cached_state.pending::<ExplicitSyncState>();
                 if pending.release.is_some() {
-                    surface_sync.as_ref().post_error(
+                    post_error!(
+                        surface_sync.as_ref(),
                         zwp_linux_surface_synchronization_v1::Error::DuplicateRelease as u32,
                         "Multiple releases added for a single surface commit.".into(),
                     )
@@ -19,6 +19,60 @@
 //! are given as input an enum specifying the event that occurred, as well as the
 //! [`DispatchData`](wayland_server::DispatchData) from `wayland_server`.
 //!
+//! ## Delegating to your state
+//!
+//! If the state of your compositor is passed as dispatch data to
+//! [`Display::dispatch`](wayland_server::Display::dispatch), the callbacks of most globals
+//! can instead be provided by implementing a handler trait on your state. The matching
+//! `delegate_*!` macro then initializes the global with one line per protocol:
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::{delegate_compositor, delegate_xdg_shell};
+//! use smithay::wayland::{
+//!     compositor::CompositorHandler,
+//!     shell::xdg::{XdgRequest, XdgShellHandler},
+//! };
+//! use wayland_server::{protocol::wl_surface::WlSurface, Display};
+//!
+//! struct State {
+//!     /* ... */
+//! }
+//!
+//! impl CompositorHandler for State {
+//!     fn commit(&mut self, surface: &WlSurface) {
+//!         /* handle the commit */
+//!     }
+//! }
+//!
+//! impl XdgShellHandler for State {
+//!     fn xdg_shell_request(&mut self, request: XdgRequest) {
+//!         /* handle the request */
+//!     }
+//! }
+//!
+//! let mut display = Display::new();
+//! delegate_compositor!(State, &mut display);
+//! let (xdg_shell_state, _xdg_shell_global) = delegate_xdg_shell!(State, &mut display);
+//!
+//! let mut state = State {};
+//! display.dispatch(std::time::Duration::from_millis(0), &mut state).unwrap();
+//! ```
+//!
+//! Macros are provided for the [`compositor`] (`delegate_compositor!`), the
+//! [`xdg`](shell::xdg) (`delegate_xdg_shell!`), [`xdg decoration`](shell::xdg::decoration)
+//! (`delegate_xdg_decoration!`), [`wl_shell`](shell::legacy) (`delegate_wl_shell!`) and
+//! [`wlr_layer`](shell::wlr_layer) (`delegate_layer_shell!`) shells, [`xdg_activation`]
+//! (`delegate_xdg_activation!`) and [`dmabuf`] (`delegate_dmabuf!`).
+//!
+//! The display must always be dispatched with a `&mut` reference to the state type given to these
+//! macros. Dispatching it with any other dispatch data is a bug of the compositor: it triggers a
+//! debug assertion, release builds log an error and skip the callback.
+//!
+//! The [`seat`], [`data_device`], [`shm`] and [`output`] globals have no such macros. The shm and
+//! output globals do not have any callbacks, the callbacks of seats and the data device are not
+//! invoked with dispatch data and keep being provided as closures.
+//!
 //! ## Provided helpers
 //!
 //! ### Core functionality
@@ -52,7 +106,7 @@
 //! over the synchronization for accessing graphics buffer with the compositor, for low-latency
 //! rendering. It is however still experimental, and largely untested.
 
-use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::atomic::{AtomicU32, Ordering};
 
 pub mod compositor;
 pub mod data_device;
@@ -69,23 +123,13 @@ pub mod xdg_foreign;
 /// A global [`SerialCounter`] for use in your compositor.
 ///
 /// Is is also used internally by some parts of Smithay.
-pub static SERIAL_COUNTER: SerialCounter = SerialCounter {
-    serial: AtomicUsize::new(0),
-};
+pub static SERIAL_COUNTER: SerialCounter = SerialCounter::new();
 
 /// A serial type, whose comparison takes into account the wrapping-around behavior of the
 /// underlying counter.
-#[derive(Debug, Copy, Clone)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
 pub struct Serial(u32);
 
-impl PartialEq for Serial {
-    fn eq(&self, other: &Self) -> bool {
-        self.0 == other.0
-    }
-}
-
-impl Eq for Serial {}
-
 impl PartialOrd for Serial {
     fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
         let distance = if self.0 > other.0 {
@@ -114,6 +158,14 @@ impl From<Serial> for u32 {
     }
 }
 
+impl Serial {
+    /// Checks if this serial was generated after or at the same time as the given serial,
+    /// taking the wrapping-around of the counter into account
+    pub fn is_no_older_than(&self, other: &Serial) -> bool {
+        other <= self
+    }
+}
+
 /// A counter for generating serials, for use in the client protocol
 ///
 /// A global instance of this counter is available as the `SERIAL_COUNTER`
@@ -122,26 +174,66 @@ impl From<Serial> for u32 {
 ///
 /// The counter will wrap around on overflow, ensuring it can run for as long
 /// as needed.
-#[derive(Debug)]
+#[derive(Debug, Default)]
 pub struct SerialCounter {
-    // TODO: replace with an AtomicU32 when stabilized
-    serial: AtomicUsize,
+    serial: AtomicU32,
 }
 
 impl SerialCounter {
+    /// Create a new counter starting at zero
+    pub const fn new() -> SerialCounter {
+        SerialCounter {
+            serial: AtomicU32::new(0),
+        }
+    }
+
     /// Retrieve the next serial from the counter
     pub fn next_serial(&self) -> Serial {
-        Serial(self.serial.fetch_add(1, Ordering::AcqRel) as u32)
+        Serial(self.serial.fetch_add(1, Ordering::AcqRel))
     }
 }
 
+/// Resolves the logger of a `delegate_*!` macro
+#[doc(hidden)]
+pub fn delegate_logger<L>(logger: L) -> ::slog::Logger
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    crate::slog_or_fallback(logger)
+}
+
+/// Retrieves the delegated state from the dispatch data of a `delegate_*!` callback
+///
+/// Dispatch data of another type is a bug of the compositor, it fails a debug assertion
+/// and logs an error otherwise.
+#[doc(hidden)]
+pub fn delegated_state<'a, T: 'static>(
+    ddata: &'a mut wayland_server::DispatchData<'_>,
+    log: &::slog::Logger,
+) -> Option<&'a mut T> {
+    let state = ddata.get::<T>();
+    if state.is_none() {
+        slog::error!(
+            log,
+            "Dispatch data is not the delegated state, skipping the callback";
+            "state" => std::any::type_name::<T>()
+        );
+    }
+    debug_assert!(
+        state.is_some(),
+        "The display has to be dispatched with the delegated state {}",
+        std::any::type_name::<T>()
+    );
+    state
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
     fn create_serial_counter(initial_value: u32) -> SerialCounter {
         SerialCounter {
-            serial: AtomicUsize::new(initial_value as usize),
+            serial: AtomicU32::new(initial_value),
         }
     }
 
@@ -185,4 +277,15 @@ mod tests {
 
         assert!(serial1 < serial2);
     }
+
+    #[test]
+    fn serial_no_older_than() {
+        let counter = create_serial_counter(u32::MAX);
+        let serial1 = counter.next_serial();
+        let serial2 = counter.next_serial();
+
+        assert!(serial2.is_no_older_than(&serial1));
+        assert!(serial1.is_no_older_than(&serial1));
+        assert!(!serial1.is_no_older_than(&serial2));
+    }
 }
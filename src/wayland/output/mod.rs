@@ -66,7 +66,7 @@ use wayland_server::{
 
 use slog::{info, o, trace, warn};
 
-use crate::utils::{Logical, Physical, Point, Raw, Size};
+use crate::utils::{user_data::UserDataMap, Logical, Physical, Point, Raw, Size};
 
 use self::xdg::XdgOutput;
 
@@ -166,9 +166,17 @@ impl Inner {
 ///
 /// This handle is stored in the event loop, and allows you to notify clients
 /// about any change in the properties of this output.
-#[derive(Debug)]
+///
+/// Cloning the handle does not create a new global, the clones refer to the same output.
+#[derive(Debug, Clone)]
 pub struct Output {
-    inner: Arc<Mutex<Inner>>,
+    data: Arc<OutputData>,
+}
+
+#[derive(Debug)]
+struct OutputData {
+    inner: Mutex<Inner>,
+    user_data: UserDataMap,
 }
 
 impl Output {
@@ -190,38 +198,41 @@ impl Output {
 
         info!(log, "Creating new wl_output"; "name" => &name);
 
-        let inner = Arc::new(Mutex::new(Inner {
-            name,
-            log,
-            instances: Vec::new(),
-            physical,
-            location: (0, 0).into(),
-            transform: Transform::Normal,
-            scale: 1,
-            modes: Vec::new(),
-            current_mode: None,
-            preferred_mode: None,
-            xdg_output: None,
-        }));
-
-        let output = Output { inner: inner.clone() };
+        let data = Arc::new(OutputData {
+            inner: Mutex::new(Inner {
+                name,
+                log,
+                instances: Vec::new(),
+                physical,
+                location: (0, 0).into(),
+                transform: Transform::Normal,
+                scale: 1,
+                modes: Vec::new(),
+                current_mode: None,
+                preferred_mode: None,
+                xdg_output: None,
+            }),
+            user_data: UserDataMap::new(),
+        });
+
+        let output = Output { data: data.clone() };
 
         let global = display.create_global(
             3,
             Filter::new(move |(output, _version): (Main<WlOutput>, _), _, _| {
                 output.assign_destructor(Filter::new(|output: WlOutput, _, _| {
-                    let inner = output.as_ref().user_data().get::<Arc<Mutex<Inner>>>().unwrap();
-                    inner
+                    let data = output.as_ref().user_data().get::<Arc<OutputData>>().unwrap();
+                    data.inner
                         .lock()
                         .unwrap()
                         .instances
                         .retain(|o| !o.as_ref().equals(output.as_ref()));
                 }));
                 output.as_ref().user_data().set_threadsafe({
-                    let inner = inner.clone();
-                    move || inner
+                    let data = data.clone();
+                    move || data
                 });
-                inner.lock().unwrap().new_global(output.deref().clone());
+                data.inner.lock().unwrap().new_global(output.deref().clone());
             }),
         );
 
@@ -233,9 +244,47 @@ impl Output {
         output
             .as_ref()
             .user_data()
-            .get::<Arc<Mutex<Inner>>>()
+            .get::<Arc<OutputData>>()
             .cloned()
-            .map(|inner| Output { inner })
+            .map(|data| Output { data })
+    }
+
+    /// Access the `UserDataMap` associated with this `Output`
+    ///
+    /// It is shared by all handles to the output, including the ones retrieved
+    /// through [`Output::from_resource`].
+    pub fn user_data(&self) -> &UserDataMap {
+        &self.data.user_data
+    }
+
+    /// Returns the name of this output
+    pub fn name(&self) -> String {
+        self.data.inner.lock().unwrap().name.clone()
+    }
+
+    /// Returns the currently advertised mode of this output, if any
+    pub fn current_mode(&self) -> Option<Mode> {
+        self.data.inner.lock().unwrap().current_mode
+    }
+
+    /// Returns the preferred mode of this output, if any
+    pub fn preferred_mode(&self) -> Option<Mode> {
+        self.data.inner.lock().unwrap().preferred_mode
+    }
+
+    /// Returns the currently advertised transformation of this output
+    pub fn current_transform(&self) -> Transform {
+        self.data.inner.lock().unwrap().transform
+    }
+
+    /// Returns the currently advertised scale of this output
+    pub fn current_scale(&self) -> i32 {
+        self.data.inner.lock().unwrap().scale
+    }
+
+    /// Returns the currently advertised location of this output
+    pub fn current_location(&self) -> Point<i32, Logical> {
+        self.data.inner.lock().unwrap().location
     }
 
     /// Sets the preferred mode of this output
@@ -243,7 +292,7 @@ impl Output {
     /// If the provided mode was not previously known to this output, it is added to its
     /// internal list.
     pub fn set_preferred(&self, mode: Mode) {
-        let mut inner = self.inner.lock().unwrap();
+        let mut inner = self.data.inner.lock().unwrap();
         inner.preferred_mode = Some(mode);
         if inner.modes.iter().all(|&m| m != mode) {
             inner.modes.push(mode);
@@ -252,7 +301,7 @@ impl Output {
 
     /// Adds a mode to the list of known modes to this output
     pub fn add_mode(&self, mode: Mode) {
-        let mut inner = self.inner.lock().unwrap();
+        let mut inner = self.data.inner.lock().unwrap();
         if inner.modes.iter().all(|&m| m != mode) {
             inner.modes.push(mode);
         }
@@ -263,7 +312,7 @@ impl Output {
     /// It will not de-advertise it from existing clients (the protocol does not
     /// allow it), but it won't be advertised to now clients from now on.
     pub fn delete_mode(&self, mode: Mode) {
-        let mut inner = self.inner.lock().unwrap();
+        let mut inner = self.data.inner.lock().unwrap();
         inner.modes.retain(|&m| m != mode);
         if inner.current_mode == Some(mode) {
             inner.current_mode = None;
@@ -289,7 +338,7 @@ impl Output {
         new_scale: Option<i32>,
         new_location: Option<Point<i32, Logical>>,
     ) {
-        let mut inner = self.inner.lock().unwrap();
+        let mut inner = self.data.inner.lock().unwrap();
         if let Some(mode) = new_mode {
             if inner.modes.iter().all(|&m| m != mode) {
                 inner.modes.push(mode);
@@ -336,7 +385,8 @@ impl Output {
 
     /// Check is given [`wl_output`](WlOutput) instance is managed by this [`Output`].
     pub fn owns(&self, output: &WlOutput) -> bool {
-        self.inner
+        self.data
+            .inner
             .lock()
             .unwrap()
             .instances
@@ -350,7 +400,8 @@ impl Output {
     where
         F: FnMut(&WlOutput),
     {
-        self.inner
+        self.data
+            .inner
             .lock()
             .unwrap()
             .instances
@@ -378,3 +429,11 @@ impl Output {
         }
     }
 }
+
+impl PartialEq for Output {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.data, &other.data)
+    }
+}
+
+impl Eq for Output {}
@@ -102,6 +102,8 @@ struct KbdInternal {
     known_kbds: Vec<WlKeyboard>,
     focus: Option<WlSurface>,
     pressed_keys: Vec<u32>,
+    last_key_press: Option<Serial>,
+    last_enter: Option<Serial>,
     mods_state: ModifiersState,
     keymap: xkb::Keymap,
     state: xkb::State,
@@ -117,6 +119,8 @@ impl fmt::Debug for KbdInternal {
             .field("known_kbds", &self.known_kbds)
             .field("focus", &self.focus)
             .field("pressed_keys", &self.pressed_keys)
+            .field("last_key_press", &self.last_key_press)
+            .field("last_enter", &self.last_enter)
             .field("mods_state", &self.mods_state)
             .field("keymap", &self.keymap.get_raw_ptr())
             .field("state", &self.state.get_raw_ptr())
@@ -160,6 +164,8 @@ impl KbdInternal {
             known_kbds: Vec::new(),
             focus: None,
             pressed_keys: Vec::new(),
+            last_key_press: None,
+            last_enter: None,
             mods_state: ModifiersState::default(),
             keymap,
             state,
@@ -401,7 +407,10 @@ impl KeyboardHandle {
             None
         };
         let wl_state = match state {
-            KeyState::Pressed => WlKeyState::Pressed,
+            KeyState::Pressed => {
+                guard.last_key_press = Some(serial);
+                WlKeyState::Pressed
+            }
             KeyState::Released => WlKeyState::Released,
         };
         guard.with_focused_kbds(|kbd, _| {
@@ -460,6 +469,7 @@ impl KeyboardHandle {
                 focus_hook(focus.as_ref());
             }
             if guard.focus.is_some() {
+                guard.last_enter = Some(serial);
                 trace!(self.arc.logger, "Focus set to new surface");
             } else {
                 trace!(self.arc.logger, "Focus unset");
@@ -481,6 +491,21 @@ impl KeyboardHandle {
             .unwrap_or(false)
     }
 
+    /// Check if the given serial is the one of the last key press forwarded to the clients
+    ///
+    /// Requests triggered by a key press, e.g. opening a popup, can be validated with this.
+    pub fn is_last_key_press(&self, serial: Serial) -> bool {
+        self.arc.internal.borrow().last_key_press == Some(serial)
+    }
+
+    /// Returns the serial of the last time the focus was set to a surface
+    ///
+    /// Requests of the focused client carrying an older serial were triggered by input the
+    /// client received before it was focused, see [`Serial::is_no_older_than`].
+    pub fn last_enter(&self) -> Option<Serial> {
+        self.arc.internal.borrow().last_enter
+    }
+
     /// Register a new keyboard to this handler
     ///
     /// The keymap will automatically be sent to it
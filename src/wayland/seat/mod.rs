@@ -51,9 +51,11 @@ pub use self::{
 
 use wayland_server::{
     protocol::{wl_seat, wl_surface},
-    Display, Filter, Global, Main, UserDataMap,
+    Display, Filter, Global, Main,
 };
 
+use crate::utils::user_data::UserDataMap;
+
 #[derive(Debug)]
 struct Inner {
     pointer: Option<PointerHandle>,
@@ -68,12 +70,11 @@ pub(crate) struct SeatRc {
     name: String,
 }
 
-// UserDataMap does not implement debug, so we have to impl Debug manually
 impl fmt::Debug for SeatRc {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         f.debug_struct("SeatRc")
             .field("inner", &self.inner)
-            .field("user_data", &"...")
+            .field("user_data", &self.user_data)
             .field("log", &self.log)
             .field("name", &self.name)
             .finish()
@@ -598,7 +598,8 @@ pub(crate) fn implement_pointer(pointer: Main<WlPointer>, handle: Option<&Pointe
                                     if compositor::give_role(&surface, CURSOR_IMAGE_ROLE).is_err()
                                         && compositor::get_role(&surface) != Some(CURSOR_IMAGE_ROLE)
                                     {
-                                        pointer.as_ref().post_error(
+                                        post_error!(
+                                            pointer.as_ref(),
                                             wl_pointer::Error::Role as u32,
                                             "Given wl_surface has another role.".into(),
                                         );
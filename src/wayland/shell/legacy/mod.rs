@@ -274,6 +274,40 @@ impl ShellState {
     }
 }
 
+/// Handler for `wl_shell` requests
+///
+/// Implement it on your compositor state and initialize the global with
+/// [`delegate_wl_shell!`](crate::delegate_wl_shell).
+pub trait WlShellHandler {
+    /// A client sent a request your compositor needs to handle
+    fn wl_shell_request(&mut self, request: ShellRequest);
+}
+
+/// Initializes the `wl_shell` global, delegating to the [`WlShellHandler`] implementation of your state
+///
+/// Takes the type of your state, the display and optionally a logger, and returns the result of
+/// [`wl_shell_init`]. See [delegating to your state](crate::wayland#delegating-to-your-state) for
+/// how the display has to be dispatched.
+#[macro_export]
+macro_rules! delegate_wl_shell {
+    ($ty:ty, $display:expr) => {
+        $crate::delegate_wl_shell!($ty, $display, ::core::option::Option::None)
+    };
+    ($ty:ty, $display:expr, $logger:expr) => {{
+        let log = $crate::wayland::delegate_logger($logger);
+        let delegate_log = log.clone();
+        $crate::wayland::shell::legacy::wl_shell_init(
+            $display,
+            move |request, mut ddata| {
+                if let Some(state) = $crate::wayland::delegated_state::<$ty>(&mut ddata, &delegate_log) {
+                    <$ty as $crate::wayland::shell::legacy::WlShellHandler>::wl_shell_request(state, request);
+                }
+            },
+            log,
+        )
+    }};
+}
+
 /// Create a new `wl_shell` global
 pub fn wl_shell_init<L, Impl>(
     display: &mut Display,
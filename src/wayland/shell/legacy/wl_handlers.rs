@@ -29,9 +29,11 @@ pub(crate) fn implement_shell<Impl>(
             _ => unreachable!(),
         };
         if compositor::give_role(&surface, WL_SHELL_SURFACE_ROLE).is_err() {
-            shell
-                .as_ref()
-                .post_error(wl_shell::Error::Role as u32, "Surface already has a role.".into());
+            post_error!(
+                shell.as_ref(),
+                wl_shell::Error::Role as u32,
+                "Surface already has a role.".into()
+            );
             return;
         }
         compositor::with_states(&surface, |states| {
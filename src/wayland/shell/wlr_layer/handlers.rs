@@ -29,13 +29,14 @@ pub(super) fn layer_shell_implementation(
             let layer = match Layer::try_from(layer) {
                 Ok(layer) => layer,
                 Err((err, msg)) => {
-                    id.as_ref().post_error(err as u32, msg);
+                    post_error!(id.as_ref(), err as u32, msg);
                     return;
                 }
             };
 
             if compositor::give_role(&surface, LAYER_SURFACE_ROLE).is_err() {
-                shell.as_ref().post_error(
+                post_error!(
+                    shell.as_ref(),
                     zwlr_layer_shell_v1::Error::Role as u32,
                     "Surface already has a role.".into(),
                 );
@@ -63,7 +64,8 @@ pub(super) fn layer_shell_implementation(
                     let pending = states.cached_state.pending::<LayerSurfaceCachedState>();
 
                     if pending.size.w == 0 && !pending.anchor.anchored_horizontally() {
-                        guard.surface.as_ref().post_error(
+                        post_error!(
+                            guard.surface.as_ref(),
                             zwlr_layer_surface_v1::Error::InvalidSize as u32,
                             "width 0 requested without setting left and right anchors".into(),
                         );
@@ -71,7 +73,8 @@ pub(super) fn layer_shell_implementation(
                     }
 
                     if pending.size.h == 0 && !pending.anchor.anchored_vertically() {
-                        guard.surface.as_ref().post_error(
+                        post_error!(
+                            guard.surface.as_ref(),
                             zwlr_layer_surface_v1::Error::InvalidSize as u32,
                             "height 0 requested without setting top and bottom anchors".into(),
                         );
@@ -156,7 +159,7 @@ fn layer_surface_implementation(
                     });
                 }
                 Err((err, msg)) => {
-                    layer_surface.as_ref().post_error(err as u32, msg);
+                    post_error!(layer_surface.as_ref(), err as u32, msg);
                 }
             };
         }
@@ -190,7 +193,7 @@ fn layer_surface_implementation(
                     });
                 }
                 Err((err, msg)) => {
-                    layer_surface.as_ref().post_error(err as u32, msg);
+                    post_error!(layer_surface.as_ref(), err as u32, msg);
                 }
             };
         }
@@ -202,7 +205,7 @@ fn layer_surface_implementation(
                     });
                 }
                 Err((err, msg)) => {
-                    layer_surface.as_ref().post_error(err as u32, msg);
+                    post_error!(layer_surface.as_ref(), err as u32, msg);
                 }
             };
         }
@@ -256,7 +259,8 @@ fn layer_surface_implementation(
             let configure = match found_configure {
                 Some(configure) => configure,
                 None => {
-                    layer_surface.as_ref().post_error(
+                    post_error!(
+                        layer_surface.as_ref(),
                         zwlr_layer_surface_v1::Error::InvalidSurfaceState as u32,
                         format!("wrong configure serial: {}", <u32>::from(serial)),
                     );
@@ -170,6 +170,42 @@ struct ShellUserData {
     shell_state: Arc<Mutex<LayerShellState>>,
 }
 
+/// Handler for `wlr_layer_shell` requests
+///
+/// Implement it on your compositor state and initialize the global with
+/// [`delegate_layer_shell!`](crate::delegate_layer_shell).
+pub trait WlrLayerShellHandler {
+    /// A client sent a request your compositor needs to handle
+    fn layer_shell_request(&mut self, request: LayerShellRequest);
+}
+
+/// Initializes the `zwlr_layer_shell_v1` global, delegating to the [`WlrLayerShellHandler`] implementation of your state
+///
+/// Takes the type of your state, the display and optionally a logger, and returns the result of
+/// [`wlr_layer_shell_init`]. See [delegating to your
+/// state](crate::wayland#delegating-to-your-state) for how the display has to be dispatched.
+#[macro_export]
+macro_rules! delegate_layer_shell {
+    ($ty:ty, $display:expr) => {
+        $crate::delegate_layer_shell!($ty, $display, ::core::option::Option::None)
+    };
+    ($ty:ty, $display:expr, $logger:expr) => {{
+        let log = $crate::wayland::delegate_logger($logger);
+        let delegate_log = log.clone();
+        $crate::wayland::shell::wlr_layer::wlr_layer_shell_init(
+            $display,
+            move |request, mut ddata| {
+                if let Some(state) = $crate::wayland::delegated_state::<$ty>(&mut ddata, &delegate_log) {
+                    <$ty as $crate::wayland::shell::wlr_layer::WlrLayerShellHandler>::layer_shell_request(
+                        state, request,
+                    );
+                }
+            },
+            log,
+        )
+    }};
+}
+
 /// Create a new `wlr_layer_shell` globals
 pub fn wlr_layer_shell_init<L, Impl>(
     display: &mut Display,
@@ -326,7 +362,8 @@ impl LayerSurface {
         })
         .unwrap();
         if !configured {
-            self.shell_surface.as_ref().post_error(
+            post_error!(
+                self.shell_surface.as_ref(),
                 zwlr_layer_shell_v1::Error::AlreadyConstructed as u32,
                 "layer_surface has never been configured".into(),
             );
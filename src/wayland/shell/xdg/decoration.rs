@@ -65,6 +65,40 @@ pub enum XdgDecorationRequest {
     },
 }
 
+/// Handler for `xdg_decoration` requests
+///
+/// Implement it on your compositor state and initialize the global with
+/// [`delegate_xdg_decoration!`](crate::delegate_xdg_decoration).
+pub trait XdgDecorationHandler {
+    /// A client sent a request your compositor needs to handle
+    fn xdg_decoration_request(&mut self, request: XdgDecorationRequest);
+}
+
+/// Initializes the `zxdg_decoration_manager_v1` global, delegating to the [`XdgDecorationHandler`] implementation of your state
+///
+/// Takes the type of your state, the display and optionally a logger, and returns the result of
+/// [`init_xdg_decoration_manager`]. See [delegating to your
+/// state](crate::wayland#delegating-to-your-state) for how the display has to be dispatched.
+#[macro_export]
+macro_rules! delegate_xdg_decoration {
+    ($ty:ty, $display:expr) => {
+        $crate::delegate_xdg_decoration!($ty, $display, ::core::option::Option::None)
+    };
+    ($ty:ty, $display:expr, $logger:expr) => {{
+        let log = $crate::wayland::delegate_logger($logger);
+        let delegate_log = log.clone();
+        $crate::wayland::shell::xdg::decoration::init_xdg_decoration_manager(
+            $display,
+            move |request, mut ddata| {
+                if let Some(state) = $crate::wayland::delegated_state::<$ty>(&mut ddata, &delegate_log) {
+                    <$ty as $crate::wayland::shell::xdg::decoration::XdgDecorationHandler>::xdg_decoration_request(state, request);
+                }
+            },
+            log,
+        )
+    }};
+}
+
 /// Create a new XDG Decoration Manager global
 pub fn init_xdg_decoration_manager<L, Impl>(
     display: &mut Display,
@@ -92,7 +126,7 @@ where
                                     *data.decoration.borrow_mut() = Some(id.deref().clone());
                                 } else {
                                     use wayland_protocols::unstable::xdg_decoration::v1::server::zxdg_toplevel_decoration_v1::Error;
-                                    id.as_ref().post_error(Error::AlreadyConstructed as u32, "toplevel decoration is already constructed".to_string());
+                                    post_error!(id.as_ref(), Error::AlreadyConstructed as u32, "toplevel decoration is already constructed".to_string());
                                 }
 
                                 let toplevel = ToplevelSurface {
@@ -61,6 +61,7 @@
 //! the subhandler you provided, or via methods on the [`ShellState`]
 //! that you are given (in an `Arc<Mutex<_>>`) as return value of the `init` function.
 
+use crate::utils::user_data::UserDataMap;
 use crate::utils::DeadResource;
 use crate::utils::{Logical, Point, Rectangle, Size};
 use crate::wayland::compositor;
@@ -81,7 +82,7 @@ use wayland_protocols::xdg_shell::server::{xdg_popup, xdg_positioner, xdg_toplev
 use wayland_server::DispatchData;
 use wayland_server::{
     protocol::{wl_output, wl_seat, wl_surface},
-    Display, Filter, Global, UserDataMap,
+    Display, Filter, Global,
 };
 
 use self::xdg_handlers::ShellSurfaceUserData;
@@ -789,6 +790,40 @@ impl Clone for ShellData {
     }
 }
 
+/// Handler for `xdg_shell` requests
+///
+/// Implement it on your compositor state and initialize the global with
+/// [`delegate_xdg_shell!`](crate::delegate_xdg_shell).
+pub trait XdgShellHandler {
+    /// A client sent a request your compositor needs to handle
+    fn xdg_shell_request(&mut self, request: XdgRequest);
+}
+
+/// Initializes the `xdg_shell` global, delegating to the [`XdgShellHandler`] implementation of your state
+///
+/// Takes the type of your state, the display and optionally a logger, and returns the result of
+/// [`xdg_shell_init`]. See [delegating to your state](crate::wayland#delegating-to-your-state) for
+/// how the display has to be dispatched.
+#[macro_export]
+macro_rules! delegate_xdg_shell {
+    ($ty:ty, $display:expr) => {
+        $crate::delegate_xdg_shell!($ty, $display, ::core::option::Option::None)
+    };
+    ($ty:ty, $display:expr, $logger:expr) => {{
+        let log = $crate::wayland::delegate_logger($logger);
+        let delegate_log = log.clone();
+        $crate::wayland::shell::xdg::xdg_shell_init(
+            $display,
+            move |request, mut ddata| {
+                if let Some(state) = $crate::wayland::delegated_state::<$ty>(&mut ddata, &delegate_log) {
+                    <$ty as $crate::wayland::shell::xdg::XdgShellHandler>::xdg_shell_request(state, request);
+                }
+            },
+            log,
+        )
+    }};
+}
+
 /// Create a new `xdg_shell` global
 pub fn xdg_shell_init<L, Impl>(
     display: &mut Display,
@@ -1115,7 +1150,8 @@ impl ToplevelSurface {
                 .user_data()
                 .get::<self::xdg_handlers::ShellSurfaceUserData>()
                 .unwrap();
-            data.xdg_surface.as_ref().post_error(
+            post_error!(
+                data.xdg_surface.as_ref(),
                 xdg_surface::Error::NotConstructed as u32,
                 "Surface has not been configured yet.".into(),
             );
@@ -1421,7 +1457,8 @@ impl PopupSurface {
                 .user_data()
                 .get::<self::xdg_handlers::ShellSurfaceUserData>()
                 .unwrap();
-            data.xdg_surface.as_ref().post_error(
+            post_error!(
+                data.xdg_surface.as_ref(),
                 xdg_surface::Error::NotConstructed as u32,
                 "Surface has not been configured yet.".into(),
             );
@@ -1476,7 +1513,8 @@ impl PopupSurface {
                 .user_data()
                 .get::<self::xdg_handlers::ShellSurfaceUserData>()
                 .unwrap();
-            data.xdg_surface.as_ref().post_error(
+            post_error!(
+                data.xdg_surface.as_ref(),
                 xdg_surface::Error::NotConstructed as u32,
                 "Surface has not been configured yet.".into(),
             );
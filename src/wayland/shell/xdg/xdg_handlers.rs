@@ -125,7 +125,8 @@ fn implement_positioner(positioner: Main<xdg_positioner::XdgPositioner>) -> xdg_
             }
             xdg_positioner::Request::SetSize { width, height } => {
                 if width < 1 || height < 1 {
-                    positioner.as_ref().post_error(
+                    post_error!(
+                        positioner.as_ref(),
                         xdg_positioner::Error::InvalidInput as u32,
                         "Invalid size for positioner.".into(),
                     );
@@ -135,7 +136,8 @@ fn implement_positioner(positioner: Main<xdg_positioner::XdgPositioner>) -> xdg_
             }
             xdg_positioner::Request::SetAnchorRect { x, y, width, height } => {
                 if width < 1 || height < 1 {
-                    positioner.as_ref().post_error(
+                    post_error!(
+                        positioner.as_ref(),
                         xdg_positioner::Error::InvalidInput as u32,
                         "Invalid size for positioner's anchor rectangle.".into(),
                     );
@@ -208,7 +210,8 @@ fn destroy_surface(surface: xdg_surface::XdgSurface) {
     }
 
     if data.has_active_role.load(Ordering::Acquire) {
-        data.wm_base.as_ref().post_error(
+        post_error!(
+            data.wm_base.as_ref(),
             xdg_wm_base::Error::Role as u32,
             "xdg_surface was destroyed before its role object".into(),
         );
@@ -235,7 +238,8 @@ fn xdg_surface_implementation(
             let shell = &data.wm_base;
 
             if compositor::give_role(surface, XDG_TOPLEVEL_ROLE).is_err() {
-                shell.as_ref().post_error(
+                post_error!(
+                    shell.as_ref(),
                     xdg_wm_base::Error::Role as u32,
                     "Surface already has a role.".into(),
                 );
@@ -305,7 +309,8 @@ fn xdg_surface_implementation(
                 ..Default::default()
             };
             if compositor::give_role(surface, XDG_POPUP_ROLE).is_err() {
-                shell.as_ref().post_error(
+                post_error!(
+                    shell.as_ref(),
                     xdg_wm_base::Error::Role as u32,
                     "Surface already has a role.".into(),
                 );
@@ -365,7 +370,8 @@ fn xdg_surface_implementation(
             let role = compositor::get_role(surface);
 
             if role.is_none() {
-                xdg_surface.as_ref().post_error(
+                post_error!(
+                    xdg_surface.as_ref(),
                     xdg_surface::Error::NotConstructed as u32,
                     "xdg_surface must have a role.".into(),
                 );
@@ -373,7 +379,8 @@ fn xdg_surface_implementation(
             }
 
             if role != Some(XDG_TOPLEVEL_ROLE) && role != Some(XDG_POPUP_ROLE) {
-                data.wm_base.as_ref().post_error(
+                post_error!(
+                    data.wm_base.as_ref(),
                     xdg_wm_base::Error::Role as u32,
                     "xdg_surface must have a role of xdg_toplevel or xdg_popup.".into(),
                 );
@@ -393,7 +400,8 @@ fn xdg_surface_implementation(
             // or xdg_popup. If none of the role matches the xdg_surface has no role set
             // which is a protocol error.
             if compositor::get_role(surface).is_none() {
-                xdg_surface.as_ref().post_error(
+                post_error!(
+                    xdg_surface.as_ref(),
                     xdg_surface::Error::NotConstructed as u32,
                     "xdg_surface must have a role.".into(),
                 );
@@ -437,14 +445,16 @@ fn xdg_surface_implementation(
             let configure = match found_configure {
                 Ok(Some(configure)) => configure,
                 Ok(None) => {
-                    data.wm_base.as_ref().post_error(
+                    post_error!(
+                        data.wm_base.as_ref(),
                         xdg_wm_base::Error::InvalidSurfaceState as u32,
                         format!("wrong configure serial: {}", <u32>::from(serial)),
                     );
                     return;
                 }
                 Err(()) => {
-                    data.wm_base.as_ref().post_error(
+                    post_error!(
+                        data.wm_base.as_ref(),
                         xdg_wm_base::Error::Role as u32,
                         "xdg_surface must have a role of xdg_toplevel or xdg_popup.".into(),
                     );
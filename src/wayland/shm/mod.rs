@@ -171,9 +171,11 @@ where
         Ok(t) => Ok(t),
         Err(()) => {
             // SIGBUS error occurred
-            buffer
-                .as_ref()
-                .post_error(wl_shm::Error::InvalidFd as u32, "Bad pool size.".into());
+            post_error!(
+                buffer.as_ref(),
+                wl_shm::Error::InvalidFd as u32,
+                "Bad pool size.".into()
+            );
             Err(BufferAccessError::BadMap)
         }
     }
@@ -188,7 +190,8 @@ impl ShmGlobalData {
             _ => unreachable!(),
         };
         if size <= 0 {
-            shm.as_ref().post_error(
+            post_error!(
+                shm.as_ref(),
                 Error::InvalidFd as u32,
                 "Invalid size for a new wl_shm_pool.".into(),
             );
@@ -197,7 +200,8 @@ impl ShmGlobalData {
         let mmap_pool = match Pool::new(fd, size as usize, self.log.clone()) {
             Ok(p) => p,
             Err(()) => {
-                shm.as_ref().post_error(
+                post_error!(
+                    shm.as_ref(),
                     wl_shm::Error::InvalidFd as u32,
                     format!("Failed mmap of fd {}.", fd),
                 );
@@ -209,7 +213,7 @@ impl ShmGlobalData {
             let mut data = self.clone();
             move |pool, req, _| data.receive_pool_message(req, pool.deref().clone())
         });
-        pool.as_ref().user_data().set(move || arc_pool);
+        pool.as_ref().user_data().set_threadsafe(move || arc_pool);
     }
 }
 
@@ -249,7 +253,8 @@ impl ShmGlobalData {
                 format,
             } => {
                 if !self.formats.contains(&format) {
-                    pool.as_ref().post_error(
+                    post_error!(
+                        pool.as_ref(),
                         wl_shm::Error::InvalidFormat as u32,
                         format!("SHM format {:?} is not supported.", format),
                     );
@@ -266,19 +271,23 @@ impl ShmGlobalData {
                     },
                 };
                 buffer.quick_assign(|_, _, _| {});
-                buffer.as_ref().user_data().set(|| data);
+                buffer.as_ref().user_data().set_threadsafe(|| data);
             }
             Request::Resize { size } => match arc_pool.resize(size) {
                 Ok(()) => {}
                 Err(ResizeError::InvalidSize) => {
-                    pool.as_ref().post_error(
+                    post_error!(
+                        pool.as_ref(),
                         wl_shm::Error::InvalidFd as u32,
                         "Invalid new size for a wl_shm_pool.".into(),
                     );
                 }
                 Err(ResizeError::MremapFailed) => {
-                    pool.as_ref()
-                        .post_error(wl_shm::Error::InvalidFd as u32, "mremap failed.".into());
+                    post_error!(
+                        pool.as_ref(),
+                        wl_shm::Error::InvalidFd as u32,
+                        "mremap failed.".into()
+                    );
                 }
             },
             Request::Destroy => {}
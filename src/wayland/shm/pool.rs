@@ -105,6 +105,11 @@ struct MemMap {
     size: usize,
 }
 
+// The mapping is only accessed through the `RwLock` of its `Pool`,
+// and SIGBUS is handled per thread.
+unsafe impl Send for MemMap {}
+unsafe impl Sync for MemMap {}
+
 impl MemMap {
     fn new(fd: RawFd, size: usize) -> Result<MemMap, ()> {
         Ok(MemMap {
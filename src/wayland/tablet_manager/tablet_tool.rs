@@ -263,7 +263,8 @@ impl TabletToolHandle {
                                     if compositor::give_role(&surface, CURSOR_IMAGE_ROLE).is_err()
                                         && compositor::get_role(&surface) != Some(CURSOR_IMAGE_ROLE)
                                     {
-                                        tool.as_ref().post_error(
+                                        post_error!(
+                                            tool.as_ref(),
                                             zwp_tablet_tool_v2::Error::Role as u32,
                                             "Given wl_surface has another role.".into(),
                                         );
@@ -86,7 +86,8 @@ fn get_activation_token(
                     _ => {}
                 };
             } else {
-                id.as_ref().post_error(
+                post_error!(
+                    id.as_ref(),
                     xdg_activation_token_v1::Error::AlreadyUsed as u32,
                     "The activation token has already been constructed".into(),
                 )
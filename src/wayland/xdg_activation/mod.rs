@@ -42,12 +42,15 @@ use std::{
 use wayland_protocols::staging::xdg_activation::v1::server::xdg_activation_v1;
 use wayland_server::{
     protocol::{wl_seat::WlSeat, wl_surface::WlSurface},
-    DispatchData, Display, Filter, Global, Main, UserDataMap,
+    DispatchData, Display, Filter, Global, Main,
 };
 
 use rand::distributions::{Alphanumeric, DistString};
 
-use crate::wayland::Serial;
+use crate::{
+    utils::user_data::UserDataMap,
+    wayland::{seat::Seat, Serial},
+};
 
 mod handlers;
 
@@ -113,6 +116,24 @@ pub struct XdgActivationTokenData {
 }
 
 impl XdgActivationTokenData {
+    /// Checks if the token was requested with the serial of an event, that happened since the
+    /// keyboard of its seat last entered a surface
+    ///
+    /// Compositors can use this to only grant activation requests triggered by input directed
+    /// at the currently focused client. Tokens without a serial or of a seat without a keyboard
+    /// are never current.
+    pub fn is_serial_current(&self) -> bool {
+        let (serial, seat) = match self.serial {
+            Some((ref serial, ref seat)) => (serial, seat),
+            None => return false,
+        };
+        Seat::from_resource(seat)
+            .and_then(|seat| seat.get_keyboard())
+            .and_then(|keyboard| keyboard.last_enter())
+            .map(|last_enter| serial.is_no_older_than(&last_enter))
+            .unwrap_or(false)
+    }
+
     fn new(
         serial: Option<(Serial, WlSeat)>,
         app_id: Option<String>,
@@ -185,6 +206,44 @@ impl XdgActivationState {
     }
 }
 
+/// Handler for `xdg_activation` events
+///
+/// Implement it on your compositor state and initialize the global with
+/// [`delegate_xdg_activation!`](crate::delegate_xdg_activation).
+pub trait XdgActivationHandler {
+    /// A client requested or used an activation token
+    fn activation_event(&mut self, activation_state: &Mutex<XdgActivationState>, event: XdgActivationEvent);
+}
+
+/// Initializes the `xdg_activation_v1` global, delegating to the [`XdgActivationHandler`] implementation of your state
+///
+/// Takes the type of your state, the display and optionally a logger, and returns the result of
+/// [`init_xdg_activation_global`]. See [delegating to your
+/// state](crate::wayland#delegating-to-your-state) for how the display has to be dispatched.
+#[macro_export]
+macro_rules! delegate_xdg_activation {
+    ($ty:ty, $display:expr) => {
+        $crate::delegate_xdg_activation!($ty, $display, ::core::option::Option::None)
+    };
+    ($ty:ty, $display:expr, $logger:expr) => {{
+        let log = $crate::wayland::delegate_logger($logger);
+        let delegate_log = log.clone();
+        $crate::wayland::xdg_activation::init_xdg_activation_global(
+            $display,
+            move |activation_state, event, mut ddata| {
+                if let Some(state) = $crate::wayland::delegated_state::<$ty>(&mut ddata, &delegate_log) {
+                    <$ty as $crate::wayland::xdg_activation::XdgActivationHandler>::activation_event(
+                        state,
+                        activation_state,
+                        event,
+                    );
+                }
+            },
+            log,
+        )
+    }};
+}
+
 /// Creates new `xdg-activation` global.
 pub fn init_xdg_activation_global<L, Impl>(
     display: &mut Display,
@@ -266,7 +266,8 @@ fn exporter_implementation(
                 && compositor::get_role(&surface) != Some(WL_SHELL_SURFACE_ROLE)
             {
                 // Protocol error if not a toplevel like
-                surface.as_ref().post_error(
+                post_error!(
+                    surface.as_ref(),
                     zxdg_exporter_v2::Error::InvalidSurface as u32,
                     "Surface must be a toplevel equivalent surface".into(),
                 );
@@ -428,7 +429,8 @@ fn imported_implementation(
                 && compositor::get_role(&surface) != Some(WL_SHELL_SURFACE_ROLE)
             {
                 // Protocol error if not a toplevel like surface
-                surface.as_ref().post_error(
+                post_error!(
+                    surface.as_ref(),
                     zxdg_imported_v2::Error::InvalidSurface as u32,
                     "Surface must be an xdg_toplevel surface".into(),
                 );
@@ -10,9 +10,14 @@
 //! function properly. You'll need to treat XWayland (and all its X11 apps) as one
 //! special client, and play the role of an X11 Window Manager.
 //!
-//! Smithay does not provide any helper for doing that yet, but it is planned.
+//! The [`xwm`] module provides [`X11Wm`](xwm::X11Wm), an X11 Window Manager
+//! translating the requests of X11 clients into events for your compositor.
+//! XWayland 23.1 and newer additionally use the [`xwayland_shell`] protocol to
+//! reliably associate X11 windows with their `wl_surface`s.
 
 mod x11_sockets;
 mod xserver;
+pub mod xwayland_shell;
+pub mod xwm;
 
 pub use self::xserver::{XWayland, XWaylandEvent, XWaylandSource};
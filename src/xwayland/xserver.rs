@@ -153,6 +153,10 @@ struct XWaylandInstance {
     child_stdout: Option<ChildStdout>,
 }
 
+// Marks the wayland client of XWayland, to restrict globals like xwayland_shell to it
+#[derive(Debug)]
+pub(super) struct XWaylandClientData;
+
 // Inner implementation of the XWayland manager
 #[derive(Debug)]
 struct Inner<Data> {
@@ -196,6 +200,7 @@ fn launch<Data: Any>(inner: &Rc<RefCell<Inner<Data>>>) -> std::io::Result<()> {
                     .create_client(wl_me.into_raw_fd(), data)
             };
             client.data_map().insert_if_missing(|| idle_inner.clone());
+            client.data_map().insert_if_missing(|| XWaylandClientData);
             client.add_destructor(Filter::new(|e: Arc<_>, _, _| client_destroy::<Data>(&e)));
 
             instance.wayland_client = Some(client);
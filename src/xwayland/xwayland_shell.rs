@@ -0,0 +1,164 @@
+//! Utilities for handling the `xwayland_shell_v1` protocol
+//!
+//! Starting with version 23.1, XWayland associates its X11 windows with their `wl_surface`s
+//! through this protocol, if the compositor advertises it: XWayland gives the surface the
+//! `xwayland_surface_v1` role and commits a unique serial, which it also sends to the window
+//! manager as a `WL_SURFACE_SERIAL` client message on the X11 window. Unlike the legacy
+//! `WL_SURFACE_ID` message, which refers to the protocol id of the surface, the serial can
+//! never match a newer surface reusing the id of a destroyed one.
+//!
+//! The global is only advertised to the wayland client of XWayland. The association is picked
+//! up by the [`X11Wm`](super::xwm::X11Wm) on its own, resulting in the usual
+//! [`XwmEvent::SurfaceAssociated`](super::xwm::XwmEvent::SurfaceAssociated).
+//!
+//! ```no_run
+//! # extern crate wayland_server;
+//! use smithay::xwayland::xwayland_shell::init_xwayland_shell_global;
+//!
+//! # let mut display = wayland_server::Display::new();
+//! let xwayland_shell_global = init_xwayland_shell_global(&mut display, None);
+//! ```
+
+use slog::{o, trace};
+use wayland_server::{protocol::wl_surface::WlSurface, Display, Filter, Global, Main};
+
+use crate::wayland::compositor::{self, Cacheable};
+
+use super::{xserver::XWaylandClientData, xwm::X11Wm};
+
+mod generated {
+    #![allow(dead_code, non_camel_case_types, unused_unsafe, unused_variables)]
+    #![allow(non_upper_case_globals, non_snake_case, unused_imports)]
+    #![allow(missing_docs, clippy::all)]
+    #![allow(unknown_lints, static_mut_refs)]
+
+    pub mod server {
+        //! Server-side API of the `xwayland_shell_v1` protocol
+        pub(crate) use wayland_commons::map::{Object, ObjectMetadata};
+        pub(crate) use wayland_commons::smallvec;
+        pub(crate) use wayland_commons::wire::{Argument, ArgumentType, Message, MessageDesc};
+        pub(crate) use wayland_commons::{Interface, MessageGroup};
+        pub(crate) use wayland_server::protocol::wl_surface;
+        pub(crate) use wayland_server::sys;
+        pub(crate) use wayland_server::{AnonymousObject, Main, Resource, ResourceMap};
+        include!(concat!(env!("OUT_DIR"), "/xwayland_shell_v1_server_api.rs"));
+    }
+}
+
+pub use self::generated::server::{xwayland_shell_v1, xwayland_surface_v1};
+
+/// The role of `wl_surface`s associated with X11 windows through the `xwayland_shell_v1` protocol
+pub const XWAYLAND_SHELL_ROLE: &str = "xwayland_surface_v1";
+
+const XWAYLAND_SHELL_VERSION: u32 = 1;
+
+/// Double-buffered association state of an `xwayland_surface_v1`
+#[derive(Debug, Default, Clone, Copy)]
+pub(super) struct XWaylandShellCachedState {
+    pub(super) serial: Option<u64>,
+}
+
+impl Cacheable for XWaylandShellCachedState {
+    fn commit(&mut self) -> Self {
+        XWaylandShellCachedState {
+            serial: self.serial.take(),
+        }
+    }
+    fn merge_into(self, into: &mut Self) {
+        if self.serial.is_some() {
+            into.serial = self.serial;
+        }
+    }
+}
+
+/// Create a new `xwayland_shell_v1` global
+///
+/// The global is only advertised to the wayland client of the [`XWayland`](super::XWayland) instance.
+pub fn init_xwayland_shell_global<L>(
+    display: &mut Display,
+    logger: L,
+) -> Global<xwayland_shell_v1::XwaylandShellV1>
+where
+    L: Into<Option<::slog::Logger>>,
+{
+    let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "xwayland_shell"));
+
+    display.create_global_with_filter(
+        XWAYLAND_SHELL_VERSION,
+        Filter::new(
+            move |(shell, _version): (Main<xwayland_shell_v1::XwaylandShellV1>, u32), _, _| {
+                let log = log.clone();
+                shell.quick_assign(move |shell, request, _| match request {
+                    xwayland_shell_v1::Request::GetXwaylandSurface { id, surface } => {
+                        if compositor::give_role(&surface, XWAYLAND_SHELL_ROLE).is_err() {
+                            post_error!(
+                                shell.as_ref(),
+                                xwayland_shell_v1::Error::Role as u32,
+                                "Surface already has a role.".into(),
+                            );
+                            return;
+                        }
+                        trace!(log, "New xwayland surface"; "surface" => ?surface);
+                        compositor::add_commit_hook(&surface, commit_hook);
+                        id.quick_assign(move |xwayland_surface, request, _| {
+                            xwayland_surface_implementation(&xwayland_surface, request, &surface)
+                        });
+                    }
+                    xwayland_shell_v1::Request::Destroy => {}
+                });
+            },
+        ),
+        |client| client.data_map().get::<XWaylandClientData>().is_some(),
+    )
+}
+
+fn xwayland_surface_implementation(
+    xwayland_surface: &Main<xwayland_surface_v1::XwaylandSurfaceV1>,
+    request: xwayland_surface_v1::Request,
+    surface: &WlSurface,
+) {
+    match request {
+        xwayland_surface_v1::Request::SetSerial { serial_lo, serial_hi } => {
+            let serial = u64::from(serial_hi) << 32 | u64::from(serial_lo);
+            if serial == 0 {
+                post_error!(
+                    xwayland_surface.as_ref(),
+                    xwayland_surface_v1::Error::InvalidSerial as u32,
+                    "The serial must not be zero.".into(),
+                );
+                return;
+            }
+            let associated = compositor::with_states(surface, |states| {
+                let associated = states
+                    .cached_state
+                    .current::<XWaylandShellCachedState>()
+                    .serial
+                    .is_some();
+                if !associated {
+                    states.cached_state.pending::<XWaylandShellCachedState>().serial = Some(serial);
+                }
+                associated
+            })
+            .unwrap_or(false);
+            if associated {
+                post_error!(
+                    xwayland_surface.as_ref(),
+                    xwayland_surface_v1::Error::AlreadyAssociated as u32,
+                    "Surface is already associated with an X11 window.".into(),
+                );
+            }
+        }
+        xwayland_surface_v1::Request::Destroy => {}
+    }
+}
+
+fn commit_hook(surface: &WlSurface) {
+    let serial = compositor::with_states(surface, |states| {
+        states.cached_state.pending::<XWaylandShellCachedState>().serial
+    })
+    .ok()
+    .flatten();
+    if let Some(serial) = serial {
+        X11Wm::associate_serial(surface, serial);
+    }
+}
@@ -0,0 +1,43 @@
+use std::io;
+
+use x11rb::rust_connection::{ConnectError, ConnectionError, ReplyError, ReplyOrIdError};
+
+/// An error emitted by the [`X11Wm`](super::X11Wm) during setup.
+#[derive(Debug, thiserror::Error)]
+pub enum X11WmError {
+    /// Connecting to XWayland failed.
+    #[error("Connecting to XWayland failed")]
+    ConnectionFailed(ConnectError),
+
+    /// Some protocol error occurred during setup.
+    #[error("Some protocol error occurred during setup")]
+    Protocol(ReplyOrIdError),
+
+    /// An I/O error occurred during setup, e.g. while inserting the event sources.
+    #[error("An I/O error occurred during setup")]
+    Io(#[source] io::Error),
+}
+
+impl From<ConnectError> for X11WmError {
+    fn from(err: ConnectError) -> Self {
+        Self::ConnectionFailed(err)
+    }
+}
+
+impl From<ReplyError> for X11WmError {
+    fn from(err: ReplyError) -> Self {
+        Self::Protocol(err.into())
+    }
+}
+
+impl From<ConnectionError> for X11WmError {
+    fn from(err: ConnectionError) -> Self {
+        Self::Protocol(err.into())
+    }
+}
+
+impl From<ReplyOrIdError> for X11WmError {
+    fn from(err: ReplyOrIdError) -> Self {
+        Self::Protocol(err)
+    }
+}
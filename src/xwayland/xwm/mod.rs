@@ -0,0 +1,1409 @@
+//! X11 Window Manager for XWayland
+//!
+//! [`X11Wm`] takes the role of the X11 window manager of a running XWayland instance. It
+//! becomes the window manager through the privileged connection provided by
+//! [`XWaylandEvent::Ready`](super::XWaylandEvent::Ready), and translates the requests of X11
+//! clients into [`XwmEvent`]s for your compositor:
+//!
+//! - X11 windows are represented as [`X11Surface`]s. Regular windows need to be mapped and
+//!   configured by the compositor through [`X11Surface::set_mapped`] and [`X11Surface::configure`],
+//!   while override-redirect windows (menus, tooltips, ...) place and map themselves.
+//! - XWayland renders every mapped window into a `wl_surface` of its wayland client. Once the
+//!   `wl_surface` of a window is known, [`XwmEvent::SurfaceAssociated`] is emitted. XWayland 23.1
+//!   and newer associate the surfaces through the
+//!   [`xwayland_shell_v1`](super::xwayland_shell) global, if it is available. Older versions
+//!   require [`X11Wm::commit_hook`] to be called on every commit of a `wl_surface`.
+//! - Basic ICCCM and EWMH support: `WM_PROTOCOLS` (`WM_DELETE_WINDOW`, `WM_TAKE_FOCUS`),
+//!   `WM_STATE`, `_NET_WM_STATE` (maximized, fullscreen, focused), `_NET_ACTIVE_WINDOW` and the
+//!   `_NET_CLIENT_LIST` and `_NET_CLIENT_LIST_STACKING` properties.
+//! - The `CLIPBOARD` selection is bridged between X11 clients and the wayland data device. See
+//!   [`XwmEvent::NewSelection`], [`XwmEvent::SendSelection`], [`X11Wm::new_selection`] and
+//!   [`X11Wm::send_selection`].
+//!
+//! ```no_run
+//! # use smithay::xwayland::{XWaylandEvent, xwm::{X11Wm, XwmEvent}};
+//! # struct State { xwm: Option<X11Wm> }
+//! # fn run(handle: smithay::reexports::calloop::LoopHandle<'static, State>, event: XWaylandEvent, state: &mut State) {
+//! match event {
+//!     XWaylandEvent::Ready { connection, client } => {
+//!         let wm = X11Wm::start_wm(handle, connection, client, |event, state: &mut State| match event {
+//!             XwmEvent::MapWindowRequest(window) => {
+//!                 window.set_mapped(true).unwrap();
+//!                 // place the window and call `window.configure(...)`
+//!             }
+//!             _ => { /* ... */ }
+//!         }, None)
+//!         .expect("Failed to attach the X11 window manager");
+//!         state.xwm = Some(wm);
+//!     }
+//!     XWaylandEvent::Exited => {
+//!         state.xwm.take();
+//!     }
+//! }
+//! # }
+//! ```
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, VecDeque},
+    fs::File,
+    io::Write,
+    os::unix::{
+        io::{FromRawFd, RawFd},
+        net::UnixStream,
+    },
+    rc::Rc,
+    sync::Arc,
+};
+
+use calloop::{
+    generic::{Fd, Generic},
+    ping::{make_ping, Ping},
+    Interest, LoopHandle, Mode, PostAction,
+};
+use nix::{
+    errno::Errno,
+    fcntl::{fcntl, FcntlArg, OFlag},
+    unistd::{close, pipe2, read},
+};
+use slog::{debug, error, o, trace, warn};
+use wayland_server::{protocol::wl_surface::WlSurface, Client};
+use x11rb::{
+    connection::{Connection as _, RequestConnection as _},
+    errors::ReplyOrIdError,
+    protocol::{
+        composite::{ConnectionExt as _, Redirect},
+        xfixes::{ConnectionExt as _, SelectionEventMask},
+        xproto::{
+            Atom, AtomEnum, ChangeWindowAttributesAux, ClientMessageEvent, ConfigWindow, ConfigureWindowAux,
+            ConnectionExt as _, EventMask, InputFocus, PropMode, SelectionNotifyEvent, SelectionRequestEvent,
+            StackMode, Window, WindowClass, SELECTION_NOTIFY_EVENT,
+        },
+        Event,
+    },
+    rust_connection::{DefaultStream, RustConnection},
+    wrapper::ConnectionExt as _,
+    CURRENT_TIME,
+};
+
+use crate::{
+    utils::{x11rb::X11Source, Logical, Rectangle},
+    wayland::compositor::{get_role, give_role},
+};
+
+use super::xwayland_shell::XWAYLAND_SHELL_ROLE;
+
+mod error;
+mod surface;
+
+pub use self::error::X11WmError;
+pub use self::surface::X11Surface;
+
+/// The role of `wl_surface`s associated with X11 windows
+pub const X11_SURFACE_ROLE: &str = "x11_surface";
+
+/// Mime type of utf-8 encoded plain text, corresponding to the `UTF8_STRING` target
+const MIME_TEXT_UTF8: &str = "text/plain;charset=utf-8";
+/// Mime type of plain text, corresponding to the `STRING` and `TEXT` targets
+const MIME_TEXT: &str = "text/plain";
+
+x11rb::atom_manager! {
+    Atoms: AtomsCookie {
+        WM_S0,
+        WL_SURFACE_ID,
+        WL_SURFACE_SERIAL,
+        WM_PROTOCOLS,
+        WM_DELETE_WINDOW,
+        WM_TAKE_FOCUS,
+        WM_STATE,
+        UTF8_STRING,
+        _NET_WM_NAME,
+        _NET_SUPPORTED,
+        _NET_SUPPORTING_WM_CHECK,
+        _NET_ACTIVE_WINDOW,
+        _NET_CLIENT_LIST,
+        _NET_CLIENT_LIST_STACKING,
+        _NET_WM_STATE,
+        _NET_WM_STATE_FOCUSED,
+        _NET_WM_STATE_MAXIMIZED_HORZ,
+        _NET_WM_STATE_MAXIMIZED_VERT,
+        _NET_WM_STATE_FULLSCREEN,
+        CLIPBOARD,
+        TARGETS,
+        TIMESTAMP,
+        MULTIPLE,
+        TEXT,
+        INCR,
+        _WL_SELECTION,
+        _SMITHAY_CLOSE_CONNECTION,
+    }
+}
+
+/// Requested change of the stacking order of a window
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reorder {
+    /// Raise the window on top of all other windows
+    Top,
+    /// Place the window directly above the given window
+    Above(u32),
+    /// Lower the window below all other windows
+    Bottom,
+    /// Place the window directly below the given window
+    Below(u32),
+}
+
+/// Events generated by the [`X11Wm`]
+#[derive(Debug)]
+pub enum XwmEvent {
+    /// A new regular window was created, it is not mapped yet
+    NewWindow(X11Surface),
+    /// A new override-redirect window was created
+    NewOverrideRedirectWindow(X11Surface),
+    /// A regular window requests to be mapped
+    ///
+    /// Map it through [`X11Surface::set_mapped`] to grant the request.
+    MapWindowRequest(X11Surface),
+    /// An override-redirect window mapped itself
+    MappedOverrideRedirectWindow(X11Surface),
+    /// A window was unmapped
+    ///
+    /// The `wl_surface` of the window is not valid anymore, a new one
+    /// will be associated once the window is mapped again.
+    UnmappedWindow(X11Surface),
+    /// A window was destroyed
+    DestroyedWindow(X11Surface),
+    /// A regular window requests a new geometry or stacking position
+    ///
+    /// Unset values are to be left unchanged. Apply the request (or any other geometry)
+    /// through [`X11Surface::configure`] and [`X11Wm::raise_window`].
+    ConfigureRequest {
+        /// The window requesting a configure
+        window: X11Surface,
+        /// Requested x coordinate, relative to the root window
+        x: Option<i32>,
+        /// Requested y coordinate, relative to the root window
+        y: Option<i32>,
+        /// Requested width
+        width: Option<u32>,
+        /// Requested height
+        height: Option<u32>,
+        /// Requested change of the stacking order
+        reorder: Option<Reorder>,
+    },
+    /// The geometry of a window changed
+    ///
+    /// This is the only notification about position changes of override-redirect windows.
+    ConfigureNotify {
+        /// The configured window
+        window: X11Surface,
+        /// The new geometry, relative to the root window
+        geometry: Rectangle<i32, Logical>,
+        /// The window directly below this window in the stacking order, if any
+        above: Option<u32>,
+    },
+    /// A window requests to be maximized
+    MaximizeRequest(X11Surface),
+    /// A window requests to be unmaximized
+    UnmaximizeRequest(X11Surface),
+    /// A window requests to be made fullscreen
+    FullscreenRequest(X11Surface),
+    /// A window requests to leave fullscreen
+    UnfullscreenRequest(X11Surface),
+    /// A window requests to be activated through `_NET_ACTIVE_WINDOW`
+    ActivateRequest(X11Surface),
+    /// The `wl_surface` of a window is now known, see [`X11Surface::wl_surface`]
+    SurfaceAssociated(X11Surface),
+    /// An X11 client set a new `CLIPBOARD` selection offering the given mime types,
+    /// or the selection was cleared
+    ///
+    /// Forward it to wayland clients through
+    /// [`set_data_device_selection`](crate::wayland::data_device::set_data_device_selection)
+    /// and request its contents with [`X11Wm::send_selection`].
+    NewSelection(Option<Vec<String>>),
+    /// An X11 client requests the contents of the selection set through [`X11Wm::new_selection`]
+    ///
+    /// Write the contents of the selection in the given mime type into the fd, e.g. by
+    /// forwarding it to the client of the current selection through
+    /// [`request_data_device_client_selection`](crate::wayland::data_device::request_data_device_client_selection).
+    /// You own the fd and have to close it once done, unless you pass it to that function,
+    /// which takes ownership of it. Contents exceeding the maximum request size of the X11
+    /// connection are refused, incremental (`INCR`) transfers are not supported.
+    SendSelection {
+        /// The requested mime type
+        mime_type: String,
+        /// The fd to write into
+        fd: RawFd,
+    },
+}
+
+/// An X11 window manager for XWayland
+///
+/// Dropping it removes it from the event loop and releases the X11 connection.
+/// See the [module-level documentation](self) for details.
+pub struct X11Wm {
+    inner: Rc<RefCell<Inner>>,
+    remove_sources: Option<Box<dyn FnOnce()>>,
+}
+
+impl std::fmt::Debug for X11Wm {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("X11Wm").field("inner", &self.inner).finish()
+    }
+}
+
+/// Contents of the wayland selection, requested by an X11 client and read from a pipe
+#[derive(Debug)]
+struct OutgoingTransfer {
+    fd: RawFd,
+    data: Vec<u8>,
+    request: SelectionRequestEvent,
+}
+
+/// Pairs X11 windows with their `wl_surface`s
+///
+/// The messages of XWayland about the surface of a window and the wayland requests creating or
+/// committing the surface are sent over different sockets, so either of them may arrive first.
+#[derive(Debug)]
+struct SurfacePairing<S> {
+    /// Windows waiting for the `wl_surface` with the given id (`WL_SURFACE_ID`)
+    unpaired_surfaces: HashMap<u32, Window>,
+    /// Windows waiting for the commit of the given serial (`WL_SURFACE_SERIAL`)
+    unpaired_serials: HashMap<u64, Window>,
+    /// Committed surfaces waiting for the `WL_SURFACE_SERIAL` message of their window
+    serial_surfaces: HashMap<u64, S>,
+}
+
+impl<S> Default for SurfacePairing<S> {
+    fn default() -> Self {
+        SurfacePairing {
+            unpaired_surfaces: HashMap::new(),
+            unpaired_serials: HashMap::new(),
+            serial_surfaces: HashMap::new(),
+        }
+    }
+}
+
+impl<S> SurfacePairing<S> {
+    /// A `WL_SURFACE_ID` message referred to a surface, that does not exist yet
+    fn window_surface_id(&mut self, window: Window, id: u32) {
+        self.unpaired_surfaces.insert(id, window);
+    }
+
+    /// A surface was committed, returns the window waiting for its id
+    fn surface_committed(&mut self, id: u32) -> Option<Window> {
+        self.unpaired_surfaces.remove(&id)
+    }
+
+    /// A `WL_SURFACE_SERIAL` message arrived, returns the surface if its serial was already committed
+    fn window_serial(&mut self, window: Window, serial: u64) -> Option<S> {
+        let surface = self.serial_surfaces.remove(&serial);
+        if surface.is_none() {
+            self.unpaired_serials.insert(serial, window);
+        }
+        surface
+    }
+
+    /// The serial of a surface was committed, returns the window if its message already arrived
+    fn surface_serial(&mut self, serial: u64, surface: S) -> Option<Window> {
+        let window = self.unpaired_serials.remove(&serial);
+        if window.is_none() {
+            self.serial_surfaces.insert(serial, surface);
+        }
+        window
+    }
+
+    /// The window was unmapped or destroyed, its pending messages are obsolete
+    fn remove_window(&mut self, window: Window) {
+        self.unpaired_surfaces.retain(|_, w| *w != window);
+        self.unpaired_serials.retain(|_, w| *w != window);
+    }
+}
+
+/// Serial of a `WL_SURFACE_SERIAL` client message, sent as its lower and upper 32 bits
+fn surface_serial(data: [u32; 5]) -> u64 {
+    u64::from(data[1]) << 32 | u64::from(data[0])
+}
+
+/// New value of a window state requested by a `_NET_WM_STATE` client message, if the message
+/// refers to any of the given state atoms
+fn requested_state(data: [u32; 5], states: &[Atom], current: bool) -> Option<bool> {
+    // _NET_WM_STATE_REMOVE, _NET_WM_STATE_ADD, _NET_WM_STATE_TOGGLE
+    let action = data[0];
+    let properties = [data[1], data[2]];
+    if !states.iter().any(|state| properties.contains(state)) {
+        return None;
+    }
+    Some(match action {
+        0 => false,
+        1 => true,
+        _ => !current,
+    })
+}
+
+/// Splits the `WM_CLASS` property into its instance and class names
+fn parse_wm_class(value: &str) -> (String, String) {
+    let mut parts = value.split('\0');
+    let instance = parts.next().unwrap_or_default().to_string();
+    let class = parts.next().unwrap_or_default().to_string();
+    (instance, class)
+}
+
+#[derive(Debug, Default)]
+struct SelectionState {
+    /// Mime types of the wayland selection currently owned by the wm window
+    wayland_mime_types: Option<Vec<(String, Atom)>>,
+    /// Whether an X11 client owns the selection
+    x11_owned: bool,
+    /// Requested conversions of the X11 selection, the first one is in progress
+    incoming: VecDeque<(Atom, RawFd)>,
+    /// Transfers from wayland clients waiting to be attached to the event loop
+    outgoing: Vec<OutgoingTransfer>,
+}
+
+#[derive(Debug)]
+struct Inner {
+    conn: Arc<RustConnection>,
+    atoms: Atoms,
+    client: Client,
+    root: Window,
+    wm_window: Window,
+    windows: Vec<X11Surface>,
+    client_list: Vec<Window>,
+    stacking: Vec<Window>,
+    pairing: SurfacePairing<WlSurface>,
+    selection: SelectionState,
+    pending_events: Vec<XwmEvent>,
+    ping: Ping,
+    log: ::slog::Logger,
+}
+
+impl X11Wm {
+    /// Start the window manager on the given connection to XWayland
+    ///
+    /// The `connection` and `client` are provided by [`XWaylandEvent::Ready`](super::XWaylandEvent::Ready).
+    /// The window manager inserts its event sources into the event loop of `handle` and passes its
+    /// events to `callback`.
+    pub fn start_wm<D, F, L>(
+        handle: LoopHandle<'static, D>,
+        connection: UnixStream,
+        client: Client,
+        callback: F,
+        logger: L,
+    ) -> Result<X11Wm, X11WmError>
+    where
+        D: 'static,
+        F: FnMut(XwmEvent, &mut D) + 'static,
+        L: Into<Option<::slog::Logger>>,
+    {
+        let log = crate::slog_or_fallback(logger).new(o!("smithay_module" => "xwayland_wm"));
+
+        // XWayland only uses screen 0
+        let stream = DefaultStream::from_unix_stream(connection).map_err(X11WmError::Io)?;
+        let conn = RustConnection::connect_to_stream(stream, 0)?;
+        let atoms = Atoms::new(&conn)?.reply()?;
+        let screen = conn.setup().roots[0].clone();
+
+        // Actually become the WM by redirecting some operations
+        conn.change_window_attributes(
+            screen.root,
+            &ChangeWindowAttributesAux::default().event_mask(
+                EventMask::SUBSTRUCTURE_REDIRECT
+                    | EventMask::SUBSTRUCTURE_NOTIFY
+                    | EventMask::PROPERTY_CHANGE,
+            ),
+        )?;
+
+        let wm_window = conn.generate_id()?;
+        conn.create_window(
+            screen.root_depth,
+            wm_window,
+            screen.root,
+            // x, y, width, height, border width
+            0,
+            0,
+            1,
+            1,
+            0,
+            WindowClass::INPUT_OUTPUT,
+            x11rb::COPY_FROM_PARENT,
+            &Default::default(),
+        )?;
+
+        // EWMH compliance
+        conn.change_property32(
+            PropMode::REPLACE,
+            wm_window,
+            atoms._NET_SUPPORTING_WM_CHECK,
+            AtomEnum::WINDOW,
+            &[wm_window],
+        )?;
+        conn.change_property8(
+            PropMode::REPLACE,
+            wm_window,
+            atoms._NET_WM_NAME,
+            atoms.UTF8_STRING,
+            b"Smithay X WM",
+        )?;
+        conn.change_property32(
+            PropMode::REPLACE,
+            screen.root,
+            atoms._NET_SUPPORTING_WM_CHECK,
+            AtomEnum::WINDOW,
+            &[wm_window],
+        )?;
+        conn.change_property32(
+            PropMode::REPLACE,
+            screen.root,
+            atoms._NET_SUPPORTED,
+            AtomEnum::ATOM,
+            &[
+                atoms._NET_WM_STATE,
+                atoms._NET_WM_STATE_FOCUSED,
+                atoms._NET_WM_STATE_MAXIMIZED_HORZ,
+                atoms._NET_WM_STATE_MAXIMIZED_VERT,
+                atoms._NET_WM_STATE_FULLSCREEN,
+                atoms._NET_ACTIVE_WINDOW,
+                atoms._NET_CLIENT_LIST,
+                atoms._NET_CLIENT_LIST_STACKING,
+            ],
+        )?;
+
+        // Get notified about selection changes of X11 clients
+        conn.xfixes_query_version(5, 0)?.reply()?;
+        conn.xfixes_select_selection_input(
+            wm_window,
+            atoms.CLIPBOARD,
+            SelectionEventMask::SET_SELECTION_OWNER
+                | SelectionEventMask::SELECTION_WINDOW_DESTROY
+                | SelectionEventMask::SELECTION_CLIENT_CLOSE,
+        )?;
+
+        // Tell XWayland that we are the WM by acquiring the WM_S0 selection. No X11 clients are accepted before this.
+        conn.set_selection_owner(wm_window, atoms.WM_S0, CURRENT_TIME)?;
+
+        // XWayland renders the windows into wl_surfaces, they should not be drawn onto the root window
+        conn.composite_redirect_subwindows(screen.root, Redirect::MANUAL)?;
+
+        conn.flush()?;
+
+        let conn = Arc::new(conn);
+        let source = X11Source::new(
+            conn.clone(),
+            wm_window,
+            atoms._SMITHAY_CLOSE_CONNECTION,
+            log.clone(),
+        );
+        let (ping, ping_source) = make_ping().map_err(X11WmError::Io)?;
+
+        let inner = Rc::new(RefCell::new(Inner {
+            conn,
+            atoms,
+            client: client.clone(),
+            root: screen.root,
+            wm_window,
+            windows: Vec::new(),
+            client_list: Vec::new(),
+            stacking: Vec::new(),
+            pairing: SurfacePairing::default(),
+            selection: SelectionState::default(),
+            pending_events: Vec::new(),
+            ping,
+            log,
+        }));
+        // Needed to associate wl_surfaces committed later on
+        client.data_map().insert_if_missing(|| Rc::downgrade(&inner));
+
+        let callback = Rc::new(RefCell::new(callback));
+        let x11_token = {
+            let inner = inner.clone();
+            let callback = callback.clone();
+            let handle2 = handle.clone();
+            handle
+                .insert_source(source, move |event, _, data| {
+                    let result = inner.borrow_mut().handle_event(event);
+                    if let Err(err) = result {
+                        error!(inner.borrow().log, "Error while handling X11 event: {}", err);
+                    }
+                    Inner::insert_transfers(&inner, &handle2);
+                    Inner::dispatch_events(&inner, &callback, data);
+                })
+                .map_err(|err| X11WmError::Io(err.error))?
+        };
+        let ping_token = {
+            let inner = inner.clone();
+            handle
+                .insert_source(ping_source, move |_, _, data| {
+                    Inner::dispatch_events(&inner, &callback, data)
+                })
+                .map_err(|err| X11WmError::Io(err.error))?
+        };
+
+        Ok(X11Wm {
+            inner,
+            remove_sources: Some(Box::new(move || {
+                handle.remove(x11_token);
+                handle.remove(ping_token);
+            })),
+        })
+    }
+
+    /// Associates the `wl_surface`s of XWayland with their X11 windows
+    ///
+    /// This needs to be called on every commit of a `wl_surface`, e.g. from your commit handler,
+    /// unless the [`xwayland_shell_v1`](super::xwayland_shell) global is used with XWayland 23.1
+    /// or newer. It does nothing for surfaces of other clients.
+    pub fn commit_hook(surface: &WlSurface) {
+        let inner = match Inner::for_surface(surface) {
+            Some(inner) => inner,
+            None => return,
+        };
+        let mut inner = inner.borrow_mut();
+        // The WL_SURFACE_ID message and the creation of the surface are sent over different
+        // sockets, the surface might only be known now
+        if let Some(window) = inner.pairing.surface_committed(surface.as_ref().id()) {
+            inner.associate(window, surface.clone());
+            inner.ping.ping();
+        }
+    }
+
+    // Called by the xwayland_shell when the serial of a surface gets committed
+    pub(super) fn associate_serial(surface: &WlSurface, serial: u64) {
+        let inner = match Inner::for_surface(surface) {
+            Some(inner) => inner,
+            None => return,
+        };
+        let mut inner = inner.borrow_mut();
+        inner
+            .pairing
+            .serial_surfaces
+            .retain(|_, surface| surface.as_ref().is_alive());
+        // otherwise wait for the WL_SURFACE_SERIAL message
+        if let Some(window) = inner.pairing.surface_serial(serial, surface.clone()) {
+            inner.associate(window, surface.clone());
+            inner.ping.ping();
+        }
+    }
+
+    /// All windows currently known to the window manager, in creation order
+    pub fn windows(&self) -> Vec<X11Surface> {
+        self.inner.borrow().windows.clone()
+    }
+
+    /// Returns the window associated with the given `wl_surface`, if any
+    pub fn window_for_surface(&self, surface: &WlSurface) -> Option<X11Surface> {
+        self.inner
+            .borrow()
+            .windows
+            .iter()
+            .find(|window| window.wl_surface().as_ref() == Some(surface))
+            .cloned()
+    }
+
+    /// Sets the input focus to the given window or clears it
+    ///
+    /// Follows ICCCM by sending `WM_TAKE_FOCUS` to supporting windows and updates `_NET_ACTIVE_WINDOW`.
+    pub fn set_focus(&self, window: Option<&X11Surface>) -> Result<(), ReplyOrIdError> {
+        let inner = self.inner.borrow();
+        let conn = &inner.conn;
+        match window {
+            Some(window) => {
+                if window.supports_protocol(inner.atoms.WM_TAKE_FOCUS) {
+                    let event = ClientMessageEvent::new(
+                        32,
+                        window.window_id(),
+                        inner.atoms.WM_PROTOCOLS,
+                        [inner.atoms.WM_TAKE_FOCUS, CURRENT_TIME, 0, 0, 0],
+                    );
+                    conn.send_event(false, window.window_id(), EventMask::NO_EVENT, event)?;
+                }
+                conn.set_input_focus(InputFocus::POINTER_ROOT, window.window_id(), CURRENT_TIME)?;
+                conn.change_property32(
+                    PropMode::REPLACE,
+                    inner.root,
+                    inner.atoms._NET_ACTIVE_WINDOW,
+                    AtomEnum::WINDOW,
+                    &[window.window_id()],
+                )?;
+            }
+            None => {
+                conn.set_input_focus(InputFocus::POINTER_ROOT, x11rb::NONE, CURRENT_TIME)?;
+                conn.change_property32(
+                    PropMode::REPLACE,
+                    inner.root,
+                    inner.atoms._NET_ACTIVE_WINDOW,
+                    AtomEnum::WINDOW,
+                    &[x11rb::NONE],
+                )?;
+            }
+        }
+        conn.flush()?;
+        Ok(())
+    }
+
+    /// Raises the given window on top of all other windows
+    pub fn raise_window(&self, window: &X11Surface) -> Result<(), ReplyOrIdError> {
+        let mut inner = self.inner.borrow_mut();
+        inner.conn.configure_window(
+            window.window_id(),
+            &ConfigureWindowAux::default().stack_mode(StackMode::ABOVE),
+        )?;
+        if let Some(pos) = inner.stacking.iter().position(|w| *w == window.window_id()) {
+            let id = inner.stacking.remove(pos);
+            inner.stacking.push(id);
+        }
+        inner.update_client_lists()?;
+        inner.conn.flush()?;
+        Ok(())
+    }
+
+    /// Offers a new wayland selection to X11 clients, or clears it
+    ///
+    /// Call this when the `CLIPBOARD` selection of the data device changes, e.g. on
+    /// [`DataDeviceEvent::NewSelection`](crate::wayland::data_device::DataDeviceEvent::NewSelection).
+    /// Requests of X11 clients for the contents are passed on as [`XwmEvent::SendSelection`].
+    ///
+    /// Selections that originate from X11 clients (see [`XwmEvent::NewSelection`]) must not be
+    /// offered back.
+    pub fn new_selection(&self, mime_types: Option<Vec<String>>) -> Result<(), ReplyOrIdError> {
+        let mut inner = self.inner.borrow_mut();
+        match mime_types {
+            Some(mime_types) => {
+                let mut offered = Vec::with_capacity(mime_types.len());
+                for mime_type in mime_types {
+                    let atom = inner.conn.intern_atom(false, mime_type.as_bytes())?.reply()?.atom;
+                    offered.push((mime_type, atom));
+                }
+                inner.selection.wayland_mime_types = Some(offered);
+                inner.selection.x11_owned = false;
+                inner
+                    .conn
+                    .set_selection_owner(inner.wm_window, inner.atoms.CLIPBOARD, CURRENT_TIME)?;
+            }
+            None => {
+                if inner.selection.wayland_mime_types.take().is_some() {
+                    inner
+                        .conn
+                        .set_selection_owner(x11rb::NONE, inner.atoms.CLIPBOARD, CURRENT_TIME)?;
+                }
+            }
+        }
+        inner.conn.flush()?;
+        Ok(())
+    }
+
+    /// Writes the contents of the current X11 selection in the given mime type into `fd`
+    ///
+    /// Call this to answer a [`DataDeviceEvent::SendSelection`](crate::wayland::data_device::DataDeviceEvent::SendSelection)
+    /// while the selection announced by [`XwmEvent::NewSelection`] is active. The fd is closed once
+    /// the transfer is complete.
+    ///
+    /// Incremental (`INCR`) transfers of large selections are not supported, the fd
+    /// is closed without writing any data in that case.
+    pub fn send_selection(&self, mime_type: String, fd: RawFd) -> Result<(), ReplyOrIdError> {
+        let mut inner = self.inner.borrow_mut();
+        if !inner.selection.x11_owned {
+            let _ = close(fd);
+            return Ok(());
+        }
+        let target = match mime_type.as_str() {
+            MIME_TEXT_UTF8 => inner.atoms.UTF8_STRING,
+            MIME_TEXT => AtomEnum::STRING.into(),
+            _ => inner.conn.intern_atom(false, mime_type.as_bytes())?.reply()?.atom,
+        };
+        inner.selection.incoming.push_back((target, fd));
+        if inner.selection.incoming.len() == 1 {
+            inner.convert_selection(target)?;
+        }
+        Ok(())
+    }
+}
+
+impl Drop for X11Wm {
+    fn drop(&mut self) {
+        if let Some(remove) = self.remove_sources.take() {
+            remove();
+        }
+    }
+}
+
+impl Inner {
+    fn for_surface(surface: &WlSurface) -> Option<Rc<RefCell<Inner>>> {
+        surface
+            .as_ref()
+            .client()?
+            .data_map()
+            .get::<std::rc::Weak<RefCell<Inner>>>()
+            .and_then(|inner| inner.upgrade())
+    }
+
+    fn dispatch_events<D, F>(inner: &Rc<RefCell<Inner>>, callback: &Rc<RefCell<F>>, data: &mut D)
+    where
+        F: FnMut(XwmEvent, &mut D),
+    {
+        // the borrow needs to end before calling back, the callback likely uses the X11Wm
+        let events = std::mem::take(&mut inner.borrow_mut().pending_events);
+        let mut callback = callback.borrow_mut();
+        for event in events {
+            (*callback)(event, data);
+        }
+    }
+
+    fn insert_transfers<D: 'static>(inner: &Rc<RefCell<Inner>>, handle: &LoopHandle<'static, D>) {
+        let (transfers, conn, log) = {
+            let mut inner = inner.borrow_mut();
+            (
+                std::mem::take(&mut inner.selection.outgoing),
+                inner.conn.clone(),
+                inner.log.clone(),
+            )
+        };
+        for mut transfer in transfers {
+            let conn = conn.clone();
+            let source_log = log.clone();
+            let fd = transfer.fd;
+            let result = handle.insert_source(
+                Generic::new(Fd(fd), Interest::READ, Mode::Level),
+                move |_, fd, _| {
+                    let mut buffer = [0u8; 4096];
+                    loop {
+                        match read(fd.0, &mut buffer) {
+                            Ok(0) => break,
+                            Ok(len) => transfer.data.extend_from_slice(&buffer[..len]),
+                            Err(Errno::EAGAIN) => return Ok(PostAction::Continue),
+                            Err(Errno::EINTR) => continue,
+                            Err(err) => {
+                                warn!(source_log, "Failed to read selection contents: {}", err);
+                                transfer.data.clear();
+                                break;
+                            }
+                        }
+                    }
+                    let _ = close(fd.0);
+                    if let Err(err) = finish_transfer(&conn, &transfer, &source_log) {
+                        warn!(source_log, "Failed to send selection contents: {}", err);
+                    }
+                    Ok(PostAction::Remove)
+                },
+            );
+            if let Err(err) = result {
+                warn!(log, "Failed to read selection contents: {}", err.error);
+                let _ = close(fd);
+            }
+        }
+    }
+
+    fn window(&self, id: Window) -> Option<X11Surface> {
+        self.windows.iter().find(|w| w.window_id() == id).cloned()
+    }
+
+    fn handle_event(&mut self, event: Event) -> Result<(), ReplyOrIdError> {
+        trace!(self.log, "X11: Got event {:?}", event);
+        match event {
+            Event::CreateNotify(n) => {
+                if n.parent != self.root || n.window == self.wm_window {
+                    return Ok(());
+                }
+                self.conn.change_window_attributes(
+                    n.window,
+                    &ChangeWindowAttributesAux::default()
+                        .event_mask(EventMask::PROPERTY_CHANGE | EventMask::FOCUS_CHANGE),
+                )?;
+                let window = X11Surface::new(
+                    n.window,
+                    n.override_redirect,
+                    Arc::downgrade(&self.conn),
+                    self.atoms,
+                    Rectangle::from_loc_and_size((n.x as i32, n.y as i32), (n.width as i32, n.height as i32)),
+                );
+                for property in [
+                    AtomEnum::WM_NAME.into(),
+                    self.atoms._NET_WM_NAME,
+                    AtomEnum::WM_CLASS.into(),
+                    AtomEnum::WM_TRANSIENT_FOR.into(),
+                    self.atoms.WM_PROTOCOLS,
+                ] {
+                    self.update_property(&window, property)?;
+                }
+                self.windows.push(window.clone());
+                self.pending_events.push(if n.override_redirect {
+                    XwmEvent::NewOverrideRedirectWindow(window)
+                } else {
+                    XwmEvent::NewWindow(window)
+                });
+            }
+            Event::MapRequest(r) => {
+                if let Some(window) = self.window(r.window) {
+                    self.pending_events.push(XwmEvent::MapWindowRequest(window));
+                } else {
+                    // not managed by us, just grant the wish
+                    self.conn.map_window(r.window)?;
+                }
+            }
+            Event::MapNotify(n) => {
+                if let Some(window) = self.window(n.window) {
+                    window.state.lock().unwrap().mapped = true;
+                    if window.is_override_redirect() {
+                        self.pending_events
+                            .push(XwmEvent::MappedOverrideRedirectWindow(window));
+                    } else {
+                        self.client_list.push(n.window);
+                        self.stacking.push(n.window);
+                        self.update_client_lists()?;
+                    }
+                }
+            }
+            Event::UnmapNotify(n) => {
+                if let Some(window) = self.window(n.window) {
+                    {
+                        let mut state = window.state.lock().unwrap();
+                        state.mapped = false;
+                        state.wl_surface = None;
+                    }
+                    self.pairing.remove_window(n.window);
+                    self.client_list.retain(|w| *w != n.window);
+                    self.stacking.retain(|w| *w != n.window);
+                    self.update_client_lists()?;
+                    self.pending_events.push(XwmEvent::UnmappedWindow(window));
+                }
+            }
+            Event::DestroyNotify(n) => {
+                if let Some(pos) = self.windows.iter().position(|w| w.window_id() == n.window) {
+                    let window = self.windows.remove(pos);
+                    {
+                        let mut state = window.state.lock().unwrap();
+                        state.alive = false;
+                        state.mapped = false;
+                    }
+                    self.pairing.remove_window(n.window);
+                    if self.client_list.contains(&n.window) {
+                        self.client_list.retain(|w| *w != n.window);
+                        self.stacking.retain(|w| *w != n.window);
+                        self.update_client_lists()?;
+                    }
+                    self.pending_events.push(XwmEvent::DestroyedWindow(window));
+                }
+            }
+            Event::ConfigureRequest(r) => {
+                let window = match self.window(r.window) {
+                    Some(window) => window,
+                    None => {
+                        // not managed by us, just grant the wish
+                        self.conn
+                            .configure_window(r.window, &ConfigureWindowAux::from_configure_request(&r))?;
+                        return Ok(());
+                    }
+                };
+                let has = |flag: ConfigWindow| r.value_mask & u16::from(flag) != 0;
+                let reorder = if has(ConfigWindow::STACK_MODE) {
+                    let sibling = if has(ConfigWindow::SIBLING) {
+                        Some(r.sibling)
+                    } else {
+                        None
+                    };
+                    match (r.stack_mode, sibling) {
+                        (StackMode::ABOVE, Some(sibling)) => Some(Reorder::Above(sibling)),
+                        (StackMode::ABOVE, None) => Some(Reorder::Top),
+                        (StackMode::BELOW, Some(sibling)) => Some(Reorder::Below(sibling)),
+                        (StackMode::BELOW, None) => Some(Reorder::Bottom),
+                        _ => None,
+                    }
+                } else {
+                    None
+                };
+                self.pending_events.push(XwmEvent::ConfigureRequest {
+                    window,
+                    x: has(ConfigWindow::X).then_some(r.x as i32),
+                    y: has(ConfigWindow::Y).then_some(r.y as i32),
+                    width: has(ConfigWindow::WIDTH).then_some(r.width as u32),
+                    height: has(ConfigWindow::HEIGHT).then_some(r.height as u32),
+                    reorder,
+                });
+            }
+            Event::ConfigureNotify(n) => {
+                if let Some(window) = self.window(n.window) {
+                    let geometry = Rectangle::from_loc_and_size(
+                        (n.x as i32, n.y as i32),
+                        (n.width as i32, n.height as i32),
+                    );
+                    window.state.lock().unwrap().geometry = geometry;
+                    self.pending_events.push(XwmEvent::ConfigureNotify {
+                        window,
+                        geometry,
+                        above: Some(n.above_sibling).filter(|w| *w != x11rb::NONE),
+                    });
+                }
+            }
+            Event::PropertyNotify(n) => {
+                if let Some(window) = self.window(n.window) {
+                    self.update_property(&window, n.atom)?;
+                }
+            }
+            Event::ClientMessage(msg) => {
+                let data = msg.data.as_data32();
+                if msg.type_ == self.atoms.WL_SURFACE_ID {
+                    let id = data[0];
+                    debug!(
+                        self.log,
+                        "X11 window {:x} corresponds to wl_surface {}", msg.window, id
+                    );
+                    match self.client.get_resource::<WlSurface>(id) {
+                        Some(surface) => self.associate(msg.window, surface),
+                        // X11 was faster than wayland, wait for the first commit (see `commit_hook`)
+                        None => self.pairing.window_surface_id(msg.window, id),
+                    }
+                } else if msg.type_ == self.atoms.WL_SURFACE_SERIAL {
+                    let serial = surface_serial(data);
+                    debug!(
+                        self.log,
+                        "X11 window {:x} corresponds to wl_surface serial {}", msg.window, serial
+                    );
+                    // otherwise the surface was not committed yet, see `X11Wm::associate_serial`
+                    if let Some(surface) = self.pairing.window_serial(msg.window, serial) {
+                        self.associate(msg.window, surface);
+                    }
+                } else if msg.type_ == self.atoms._NET_WM_STATE {
+                    if let Some(window) = self.window(msg.window) {
+                        self.handle_state_request(window, data);
+                    }
+                } else if msg.type_ == self.atoms._NET_ACTIVE_WINDOW {
+                    if let Some(window) = self.window(msg.window) {
+                        self.pending_events.push(XwmEvent::ActivateRequest(window));
+                    }
+                }
+            }
+            Event::XfixesSelectionNotify(n) => {
+                if n.selection != self.atoms.CLIPBOARD || n.owner == self.wm_window {
+                    return Ok(());
+                }
+                if n.owner == x11rb::NONE {
+                    if self.selection.x11_owned {
+                        self.selection.x11_owned = false;
+                        self.pending_events.push(XwmEvent::NewSelection(None));
+                    }
+                } else {
+                    // the wayland selection was replaced, find out what the new one offers
+                    self.selection.wayland_mime_types = None;
+                    self.selection.x11_owned = true;
+                    self.convert_selection(self.atoms.TARGETS)?;
+                }
+            }
+            Event::SelectionRequest(r) => self.handle_selection_request(r)?,
+            Event::SelectionNotify(n)
+                if n.requestor == self.wm_window && n.selection == self.atoms.CLIPBOARD =>
+            {
+                self.handle_selection_notify(n.target, n.property)?;
+            }
+            _ => {}
+        }
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn associate(&mut self, window: Window, surface: WlSurface) {
+        let window = match self.window(window) {
+            Some(window) => window,
+            None => return,
+        };
+        // surfaces associated through the xwayland_shell already have their role
+        if get_role(&surface) != Some(XWAYLAND_SHELL_ROLE) && give_role(&surface, X11_SURFACE_ROLE).is_err() {
+            // It makes no sense to post a protocol error here since that would only kill Xwayland
+            error!(self.log, "Surface {:?} already has a role", surface);
+            return;
+        }
+        window.state.lock().unwrap().wl_surface = Some(surface);
+        self.pending_events.push(XwmEvent::SurfaceAssociated(window));
+    }
+
+    fn handle_state_request(&mut self, window: X11Surface, data: [u32; 5]) {
+        let atoms = &self.atoms;
+        match requested_state(data, &[atoms._NET_WM_STATE_FULLSCREEN], window.is_fullscreen()) {
+            Some(true) => self
+                .pending_events
+                .push(XwmEvent::FullscreenRequest(window.clone())),
+            Some(false) => self
+                .pending_events
+                .push(XwmEvent::UnfullscreenRequest(window.clone())),
+            None => {}
+        }
+        let maximized = [
+            atoms._NET_WM_STATE_MAXIMIZED_HORZ,
+            atoms._NET_WM_STATE_MAXIMIZED_VERT,
+        ];
+        match requested_state(data, &maximized, window.is_maximized()) {
+            Some(true) => self.pending_events.push(XwmEvent::MaximizeRequest(window)),
+            Some(false) => self.pending_events.push(XwmEvent::UnmaximizeRequest(window)),
+            None => {}
+        }
+    }
+
+    fn update_property(&self, window: &X11Surface, property: Atom) -> Result<(), ReplyOrIdError> {
+        let atoms = &self.atoms;
+        if property == atoms._NET_WM_NAME || property == AtomEnum::WM_NAME.into() {
+            // _NET_WM_NAME takes precedence over the legacy WM_NAME
+            let title = match self.get_string_property(window.window_id(), atoms._NET_WM_NAME)? {
+                Some(title) => Some(title),
+                None => self.get_string_property(window.window_id(), AtomEnum::WM_NAME.into())?,
+            };
+            window.state.lock().unwrap().title = title.unwrap_or_default();
+        } else if property == AtomEnum::WM_CLASS.into() {
+            let value = self.get_string_property(window.window_id(), property)?;
+            let (instance, class) = parse_wm_class(value.as_deref().unwrap_or_default());
+            let mut state = window.state.lock().unwrap();
+            state.instance = instance;
+            state.class = class;
+        } else if property == AtomEnum::WM_TRANSIENT_FOR.into() {
+            let reply = self
+                .conn
+                .get_property(false, window.window_id(), property, AtomEnum::WINDOW, 0, 1)?
+                .reply_unchecked()?;
+            window.state.lock().unwrap().transient_for = reply
+                .and_then(|reply| reply.value32().and_then(|mut value| value.next()))
+                .filter(|parent| *parent != x11rb::NONE);
+        } else if property == atoms.WM_PROTOCOLS {
+            let reply = self
+                .conn
+                .get_property(false, window.window_id(), property, AtomEnum::ATOM, 0, 1024)?
+                .reply_unchecked()?;
+            window.state.lock().unwrap().protocols = reply
+                .and_then(|reply| reply.value32().map(|value| value.collect()))
+                .unwrap_or_default();
+        }
+        Ok(())
+    }
+
+    fn get_string_property(&self, window: Window, property: Atom) -> Result<Option<String>, ReplyOrIdError> {
+        let reply = self
+            .conn
+            .get_property(false, window, property, AtomEnum::ANY, 0, 2048)?
+            .reply_unchecked()?;
+        Ok(reply
+            .filter(|reply| reply.type_ != x11rb::NONE)
+            .map(|reply| String::from_utf8_lossy(&reply.value).into_owned()))
+    }
+
+    fn update_client_lists(&self) -> Result<(), ReplyOrIdError> {
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms._NET_CLIENT_LIST,
+            AtomEnum::WINDOW,
+            &self.client_list,
+        )?;
+        self.conn.change_property32(
+            PropMode::REPLACE,
+            self.root,
+            self.atoms._NET_CLIENT_LIST_STACKING,
+            AtomEnum::WINDOW,
+            &self.stacking,
+        )?;
+        Ok(())
+    }
+
+    fn convert_selection(&self, target: Atom) -> Result<(), ReplyOrIdError> {
+        self.conn.convert_selection(
+            self.wm_window,
+            self.atoms.CLIPBOARD,
+            target,
+            self.atoms._WL_SELECTION,
+            CURRENT_TIME,
+        )?;
+        self.conn.flush()?;
+        Ok(())
+    }
+
+    fn handle_selection_notify(&mut self, target: Atom, property: Atom) -> Result<(), ReplyOrIdError> {
+        if target == self.atoms.TARGETS {
+            if property == x11rb::NONE {
+                // the owner cannot be asked for its contents, don't keep offering the previous selection
+                warn!(self.log, "Failed to query the targets of the X11 selection");
+                self.selection.x11_owned = false;
+                self.pending_events.push(XwmEvent::NewSelection(None));
+                return Ok(());
+            }
+            let reply = self
+                .conn
+                .get_property(true, self.wm_window, property, AtomEnum::ATOM, 0, 4096)?
+                .reply()?;
+            let mut mime_types = Vec::new();
+            for atom in reply.value32().into_iter().flatten() {
+                if let Some(mime_type) = self.mime_type_for_target(atom)? {
+                    if !mime_types.contains(&mime_type) {
+                        mime_types.push(mime_type);
+                    }
+                }
+            }
+            self.pending_events.push(XwmEvent::NewSelection(Some(mime_types)));
+            return Ok(());
+        }
+
+        let fd = match self.selection.incoming.front() {
+            Some((pending, fd)) if *pending == target => *fd,
+            _ => return Ok(()),
+        };
+        self.selection.incoming.pop_front();
+        // Safety: the fd was passed to `X11Wm::send_selection`, which takes ownership of it
+        let mut file = unsafe { File::from_raw_fd(fd) };
+        if property != x11rb::NONE {
+            let reply = self
+                .conn
+                .get_property(true, self.wm_window, property, AtomEnum::ANY, 0, 0x1fff_ffff)?
+                .reply()?;
+            if reply.type_ == self.atoms.INCR {
+                warn!(self.log, "Incremental selection transfers are not supported");
+            } else if let Err(err) = file.write_all(&reply.value) {
+                warn!(self.log, "Failed to write selection contents: {}", err);
+            }
+        }
+        drop(file);
+
+        if let Some((target, _)) = self.selection.incoming.front() {
+            self.convert_selection(*target)?;
+        }
+        Ok(())
+    }
+
+    fn mime_type_for_target(&self, target: Atom) -> Result<Option<String>, ReplyOrIdError> {
+        let atoms = &self.atoms;
+        if target == atoms.UTF8_STRING {
+            return Ok(Some(MIME_TEXT_UTF8.to_string()));
+        }
+        if target == atoms.TEXT || target == AtomEnum::STRING.into() {
+            return Ok(Some(MIME_TEXT.to_string()));
+        }
+        if [atoms.TARGETS, atoms.TIMESTAMP, atoms.MULTIPLE].contains(&target) {
+            return Ok(None);
+        }
+        let name = self.conn.get_atom_name(target)?.reply()?.name;
+        let name = String::from_utf8_lossy(&name).into_owned();
+        // only forward targets that look like mime types
+        Ok(Some(name).filter(|name| name.contains('/')))
+    }
+
+    fn handle_selection_request(&mut self, request: SelectionRequestEvent) -> Result<(), ReplyOrIdError> {
+        let offered = match &self.selection.wayland_mime_types {
+            Some(offered) if request.selection == self.atoms.CLIPBOARD => offered,
+            _ => return refuse_selection_request(&self.conn, &request),
+        };
+        // obsolete clients use the target as property
+        let property = if request.property == x11rb::NONE {
+            request.target
+        } else {
+            request.property
+        };
+
+        if request.target == self.atoms.TARGETS {
+            let mut targets = vec![self.atoms.TARGETS, self.atoms.TIMESTAMP];
+            for (mime_type, atom) in offered {
+                targets.push(*atom);
+                if mime_type == MIME_TEXT_UTF8 {
+                    targets.push(self.atoms.UTF8_STRING);
+                } else if mime_type == MIME_TEXT {
+                    targets.push(AtomEnum::STRING.into());
+                    targets.push(self.atoms.TEXT);
+                }
+            }
+            self.conn.change_property32(
+                PropMode::REPLACE,
+                request.requestor,
+                property,
+                AtomEnum::ATOM,
+                &targets,
+            )?;
+            return notify_selection_request(&self.conn, &request, property);
+        }
+
+        let mime_type = offered
+            .iter()
+            .find(|(mime_type, atom)| {
+                *atom == request.target
+                    || (request.target == self.atoms.UTF8_STRING && mime_type == MIME_TEXT_UTF8)
+                    || ((request.target == self.atoms.TEXT || request.target == AtomEnum::STRING.into())
+                        && mime_type == MIME_TEXT)
+            })
+            .map(|(mime_type, _)| mime_type.clone());
+        let mime_type = match mime_type {
+            Some(mime_type) => mime_type,
+            None => return refuse_selection_request(&self.conn, &request),
+        };
+
+        let (read_fd, write_fd) = match pipe2(OFlag::O_CLOEXEC) {
+            Ok(fds) => fds,
+            Err(err) => {
+                warn!(self.log, "Failed to create pipe for selection transfer: {}", err);
+                return refuse_selection_request(&self.conn, &request);
+            }
+        };
+        if let Err(err) = fcntl(read_fd, FcntlArg::F_SETFL(OFlag::O_NONBLOCK)) {
+            warn!(self.log, "Failed to create pipe for selection transfer: {}", err);
+            let _ = close(read_fd);
+            let _ = close(write_fd);
+            return refuse_selection_request(&self.conn, &request);
+        }
+
+        self.selection.outgoing.push(OutgoingTransfer {
+            fd: read_fd,
+            data: Vec::new(),
+            request: SelectionRequestEvent { property, ..request },
+        });
+        self.pending_events.push(XwmEvent::SendSelection {
+            mime_type,
+            fd: write_fd,
+        });
+        Ok(())
+    }
+}
+
+fn notify_selection_request(
+    conn: &RustConnection,
+    request: &SelectionRequestEvent,
+    property: Atom,
+) -> Result<(), ReplyOrIdError> {
+    let event = SelectionNotifyEvent {
+        response_type: SELECTION_NOTIFY_EVENT,
+        sequence: 0,
+        time: request.time,
+        requestor: request.requestor,
+        selection: request.selection,
+        target: request.target,
+        property,
+    };
+    conn.send_event(false, request.requestor, EventMask::NO_EVENT, event)?;
+    conn.flush()?;
+    Ok(())
+}
+
+fn refuse_selection_request(
+    conn: &RustConnection,
+    request: &SelectionRequestEvent,
+) -> Result<(), ReplyOrIdError> {
+    notify_selection_request(conn, request, x11rb::NONE)
+}
+
+/// Size of the `ChangeProperty` request header, without the property data
+const CHANGE_PROPERTY_HEADER_BYTES: usize = 24;
+
+fn finish_transfer(
+    conn: &RustConnection,
+    transfer: &OutgoingTransfer,
+    log: &::slog::Logger,
+) -> Result<(), ReplyOrIdError> {
+    let request = &transfer.request;
+    if transfer.data.is_empty() {
+        return refuse_selection_request(conn, request);
+    }
+    // Larger contents would require an incremental (INCR) transfer
+    if transfer.data.len() + CHANGE_PROPERTY_HEADER_BYTES > conn.maximum_request_bytes() {
+        warn!(
+            log,
+            "Selection contents of {} bytes exceed the maximum X11 request size, refusing the transfer",
+            transfer.data.len()
+        );
+        return refuse_selection_request(conn, request);
+    }
+    conn.change_property8(
+        PropMode::REPLACE,
+        request.requestor,
+        request.property,
+        request.target,
+        &transfer.data,
+    )?;
+    notify_selection_request(conn, request, request.property)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_wm_class, requested_state, surface_serial, SurfacePairing};
+
+    const FULLSCREEN: u32 = 10;
+    const MAXIMIZED_HORZ: u32 = 11;
+    const MAXIMIZED_VERT: u32 = 12;
+
+    #[test]
+    fn net_wm_state_actions() {
+        // _NET_WM_STATE_REMOVE, _NET_WM_STATE_ADD, _NET_WM_STATE_TOGGLE
+        assert_eq!(
+            requested_state([0, FULLSCREEN, 0, 1, 0], &[FULLSCREEN], true),
+            Some(false)
+        );
+        assert_eq!(
+            requested_state([1, FULLSCREEN, 0, 1, 0], &[FULLSCREEN], true),
+            Some(true)
+        );
+        assert_eq!(
+            requested_state([2, FULLSCREEN, 0, 1, 0], &[FULLSCREEN], true),
+            Some(false)
+        );
+        assert_eq!(
+            requested_state([2, FULLSCREEN, 0, 1, 0], &[FULLSCREEN], false),
+            Some(true)
+        );
+        // either property may carry the state
+        let maximized = [MAXIMIZED_HORZ, MAXIMIZED_VERT];
+        assert_eq!(
+            requested_state([1, MAXIMIZED_VERT, 0, 1, 0], &maximized, false),
+            Some(true)
+        );
+        assert_eq!(
+            requested_state([1, MAXIMIZED_HORZ, MAXIMIZED_VERT, 1, 0], &maximized, false),
+            Some(true)
+        );
+        assert_eq!(
+            requested_state([1, 0, FULLSCREEN, 1, 0], &[FULLSCREEN], false),
+            Some(true)
+        );
+        assert_eq!(requested_state([1, FULLSCREEN, 0, 1, 0], &maximized, false), None);
+    }
+
+    #[test]
+    fn wm_class() {
+        assert_eq!(
+            parse_wm_class("xterm\0XTerm\0"),
+            ("xterm".to_string(), "XTerm".to_string())
+        );
+        assert_eq!(parse_wm_class("xterm"), ("xterm".to_string(), String::new()));
+        assert_eq!(parse_wm_class(""), (String::new(), String::new()));
+    }
+
+    #[test]
+    fn wl_surface_serial_message() {
+        assert_eq!(
+            surface_serial([0x89ab_cdef, 0x0123_4567, 0, 0, 0]),
+            0x0123_4567_89ab_cdef
+        );
+    }
+
+    #[test]
+    fn pair_surface_id() {
+        let mut pairing = SurfacePairing::<&str>::default();
+        assert_eq!(pairing.surface_committed(3), None);
+        // WL_SURFACE_ID arrives before the surface is created
+        pairing.window_surface_id(100, 3);
+        assert_eq!(pairing.surface_committed(4), None);
+        assert_eq!(pairing.surface_committed(3), Some(100));
+        assert_eq!(pairing.surface_committed(3), None);
+
+        // messages of unmapped windows are dropped
+        pairing.window_surface_id(101, 5);
+        pairing.remove_window(101);
+        assert_eq!(pairing.surface_committed(5), None);
+    }
+
+    #[test]
+    fn pair_surface_serial() {
+        let mut pairing = SurfacePairing::default();
+        // the serial is committed before WL_SURFACE_SERIAL arrives
+        assert_eq!(pairing.surface_serial(1, "first"), None);
+        assert_eq!(pairing.window_serial(100, 1), Some("first"));
+        // WL_SURFACE_SERIAL arrives before the serial is committed
+        assert_eq!(pairing.window_serial(101, 2), None);
+        assert_eq!(pairing.surface_serial(2, "second"), Some(101));
+        // every message pairs only once
+        assert_eq!(pairing.window_serial(102, 1), None);
+        assert_eq!(pairing.surface_serial(2, "third"), None);
+
+        // messages of unmapped windows are dropped
+        assert_eq!(pairing.window_serial(103, 4), None);
+        pairing.remove_window(103);
+        assert_eq!(pairing.surface_serial(4, "fourth"), None);
+    }
+}
@@ -0,0 +1,295 @@
+use std::{
+    collections::HashSet,
+    sync::{Arc, Mutex, Weak},
+};
+
+use wayland_server::protocol::wl_surface::WlSurface;
+use x11rb::{
+    connection::Connection as _,
+    errors::ConnectionError,
+    protocol::xproto::{
+        Atom, AtomEnum, ClientMessageEvent, ConfigureNotifyEvent, ConfigureWindowAux, ConnectionExt as _,
+        EventMask, PropMode, Window, CONFIGURE_NOTIFY_EVENT,
+    },
+    rust_connection::RustConnection,
+    wrapper::ConnectionExt as _,
+    CURRENT_TIME,
+};
+
+use crate::utils::{Logical, Rectangle};
+
+use super::Atoms;
+
+/// ICCCM `WM_STATE` values
+const WM_STATE_WITHDRAWN: u32 = 0;
+const WM_STATE_NORMAL: u32 = 1;
+
+#[derive(Debug)]
+pub(super) struct SharedSurfaceState {
+    pub(super) alive: bool,
+    pub(super) mapped: bool,
+    pub(super) wl_surface: Option<WlSurface>,
+    pub(super) geometry: Rectangle<i32, Logical>,
+    pub(super) title: String,
+    pub(super) class: String,
+    pub(super) instance: String,
+    pub(super) protocols: Vec<Atom>,
+    pub(super) net_state: HashSet<Atom>,
+    pub(super) transient_for: Option<Window>,
+}
+
+/// A window of an X11 client, managed by an [`X11Wm`](super::X11Wm)
+///
+/// Cloning an X11 surface creates a new handle to the same window.
+#[derive(Debug, Clone)]
+pub struct X11Surface {
+    pub(super) window: Window,
+    pub(super) override_redirect: bool,
+    pub(super) conn: Weak<RustConnection>,
+    pub(super) atoms: Atoms,
+    pub(super) state: Arc<Mutex<SharedSurfaceState>>,
+}
+
+impl PartialEq for X11Surface {
+    fn eq(&self, other: &Self) -> bool {
+        self.window == other.window && Arc::ptr_eq(&self.state, &other.state)
+    }
+}
+
+impl Eq for X11Surface {}
+
+impl X11Surface {
+    pub(super) fn new(
+        window: Window,
+        override_redirect: bool,
+        conn: Weak<RustConnection>,
+        atoms: Atoms,
+        geometry: Rectangle<i32, Logical>,
+    ) -> X11Surface {
+        X11Surface {
+            window,
+            override_redirect,
+            conn,
+            atoms,
+            state: Arc::new(Mutex::new(SharedSurfaceState {
+                alive: true,
+                mapped: false,
+                wl_surface: None,
+                geometry,
+                title: String::new(),
+                class: String::new(),
+                instance: String::new(),
+                protocols: Vec::new(),
+                net_state: HashSet::new(),
+                transient_for: None,
+            })),
+        }
+    }
+
+    /// The X11 id of the window
+    pub fn window_id(&self) -> u32 {
+        self.window
+    }
+
+    /// Is the window still alive?
+    pub fn alive(&self) -> bool {
+        self.state.lock().unwrap().alive && self.conn.strong_count() != 0
+    }
+
+    /// Returns if this window is override-redirect, e.g. a menu or tooltip
+    ///
+    /// Override-redirect windows place and map themselves and should not be managed.
+    pub fn is_override_redirect(&self) -> bool {
+        self.override_redirect
+    }
+
+    /// Returns if the window is currently mapped
+    pub fn is_mapped(&self) -> bool {
+        self.state.lock().unwrap().mapped
+    }
+
+    /// The `wl_surface` of this window, once it is associated
+    pub fn wl_surface(&self) -> Option<WlSurface> {
+        self.state.lock().unwrap().wl_surface.clone()
+    }
+
+    /// The last known geometry of the window, relative to the X11 root window
+    pub fn geometry(&self) -> Rectangle<i32, Logical> {
+        self.state.lock().unwrap().geometry
+    }
+
+    /// The title of the window, from `_NET_WM_NAME` or `WM_NAME`
+    pub fn title(&self) -> String {
+        self.state.lock().unwrap().title.clone()
+    }
+
+    /// The class of the window, from `WM_CLASS`
+    pub fn class(&self) -> String {
+        self.state.lock().unwrap().class.clone()
+    }
+
+    /// The instance name of the window, from `WM_CLASS`
+    pub fn instance(&self) -> String {
+        self.state.lock().unwrap().instance.clone()
+    }
+
+    /// The window this window is transient for, e.g. the parent of a dialog
+    pub fn transient_for(&self) -> Option<u32> {
+        self.state.lock().unwrap().transient_for
+    }
+
+    /// Maps or unmaps the window, e.g. in response to a [`XwmEvent::MapWindowRequest`](super::XwmEvent::MapWindowRequest)
+    pub fn set_mapped(&self, mapped: bool) -> Result<(), ConnectionError> {
+        if self.override_redirect {
+            return Ok(());
+        }
+        let conn = match self.conn.upgrade() {
+            Some(conn) => conn,
+            None => return Ok(()),
+        };
+        let wm_state = if mapped {
+            conn.map_window(self.window)?;
+            WM_STATE_NORMAL
+        } else {
+            conn.unmap_window(self.window)?;
+            WM_STATE_WITHDRAWN
+        };
+        conn.change_property32(
+            PropMode::REPLACE,
+            self.window,
+            self.atoms.WM_STATE,
+            self.atoms.WM_STATE,
+            &[wm_state, x11rb::NONE],
+        )?;
+        conn.flush()
+    }
+
+    /// Moves and resizes the window
+    ///
+    /// The location is relative to the X11 root window. As demanded by ICCCM, the
+    /// client is notified of the new geometry through a synthetic `ConfigureNotify`.
+    pub fn configure(&self, rect: Rectangle<i32, Logical>) -> Result<(), ConnectionError> {
+        if self.override_redirect {
+            return Ok(());
+        }
+        let conn = match self.conn.upgrade() {
+            Some(conn) => conn,
+            None => return Ok(()),
+        };
+        let aux = ConfigureWindowAux::default()
+            .x(rect.loc.x)
+            .y(rect.loc.y)
+            .width(rect.size.w.max(1) as u32)
+            .height(rect.size.h.max(1) as u32)
+            .border_width(0);
+        conn.configure_window(self.window, &aux)?;
+        let event = ConfigureNotifyEvent {
+            response_type: CONFIGURE_NOTIFY_EVENT,
+            sequence: 0,
+            event: self.window,
+            window: self.window,
+            above_sibling: x11rb::NONE,
+            x: rect.loc.x as i16,
+            y: rect.loc.y as i16,
+            width: rect.size.w.max(1) as u16,
+            height: rect.size.h.max(1) as u16,
+            border_width: 0,
+            override_redirect: false,
+        };
+        conn.send_event(false, self.window, EventMask::STRUCTURE_NOTIFY, event)?;
+        conn.flush()?;
+        self.state.lock().unwrap().geometry = rect;
+        Ok(())
+    }
+
+    /// Asks the client to close the window
+    ///
+    /// Uses `WM_DELETE_WINDOW`, if the client supports it, and disconnects the client otherwise.
+    pub fn close(&self) -> Result<(), ConnectionError> {
+        let conn = match self.conn.upgrade() {
+            Some(conn) => conn,
+            None => return Ok(()),
+        };
+        if self.supports_protocol(self.atoms.WM_DELETE_WINDOW) {
+            let event = ClientMessageEvent::new(
+                32,
+                self.window,
+                self.atoms.WM_PROTOCOLS,
+                [self.atoms.WM_DELETE_WINDOW, CURRENT_TIME, 0, 0, 0],
+            );
+            conn.send_event(false, self.window, EventMask::NO_EVENT, event)?;
+        } else {
+            conn.kill_client(self.window)?;
+        }
+        conn.flush()
+    }
+
+    /// Sets the activated state of the window through `_NET_WM_STATE_FOCUSED`
+    ///
+    /// This does not change the input focus, see [`X11Wm::set_focus`](super::X11Wm::set_focus).
+    pub fn set_activated(&self, activated: bool) -> Result<(), ConnectionError> {
+        self.change_net_state(&[self.atoms._NET_WM_STATE_FOCUSED], activated)
+    }
+
+    /// Sets the maximized state of the window through `_NET_WM_STATE_MAXIMIZED_{HORZ,VERT}`
+    pub fn set_maximized(&self, maximized: bool) -> Result<(), ConnectionError> {
+        self.change_net_state(
+            &[
+                self.atoms._NET_WM_STATE_MAXIMIZED_HORZ,
+                self.atoms._NET_WM_STATE_MAXIMIZED_VERT,
+            ],
+            maximized,
+        )
+    }
+
+    /// Sets the fullscreen state of the window through `_NET_WM_STATE_FULLSCREEN`
+    pub fn set_fullscreen(&self, fullscreen: bool) -> Result<(), ConnectionError> {
+        self.change_net_state(&[self.atoms._NET_WM_STATE_FULLSCREEN], fullscreen)
+    }
+
+    /// Returns if the window is maximized
+    pub fn is_maximized(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.net_state.contains(&self.atoms._NET_WM_STATE_MAXIMIZED_HORZ)
+            && state.net_state.contains(&self.atoms._NET_WM_STATE_MAXIMIZED_VERT)
+    }
+
+    /// Returns if the window is fullscreen
+    pub fn is_fullscreen(&self) -> bool {
+        self.state
+            .lock()
+            .unwrap()
+            .net_state
+            .contains(&self.atoms._NET_WM_STATE_FULLSCREEN)
+    }
+
+    pub(super) fn supports_protocol(&self, protocol: Atom) -> bool {
+        self.state.lock().unwrap().protocols.contains(&protocol)
+    }
+
+    fn change_net_state(&self, atoms: &[Atom], set: bool) -> Result<(), ConnectionError> {
+        let conn = match self.conn.upgrade() {
+            Some(conn) => conn,
+            None => return Ok(()),
+        };
+        let net_state = {
+            let mut state = self.state.lock().unwrap();
+            for atom in atoms {
+                if set {
+                    state.net_state.insert(*atom);
+                } else {
+                    state.net_state.remove(atom);
+                }
+            }
+            state.net_state.iter().copied().collect::<Vec<_>>()
+        };
+        conn.change_property32(
+            PropMode::REPLACE,
+            self.window,
+            self.atoms._NET_WM_STATE,
+            AtomEnum::ATOM,
+            &net_state,
+        )?;
+        conn.flush()
+    }
+}